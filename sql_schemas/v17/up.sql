@@ -0,0 +1,9 @@
+CREATE TABLE rollover_runs (
+    id uuid NOT NULL PRIMARY KEY,
+    ran_at timestamptz NOT NULL DEFAULT now(),
+
+    trigger varchar(63) NOT NULL,
+    success boolean NOT NULL,
+    teachers_updated integer NOT NULL,
+    error text
+);
@@ -0,0 +1 @@
+DROP TABLE absence_history;
@@ -0,0 +1,27 @@
+START TRANSACTION;
+
+CREATE TABLE absence_history (
+    teacher uuid NOT NULL REFERENCES teachers(id) ON DELETE CASCADE,
+    date date NOT NULL,
+
+    periods uuid[] NOT NULL,
+    fully_absent boolean NOT NULL,
+    comment text,
+
+    CONSTRAINT unique_teacher_day_history UNIQUE (teacher, date)
+);
+
+INSERT INTO absence_history (teacher, date, periods, fully_absent)
+    (
+        SELECT
+            t.id as teacher,
+            CURRENT_DATE as date,
+            array_remove(array_agg(a.period_id), NULL) as periods,
+            t.fully_absent
+        FROM teachers AS t
+            LEFT JOIN absence_xref AS a ON a.teacher_id = t.id
+        GROUP BY t.id
+        HAVING t.fully_absent OR count(a.period_id) > 0
+    );
+
+COMMIT;
@@ -0,0 +1,4 @@
+ALTER TABLE clients
+DROP COLUMN expires_at,
+DROP COLUMN revoked_at,
+DROP COLUMN last_seen_at;
@@ -0,0 +1,4 @@
+ALTER TABLE clients
+ADD COLUMN expires_at timestamptz,
+ADD COLUMN revoked_at timestamptz,
+ADD COLUMN last_seen_at timestamptz;
@@ -0,0 +1,2 @@
+ALTER TABLE clients
+ADD COLUMN rate_limit integer;
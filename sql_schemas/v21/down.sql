@@ -0,0 +1,5 @@
+ALTER TABLE teacher_future_schedules
+DROP COLUMN status,
+DROP COLUMN requested_at,
+DROP COLUMN reviewed_by,
+DROP COLUMN reviewed_at;
@@ -0,0 +1,5 @@
+ALTER TABLE teacher_future_schedules
+ADD COLUMN status varchar(63) NOT NULL DEFAULT 'approved',
+ADD COLUMN requested_at timestamptz NOT NULL DEFAULT now(),
+ADD COLUMN reviewed_by uuid REFERENCES teachers(id) ON DELETE SET NULL,
+ADD COLUMN reviewed_at timestamptz;
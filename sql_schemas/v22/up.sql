@@ -0,0 +1,17 @@
+START TRANSACTION;
+
+CREATE TABLE coverage (
+    teacher_id uuid NOT NULL REFERENCES teachers(id) ON DELETE CASCADE,
+    period_id uuid NOT NULL REFERENCES periods(id) ON DELETE CASCADE,
+
+    substitute_id uuid REFERENCES teachers(id) ON DELETE CASCADE,
+    external_name text,
+
+    CONSTRAINT coverage_pkey PRIMARY KEY (teacher_id, period_id),
+    CONSTRAINT coverage_one_substitute CHECK ((substitute_id IS NULL) <> (external_name IS NULL))
+);
+
+CREATE UNIQUE INDEX coverage_no_double_booking ON coverage (substitute_id, period_id)
+    WHERE substitute_id IS NOT NULL;
+
+COMMIT;
@@ -0,0 +1,6 @@
+START TRANSACTION;
+
+DROP TABLE room_assignments;
+DROP TABLE rooms;
+
+COMMIT;
@@ -0,0 +1,18 @@
+START TRANSACTION;
+
+CREATE TABLE rooms (
+    id uuid NOT NULL PRIMARY KEY,
+
+    name text NOT NULL UNIQUE
+);
+
+CREATE TABLE room_assignments (
+    teacher_id uuid NOT NULL REFERENCES teachers(id) ON DELETE CASCADE,
+    period_id uuid NOT NULL REFERENCES periods(id) ON DELETE CASCADE,
+
+    room_id uuid NOT NULL REFERENCES rooms(id) ON DELETE CASCADE,
+
+    CONSTRAINT room_assignments_pkey PRIMARY KEY (teacher_id, period_id)
+);
+
+COMMIT;
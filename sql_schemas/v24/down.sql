@@ -0,0 +1,7 @@
+START TRANSACTION;
+
+DROP TABLE schedule_calendar;
+DROP TABLE bell_schedule_periods;
+DROP TABLE bell_schedules;
+
+COMMIT;
@@ -0,0 +1,25 @@
+START TRANSACTION;
+
+CREATE TABLE bell_schedules (
+    id uuid NOT NULL PRIMARY KEY,
+
+    name text NOT NULL UNIQUE
+);
+
+CREATE TABLE bell_schedule_periods (
+    schedule_id uuid NOT NULL REFERENCES bell_schedules(id) ON DELETE CASCADE,
+    period_id uuid NOT NULL REFERENCES periods(id) ON DELETE CASCADE,
+
+    start_time TIME NOT NULL,
+    end_time TIME NOT NULL,
+
+    CONSTRAINT bell_schedule_periods_pkey PRIMARY KEY (schedule_id, period_id)
+);
+
+CREATE TABLE schedule_calendar (
+    date date NOT NULL PRIMARY KEY,
+
+    schedule_id uuid NOT NULL REFERENCES bell_schedules(id) ON DELETE CASCADE
+);
+
+COMMIT;
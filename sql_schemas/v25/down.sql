@@ -0,0 +1,6 @@
+START TRANSACTION;
+
+DROP FUNCTION is_school_day(date);
+DROP TABLE calendar_days;
+
+COMMIT;
@@ -0,0 +1,21 @@
+START TRANSACTION;
+
+-- Days that differ from the default calendar, where weekdays are school days
+-- and weekends aren't.
+CREATE TABLE calendar_days (
+    date date NOT NULL PRIMARY KEY,
+
+    kind varchar(63) NOT NULL CHECK (kind IN ('instructional', 'holiday', 'break')),
+    name text
+);
+
+CREATE FUNCTION is_school_day(day date) RETURNS boolean
+    LANGUAGE sql STABLE
+    AS $$
+        SELECT COALESCE(
+            (SELECT kind = 'instructional' FROM calendar_days WHERE date = day),
+            EXTRACT(ISODOW FROM day) < 6
+        );
+    $$;
+
+COMMIT;
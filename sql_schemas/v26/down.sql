@@ -0,0 +1,6 @@
+START TRANSACTION;
+
+DROP TABLE teacher_recurring_absence_exceptions;
+DROP TABLE teacher_recurring_absences;
+
+COMMIT;
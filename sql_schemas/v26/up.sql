@@ -0,0 +1,26 @@
+START TRANSACTION;
+
+CREATE TABLE teacher_recurring_absences (
+    id uuid NOT NULL PRIMARY KEY,
+    teacher uuid NOT NULL REFERENCES teachers(id) ON DELETE CASCADE,
+
+    periods uuid[] NOT NULL,
+    fully_absent boolean NOT NULL,
+    comment text,
+
+    start_date date NOT NULL,
+    interval_weeks integer NOT NULL DEFAULT 1 CHECK (interval_weeks > 0),
+    -- ISO weekdays, where 1 is Monday
+    weekdays smallint[] NOT NULL,
+    until date,
+    count integer CHECK (count > 0)
+);
+
+CREATE TABLE teacher_recurring_absence_exceptions (
+    recurrence_id uuid NOT NULL REFERENCES teacher_recurring_absences(id) ON DELETE CASCADE,
+    date date NOT NULL,
+
+    CONSTRAINT teacher_recurring_absence_exceptions_pkey PRIMARY KEY (recurrence_id, date)
+);
+
+COMMIT;
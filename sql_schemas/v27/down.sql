@@ -0,0 +1,5 @@
+START TRANSACTION;
+
+DROP TABLE calendar_feeds;
+
+COMMIT;
@@ -0,0 +1,14 @@
+START TRANSACTION;
+
+-- Subscribable iCalendar feeds. A feed without a teacher is the staff-wide
+-- feed of everyone's absences.
+CREATE TABLE calendar_feeds (
+    id uuid NOT NULL PRIMARY KEY,
+    teacher_id uuid REFERENCES teachers(id) ON DELETE CASCADE,
+
+    -- Hex SHA-256 of the feed's token
+    token_hash varchar(64) NOT NULL UNIQUE,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+COMMIT;
@@ -0,0 +1,5 @@
+START TRANSACTION;
+
+DROP TABLE sheet_sync_runs;
+
+COMMIT;
@@ -0,0 +1,17 @@
+START TRANSACTION;
+
+-- The outcome of each push to or pull from the mirrored spreadsheet
+CREATE TABLE sheet_sync_runs (
+    id uuid NOT NULL PRIMARY KEY,
+    ran_at timestamptz NOT NULL DEFAULT now(),
+
+    sheet_id varchar(255) NOT NULL,
+    direction varchar(63) NOT NULL CHECK (direction IN ('push', 'pull')),
+    success boolean NOT NULL,
+    -- Teacher rows written for a push, or teachers updated from a pull
+    rows integer NOT NULL,
+    attempts integer NOT NULL,
+    error text
+);
+
+COMMIT;
@@ -0,0 +1,6 @@
+START TRANSACTION;
+
+DROP TABLE webhook_deliveries;
+DROP TABLE webhooks;
+
+COMMIT;
@@ -0,0 +1,39 @@
+START TRANSACTION;
+
+-- Endpoints that are sent signed notifications of events
+CREATE TABLE webhooks (
+    id uuid NOT NULL PRIMARY KEY,
+    url text NOT NULL,
+    description text NOT NULL DEFAULT '',
+    -- The event types the endpoint is sent, like 'absence_set'
+    events varchar(63)[] NOT NULL,
+
+    -- Deliveries are signed with this, so it has to be kept in plaintext
+    secret varchar(255) NOT NULL,
+    active boolean NOT NULL DEFAULT true,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+-- The outbox of deliveries, kept until they're delivered or given up on
+CREATE TABLE webhook_deliveries (
+    id uuid NOT NULL PRIMARY KEY,
+    webhook_id uuid NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+    event varchar(63) NOT NULL,
+    -- The exact body that's sent, so retries have the same signature input
+    payload text NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+
+    attempts integer NOT NULL DEFAULT 0,
+    next_attempt_at timestamptz NOT NULL DEFAULT now(),
+    last_status integer,
+    last_error text,
+
+    delivered_at timestamptz,
+    failed_at timestamptz
+);
+
+CREATE INDEX webhook_deliveries_pending
+    ON webhook_deliveries (next_attempt_at)
+    WHERE delivered_at IS NULL AND failed_at IS NULL;
+
+COMMIT;
@@ -0,0 +1,43 @@
+START TRANSACTION;
+
+-- A record of every mutation, kept forever
+CREATE TABLE audit_log (
+    id uuid NOT NULL PRIMARY KEY,
+    at timestamptz NOT NULL DEFAULT now(),
+
+    request_id uuid NOT NULL,
+    -- Not foreign keys, so entries outlive the clients and teachers in them
+    client_id uuid,
+    teacher_id uuid,
+
+    -- The path of the mutation field, like 'updateTeacherAbsence' or
+    -- 'webhooks.create'
+    mutation varchar(255) NOT NULL,
+    arguments jsonb NOT NULL,
+
+    -- What the mutation changed, like 'teacher', and its state before and
+    -- after, if it's one of the things that's tracked
+    subject varchar(63) CHECK (subject IN ('teacher', 'period', 'all_periods', 'absence_board', 'config')),
+    subject_id uuid,
+    before jsonb,
+    after jsonb,
+
+    success boolean NOT NULL,
+    error text
+);
+
+CREATE INDEX audit_log_at ON audit_log (at);
+CREATE INDEX audit_log_teacher ON audit_log (teacher_id, at);
+CREATE INDEX audit_log_subject ON audit_log (subject_id, at);
+
+CREATE FUNCTION audit_log_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_append_only
+    BEFORE UPDATE OR DELETE OR TRUNCATE ON audit_log
+    FOR EACH STATEMENT EXECUTE FUNCTION audit_log_append_only();
+
+COMMIT;
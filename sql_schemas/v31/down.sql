@@ -0,0 +1,11 @@
+START TRANSACTION;
+
+ALTER TABLE names DROP CONSTRAINT names_name_of_fkey;
+ALTER TABLE names
+    ADD CONSTRAINT names_name_of_fkey
+    FOREIGN KEY (name_of) REFERENCES teachers(id);
+
+ALTER TABLE periods DROP COLUMN archived;
+ALTER TABLE teachers DROP COLUMN archived;
+
+COMMIT;
@@ -0,0 +1,14 @@
+START TRANSACTION;
+
+-- Soft deleted teachers and periods are archived instead of removed, so they
+-- can be restored later
+ALTER TABLE teachers ADD COLUMN archived boolean NOT NULL DEFAULT false;
+ALTER TABLE periods ADD COLUMN archived boolean NOT NULL DEFAULT false;
+
+-- Hard deleting a teacher removes their name along with everything else
+ALTER TABLE names DROP CONSTRAINT names_name_of_fkey;
+ALTER TABLE names
+    ADD CONSTRAINT names_name_of_fkey
+    FOREIGN KEY (name_of) REFERENCES teachers(id) ON DELETE CASCADE;
+
+COMMIT;
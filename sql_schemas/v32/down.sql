@@ -0,0 +1,8 @@
+START TRANSACTION;
+
+ALTER TABLE absence_history DROP CONSTRAINT absence_history_teacher_fkey;
+ALTER TABLE absence_history
+    ADD CONSTRAINT absence_history_teacher_fkey
+    FOREIGN KEY (teacher) REFERENCES teachers(id) ON DELETE CASCADE;
+
+COMMIT;
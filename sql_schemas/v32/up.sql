@@ -0,0 +1,10 @@
+START TRANSACTION;
+
+-- Absence history is kept for reporting, so it must never disappear along
+-- with a teacher. Teachers with history can only be archived.
+ALTER TABLE absence_history DROP CONSTRAINT absence_history_teacher_fkey;
+ALTER TABLE absence_history
+    ADD CONSTRAINT absence_history_teacher_fkey
+    FOREIGN KEY (teacher) REFERENCES teachers(id);
+
+COMMIT;
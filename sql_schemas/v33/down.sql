@@ -0,0 +1,6 @@
+START TRANSACTION;
+
+UPDATE clients
+SET scopes = array_to_string(array_remove(string_to_array(scopes, ' '), 'write_teacher_futures'), ' ');
+
+COMMIT;
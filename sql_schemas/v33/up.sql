@@ -0,0 +1,12 @@
+START TRANSACTION;
+
+-- Future absence mutations now need the write_teacher_futures scope instead of
+-- experimental and admin. Only clients that had both could use them, so only
+-- they get it; admins can grant it to anyone else.
+UPDATE clients
+SET scopes = scopes || ' write_teacher_futures'
+WHERE
+    string_to_array(scopes, ' ') @> ARRAY['experimental', 'admin'] AND
+    NOT ('write_teacher_futures' = ANY(string_to_array(scopes, ' ')));
+
+COMMIT;
@@ -0,0 +1,13 @@
+START TRANSACTION;
+
+DELETE FROM coverage WHERE date <> CURRENT_DATE;
+
+DROP INDEX coverage_no_double_booking;
+ALTER TABLE coverage DROP CONSTRAINT coverage_pkey;
+ALTER TABLE coverage DROP COLUMN date;
+
+ALTER TABLE coverage ADD CONSTRAINT coverage_pkey PRIMARY KEY (teacher_id, period_id);
+CREATE UNIQUE INDEX coverage_no_double_booking ON coverage (substitute_id, period_id)
+    WHERE substitute_id IS NOT NULL;
+
+COMMIT;
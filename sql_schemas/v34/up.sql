@@ -0,0 +1,14 @@
+START TRANSACTION;
+
+-- Coverage is only for the day it was assigned, so it doesn't carry over if
+-- the day is flushed without the rollover clearing it.
+ALTER TABLE coverage ADD COLUMN date date NOT NULL DEFAULT CURRENT_DATE;
+
+ALTER TABLE coverage DROP CONSTRAINT coverage_pkey;
+ALTER TABLE coverage ADD CONSTRAINT coverage_pkey PRIMARY KEY (teacher_id, period_id, date);
+
+DROP INDEX coverage_no_double_booking;
+CREATE UNIQUE INDEX coverage_no_double_booking ON coverage (substitute_id, period_id, date)
+    WHERE substitute_id IS NOT NULL;
+
+COMMIT;
@@ -0,0 +1,5 @@
+START TRANSACTION;
+
+ALTER TABLE calendar_feeds DROP COLUMN created_by;
+
+COMMIT;
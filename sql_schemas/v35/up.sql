@@ -0,0 +1,13 @@
+START TRANSACTION;
+
+-- Feeds remember who created them, so they stop working once that teacher is
+-- archived or loses the privileges the feed needed. Staff-wide feeds from
+-- before this have no known creator, so they're revoked and need recreating.
+ALTER TABLE calendar_feeds ADD COLUMN created_by uuid REFERENCES teachers(id) ON DELETE CASCADE;
+
+DELETE FROM calendar_feeds WHERE teacher_id IS NULL;
+UPDATE calendar_feeds SET created_by = teacher_id;
+
+ALTER TABLE calendar_feeds ALTER COLUMN created_by SET NOT NULL;
+
+COMMIT;
@@ -0,0 +1,10 @@
+START TRANSACTION;
+
+-- The audit log is append-only, so entries with the newer subjects are kept
+-- and the old check only applies to new entries
+ALTER TABLE audit_log DROP CONSTRAINT audit_log_subject_check;
+ALTER TABLE audit_log ADD CONSTRAINT audit_log_subject_check CHECK (subject IN (
+    'teacher', 'period', 'all_periods', 'absence_board', 'config'
+)) NOT VALID;
+
+COMMIT;
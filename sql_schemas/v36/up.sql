@@ -0,0 +1,11 @@
+START TRANSACTION;
+
+-- Future absences, recurring absences, coverage and roster imports keep their
+-- state before and after in the audit log too
+ALTER TABLE audit_log DROP CONSTRAINT audit_log_subject_check;
+ALTER TABLE audit_log ADD CONSTRAINT audit_log_subject_check CHECK (subject IN (
+    'teacher', 'period', 'all_periods', 'absence_board', 'config',
+    'future_absences', 'recurring_absence', 'coverage', 'roster'
+));
+
+COMMIT;
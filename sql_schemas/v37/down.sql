@@ -0,0 +1,5 @@
+START TRANSACTION;
+
+ALTER TABLE absence_history DROP COLUMN covered_periods;
+
+COMMIT;
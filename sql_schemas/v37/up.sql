@@ -0,0 +1,7 @@
+START TRANSACTION;
+
+-- Coverage is cleared every day, so which absent periods had a substitute is
+-- kept with the day's history for the uncovered periods report
+ALTER TABLE absence_history ADD COLUMN covered_periods uuid[] NOT NULL DEFAULT '{}';
+
+COMMIT;
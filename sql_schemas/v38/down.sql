@@ -0,0 +1,12 @@
+START TRANSACTION;
+
+DELETE FROM absence_history WHERE teacher IS NULL;
+
+ALTER TABLE absence_history DROP CONSTRAINT absence_history_teacher_fkey;
+ALTER TABLE absence_history
+    ADD CONSTRAINT absence_history_teacher_fkey
+    FOREIGN KEY (teacher) REFERENCES teachers(id);
+
+ALTER TABLE absence_history ALTER COLUMN teacher SET NOT NULL;
+
+COMMIT;
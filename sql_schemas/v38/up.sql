@@ -0,0 +1,12 @@
+START TRANSACTION;
+
+-- Deleting a teacher for good detaches their absence history instead of
+-- refusing, so reports keep the days they were out without saying who it was
+ALTER TABLE absence_history ALTER COLUMN teacher DROP NOT NULL;
+
+ALTER TABLE absence_history DROP CONSTRAINT absence_history_teacher_fkey;
+ALTER TABLE absence_history
+    ADD CONSTRAINT absence_history_teacher_fkey
+    FOREIGN KEY (teacher) REFERENCES teachers(id) ON DELETE SET NULL;
+
+COMMIT;
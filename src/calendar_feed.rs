@@ -0,0 +1,244 @@
+//! This module builds the iCalendar (RFC 5545) feeds of future absences served
+//! at `/calendar/{token}.ics`, so staff can subscribe to them from their own
+//! calendar apps.
+//!
+//! Calendar apps can't send headers, so each feed is authenticated by the
+//! random token in its URL. Only the SHA-256 of a token is stored, and a feed
+//! is revoked by deleting it. A feed also stops working once the teacher who
+//! created it couldn't create it anymore.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use base64::engine::{ general_purpose::URL_SAFE_NO_PAD, Engine };
+use chrono::{ Days, NaiveDate, Utc };
+use sha2::{ Digest, Sha256 };
+use uuid::Uuid;
+
+use crate::database::Ctx;
+use crate::state::AppState;
+use crate::types::{ CalendarFeed, PackedAbsenceState };
+
+
+/// Where a feed is served, given its token.
+pub fn feed_path(token: &str) -> String {
+    format!("/calendar/{token}.ics")
+}
+
+/// Generates a new feed token, returning it along with its hash.
+pub fn new_token() -> (String, String) {
+    use rand::RngCore;
+
+    let mut token = [0; 32];
+    rand::thread_rng().fill_bytes(&mut token);
+    let token = URL_SAFE_NO_PAD.encode(token);
+
+    let hash = hash_token(&token);
+    (token, hash)
+}
+
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Builds the feed with a token, or returns `None` if no feed has that token,
+/// its teacher has been archived, or its creator couldn't create it anymore.
+///
+/// The feed covers today and the next `CALENDAR_FEED_DAYS` days.
+pub async fn render(state: &AppState, token: &str) -> Result<Option<String>, sqlx::Error> {
+    use crate::database::prepared::calendar::get_today;
+    use crate::database::prepared::calendar_feeds::get_calendar_feed_by_hash;
+    use crate::database::prepared::future_absences::{ get_all_future_days, get_future_days_for_teacher };
+    use crate::database::prepared::teacher::{ get_all_teachers, get_teacher };
+
+    let mut db_conn = state.db().acquire().await?;
+
+    let Some(feed) = get_calendar_feed_by_hash(&mut db_conn, &hash_token(token)).await? else {
+        return Ok(None);
+    };
+    if !creator_allowed(&mut db_conn, &feed).await? {
+        return Ok(None);
+    }
+
+    let start = get_today(&mut db_conn).await?;
+    let end = start
+        .checked_add_days(Days::new(crate::env::calendar_feed_days()))
+        .unwrap_or(NaiveDate::MAX);
+
+    let (name, days, names) = match feed.teacher_id {
+        Some(teacher_id) => {
+            let teacher = get_teacher(&mut db_conn, teacher_id).await?;
+            if teacher.get_archived() {
+                return Ok(None);
+            }
+            let days = get_future_days_for_teacher(&mut db_conn, teacher_id, start, end).await?;
+
+            (format!("{} absences", teacher.get_name().short()), days, None)
+        },
+        None => {
+            let names: HashMap<_, _> = get_all_teachers(&mut db_conn)
+                .await?
+                .into_iter()
+                .map(|teacher| (teacher.get_id(), teacher.get_name().short()))
+                .collect();
+            let days = get_all_future_days(&mut db_conn, start, end)
+                .await?
+                .into_iter()
+                .flat_map(|list| list.1)
+                .collect();
+
+            ("Staff absences".to_string(), days, Some(names))
+        },
+    };
+
+    Ok(Some(build_calendar(&feed, &name, &days, names.as_ref())))
+}
+
+/// Checks that the feed's creator could still create it: they haven't been
+/// archived, and it's their own feed or they're still a secretary or admin.
+async fn creator_allowed(db_conn: &mut Ctx, feed: &CalendarFeed) -> Result<bool, sqlx::Error> {
+    use crate::database::prepared::privileges::get_privileges;
+    use crate::database::prepared::teacher::get_teacher;
+
+    if get_teacher(db_conn, feed.created_by).await?.get_archived() {
+        return Ok(false);
+    }
+    if feed.teacher_id == Some(feed.created_by) {
+        return Ok(true);
+    }
+
+    let privileges = get_privileges(db_conn, feed.created_by).await?;
+    Ok(privileges.secretary || privileges.admin)
+}
+
+/// Builds a calendar with an all-day event for each absence. Staff-wide feeds
+/// pass `names` so each event says who is absent.
+fn build_calendar(
+    feed: &CalendarFeed,
+    name: &str,
+    days: &[PackedAbsenceState],
+    names: Option<&HashMap<Uuid, String>>,
+) -> String {
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ");
+
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//TableJet//Improved Eureka//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        format!("X-WR-CALNAME:{}", escape_text(name)),
+    ];
+
+    for day in days {
+        let what = if day.fully || day.periods.is_empty() {
+            "Absent".to_string()
+        } else {
+            let periods: Vec<_> = day.periods.iter().map(|period| period.name.as_str()).collect();
+            format!("Absent {}", periods.join(", "))
+        };
+        let summary = match names.and_then(|names| names.get(&day.teacher_id)) {
+            Some(teacher) => format!("{teacher}: {what}"),
+            None => what,
+        };
+
+        let next_day = day.date.checked_add_days(Days::new(1)).unwrap_or(day.date);
+
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}-{}-{}@improved-eureka", feed.id, day.teacher_id, day.date.format("%Y%m%d")));
+        lines.push(format!("DTSTAMP:{stamp}"));
+        lines.push(format!("DTSTART;VALUE=DATE:{}", day.date.format("%Y%m%d")));
+        lines.push(format!("DTEND;VALUE=DATE:{}", next_day.format("%Y%m%d")));
+        lines.push(format!("SUMMARY:{}", escape_text(&summary)));
+        if let Some(comment) = &day.comments {
+            lines.push(format!("DESCRIPTION:{}", escape_text(comment)));
+        }
+        lines.push("TRANSP:TRANSPARENT".to_string());
+        lines.push("END:VEVENT".to_string());
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut calendar = String::new();
+    for line in lines {
+        let _ = write!(calendar, "{}\r\n", fold_line(&line));
+    }
+    calendar
+}
+
+/// Escapes a TEXT value (RFC 5545 section 3.3.11).
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {},
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Folds a content line so no line is longer than 75 octets (RFC 5545 section
+/// 3.1), without splitting a UTF-8 character.
+fn fold_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut line_len = 0;
+
+    for c in line.chars() {
+        if line_len + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            line_len = 1;
+        }
+        folded.push(c);
+        line_len += c.len_utf8();
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_text() {
+        assert_eq!(escape_text("Absent P1, P2; back soon"), "Absent P1\\, P2\\; back soon");
+        assert_eq!(escape_text("C:\\path"), "C:\\\\path");
+        assert_eq!(escape_text("line one\r\nline two"), "line one\\nline two");
+        assert_eq!(escape_text("plain: text"), "plain: text");
+    }
+
+    #[test]
+    fn leaves_short_lines_alone() {
+        let line = "a".repeat(75);
+        assert_eq!(fold_line(&line), line);
+    }
+
+    #[test]
+    fn folds_long_lines() {
+        let line = "a".repeat(160);
+        let folded = fold_line(&line);
+
+        let parts: Vec<_> = folded.split("\r\n").collect();
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0].len(), 75);
+        assert_eq!(parts[1].len(), 75);
+        assert!(parts[1..].iter().all(|part| part.starts_with(' ')));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+    }
+
+    #[test]
+    fn folds_without_splitting_characters() {
+        // 74 ASCII octets, then a 2 octet character that doesn't fit
+        let line = format!("{}é{}", "a".repeat(74), "b".repeat(10));
+        let folded = fold_line(&line);
+
+        let parts: Vec<_> = folded.split("\r\n").collect();
+        assert_eq!(parts[0], "a".repeat(74));
+        assert!(parts.iter().all(|part| part.len() <= 75));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+    }
+}
@@ -26,7 +26,7 @@ use sqlx::PgPool;
 /// - creating a [`DbContext`] for shared use within `GraphQl`
 /// 
 /// General usage would look somewhat like this:
-/// ```
+/// ```ignore
 /// let postgres_connect_result = connect_with("localhost", "improved-eureka").await;
 /// let db_ctx = match postgres_connect_result {
 ///     Ok(client) => client,
@@ -91,7 +91,10 @@ pub fn unwrap_connection(connection_result: Result<PgPool, sqlx::Error>) -> PgPo
     }
 }
 
-pub type Ctx = sqlx::pool::PoolConnection<sqlx::Postgres>;
+/// The connection every prepared query runs on. A pooled connection and an
+/// open transaction both deref to one, so the same queries can run either on
+/// their own or as part of a bigger change.
+pub type Ctx = sqlx::PgConnection;
 
 
 // /// At the moment, this is just a wrapper struct around a [PgPool].
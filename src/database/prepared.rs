@@ -2,14 +2,29 @@
 //! See their documentation for more information.
 
 pub mod teacher;
+pub mod roster;
 pub mod period;
+pub mod bell_schedules;
+pub mod calendar;
+pub mod calendar_feeds;
 pub mod absences;
+pub mod absence_history;
+pub mod coverage;
+pub mod reports;
 
 pub mod future_absences;
+pub mod recurring_absences;
+pub mod absence_requests;
 pub mod privileges;
 
 pub mod clients;
+pub mod webhooks;
 pub mod config;
+pub mod rooms;
+
+pub mod rollovers;
+pub mod sheet_syncs;
+pub mod audit_log;
 
 macro_rules! prepared_query {
     (
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::NaiveDate;
+use sqlx::{ query, query_as };
+use uuid::Uuid;
+
+use crate::types::PackedAbsenceState;
+
+use super::super::Ctx;
+use super::future_absences::{ BarebonesFutureDay, get_packed_absence_state };
+use super::period::get_periods;
+
+
+/// Records a teacher's absence state as their absence for today, replacing
+/// anything already recorded for today.
+/// 
+/// If the teacher isn't absent at all, today's record is removed instead.
+/// A `None` comment keeps whatever comment was already recorded for today.
+pub async fn archive_today(
+    ctx: &mut Ctx,
+    teacher: Uuid,
+    periods: &[Uuid],
+    fully_absent: bool,
+    comment: Option<String>,
+) -> Result<(), sqlx::Error> {
+    if periods.is_empty() && !fully_absent {
+        let remove_today = query!(
+            r#"
+                DELETE FROM absence_history
+                WHERE
+                    teacher = $1 AND
+                    date = CURRENT_DATE;
+            "#,
+            teacher,
+        );
+
+        remove_today.execute(&mut *ctx).await?;
+        return Ok(());
+    }
+
+    let archive_today = query!(
+        r#"
+            INSERT INTO absence_history (teacher, date, periods, fully_absent, comment)
+            VALUES ($1, CURRENT_DATE, $2, $3, $4)
+                ON CONFLICT (teacher, date)
+                DO UPDATE SET
+                    periods = $2,
+                    fully_absent = $3,
+                    comment = COALESCE($4, absence_history.comment);
+        "#,
+        teacher,
+        periods, fully_absent, comment,
+    );
+
+    archive_today.execute(&mut *ctx).await?;
+
+    Ok(())
+}
+
+/// Gets every archived absence day between `start` and `end` (inclusive),
+/// optionally only for a single teacher.
+/// 
+/// Periods that have since been removed are left out of the returned states,
+/// and so are the days of teachers that have been deleted for good.
+pub async fn get_absence_history(
+    ctx: &mut Ctx,
+    teacher: Option<Uuid>,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Result<Vec<PackedAbsenceState>, sqlx::Error> {
+    let start = start.signed_duration_since(NaiveDate::default()).num_days() as f64;
+    let end = end.signed_duration_since(NaiveDate::default()).num_days() as f64;
+
+    let get_history_in_range = query_as!(
+        BarebonesFutureDay,
+        r#"
+            SELECT
+                ah.teacher as "teacher_id!",
+                EXTRACT(EPOCH FROM date)::float / 86400 as "date!",
+                periods,
+                fully_absent,
+                comment
+            FROM absence_history as ah
+            WHERE
+                ah.teacher IS NOT NULL AND
+                ($1::uuid IS NULL OR ah.teacher = $1) AND
+                DATE '1/1/1970' + $2 * INTERVAL '1 day' <= ah.date AND
+                ah.date <= DATE '1/1/1970' + $3 * INTERVAL '1 day'
+            ORDER BY ah.date, ah.teacher;
+        "#,
+        teacher,
+        start,
+        end,
+    );
+
+    let period_map: HashMap<_, _> = get_periods(ctx, true)
+        .await?
+        .into_iter()
+        .map(|period| (period.id, Arc::new(period)))
+        .collect();
+
+    let data = get_history_in_range.fetch_all(&mut *ctx).await?;
+
+    data.into_iter()
+        .map(|mut day| {
+            day.periods.retain(|id| period_map.contains_key(id));
+            get_packed_absence_state(day, &period_map)
+        })
+        .collect()
+}
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::{ DateTime, NaiveDate, Utc };
+use sqlx::query;
+use uuid::Uuid;
+
+use crate::types::{ AbsenceRequest, AbsenceRequestStatus };
+
+use super::super::Ctx;
+use super::future_absences::{ BarebonesFutureDay, get_packed_absence_state };
+use super::period::get_periods;
+use super::prepared_query;
+
+
+fn decode_timestamp(column: &str, secs: f64) -> Result<DateTime<Utc>, sqlx::Error> {
+    DateTime::from_timestamp(secs.floor() as i64, (secs.fract() * 1_000_000_000.0) as u32)
+        .ok_or_else(|| sqlx::Error::Decode(format!("teacher_future_schedules.{column} out of range: {secs}").into()))
+}
+
+/// Gets every future absence between `start` and `end` (inclusive) along with
+/// its review state, optionally only for a single teacher or with a single
+/// status.
+pub async fn get_absence_requests(
+    ctx: &mut Ctx,
+    teacher: Option<Uuid>,
+    start: NaiveDate,
+    end: NaiveDate,
+    status: Option<AbsenceRequestStatus>,
+) -> Result<Vec<AbsenceRequest>, sqlx::Error> {
+    let start = start.signed_duration_since(NaiveDate::default()).num_days() as f64;
+    let end = end.signed_duration_since(NaiveDate::default()).num_days() as f64;
+
+    let get_requests_in_range = prepared_query!(
+        r#"
+            SELECT
+                tfs.teacher as teacher_id,
+                EXTRACT(EPOCH FROM date)::float / 86400 as "date!",
+                periods,
+                fully_absent,
+                comment,
+                status,
+                EXTRACT(EPOCH FROM requested_at)::float as "requested_at!",
+                reviewed_by,
+                EXTRACT(EPOCH FROM reviewed_at)::float as reviewed_at
+            FROM teacher_future_schedules as tfs
+            WHERE
+                ($1::uuid IS NULL OR tfs.teacher = $1) AND
+                ($4::varchar IS NULL OR tfs.status = $4) AND
+                DATE '1/1/1970' + $2 * INTERVAL '1 day' <= tfs.date AND
+                tfs.date <= DATE '1/1/1970' + $3 * INTERVAL '1 day'
+            ORDER BY tfs.date, tfs.requested_at;
+        "#;
+        {
+            teacher_id: Uuid,
+            date: f64,
+            periods: Vec<Uuid>,
+            fully_absent: bool,
+            comment: Option<String>,
+            status: String,
+            requested_at: f64,
+            reviewed_by: Option<Uuid>,
+            reviewed_at: Option<f64>,
+        };
+        teacher,
+        start,
+        end,
+        status.map(|status| status.str()),
+    );
+
+    let period_map: HashMap<_, _> = get_periods(ctx, true)
+        .await?
+        .into_iter()
+        .map(|period| (period.id, Arc::new(period)))
+        .collect();
+
+    let data = get_requests_in_range.fetch_all(&mut *ctx).await?;
+
+    data.into_iter()
+        .map(|row| {
+            let status = AbsenceRequestStatus::try_from_str(&row.status)
+                .ok_or_else(|| sqlx::Error::Decode(format!("Invalid absence request status: {}", row.status).into()))?;
+
+            let absence = get_packed_absence_state(
+                BarebonesFutureDay {
+                    teacher_id: row.teacher_id,
+                    date: row.date,
+                    periods: row.periods,
+                    fully_absent: row.fully_absent,
+                    comment: row.comment,
+                },
+                &period_map,
+            )?;
+
+            Ok(AbsenceRequest {
+                absence,
+                status,
+                requested_at: decode_timestamp("requested_at", row.requested_at)?,
+                reviewed_by: row.reviewed_by,
+                reviewed_at: row.reviewed_at.map(|secs| decode_timestamp("reviewed_at", secs)).transpose()?,
+            })
+        })
+        .collect()
+}
+
+/// Approves or rejects a teacher's pending future absences from `start` to
+/// `end` (inclusive).
+///
+/// Returns how many days were reviewed.
+pub async fn review_future_days(
+    ctx: &mut Ctx,
+
+    start: NaiveDate,
+    end: NaiveDate,
+    id: Uuid,
+
+    status: AbsenceRequestStatus,
+    reviewer: Uuid,
+) -> Result<u64, sqlx::Error> {
+    let start_days_since_epoch = start.signed_duration_since(NaiveDate::default()).num_days() as f64;
+    let end_days_since_epoch = end.signed_duration_since(NaiveDate::default()).num_days() as f64;
+
+    let review_query = query!(
+        r#"
+            UPDATE teacher_future_schedules
+            SET
+                status = $4,
+                reviewed_by = $5,
+                reviewed_at = now()
+            WHERE
+                teacher = $1 AND
+                status = 'pending' AND
+                daterange(
+                    (DATE '1/1/1970' + $2 * INTERVAL '1 day')::date,
+                    (DATE '1/1/1970' + $3 * INTERVAL '1 day')::date,
+                    '[]'::text
+                ) @> date;
+        "#,
+        id,
+        start_days_since_epoch,
+        end_days_since_epoch,
+        status.str(),
+        reviewer,
+    );
+
+    Ok(review_query.execute(&mut *ctx).await?.rows_affected())
+}
@@ -1,7 +1,7 @@
 use sqlx::query_as;
 use uuid::Uuid;
 
-use super::{super::Ctx, teacher::update_teacher_full_absence};
+use super::{super::Ctx, teacher::update_teacher_full_absence, absence_history::archive_today, coverage::remove_stale_coverage};
 use crate::types::Absence;
 
 
@@ -18,7 +18,7 @@ pub async fn get_absence(ctx: &mut Ctx, id: Uuid) -> Result<Absence, sqlx::Error
         id,
     );
 
-    get_absence_query.fetch_one(&mut **ctx).await
+    get_absence_query.fetch_one(&mut *ctx).await
 }
 
 pub async fn get_all_absences(ctx: &mut Ctx) -> Result<Vec<Absence>, sqlx::Error> {
@@ -29,7 +29,7 @@ pub async fn get_all_absences(ctx: &mut Ctx) -> Result<Vec<Absence>, sqlx::Error
         "#,
     );
 
-    get_all_absences_query.fetch_all(&mut **ctx).await
+    get_all_absences_query.fetch_all(&mut *ctx).await
 }
 
 pub async fn get_all_absences_for_period(ctx: &mut Ctx, id: Uuid) -> Result<Vec<Absence>, sqlx::Error> {
@@ -46,7 +46,7 @@ pub async fn get_all_absences_for_period(ctx: &mut Ctx, id: Uuid) -> Result<Vec<
         id
     );
 
-    get_period_absences_query.fetch_all(&mut **ctx).await
+    get_period_absences_query.fetch_all(&mut *ctx).await
 }
 
 pub async fn get_all_absences_for_teacher(ctx: &mut Ctx, id: Uuid) -> Result<Vec<Absence>, sqlx::Error> {
@@ -63,7 +63,7 @@ pub async fn get_all_absences_for_teacher(ctx: &mut Ctx, id: Uuid) -> Result<Vec
         id
     );
 
-    get_teacher_absences_query.fetch_all(&mut **ctx).await
+    get_teacher_absences_query.fetch_all(&mut *ctx).await
 }
 
 struct Id { id: Uuid }
@@ -79,7 +79,7 @@ pub async fn add_absence(ctx: &mut Ctx, period: Uuid, teacher: Uuid) -> Result<A
         teacher
     );
 
-    let id = add_absence_query.fetch_one(&mut **ctx).await?.id;
+    let id = add_absence_query.fetch_one(&mut *ctx).await?.id;
 
     get_absence(ctx, id).await
 }
@@ -97,7 +97,7 @@ pub async fn remove_absence(ctx: &mut Ctx, period: Uuid, teacher: Uuid) -> Resul
         teacher
     );
 
-    remove_absence_query.execute(&mut **ctx).await.map(|_| ())
+    remove_absence_query.execute(&mut *ctx).await.map(|_| ())
 }
 pub async fn remove_absences_for_teacher(ctx: &mut Ctx, teacher: Uuid) -> Result<(), sqlx::Error> {
     let remove_absences_query = query_as!(
@@ -110,10 +110,20 @@ pub async fn remove_absences_for_teacher(ctx: &mut Ctx, teacher: Uuid) -> Result
         teacher
     );
 
-    remove_absences_query.execute(&mut **ctx).await.map(|_| ())
+    remove_absences_query.execute(&mut *ctx).await.map(|_| ())
 }
 
-pub async fn update_absences_for_teacher(ctx: &mut Ctx, teacher: Uuid, periods: &[Uuid], fully_absent: bool) -> Result<Vec<Absence>, sqlx::Error> {
+/// Replaces the current absence state of a teacher, and archives it as their
+/// absence for today. Coverage that no longer makes sense (of periods they're
+/// no longer absent in, or by them in periods they're now absent in) is
+/// removed.
+pub async fn update_absences_for_teacher(
+    ctx: &mut Ctx,
+    teacher: Uuid,
+    periods: &[Uuid],
+    fully_absent: bool,
+    comment: Option<String>,
+) -> Result<Vec<Absence>, sqlx::Error> {
 
     remove_absences_for_teacher(ctx, teacher).await?;
 
@@ -122,6 +132,8 @@ pub async fn update_absences_for_teacher(ctx: &mut Ctx, teacher: Uuid, periods:
         absences.push(add_absence(ctx, *period, teacher).await?);
     }
     update_teacher_full_absence(ctx, teacher, fully_absent).await?;
+    remove_stale_coverage(ctx, teacher, periods, fully_absent).await?;
+    archive_today(ctx, teacher, periods, fully_absent, comment).await?;
     
     Ok(absences)
 }
@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+
+use chrono::{ DateTime, NaiveDate, Utc };
+use futures_util::future::BoxFuture;
+use serde_json::{ json, Map };
+use sqlx::Connection;
+use sqlx::types::JsonValue;
+use uuid::Uuid;
+
+use crate::types::{ AuditEntry, AuditFilter, AuditLogPage, AuditOrigin, AuditSubject, AuditTarget, Substitute };
+
+use super::super::Ctx;
+use super::prepared_query;
+
+
+fn epoch_of(time: DateTime<Utc>) -> f64 {
+    time.timestamp() as f64 + f64::from(time.timestamp_subsec_nanos()) / 1_000_000_000.0
+}
+
+/// Appends an entry to the audit log.
+pub async fn record_audit_entry(ctx: &mut Ctx, entry: &AuditEntry) -> Result<(), sqlx::Error> {
+    let record_query = prepared_query!(
+        r"
+            INSERT INTO audit_log (
+                id, at,
+                request_id, client_id, teacher_id,
+                mutation, arguments,
+                subject, subject_id, before, after,
+                success, error
+            )
+            VALUES ($1, to_timestamp($2), $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13);
+        ";
+        {  };
+        entry.id, epoch_of(entry.at),
+        entry.request_id, entry.client_id, entry.teacher_id,
+        entry.mutation, entry.arguments,
+        entry.subject.map(|subject| subject.str()), entry.subject_id, entry.before, entry.after,
+        entry.success, entry.error,
+    );
+
+    record_query.execute(&mut *ctx).await?;
+
+    Ok(())
+}
+
+/// Makes a change in a transaction, and records it in the audit log along
+/// with the state of `target` before and after, in the same transaction.
+pub async fn audited<T, F>(
+    ctx: &mut Ctx,
+    origin: &AuditOrigin,
+    target: AuditTarget,
+    change: F,
+) -> Result<T, sqlx::Error>
+where
+    F: for<'c> FnOnce(&'c mut Ctx) -> BoxFuture<'c, Result<T, sqlx::Error>>,
+{
+    let mut txn = Connection::begin(&mut *ctx).await?;
+
+    let before = snapshot(&mut txn, target).await?;
+    let changed = change(&mut txn).await?;
+    let after = snapshot(&mut txn, target).await?;
+    record_change(&mut txn, origin, target, before, after).await?;
+
+    txn.commit().await?;
+
+    Ok(changed)
+}
+
+/// Records a successful change in the audit log. Call this with the
+/// transaction that made the change, so the entry is only kept if the change
+/// is.
+pub async fn record_change(
+    ctx: &mut Ctx,
+    origin: &AuditOrigin,
+    target: AuditTarget,
+    before: Option<JsonValue>,
+    after: Option<JsonValue>,
+) -> Result<(), sqlx::Error> {
+    record_audit_entry(ctx, &AuditEntry {
+        id: Uuid::new_v4(),
+        at: Utc::now(),
+        request_id: origin.request_id,
+        client_id: origin.client_id,
+        teacher_id: origin.teacher_id,
+        mutation: origin.mutation.clone(),
+        arguments: origin.arguments.clone(),
+        subject: Some(target.subject()),
+        subject_id: target.subject_id(),
+        before,
+        after,
+        success: true,
+        error: None,
+    }).await?;
+
+    origin.recorded.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Gets the current state of what a change is made to, or `None` if it
+/// doesn't exist.
+pub async fn snapshot(ctx: &mut Ctx, target: AuditTarget) -> Result<Option<JsonValue>, sqlx::Error> {
+    use super::absences::{ get_all_absences, get_all_absences_for_teacher };
+    use super::config::{ get_attribs, get_report_to, get_sheet_id };
+    use super::coverage::get_coverage;
+    use super::period::{ get_all_periods, get_period };
+    use super::recurring_absences::get_recurring_absence;
+    use super::roster::get_roster;
+    use super::teacher::{ get_all_teachers, get_teacher };
+
+    let not_found_as_none = |result: Result<JsonValue, sqlx::Error>| match result {
+        Ok(snapshot) => Ok(Some(snapshot)),
+        Err(sqlx::Error::RowNotFound) => Ok(None),
+        Err(e) => Err(e),
+    };
+
+    match target {
+        AuditTarget::Teacher(id) => not_found_as_none(async {
+            let teacher = get_teacher(ctx, id).await?;
+            let absences = get_all_absences_for_teacher(ctx, id).await?;
+            Ok(json!({
+                "teacher": teacher,
+                "absentPeriods": absences.iter().map(|absence| absence.period).collect::<Vec<_>>(),
+            }))
+        }.await),
+        AuditTarget::Period(id) => not_found_as_none(
+            get_period(ctx, id).await.map(|period| json!(period))
+        ),
+        AuditTarget::AllPeriods => Ok(Some(json!(get_all_periods(ctx).await?))),
+        AuditTarget::AbsenceBoard => {
+            let mut absent_in: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+            for absence in get_all_absences(ctx).await? {
+                absent_in.entry(absence.teacher).or_default().push(absence.period);
+            }
+
+            let board: Map<_, _> = get_all_teachers(ctx)
+                .await?
+                .into_iter()
+                .filter_map(|teacher| {
+                    let periods = absent_in.remove(&teacher.get_id()).unwrap_or_default();
+                    (teacher.get_fully_absent() || !periods.is_empty()).then(|| (
+                        teacher.get_id().to_string(),
+                        json!({ "fullyAbsent": teacher.get_fully_absent(), "periods": periods }),
+                    ))
+                })
+                .collect();
+
+            Ok(Some(JsonValue::Object(board)))
+        },
+        AuditTarget::Config => Ok(Some(json!({
+            "sheetId": get_sheet_id(ctx).await?,
+            "reportTo": get_report_to(ctx).await?,
+            "attribs": get_attribs(ctx).await?,
+        }))),
+        AuditTarget::FutureAbsences { teacher, start, end } => Ok(Some(
+            future_absences_snapshot(ctx, teacher, start, end).await?
+        )),
+        AuditTarget::RecurringAbsence(id) => not_found_as_none(
+            get_recurring_absence(ctx, id).await.map(|recurrence| json!({
+                "teacherId": recurrence.teacher_id,
+                "periods": recurrence.periods,
+                "fullyAbsent": recurrence.fully_absent,
+                "comment": recurrence.comment,
+                "start": recurrence.rule.start,
+                "intervalWeeks": recurrence.rule.interval_weeks,
+                "weekdays": recurrence.rule.weekdays
+                    .iter()
+                    .map(|weekday| weekday.number_from_monday())
+                    .collect::<Vec<_>>(),
+                "until": recurrence.rule.until,
+                "count": recurrence.rule.count,
+                "exceptions": recurrence.exceptions,
+            }))
+        ),
+        AuditTarget::Coverage { teacher, period } => Ok(
+            get_coverage(ctx, teacher, period).await?.map(|coverage| match coverage.substitute {
+                Substitute::Teacher(id) => json!({ "periodId": period, "substituteId": id }),
+                Substitute::External(name) => json!({ "periodId": period, "externalName": name }),
+            })
+        ),
+        AuditTarget::Roster => Ok(Some(JsonValue::Array(
+            get_roster(ctx)
+                .await?
+                .into_iter()
+                .map(|entry| json!({
+                    "id": entry.id,
+                    "name": entry.name,
+                    "pronouns": entry.pronouns,
+                    "oauth": entry.oauth
+                        .iter()
+                        .map(|link| json!({ "provider": link.provider, "sub": link.sub }))
+                        .collect::<Vec<_>>(),
+                }))
+                .collect()
+        ))),
+    }
+}
+
+/// Gets every day of a teacher's future absences from `start` to `end`
+/// (inclusive), whatever their review state.
+async fn future_absences_snapshot(
+    ctx: &mut Ctx,
+    teacher: Uuid,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Result<JsonValue, sqlx::Error> {
+    let start_days_since_epoch = start.signed_duration_since(NaiveDate::default()).num_days() as f64;
+    let end_days_since_epoch = end.signed_duration_since(NaiveDate::default()).num_days() as f64;
+
+    let get_days_query = prepared_query!(
+        r#"
+            SELECT COALESCE(
+                jsonb_agg(
+                    jsonb_build_object(
+                        'date', date,
+                        'periods', periods,
+                        'fullyAbsent', fully_absent,
+                        'comment', comment,
+                        'status', status,
+                        'reviewedBy', reviewed_by
+                    )
+                    ORDER BY date
+                ),
+                '[]'::jsonb
+            ) as "days!"
+            FROM teacher_future_schedules
+            WHERE
+                teacher = $1 AND
+                DATE '1/1/1970' + $2 * INTERVAL '1 day' <= date AND
+                date <= DATE '1/1/1970' + $3 * INTERVAL '1 day';
+        "#;
+        { days: JsonValue };
+        teacher, start_days_since_epoch, end_days_since_epoch,
+    );
+
+    Ok(get_days_query.fetch_one(&mut *ctx).await?.days)
+}
+
+/// Gets a page of the audit log entries that match `filter`, newest first.
+/// A `mutation` filter also matches the mutations nested under it, so
+/// `webhooks` matches `webhooks.create`.
+pub async fn get_audit_log(
+    ctx: &mut Ctx,
+    filter: &AuditFilter,
+    offset: i64,
+    limit: i64,
+) -> Result<AuditLogPage, sqlx::Error> {
+    let get_entries_query = prepared_query!(
+        r#"
+            SELECT
+                id,
+                EXTRACT(EPOCH FROM at)::float as "at!",
+                request_id, client_id, teacher_id,
+                mutation, arguments,
+                subject, subject_id, before, after,
+                success, error,
+                COUNT(*) OVER () as "total!"
+            FROM audit_log
+            WHERE
+                ($1::varchar IS NULL OR mutation = $1 OR mutation LIKE $1 || '.%') AND
+                ($2::uuid IS NULL OR request_id = $2) AND
+                ($3::uuid IS NULL OR client_id = $3) AND
+                ($4::uuid IS NULL OR teacher_id = $4) AND
+                ($5::varchar IS NULL OR subject = $5) AND
+                ($6::uuid IS NULL OR subject_id = $6) AND
+                ($7::boolean IS NULL OR success = $7) AND
+                ($8::float IS NULL OR at >= to_timestamp($8)) AND
+                ($9::float IS NULL OR at < to_timestamp($9))
+            ORDER BY at DESC, id
+            OFFSET $10
+            LIMIT $11;
+        "#;
+        {
+            id: Uuid,
+            at: f64,
+            request_id: Uuid,
+            client_id: Option<Uuid>,
+            teacher_id: Option<Uuid>,
+            mutation: String,
+            arguments: JsonValue,
+            subject: Option<String>,
+            subject_id: Option<Uuid>,
+            before: Option<JsonValue>,
+            after: Option<JsonValue>,
+            success: bool,
+            error: Option<String>,
+            total: i64,
+        };
+        filter.mutation.as_deref(),
+        filter.request_id,
+        filter.client_id,
+        filter.teacher_id,
+        filter.subject.map(|subject| subject.str()),
+        filter.subject_id,
+        filter.success,
+        filter.since.map(epoch_of),
+        filter.until.map(epoch_of),
+        offset,
+        limit,
+    );
+
+    let rows = get_entries_query.fetch_all(&mut *ctx).await?;
+    let total = rows.first().map_or(0, |row| row.total);
+
+    let entries = rows
+        .into_iter()
+        .map(|row| {
+            let at = DateTime::from_timestamp(
+                row.at.floor() as i64,
+                (row.at.fract() * 1_000_000_000.0) as u32,
+            ).ok_or_else(|| sqlx::Error::Decode(format!("audit_log.at out of range: {}", row.at).into()))?;
+
+            let subject = row.subject
+                .map(|subject| AuditSubject::try_from_str(&subject)
+                    .ok_or_else(|| sqlx::Error::Decode(format!("Invalid audit subject: {subject}").into())))
+                .transpose()?;
+
+            Ok(AuditEntry {
+                id: row.id,
+                at,
+                request_id: row.request_id,
+                client_id: row.client_id,
+                teacher_id: row.teacher_id,
+                mutation: row.mutation,
+                arguments: row.arguments,
+                subject,
+                subject_id: row.subject_id,
+                before: row.before,
+                after: row.after,
+                success: row.success,
+                error: row.error,
+            })
+        })
+        .collect::<Result<_, sqlx::Error>>()?;
+
+    Ok(AuditLogPage { entries, total })
+}
@@ -0,0 +1,284 @@
+use chrono::{ Days, NaiveDate };
+use uuid::Uuid;
+
+use crate::types::{ BellSchedule, BellSchedulePeriod, ScheduleDay };
+
+use super::super::Ctx;
+use super::prepared_query;
+
+
+pub async fn get_bell_schedule(ctx: &mut Ctx, id: Uuid) -> Result<BellSchedule, sqlx::Error> {
+    let get_schedule_query = prepared_query!(
+        r"
+            SELECT id, name
+            FROM bell_schedules
+            WHERE id = $1;
+        ";
+        { id: Uuid, name: String };
+        id,
+    );
+
+    let schedule = get_schedule_query.fetch_one(&mut *ctx).await?;
+
+    Ok(BellSchedule { id: schedule.id, name: schedule.name })
+}
+
+pub async fn get_all_bell_schedules(ctx: &mut Ctx) -> Result<Vec<BellSchedule>, sqlx::Error> {
+    let get_all_schedules_query = prepared_query!(
+        r"
+            SELECT id, name
+            FROM bell_schedules
+            ORDER BY name;
+        ";
+        { id: Uuid, name: String };
+    );
+
+    let schedules = get_all_schedules_query.fetch_all(&mut *ctx).await?;
+
+    Ok(schedules.into_iter().map(|schedule| BellSchedule { id: schedule.id, name: schedule.name }).collect())
+}
+
+pub async fn add_bell_schedule(ctx: &mut Ctx, name: &str) -> Result<BellSchedule, sqlx::Error> {
+    let add_schedule_query = prepared_query!(
+        r"
+            INSERT INTO bell_schedules (id, name)
+            VALUES (uuid_generate_v4(), $1)
+            RETURNING id;
+        ";
+        { id: Uuid };
+        name,
+    );
+
+    let id = add_schedule_query.fetch_one(&mut *ctx).await?.id;
+
+    Ok(BellSchedule { id, name: name.to_string() })
+}
+
+pub async fn update_bell_schedule_name(ctx: &mut Ctx, id: Uuid, name: &str) -> Result<BellSchedule, sqlx::Error> {
+    let update_name_query = prepared_query!(
+        r"
+            UPDATE bell_schedules
+            SET name = $2
+            WHERE id = $1;
+        ";
+        {  };
+        id, name,
+    );
+
+    if update_name_query.execute(&mut *ctx).await?.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    Ok(BellSchedule { id, name: name.to_string() })
+}
+
+/// Deletes a bell schedule, along with its period times and every day it was
+/// used on.
+///
+/// Returns whether the schedule existed.
+pub async fn delete_bell_schedule(ctx: &mut Ctx, id: Uuid) -> Result<bool, sqlx::Error> {
+    let delete_schedule_query = prepared_query!(
+        r"
+            DELETE FROM bell_schedules
+            WHERE id = $1;
+        ";
+        {  };
+        id,
+    );
+
+    Ok(delete_schedule_query.execute(&mut *ctx).await?.rows_affected() > 0)
+}
+
+
+pub async fn get_bell_schedule_periods(ctx: &mut Ctx, schedule: Uuid) -> Result<Vec<BellSchedulePeriod>, sqlx::Error> {
+    let get_periods_query = prepared_query!(
+        r#"
+            SELECT
+                schedule_id,
+                period_id,
+                EXTRACT(EPOCH FROM start_time)::float as "start!",
+                EXTRACT(EPOCH FROM end_time)::float as "end!"
+            FROM bell_schedule_periods
+            WHERE schedule_id = $1
+            ORDER BY start_time;
+        "#;
+        {
+            schedule_id: Uuid,
+            period_id: Uuid,
+            start: f64,
+            end: f64,
+        };
+        schedule,
+    );
+
+    let periods = get_periods_query.fetch_all(&mut *ctx).await?;
+
+    Ok(
+        periods
+            .into_iter()
+            .map(|period| BellSchedulePeriod {
+                schedule_id: period.schedule_id,
+                period_id: period.period_id,
+                start: period.start,
+                end: period.end,
+            })
+            .collect()
+    )
+}
+
+/// Sets a period's time in a bell schedule, adding the period to the schedule
+/// if it isn't already in it.
+pub async fn set_bell_schedule_period_time(
+    ctx: &mut Ctx,
+    schedule: Uuid,
+    period: Uuid,
+    time_range: [f64; 2],
+) -> Result<(), sqlx::Error> {
+    let set_time_query = prepared_query!(
+        r"
+            INSERT INTO bell_schedule_periods (schedule_id, period_id, start_time, end_time)
+            VALUES (
+                $1, $2,
+                TIME '00:00' + $3 * INTERVAL '1 second',
+                TIME '00:00' + $4 * INTERVAL '1 second'
+            )
+                ON CONFLICT (schedule_id, period_id)
+                DO UPDATE SET
+                    start_time = EXCLUDED.start_time,
+                    end_time = EXCLUDED.end_time;
+        ";
+        {  };
+        schedule, period,
+        time_range[0], time_range[1],
+    );
+
+    set_time_query.execute(&mut *ctx).await?;
+    Ok(())
+}
+
+/// Removes a period from a bell schedule, so it uses its default time on days
+/// with that schedule.
+pub async fn clear_bell_schedule_period_time(ctx: &mut Ctx, schedule: Uuid, period: Uuid) -> Result<(), sqlx::Error> {
+    let clear_time_query = prepared_query!(
+        r"
+            DELETE FROM bell_schedule_periods
+            WHERE schedule_id = $1 AND period_id = $2;
+        ";
+        {  };
+        schedule, period,
+    );
+
+    clear_time_query.execute(&mut *ctx).await?;
+    Ok(())
+}
+
+
+/// Sets the bell schedule used on every day from `start` to `end`
+/// (inclusive), or clears it so the periods' default times are used if
+/// `schedule` is `None`.
+pub async fn set_schedule_days(
+    ctx: &mut Ctx,
+    start: NaiveDate,
+    end: NaiveDate,
+    schedule: Option<Uuid>,
+) -> Result<(), sqlx::Error> {
+    let start_days_since_epoch = start.signed_duration_since(NaiveDate::default()).num_days();
+    let end_days_since_epoch = end.signed_duration_since(NaiveDate::default()).num_days();
+
+    let Some(schedule) = schedule else {
+        let clear_days_query = prepared_query!(
+            r"
+                DELETE FROM schedule_calendar
+                WHERE
+                    daterange(
+                        (DATE '1/1/1970' + $1 * INTERVAL '1 day')::date,
+                        (DATE '1/1/1970' + $2 * INTERVAL '1 day')::date,
+                        '[]'::text
+                    ) @> date;
+            ";
+            {  };
+            start_days_since_epoch as f64, end_days_since_epoch as f64,
+        );
+
+        clear_days_query.execute(&mut *ctx).await?;
+        return Ok(());
+    };
+
+    let set_days_query = prepared_query!(
+        r"
+            INSERT INTO schedule_calendar (date, schedule_id)
+            (
+                SELECT
+                    DATE '1/1/1970' + date_idx * INTERVAL '1 day',
+                    $3
+                FROM generate_series($1::bigint, $2::bigint) as date_idx
+            )
+                ON CONFLICT (date)
+                DO UPDATE SET schedule_id = $3;
+        ";
+        {  };
+        start_days_since_epoch, end_days_since_epoch,
+        schedule,
+    );
+
+    set_days_query.execute(&mut *ctx).await?;
+    Ok(())
+}
+
+/// Gets every day from `start` to `end` (inclusive) that has a bell schedule.
+pub async fn get_schedule_days(ctx: &mut Ctx, start: NaiveDate, end: NaiveDate) -> Result<Vec<ScheduleDay>, sqlx::Error> {
+    let start = start.signed_duration_since(NaiveDate::default()).num_days() as f64;
+    let end = end.signed_duration_since(NaiveDate::default()).num_days() as f64;
+
+    let get_days_query = prepared_query!(
+        r#"
+            SELECT
+                EXTRACT(EPOCH FROM sc.date)::float / 86400 as "date!",
+                bs.id,
+                bs.name
+            FROM schedule_calendar AS sc
+                INNER JOIN bell_schedules AS bs ON bs.id = sc.schedule_id
+            WHERE
+                DATE '1/1/1970' + $1 * INTERVAL '1 day' <= sc.date AND
+                sc.date <= DATE '1/1/1970' + $2 * INTERVAL '1 day'
+            ORDER BY sc.date;
+        "#;
+        {
+            date: f64,
+            id: Uuid,
+            name: String,
+        };
+        start,
+        end,
+    );
+
+    get_days_query
+        .fetch_all(&mut *ctx)
+        .await?
+        .into_iter()
+        .map(|day| {
+            let date = NaiveDate::default()
+                .checked_add_days(Days::new(day.date as u64))
+                .ok_or_else(|| sqlx::Error::Decode(format!("schedule_calendar.date out of range: {}", day.date).into()))?;
+
+            Ok(ScheduleDay { date, schedule: BellSchedule { id: day.id, name: day.name } })
+        })
+        .collect()
+}
+
+/// Gets today's bell schedule, if it has one.
+pub async fn get_todays_bell_schedule(ctx: &mut Ctx) -> Result<Option<BellSchedule>, sqlx::Error> {
+    let get_today_query = prepared_query!(
+        r"
+            SELECT bs.id, bs.name
+            FROM schedule_calendar AS sc
+                INNER JOIN bell_schedules AS bs ON bs.id = sc.schedule_id
+            WHERE sc.date = CURRENT_DATE;
+        ";
+        { id: Uuid, name: String };
+    );
+
+    let schedule = get_today_query.fetch_optional(&mut *ctx).await?;
+
+    Ok(schedule.map(|schedule| BellSchedule { id: schedule.id, name: schedule.name }))
+}
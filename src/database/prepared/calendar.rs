@@ -0,0 +1,146 @@
+use chrono::{ Days, NaiveDate };
+
+use crate::types::{ CalendarDay, CalendarDayKind };
+
+use super::super::Ctx;
+use super::prepared_query;
+
+
+/// Gets every day from `start` to `end` (inclusive) in the school calendar.
+/// Weekdays are instructional and weekends aren't, unless they were set
+/// otherwise.
+pub async fn get_calendar_days(ctx: &mut Ctx, start: NaiveDate, end: NaiveDate) -> Result<Vec<CalendarDay>, sqlx::Error> {
+    let start_days_since_epoch = start.signed_duration_since(NaiveDate::default()).num_days();
+    let end_days_since_epoch = end.signed_duration_since(NaiveDate::default()).num_days();
+
+    let get_days_query = prepared_query!(
+        r#"
+            SELECT
+                days.date_idx as "date_idx!",
+                COALESCE(
+                    cd.kind,
+                    CASE
+                        WHEN EXTRACT(ISODOW FROM days.date) < 6 THEN 'instructional'
+                        ELSE 'weekend'
+                    END
+                ) as "kind!",
+                cd.name as "name?"
+            FROM (
+                SELECT date_idx, (DATE '1/1/1970' + date_idx * INTERVAL '1 day')::date as date
+                FROM generate_series($1::bigint, $2::bigint) as date_idx
+            ) as days
+                LEFT JOIN calendar_days AS cd ON cd.date = days.date
+            ORDER BY days.date_idx;
+        "#;
+        {
+            date_idx: i64,
+            kind: String,
+            name: Option<String>,
+        };
+        start_days_since_epoch,
+        end_days_since_epoch,
+    );
+
+    get_days_query
+        .fetch_all(&mut *ctx)
+        .await?
+        .into_iter()
+        .map(|day| {
+            let date = u64::try_from(day.date_idx)
+                .ok()
+                .and_then(|days| NaiveDate::default().checked_add_days(Days::new(days)))
+                .ok_or_else(|| sqlx::Error::Decode(format!("Calendar date out of range: {}", day.date_idx).into()))?;
+            let kind = CalendarDayKind::try_from_str(&day.kind)
+                .ok_or_else(|| sqlx::Error::Decode(format!("Invalid calendar day kind: {}", day.kind).into()))?;
+
+            Ok(CalendarDay { date, kind, name: day.name })
+        })
+        .collect()
+}
+
+/// Marks every day from `start` to `end` (inclusive) as `kind`, or resets
+/// them to the default calendar if `kind` is `None`.
+///
+/// `kind` can't be [`CalendarDayKind::Weekend`], since that's only a default.
+pub async fn set_calendar_days(
+    ctx: &mut Ctx,
+    start: NaiveDate,
+    end: NaiveDate,
+    kind: Option<CalendarDayKind>,
+    name: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    let start_days_since_epoch = start.signed_duration_since(NaiveDate::default()).num_days();
+    let end_days_since_epoch = end.signed_duration_since(NaiveDate::default()).num_days();
+
+    let Some(kind) = kind else {
+        let clear_days_query = prepared_query!(
+            r"
+                DELETE FROM calendar_days
+                WHERE
+                    daterange(
+                        (DATE '1/1/1970' + $1 * INTERVAL '1 day')::date,
+                        (DATE '1/1/1970' + $2 * INTERVAL '1 day')::date,
+                        '[]'::text
+                    ) @> date;
+            ";
+            {  };
+            start_days_since_epoch as f64, end_days_since_epoch as f64,
+        );
+
+        clear_days_query.execute(&mut *ctx).await?;
+        return Ok(());
+    };
+
+    let set_days_query = prepared_query!(
+        r"
+            INSERT INTO calendar_days (date, kind, name)
+            (
+                SELECT
+                    DATE '1/1/1970' + date_idx * INTERVAL '1 day',
+                    $3,
+                    $4
+                FROM generate_series($1::bigint, $2::bigint) as date_idx
+            )
+                ON CONFLICT (date)
+                DO UPDATE SET
+                    kind = EXCLUDED.kind,
+                    name = EXCLUDED.name;
+        ";
+        {  };
+        start_days_since_epoch, end_days_since_epoch,
+        kind.str(), name,
+    );
+
+    set_days_query.execute(&mut *ctx).await?;
+    Ok(())
+}
+
+/// Gets today's date by the database's `CURRENT_DATE`, so it agrees with the
+/// queries that use it.
+pub async fn get_today(ctx: &mut Ctx) -> Result<NaiveDate, sqlx::Error> {
+    let today_query = prepared_query!(
+        r#"
+            SELECT CURRENT_DATE - DATE '1/1/1970' as "today!";
+        "#;
+        { today: i32 };
+    );
+
+    let today = today_query.fetch_one(&mut *ctx).await?.today;
+
+    u64::try_from(today)
+        .ok()
+        .and_then(|days| NaiveDate::default().checked_add_days(Days::new(days)))
+        .ok_or_else(|| sqlx::Error::Decode(format!("CURRENT_DATE out of range: {today}").into()))
+}
+
+/// Checks whether today (by the database's `CURRENT_DATE`) is a school day.
+pub async fn is_school_day_today(ctx: &mut Ctx) -> Result<bool, sqlx::Error> {
+    let school_day_query = prepared_query!(
+        r#"
+            SELECT is_school_day(CURRENT_DATE) as "school_day!";
+        "#;
+        { school_day: bool };
+    );
+
+    Ok(school_day_query.fetch_one(&mut *ctx).await?.school_day)
+}
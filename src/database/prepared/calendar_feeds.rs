@@ -0,0 +1,110 @@
+use chrono::{ DateTime, Utc };
+use uuid::Uuid;
+
+use crate::types::CalendarFeed;
+
+use super::super::Ctx;
+use super::prepared_query;
+
+
+fn decode_created_at(secs: f64) -> Result<DateTime<Utc>, sqlx::Error> {
+    DateTime::from_timestamp(secs.floor() as i64, (secs.fract() * 1_000_000_000.0) as u32)
+        .ok_or_else(|| sqlx::Error::Decode(format!("calendar_feeds.created_at out of range: {secs}").into()))
+}
+
+/// Adds a calendar feed for a teacher, or the staff-wide feed if `teacher` is
+/// `None`, created by the teacher `created_by`. Only the hash of the feed's
+/// token is stored.
+pub async fn add_calendar_feed(
+    ctx: &mut Ctx,
+    teacher: Option<Uuid>,
+    created_by: Uuid,
+    token_hash: &str,
+) -> Result<CalendarFeed, sqlx::Error> {
+    let add_feed_query = prepared_query!(
+        r#"
+            INSERT INTO calendar_feeds (id, teacher_id, created_by, token_hash)
+            VALUES (uuid_generate_v4(), $1, $2, $3)
+            RETURNING id, EXTRACT(EPOCH FROM created_at)::float as "created_at!";
+        "#;
+        { id: Uuid, created_at: f64 };
+        teacher, created_by, token_hash,
+    );
+
+    let feed = add_feed_query.fetch_one(&mut *ctx).await?;
+
+    Ok(CalendarFeed { id: feed.id, teacher_id: teacher, created_by, created_at: decode_created_at(feed.created_at)? })
+}
+
+pub async fn get_calendar_feed(ctx: &mut Ctx, id: Uuid) -> Result<CalendarFeed, sqlx::Error> {
+    let get_feed_query = prepared_query!(
+        r#"
+            SELECT id, teacher_id, created_by, EXTRACT(EPOCH FROM created_at)::float as "created_at!"
+            FROM calendar_feeds
+            WHERE id = $1;
+        "#;
+        { id: Uuid, teacher_id: Option<Uuid>, created_by: Uuid, created_at: f64 };
+        id,
+    );
+
+    let feed = get_feed_query.fetch_one(&mut *ctx).await?;
+
+    Ok(CalendarFeed { id: feed.id, teacher_id: feed.teacher_id, created_by: feed.created_by, created_at: decode_created_at(feed.created_at)? })
+}
+
+/// Finds the calendar feed with a token hash, if there is one.
+pub async fn get_calendar_feed_by_hash(ctx: &mut Ctx, token_hash: &str) -> Result<Option<CalendarFeed>, sqlx::Error> {
+    let get_feed_query = prepared_query!(
+        r#"
+            SELECT id, teacher_id, created_by, EXTRACT(EPOCH FROM created_at)::float as "created_at!"
+            FROM calendar_feeds
+            WHERE token_hash = $1;
+        "#;
+        { id: Uuid, teacher_id: Option<Uuid>, created_by: Uuid, created_at: f64 };
+        token_hash,
+    );
+
+    let Some(feed) = get_feed_query.fetch_optional(&mut *ctx).await? else {
+        return Ok(None);
+    };
+
+    Ok(Some(CalendarFeed { id: feed.id, teacher_id: feed.teacher_id, created_by: feed.created_by, created_at: decode_created_at(feed.created_at)? }))
+}
+
+/// Gets a teacher's calendar feeds, or the staff-wide feeds if `teacher` is
+/// `None`.
+pub async fn get_calendar_feeds(ctx: &mut Ctx, teacher: Option<Uuid>) -> Result<Vec<CalendarFeed>, sqlx::Error> {
+    let get_feeds_query = prepared_query!(
+        r#"
+            SELECT id, teacher_id, created_by, EXTRACT(EPOCH FROM created_at)::float as "created_at!"
+            FROM calendar_feeds
+            WHERE teacher_id IS NOT DISTINCT FROM $1
+            ORDER BY created_at;
+        "#;
+        { id: Uuid, teacher_id: Option<Uuid>, created_by: Uuid, created_at: f64 };
+        teacher,
+    );
+
+    get_feeds_query
+        .fetch_all(&mut *ctx)
+        .await?
+        .into_iter()
+        .map(|feed| Ok(CalendarFeed { id: feed.id, teacher_id: feed.teacher_id, created_by: feed.created_by, created_at: decode_created_at(feed.created_at)? }))
+        .collect()
+}
+
+/// Deletes a calendar feed, so its token stops working.
+///
+/// Returns whether the feed existed.
+pub async fn delete_calendar_feed(ctx: &mut Ctx, id: Uuid) -> Result<bool, sqlx::Error> {
+    let delete_feed_query = prepared_query!(
+        r"
+            DELETE FROM calendar_feeds
+            WHERE id = $1;
+        ";
+        {  };
+        id,
+    );
+
+    Ok(delete_feed_query.execute(&mut *ctx).await?.rows_affected() > 0)
+}
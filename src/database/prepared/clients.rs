@@ -1,22 +1,29 @@
+use chrono::{ DateTime, Utc };
 use uuid::Uuid;
 
+use crate::types::Client;
 use crate::verification::scopes::Scopes;
 
 use super::super::Ctx;
 use super::prepared_query;
 
+/// Gets the client key of a client that is still usable, meaning it hasn't
+/// been revoked and hasn't expired.
 pub async fn get_client_secret(ctx: &mut Ctx, id: Uuid) -> Result<Option<String>, sqlx::Error> {
     let get_key_query = prepared_query!(
         r"
             SELECT client_key
             FROM clients
-            WHERE id = $1;
+            WHERE
+                id = $1 AND
+                revoked_at IS NULL AND
+                (expires_at IS NULL OR expires_at > now());
         ";
         { client_key: String };
         id
     );
 
-    let res = get_key_query.fetch_optional(&mut **ctx).await?;
+    let res = get_key_query.fetch_optional(&mut *ctx).await?;
 
     Ok(res.map(|key| key.client_key))
 }
@@ -32,7 +39,357 @@ pub async fn get_client_scopes(ctx: &mut Ctx, id: Uuid) -> Result<Option<Scopes>
         id
     );
 
-    let res = get_scopes_query.fetch_optional(&mut **ctx).await?;
+    let res = get_scopes_query.fetch_optional(&mut *ctx).await?;
 
     Ok(res.and_then(|scopes| Scopes::try_from_str(&scopes.scopes)))
 }
+
+/// Records that a client was just used.
+///
+/// To avoid a write on every single request, this is only updated once a
+/// minute at most.
+pub async fn mark_client_seen(ctx: &mut Ctx, id: Uuid) -> Result<(), sqlx::Error> {
+    let mark_seen_query = sqlx::query!(
+        r"
+            UPDATE clients
+            SET last_seen_at = now()
+            WHERE
+                id = $1 AND
+                (last_seen_at IS NULL OR last_seen_at < now() - INTERVAL '1 minute');
+        ",
+        id,
+    );
+
+    mark_seen_query.execute(&mut *ctx).await?;
+
+    Ok(())
+}
+
+
+fn timestamp_from_epoch(column: &str, epoch: Option<f64>) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    let Some(epoch) = epoch else {
+        return Ok(None);
+    };
+
+    DateTime::from_timestamp(
+        epoch.floor() as i64,
+        (epoch.fract() * 1_000_000_000.0) as u32,
+    )
+        .map(Some)
+        .ok_or_else(|| sqlx::Error::Decode(format!("clients.{column} out of range: {epoch}").into()))
+}
+
+fn epoch_from_timestamp(timestamp: Option<DateTime<Utc>>) -> Option<f64> {
+    timestamp.map(|timestamp| timestamp.timestamp_micros() as f64 / 1_000_000.0)
+}
+
+/// Clients with unparsable scopes can't authenticate at all (see
+/// [`get_client_scopes`]), so they're reported as having no scopes.
+fn client_from_row(
+    id: Uuid,
+    scopes: &str,
+    description: String,
+    expires_at: Option<f64>,
+    revoked_at: Option<f64>,
+    last_seen_at: Option<f64>,
+    rate_limit: Option<i32>,
+) -> Result<Client, sqlx::Error> {
+    Ok(Client {
+        id,
+        scopes: Scopes::try_from_str(scopes).unwrap_or_else(Scopes::none),
+        description,
+        expires_at: timestamp_from_epoch("expires_at", expires_at)?,
+        revoked_at: timestamp_from_epoch("revoked_at", revoked_at)?,
+        last_seen_at: timestamp_from_epoch("last_seen_at", last_seen_at)?,
+        rate_limit,
+    })
+}
+
+pub async fn get_client(ctx: &mut Ctx, id: Uuid) -> Result<Client, sqlx::Error> {
+    let get_client_query = prepared_query!(
+        r"
+            SELECT
+                id, scopes, description,
+                EXTRACT(EPOCH FROM expires_at)::float as expires_at,
+                EXTRACT(EPOCH FROM revoked_at)::float as revoked_at,
+                EXTRACT(EPOCH FROM last_seen_at)::float as last_seen_at,
+                rate_limit
+            FROM clients
+            WHERE id = $1;
+        ";
+        {
+            id: Uuid,
+            scopes: String,
+            description: String,
+            expires_at: Option<f64>,
+            revoked_at: Option<f64>,
+            last_seen_at: Option<f64>,
+            rate_limit: Option<i32>,
+        };
+        id,
+    );
+
+    let client = get_client_query.fetch_one(&mut *ctx).await?;
+
+    client_from_row(
+        client.id, &client.scopes, client.description,
+        client.expires_at, client.revoked_at, client.last_seen_at,
+            client.rate_limit,
+    )
+}
+
+pub async fn get_all_clients(ctx: &mut Ctx) -> Result<Vec<Client>, sqlx::Error> {
+    let get_clients_query = prepared_query!(
+        r"
+            SELECT
+                id, scopes, description,
+                EXTRACT(EPOCH FROM expires_at)::float as expires_at,
+                EXTRACT(EPOCH FROM revoked_at)::float as revoked_at,
+                EXTRACT(EPOCH FROM last_seen_at)::float as last_seen_at,
+                rate_limit
+            FROM clients
+            ORDER BY description, id;
+        ";
+        {
+            id: Uuid,
+            scopes: String,
+            description: String,
+            expires_at: Option<f64>,
+            revoked_at: Option<f64>,
+            last_seen_at: Option<f64>,
+            rate_limit: Option<i32>,
+        };
+    );
+
+    let clients = get_clients_query.fetch_all(&mut *ctx).await?;
+
+    clients
+        .into_iter()
+        .map(|client| client_from_row(
+            client.id, &client.scopes, client.description,
+            client.expires_at, client.revoked_at, client.last_seen_at,
+            client.rate_limit,
+        ))
+        .collect()
+}
+
+/// Gets every client that hasn't been revoked yet but either has expired or
+/// hasn't been used in `unused_for_days` days. Clients that were never used
+/// are included too.
+///
+/// The least recently used clients come first.
+pub async fn get_stale_clients(ctx: &mut Ctx, unused_for_days: i32) -> Result<Vec<Client>, sqlx::Error> {
+    let get_stale_query = prepared_query!(
+        r"
+            SELECT
+                id, scopes, description,
+                EXTRACT(EPOCH FROM expires_at)::float as expires_at,
+                EXTRACT(EPOCH FROM revoked_at)::float as revoked_at,
+                EXTRACT(EPOCH FROM last_seen_at)::float as last_seen_at,
+                rate_limit
+            FROM clients
+            WHERE
+                revoked_at IS NULL AND (
+                    expires_at <= now() OR
+                    last_seen_at IS NULL OR
+                    last_seen_at < now() - $1 * INTERVAL '1 day'
+                )
+            ORDER BY last_seen_at ASC NULLS FIRST, id;
+        ";
+        {
+            id: Uuid,
+            scopes: String,
+            description: String,
+            expires_at: Option<f64>,
+            revoked_at: Option<f64>,
+            last_seen_at: Option<f64>,
+            rate_limit: Option<i32>,
+        };
+        unused_for_days as f64,
+    );
+
+    let clients = get_stale_query.fetch_all(&mut *ctx).await?;
+
+    clients
+        .into_iter()
+        .map(|client| client_from_row(
+            client.id, &client.scopes, client.description,
+            client.expires_at, client.revoked_at, client.last_seen_at,
+            client.rate_limit,
+        ))
+        .collect()
+}
+
+pub async fn create_client(
+    ctx: &mut Ctx,
+    id: Uuid,
+    keystr: &str,
+    scopes: &Scopes,
+    description: &str,
+    expires_at: Option<DateTime<Utc>>,
+) -> Result<Client, sqlx::Error> {
+    let create_client_query = sqlx::query!(
+        r"
+            INSERT INTO clients (id, client_key, scopes, description, expires_at)
+            VALUES ($1, $2, $3, $4, to_timestamp($5));
+        ",
+        id, keystr, scopes.to_string(), description, epoch_from_timestamp(expires_at),
+    );
+
+    create_client_query.execute(&mut *ctx).await?;
+
+    get_client(ctx, id).await
+}
+
+pub async fn set_client_key(ctx: &mut Ctx, id: Uuid, keystr: &str) -> Result<Client, sqlx::Error> {
+    let set_key_query = sqlx::query!(
+        r"
+            UPDATE clients
+            SET client_key = $2
+            WHERE id = $1;
+        ",
+        id, keystr,
+    );
+
+    if set_key_query.execute(&mut *ctx).await?.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    get_client(ctx, id).await
+}
+
+pub async fn set_client_scopes(ctx: &mut Ctx, id: Uuid, scopes: &Scopes) -> Result<Client, sqlx::Error> {
+    let set_scopes_query = sqlx::query!(
+        r"
+            UPDATE clients
+            SET scopes = $2
+            WHERE id = $1;
+        ",
+        id, scopes.to_string(),
+    );
+
+    if set_scopes_query.execute(&mut *ctx).await?.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    get_client(ctx, id).await
+}
+
+pub async fn set_client_description(ctx: &mut Ctx, id: Uuid, description: &str) -> Result<Client, sqlx::Error> {
+    let set_description_query = sqlx::query!(
+        r"
+            UPDATE clients
+            SET description = $2
+            WHERE id = $1;
+        ",
+        id, description,
+    );
+
+    if set_description_query.execute(&mut *ctx).await?.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    get_client(ctx, id).await
+}
+
+/// Sets when a client stops working. `None` means it never expires.
+pub async fn set_client_expiry(ctx: &mut Ctx, id: Uuid, expires_at: Option<DateTime<Utc>>) -> Result<Client, sqlx::Error> {
+    let set_expiry_query = sqlx::query!(
+        r"
+            UPDATE clients
+            SET expires_at = to_timestamp($2)
+            WHERE id = $1;
+        ",
+        id, epoch_from_timestamp(expires_at),
+    );
+
+    if set_expiry_query.execute(&mut *ctx).await?.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    get_client(ctx, id).await
+}
+
+/// Sets how many requests per minute a client may make. `None` uses the
+/// server default.
+pub async fn set_client_rate_limit(ctx: &mut Ctx, id: Uuid, rate_limit: Option<i32>) -> Result<Client, sqlx::Error> {
+    let set_rate_limit_query = sqlx::query!(
+        r"
+            UPDATE clients
+            SET rate_limit = $2
+            WHERE id = $1;
+        ",
+        id, rate_limit,
+    );
+
+    if set_rate_limit_query.execute(&mut *ctx).await?.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    get_client(ctx, id).await
+}
+
+/// Gets the rate limit of a client that is still usable.
+/// 
+/// Returns `None` if there is no such client, and `Some(None)` if it uses the
+/// server default.
+pub async fn get_client_rate_limit(ctx: &mut Ctx, id: Uuid) -> Result<Option<Option<i32>>, sqlx::Error> {
+    let get_rate_limit_query = prepared_query!(
+        r"
+            SELECT rate_limit
+            FROM clients
+            WHERE
+                id = $1 AND
+                revoked_at IS NULL AND
+                (expires_at IS NULL OR expires_at > now());
+        ";
+        { rate_limit: Option<i32> };
+        id,
+    );
+
+    let res = get_rate_limit_query.fetch_optional(&mut *ctx).await?;
+
+    Ok(res.map(|row| row.rate_limit))
+}
+
+/// Revokes a client, so its id and secret stop working immediately. The row
+/// is kept around so it still shows up when listing clients.
+///
+/// Returns whether the client existed and wasn't already revoked.
+pub async fn revoke_client(ctx: &mut Ctx, id: Uuid) -> Result<bool, sqlx::Error> {
+    let revoke_query = sqlx::query!(
+        r"
+            UPDATE clients
+            SET revoked_at = now()
+            WHERE
+                id = $1 AND
+                revoked_at IS NULL;
+        ",
+        id,
+    );
+
+    let res = revoke_query.execute(&mut *ctx).await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// Swaps a client's key for a new one, but only if it still has `old_keystr`,
+/// so it can't overwrite a key that was rotated in the meantime.
+/// 
+/// Returns whether the key was replaced.
+pub async fn replace_client_key(ctx: &mut Ctx, id: Uuid, old_keystr: &str, new_keystr: &str) -> Result<bool, sqlx::Error> {
+    let replace_key_query = sqlx::query!(
+        r"
+            UPDATE clients
+            SET client_key = $3
+            WHERE
+                id = $1 AND
+                client_key = $2;
+        ",
+        id, old_keystr, new_keystr,
+    );
+
+    let res = replace_key_query.execute(&mut *ctx).await?;
+
+    Ok(res.rows_affected() > 0)
+}
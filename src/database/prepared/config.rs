@@ -2,7 +2,7 @@ use crate::database::Ctx;
 
 use super::prepared_query;
 
-/** 
+/*
  * Sheet ID
  */
 
@@ -18,7 +18,7 @@ mod sheet_id {
             { sheet_id: String };
         );
     
-        let res = get_key_query.fetch_one(&mut **ctx).await?;
+        let res = get_key_query.fetch_one(&mut *ctx).await?;
     
         Ok(res.sheet_id)
     }
@@ -33,7 +33,7 @@ mod sheet_id {
             id
         );
     
-        set_key_query.execute(&mut **ctx).await?;
+        set_key_query.execute(&mut *ctx).await?;
     
         Ok(())
     }
@@ -46,7 +46,7 @@ pub use sheet_id::{ get as get_sheet_id, set as set_sheet_id };
 
 
 
-/** 
+/*
  * Report To
  */
 
@@ -62,7 +62,7 @@ mod report_to {
             { report_to: String };
         );
     
-        let res = get_key_query.fetch_one(&mut **ctx).await?;
+        let res = get_key_query.fetch_one(&mut *ctx).await?;
     
         Ok(res.report_to)
     }
@@ -77,7 +77,7 @@ mod report_to {
             report_to
         );
     
-        set_key_query.execute(&mut **ctx).await?;
+        set_key_query.execute(&mut *ctx).await?;
     
         Ok(())
     }
@@ -86,7 +86,7 @@ pub use report_to::{ get as get_report_to, set as set_report_to };
 
 
 
-/** 
+/*
  * Attribs
  */
 
@@ -105,7 +105,7 @@ mod attribs {
             { attribs: JsonValue };
         );
     
-        let res = get_key_query.fetch_one(&mut **ctx).await?;
+        let res = get_key_query.fetch_one(&mut *ctx).await?;
         let attribs = res.attribs;
 
         if let JsonValue::Object(attribs) = attribs {
@@ -126,7 +126,7 @@ mod attribs {
             key.as_slice(), attrib
         );
     
-        set_key_query.execute(&mut **ctx).await?;
+        set_key_query.execute(&mut *ctx).await?;
     
         Ok(())
     }
@@ -142,7 +142,7 @@ mod attribs {
             key.as_slice()
         );
     
-        set_key_query.execute(&mut **ctx).await?;
+        set_key_query.execute(&mut *ctx).await?;
     
         Ok(())
     }
@@ -158,7 +158,7 @@ mod attribs {
             attribs
         );
     
-        set_key_query.execute(&mut **ctx).await?;
+        set_key_query.execute(&mut *ctx).await?;
     
         Ok(())
     }
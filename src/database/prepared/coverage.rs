@@ -0,0 +1,231 @@
+use uuid::Uuid;
+
+use crate::types::{ Coverage, CoverageConflict, Substitute };
+
+use super::super::Ctx;
+use super::prepared_query;
+
+
+fn coverage_from_row(
+    teacher_id: Uuid,
+    period_id: Uuid,
+    substitute_id: Option<Uuid>,
+    external_name: Option<String>,
+) -> Result<Coverage, sqlx::Error> {
+    let substitute = match (substitute_id, external_name) {
+        (Some(id), _) => Substitute::Teacher(id),
+        (None, Some(name)) => Substitute::External(name),
+        (None, None) => return Err(sqlx::Error::Decode(
+            format!("Coverage of teacher {teacher_id} in period {period_id} has no substitute").into(),
+        )),
+    };
+
+    Ok(Coverage { teacher_id, period_id, substitute })
+}
+
+/// Gets today's substitute for a teacher in a period, if they're still absent
+/// in it.
+pub async fn get_coverage(ctx: &mut Ctx, teacher: Uuid, period: Uuid) -> Result<Option<Coverage>, sqlx::Error> {
+    let get_coverage_query = prepared_query!(
+        r"
+            SELECT c.teacher_id, c.period_id, c.substitute_id, c.external_name
+            FROM coverage AS c
+                INNER JOIN teachers AS t ON t.id = c.teacher_id
+            WHERE
+                c.teacher_id = $1 AND
+                c.period_id = $2 AND
+                c.date = CURRENT_DATE AND
+                (t.fully_absent OR EXISTS (
+                    SELECT 1 FROM absence_xref AS a
+                    WHERE a.teacher_id = c.teacher_id AND a.period_id = c.period_id
+                ));
+        ";
+        {
+            teacher_id: Uuid,
+            period_id: Uuid,
+            substitute_id: Option<Uuid>,
+            external_name: Option<String>,
+        };
+        teacher,
+        period,
+    );
+
+    get_coverage_query
+        .fetch_optional(&mut *ctx)
+        .await?
+        .map(|row| coverage_from_row(row.teacher_id, row.period_id, row.substitute_id, row.external_name))
+        .transpose()
+}
+
+/// Checks whether `substitute` (or an external substitute, if `None`) can
+/// cover `teacher` in `period`.
+pub async fn find_coverage_conflict(
+    ctx: &mut Ctx,
+    teacher: Uuid,
+    period: Uuid,
+    substitute: Option<Uuid>,
+) -> Result<Option<CoverageConflict>, sqlx::Error> {
+    let conflict_query = prepared_query!(
+        r#"
+            SELECT
+                EXISTS (
+                    SELECT 1 FROM absence_xref
+                    WHERE teacher_id = $1 AND period_id = $2
+                ) OR EXISTS (
+                    SELECT 1 FROM teachers
+                    WHERE id = $1 AND fully_absent
+                ) as "teacher_absent!",
+                EXISTS (
+                    SELECT 1 FROM absence_xref
+                    WHERE teacher_id = $3 AND period_id = $2
+                ) OR EXISTS (
+                    SELECT 1 FROM teachers
+                    WHERE id = $3 AND fully_absent
+                ) as "substitute_absent!",
+                (
+                    SELECT teacher_id FROM coverage
+                    WHERE
+                        substitute_id = $3 AND
+                        period_id = $2 AND
+                        date = CURRENT_DATE AND
+                        teacher_id <> $1
+                    LIMIT 1
+                ) as already_covering;
+        "#;
+        {
+            teacher_absent: bool,
+            substitute_absent: bool,
+            already_covering: Option<Uuid>,
+        };
+        teacher,
+        period,
+        substitute,
+    );
+
+    let state = conflict_query.fetch_one(&mut *ctx).await?;
+
+    let conflict = if !state.teacher_absent {
+        Some(CoverageConflict::NotAbsent)
+    } else if substitute == Some(teacher) {
+        Some(CoverageConflict::SelfCoverage)
+    } else if state.substitute_absent {
+        Some(CoverageConflict::SubstituteAbsent)
+    } else {
+        state.already_covering.map(CoverageConflict::DoubleBooked)
+    };
+
+    Ok(conflict)
+}
+
+/// Assigns a substitute to cover a teacher in a period today, replacing any
+/// existing substitute.
+///
+/// This doesn't check for conflicts, see [`find_coverage_conflict`].
+pub async fn set_coverage(
+    ctx: &mut Ctx,
+    teacher: Uuid,
+    period: Uuid,
+    substitute: Substitute,
+) -> Result<Coverage, sqlx::Error> {
+    let (substitute_id, external_name) = match &substitute {
+        Substitute::Teacher(id) => (Some(*id), None),
+        Substitute::External(name) => (None, Some(name.as_str())),
+    };
+
+    let set_coverage_query = prepared_query!(
+        r"
+            INSERT INTO coverage (teacher_id, period_id, date, substitute_id, external_name)
+            VALUES ($1, $2, CURRENT_DATE, $3, $4)
+                ON CONFLICT (teacher_id, period_id, date)
+                DO UPDATE SET
+                    substitute_id = $3,
+                    external_name = $4;
+        ";
+        {  };
+        teacher, period, substitute_id, external_name,
+    );
+
+    set_coverage_query.execute(&mut *ctx).await?;
+
+    Ok(Coverage { teacher_id: teacher, period_id: period, substitute })
+}
+
+/// Removes the substitute covering a teacher in a period today.
+///
+/// Returns whether there was one.
+pub async fn clear_coverage(ctx: &mut Ctx, teacher: Uuid, period: Uuid) -> Result<bool, sqlx::Error> {
+    let clear_coverage_query = prepared_query!(
+        r"
+            DELETE FROM coverage
+            WHERE teacher_id = $1 AND period_id = $2 AND date = CURRENT_DATE;
+        ";
+        {  };
+        teacher, period,
+    );
+
+    Ok(clear_coverage_query.execute(&mut *ctx).await?.rows_affected() > 0)
+}
+
+/// Removes a teacher's coverage today for every period they're no longer
+/// absent in, and drops them as a substitute from every period they're now
+/// absent in.
+///
+/// A fully absent teacher keeps all of their coverage, since they're absent in
+/// every period.
+pub async fn remove_stale_coverage(
+    ctx: &mut Ctx,
+    teacher: Uuid,
+    periods: &[Uuid],
+    fully_absent: bool,
+) -> Result<(), sqlx::Error> {
+    let remove_stale_query = prepared_query!(
+        r"
+            DELETE FROM coverage
+            WHERE
+                date = CURRENT_DATE AND (
+                    (teacher_id = $1 AND NOT $3 AND NOT (period_id = ANY($2))) OR
+                    (substitute_id = $1 AND ($3 OR period_id = ANY($2)))
+                );
+        ";
+        {  };
+        teacher, periods, fully_absent,
+    );
+
+    remove_stale_query.execute(&mut *ctx).await?;
+    Ok(())
+}
+
+/// Removes every substitute assignment from before today, since coverage only
+/// lasts for a day. Which periods were covered is kept in `absence_history`
+/// first, for the uncovered periods report.
+pub async fn clear_all_coverage(ctx: &mut Ctx) -> Result<(), sqlx::Error> {
+    let archive_covered_query = prepared_query!(
+        r"
+            UPDATE absence_history AS ah
+            SET covered_periods = covered.periods
+            FROM (
+                SELECT teacher_id, date, array_agg(period_id) AS periods
+                FROM coverage
+                WHERE date < CURRENT_DATE
+                GROUP BY teacher_id, date
+            ) AS covered
+            WHERE
+                ah.teacher = covered.teacher_id AND
+                ah.date = covered.date;
+        ";
+        {  };
+    );
+
+    archive_covered_query.execute(&mut *ctx).await?;
+
+    let clear_all_query = prepared_query!(
+        r"
+            DELETE FROM coverage
+            WHERE date < CURRENT_DATE;
+        ";
+        {  };
+    );
+
+    clear_all_query.execute(&mut *ctx).await?;
+    Ok(())
+}
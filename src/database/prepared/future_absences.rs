@@ -1,17 +1,26 @@
-use std::collections::HashMap;
+use std::collections::{ HashMap, HashSet };
 use std::sync::Arc;
 
 use chrono::{NaiveDate, Days};
 use sqlx::{ query, query_as };
 use uuid::Uuid;
 
-use crate::types::{PackedAbsenceState, Period, TeacherAbsenceStateList};
+use crate::types::{AbsenceRequestStatus, PackedAbsenceState, Period, TeacherAbsenceStateList};
 
 use super::super::Ctx;
 use super::absences::update_absences_for_teacher;
-use super::period::get_all_periods;
-
-
+use super::period::get_periods;
+use super::teacher::get_teachers;
+use super::recurring_absences::get_recurring_days;
+
+
+/// Sets a teacher's future absence on every school day from `start` to `end`
+/// (inclusive), replacing whatever was there before.
+/// 
+/// Anything other than a pending request is counted as reviewed now, by
+/// `reviewed_by`. Pending requests that don't change an approved day leave it
+/// approved, but changing one sends it back for review.
+#[allow(clippy::too_many_arguments)]
 pub async fn set_future_day(
     ctx: &mut Ctx,
 
@@ -22,6 +31,9 @@ pub async fn set_future_day(
     periods: &[Uuid],
     fully_absent: bool,
     comment: Option<String>,
+
+    status: AbsenceRequestStatus,
+    reviewed_by: Option<Uuid>,
 ) -> Result<(), sqlx::Error> {
     let start_days_since_epoch = start.signed_duration_since(NaiveDate::default()).num_days();
     let end_days_since_epoch = end.signed_duration_since(NaiveDate::default()).num_days();
@@ -31,7 +43,9 @@ pub async fn set_future_day(
             INSERT INTO teacher_future_schedules (
                 teacher, date,
 
-                periods, fully_absent, comment
+                periods, fully_absent, comment,
+
+                status, requested_at, reviewed_by, reviewed_at
             )
             (
                 SELECT
@@ -39,40 +53,63 @@ pub async fn set_future_day(
                     DATE '1/1/1970' + date_idx * INTERVAL '1 day',
                     $2,
                     $3,
-                    $4
+                    $4,
+                    $7::varchar,
+                    now(),
+                    $8,
+                    CASE WHEN $7::varchar = 'pending' THEN NULL ELSE now() END
                 FROM generate_series($5::bigint, $6::bigint) as date_idx
+                WHERE is_school_day((DATE '1/1/1970' + date_idx * INTERVAL '1 day')::date)
             )
                 ON CONFLICT (teacher, date)
                 DO UPDATE SET
                     periods = $2,
                     fully_absent = $3,
-                    comment = $4;
+                    comment = $4,
+                    status = EXCLUDED.status,
+                    requested_at = EXCLUDED.requested_at,
+                    reviewed_by = EXCLUDED.reviewed_by,
+                    reviewed_at = EXCLUDED.reviewed_at
+                WHERE NOT (
+                    teacher_future_schedules.status = 'approved' AND
+                    EXCLUDED.status = 'pending' AND
+                    teacher_future_schedules.periods = EXCLUDED.periods AND
+                    teacher_future_schedules.fully_absent = EXCLUDED.fully_absent AND
+                    teacher_future_schedules.comment IS NOT DISTINCT FROM EXCLUDED.comment
+                );
         "#,
         id,
         &periods, fully_absent, comment,
         start_days_since_epoch, end_days_since_epoch,
+        status.str(), reviewed_by,
     );
 
-    add_or_update_future_date.execute(&mut **ctx).await?;
+    add_or_update_future_date.execute(&mut *ctx).await?;
 
     Ok(())
 }
 
+/// Cancels a teacher's pending and approved future absences from `start` to
+/// `end` (inclusive).
+///
+/// Returns how many days were cancelled.
 pub async fn clear_future_day(
     ctx: &mut Ctx,
     
     start: NaiveDate,
     end: NaiveDate,
     id: Uuid,
-) -> Result<(), sqlx::Error> {
+) -> Result<u64, sqlx::Error> {
     let start_days_since_epoch = start.signed_duration_since(NaiveDate::default()).num_days() as f64;
     let end_days_since_epoch = end.signed_duration_since(NaiveDate::default()).num_days() as f64;
 
     let remove_teacher_oauth = query!(
         r#"
-            DELETE FROM teacher_future_schedules
+            UPDATE teacher_future_schedules
+            SET status = 'cancelled'
             WHERE
                 teacher = $1 AND
+                status IN ('pending', 'approved') AND
                 daterange(
                     (DATE '1/1/1970' + $2 * INTERVAL '1 day')::date,
                     (DATE '1/1/1970' + $3 * INTERVAL '1 day')::date,
@@ -84,9 +121,7 @@ pub async fn clear_future_day(
         end_days_since_epoch,
     );
 
-    remove_teacher_oauth.execute(&mut **ctx).await?;
-
-    Ok(())
+    Ok(remove_teacher_oauth.execute(&mut *ctx).await?.rows_affected())
 }
 
 
@@ -99,7 +134,31 @@ pub struct FutureDay {
     pub comment: Option<String>,
 }
 
-pub async fn flush_today(ctx: &mut Ctx) -> Result<(), sqlx::Error> {
+/// Applies today's approved future absences and recurring absences to the
+/// current absence state, then removes every future absence that is no longer
+/// in the future. A future absence takes the place of a recurring one on the
+/// same day.
+/// 
+/// Does nothing if today isn't a school day.
+/// 
+/// Returns the ids of the teachers whose absences were updated.
+pub async fn flush_today(ctx: &mut Ctx) -> Result<Vec<Uuid>, sqlx::Error> {
+    let school_day_query = query!(
+        r#"
+            SELECT
+                is_school_day(CURRENT_DATE) as "school_day!",
+                CURRENT_DATE - DATE '1/1/1970' as "today!";
+        "#,
+    );
+
+    let school_day = school_day_query.fetch_one(&mut *ctx).await?;
+    if !school_day.school_day {
+        return Ok(Vec::new());
+    }
+    let today = NaiveDate::default()
+        .checked_add_days(Days::new(school_day.today.unsigned_abs().into()))
+        .ok_or_else(|| sqlx::Error::Decode(format!("CURRENT_DATE out of range: {}", school_day.today).into()))?;
+
     let get_futures_for_today = query_as!(
         FutureDay,
         r#"
@@ -110,7 +169,7 @@ pub async fn flush_today(ctx: &mut Ctx) -> Result<(), sqlx::Error> {
                 fully_absent,
                 comment
             FROM teacher_future_schedules
-            WHERE date = CURRENT_DATE;
+            WHERE date = CURRENT_DATE AND status = 'approved';
         "#,
     );
 
@@ -121,16 +180,54 @@ pub async fn flush_today(ctx: &mut Ctx) -> Result<(), sqlx::Error> {
         "#,
     );
 
-    let today_data = get_futures_for_today.fetch_all(&mut **ctx).await?;
-    remove_past.execute(&mut **ctx).await?;
+    let mut today_data = get_futures_for_today.fetch_all(&mut *ctx).await?;
+    remove_past.execute(&mut *ctx).await?;
+
+    let recurring_today = get_recurring_days(ctx, None, today, today).await?;
+    for recurring in recurring_today {
+        if today_data.iter().any(|future_day| future_day.id == recurring.teacher_id) {
+            continue;
+        }
+        today_data.push(FutureDay {
+            id: recurring.teacher_id,
+            date: recurring.date,
+            periods: recurring.periods,
+            fully_absent: recurring.fully_absent,
+            comment: recurring.comment,
+        });
+    }
+
+    // Archived teachers and periods stay off the absence board
+    let archived_query = query!(
+        r#"
+            SELECT
+                ARRAY(SELECT id FROM teachers WHERE archived) as "teachers!",
+                ARRAY(SELECT id FROM periods WHERE archived) as "periods!";
+        "#,
+    );
+    let archived = archived_query.fetch_one(&mut *ctx).await?;
+    let archived_teachers: HashSet<Uuid> = archived.teachers.into_iter().collect();
+    let archived_periods: HashSet<Uuid> = archived.periods.into_iter().collect();
+
+    today_data.retain(|teacher_today| !archived_teachers.contains(&teacher_today.id));
+    for teacher_today in &mut today_data {
+        teacher_today.periods.retain(|period| !archived_periods.contains(period));
+    }
 
+    let mut updated = Vec::with_capacity(today_data.len());
     for teacher_today in today_data {
-        update_absences_for_teacher(ctx, teacher_today.id, &teacher_today.periods, teacher_today.fully_absent).await?;
+        update_absences_for_teacher(
+            ctx,
+            teacher_today.id,
+            &teacher_today.periods, teacher_today.fully_absent,
+            teacher_today.comment,
+        ).await?;
+        updated.push(teacher_today.id);
     }
-    Ok(())
+    Ok(updated)
 }
 
-struct BarebonesFutureDay {
+pub (super) struct BarebonesFutureDay {
     pub teacher_id: Uuid,
     pub date: f64,
     pub periods: Vec<Uuid>,
@@ -162,7 +259,32 @@ impl std::error::Error for ColumnDecodeError {
     }
 }
 
-fn get_packed_absence_state(future_day: BarebonesFutureDay, period_map: &HashMap<Uuid, Arc<Period>>) -> Result<PackedAbsenceState, sqlx::Error> {
+/// Adds the days in `recurring` to `future_days`, except for the ones where the
+/// teacher already has a future absence set.
+fn merge_recurring_days(mut future_days: Vec<BarebonesFutureDay>, recurring: Vec<BarebonesFutureDay>) -> Vec<BarebonesFutureDay> {
+    let set_days: HashSet<_> = future_days
+        .iter()
+        .map(|future_day| (future_day.teacher_id, future_day.date as i64))
+        .collect();
+
+    future_days.extend(
+        recurring
+            .into_iter()
+            .filter(|recurring_day| !set_days.contains(&(recurring_day.teacher_id, recurring_day.date as i64))),
+    );
+    future_days
+}
+
+/// Takes periods that aren't in `period_map` out of every day, then drops the
+/// partial absences that no longer have any periods.
+fn drop_missing_periods(future_days: &mut Vec<BarebonesFutureDay>, period_map: &HashMap<Uuid, Arc<Period>>) {
+    for future_day in future_days.iter_mut() {
+        future_day.periods.retain(|id| period_map.contains_key(id));
+    }
+    future_days.retain(|future_day| future_day.fully_absent || !future_day.periods.is_empty());
+}
+
+pub (super) fn get_packed_absence_state(future_day: BarebonesFutureDay, period_map: &HashMap<Uuid, Arc<Period>>) -> Result<PackedAbsenceState, sqlx::Error> {
     let BarebonesFutureDay { teacher_id, periods, date, fully_absent, comment } = future_day;
 
     let periods: Result<Vec<_>, _> = periods.into_iter()
@@ -194,9 +316,13 @@ fn get_packed_absence_state(future_day: BarebonesFutureDay, period_map: &HashMap
     })
 }
 
-pub async fn get_future_days_for_teacher(ctx: &mut Ctx, id: Uuid, start: NaiveDate, end: NaiveDate) -> Result<Vec<PackedAbsenceState>, sqlx::Error> {
-    let start = start.signed_duration_since(NaiveDate::default()).num_days() as f64;
-    let end = end.signed_duration_since(NaiveDate::default()).num_days() as f64;
+/// Gets a teacher's approved future absences and the occurrences of their
+/// recurring absences from `start` to `end` (inclusive).
+///
+/// Archived periods are left out of the returned states.
+pub async fn get_future_days_for_teacher(ctx: &mut Ctx, id: Uuid, start_date: NaiveDate, end_date: NaiveDate) -> Result<Vec<PackedAbsenceState>, sqlx::Error> {
+    let start = start_date.signed_duration_since(NaiveDate::default()).num_days() as f64;
+    let end = end_date.signed_duration_since(NaiveDate::default()).num_days() as f64;
 
     let get_future_days_in_range = query_as!(
         BarebonesFutureDay,
@@ -210,6 +336,7 @@ pub async fn get_future_days_for_teacher(ctx: &mut Ctx, id: Uuid, start: NaiveDa
             FROM teacher_future_schedules as tfs
             WHERE
                 tfs.teacher = $1 AND
+                tfs.status = 'approved' AND
                 DATE '1/1/1970' + $2 * INTERVAL '1 day' <= tfs.date AND
                 tfs.date <= DATE '1/1/1970' + $3 * INTERVAL '1 day';
         "#,
@@ -218,22 +345,32 @@ pub async fn get_future_days_for_teacher(ctx: &mut Ctx, id: Uuid, start: NaiveDa
         end,
     );
 
-    let period_map: HashMap<_, _> = get_all_periods(ctx)
+    let period_map: HashMap<_, _> = get_periods(ctx, false)
         .await?
         .into_iter()
         .map(|period| (period.id, Arc::new(period)))
         .collect();
 
-    let data = get_future_days_in_range.fetch_all(&mut **ctx).await?;
+    let data = get_future_days_in_range.fetch_all(&mut *ctx).await?;
+    let recurring = get_recurring_days(ctx, Some(id), start_date, end_date).await?;
+
+    let mut data = merge_recurring_days(data, recurring);
+    data.sort_by(|a, b| a.date.total_cmp(&b.date));
+
+    drop_missing_periods(&mut data, &period_map);
 
     data.into_iter()
         .map(|future_day| get_packed_absence_state(future_day, &period_map))
         .collect()
 }
 
-pub async fn get_all_future_days(ctx: &mut Ctx, start: NaiveDate, end: NaiveDate) -> Result<Vec<TeacherAbsenceStateList>, sqlx::Error> {
-    let start = start.signed_duration_since(NaiveDate::default()).num_days() as f64;
-    let end = end.signed_duration_since(NaiveDate::default()).num_days() as f64;
+/// Gets every teacher's approved future absences and the occurrences of their
+/// recurring absences from `start` to `end` (inclusive).
+///
+/// Archived teachers are left out, and so are archived periods.
+pub async fn get_all_future_days(ctx: &mut Ctx, start_date: NaiveDate, end_date: NaiveDate) -> Result<Vec<TeacherAbsenceStateList>, sqlx::Error> {
+    let start = start_date.signed_duration_since(NaiveDate::default()).num_days() as f64;
+    let end = end_date.signed_duration_since(NaiveDate::default()).num_days() as f64;
 
     let get_all_future_days_in_range = query_as!(
         BarebonesFutureDay,
@@ -246,6 +383,7 @@ pub async fn get_all_future_days(ctx: &mut Ctx, start: NaiveDate, end: NaiveDate
                 comment
             FROM teacher_future_schedules as tfs
             WHERE
+                tfs.status = 'approved' AND
                 DATE '1/1/1970' + $1 * INTERVAL '1 day' <= tfs.date AND
                 tfs.date <= DATE '1/1/1970' + $2 * INTERVAL '1 day'
             ORDER BY tfs.teacher;
@@ -254,13 +392,24 @@ pub async fn get_all_future_days(ctx: &mut Ctx, start: NaiveDate, end: NaiveDate
         end,
     );
 
-    let period_map: HashMap<_, _> = get_all_periods(ctx)
+    let period_map: HashMap<_, _> = get_periods(ctx, false)
         .await?
         .into_iter()
         .map(|period| (period.id, Arc::new(period)))
         .collect();
+    let teachers: HashSet<_> = get_teachers(ctx, false)
+        .await?
+        .iter()
+        .map(|teacher| teacher.get_id())
+        .collect();
+
+    let data = get_all_future_days_in_range.fetch_all(&mut *ctx).await?;
+    let recurring = get_recurring_days(ctx, None, start_date, end_date).await?;
 
-    let data = get_all_future_days_in_range.fetch_all(&mut **ctx).await?;
+    let mut data = merge_recurring_days(data, recurring);
+    data.retain(|future_day| teachers.contains(&future_day.teacher_id));
+    data.sort_by(|a, b| a.date.total_cmp(&b.date));
+    drop_missing_periods(&mut data, &period_map);
 
     let future_day_iterator = data.into_iter().map(|future_day| get_packed_absence_state(future_day, &period_map));
     
@@ -1,4 +1,4 @@
-use sqlx::query_as;
+use sqlx::{ query_as, Connection };
 use uuid::Uuid;
 
 use super::super::Ctx;
@@ -12,43 +12,66 @@ pub async fn get_period(ctx: &mut Ctx, id: Uuid) -> Result<Period, sqlx::Error>
         Period,
         r#"
             SELECT
-                id,
-                name,
+                periods.id,
+                periods.name,
                 null as short_name,
 
-                EXTRACT(EPOCH FROM start_time)::float as "start!",
-                EXTRACT(EPOCH FROM end_time)::float as "end!",
+                EXTRACT(EPOCH FROM periods.start_time)::float as "start!",
+                EXTRACT(EPOCH FROM periods.end_time)::float as "end!",
 
-                EXTRACT(EPOCH FROM temp_start)::float as temp_start,
-                EXTRACT(EPOCH FROM temp_end)::float as temp_end
+                EXTRACT(EPOCH FROM periods.temp_start)::float as temp_start,
+                EXTRACT(EPOCH FROM periods.temp_end)::float as temp_end,
+
+                EXTRACT(EPOCH FROM bsp.start_time)::float as schedule_start,
+                EXTRACT(EPOCH FROM bsp.end_time)::float as schedule_end,
+
+                periods.archived
             FROM periods
-            WHERE id = $1;
+                LEFT JOIN schedule_calendar AS sc ON sc.date = CURRENT_DATE
+                LEFT JOIN bell_schedule_periods AS bsp
+                    ON bsp.schedule_id = sc.schedule_id AND bsp.period_id = periods.id
+            WHERE periods.id = $1;
         "#,
         id,
     );
 
-    get_period_query.fetch_one(&mut **ctx).await
+    get_period_query.fetch_one(&mut *ctx).await
 }
 
+/// Gets every period that hasn't been archived.
 pub async fn get_all_periods(ctx: &mut Ctx) -> Result<Vec<Period>, sqlx::Error> {
+    get_periods(ctx, false).await
+}
+
+pub async fn get_periods(ctx: &mut Ctx, include_archived: bool) -> Result<Vec<Period>, sqlx::Error> {
     let get_all_periods_query = query_as!(
         Period,
         r#"
             SELECT
-                id,
-                name,
+                periods.id as "id!",
+                periods.name as "name!",
                 null as short_name,
 
-                EXTRACT(EPOCH FROM start_time)::float as "start!",
-                EXTRACT(EPOCH FROM end_time)::float as "end!",
+                EXTRACT(EPOCH FROM periods.start_time)::float as "start!",
+                EXTRACT(EPOCH FROM periods.end_time)::float as "end!",
 
-                EXTRACT(EPOCH FROM temp_start)::float as temp_start,
-                EXTRACT(EPOCH FROM temp_end)::float as temp_end
-            FROM periods;
+                EXTRACT(EPOCH FROM periods.temp_start)::float as temp_start,
+                EXTRACT(EPOCH FROM periods.temp_end)::float as temp_end,
+
+                EXTRACT(EPOCH FROM bsp.start_time)::float as schedule_start,
+                EXTRACT(EPOCH FROM bsp.end_time)::float as schedule_end,
+
+                periods.archived as "archived!"
+            FROM periods
+                LEFT JOIN schedule_calendar AS sc ON sc.date = CURRENT_DATE
+                LEFT JOIN bell_schedule_periods AS bsp
+                    ON bsp.schedule_id = sc.schedule_id AND bsp.period_id = periods.id
+            WHERE $1 OR NOT periods.archived;
         "#,
+        include_archived,
     );
 
-    get_all_periods_query.fetch_all(&mut **ctx).await
+    get_all_periods_query.fetch_all(&mut *ctx).await
 }
 
 pub async fn create_period(ctx: &mut Ctx, name: &str, time_range: [f64; 2]) -> Result<Period, sqlx::Error> {
@@ -66,7 +89,7 @@ pub async fn create_period(ctx: &mut Ctx, name: &str, time_range: [f64; 2]) -> R
         time_range[0], time_range[1],
     );
     
-    let id = add_period.fetch_one(&mut **ctx).await?.id;
+    let id = add_period.fetch_one(&mut *ctx).await?.id;
 
     get_period(ctx, id).await
 }
@@ -83,7 +106,7 @@ pub async fn update_period_name(ctx: &mut Ctx, id: Uuid, name: &str) -> sqlx::Re
         name
     );
 
-    update_name.execute(&mut **ctx).await?;
+    update_name.execute(&mut *ctx).await?;
     get_period(ctx, id).await
 }    
 
@@ -101,7 +124,7 @@ pub async fn update_period_time(ctx: &mut Ctx, id: Uuid, time_range: [f64; 2]) -
         time_range[0], time_range[1],
     );
     
-    update_time.execute(&mut **ctx).await?;
+    update_time.execute(&mut *ctx).await?;
     get_period(ctx, id).await
 }
 
@@ -119,7 +142,7 @@ pub async fn set_period_temp_time(ctx: &mut Ctx, id: Uuid, temp_time_range: [f64
         temp_time_range[0], temp_time_range[1],
     );
     
-    update_temp_time.execute(&mut **ctx).await?;
+    update_temp_time.execute(&mut *ctx).await?;
     get_period(ctx, id).await
 }
 
@@ -134,7 +157,7 @@ pub async fn clear_period_temp_time(ctx: &mut Ctx, id: Uuid) -> sqlx::Result<Per
         id,
     );
     
-    update_temp_time.execute(&mut **ctx).await?;
+    update_temp_time.execute(&mut *ctx).await?;
     get_period(ctx, id).await
 }
 
@@ -147,7 +170,122 @@ pub async fn flush_all_temp_times(ctx: &mut Ctx) -> sqlx::Result<()> {
         {  };
     );
     
-    flush_temp_times.execute(&mut **ctx).await?;
+    flush_temp_times.execute(&mut *ctx).await?;
     Ok(())
 }
 
+
+/// Archives a period, hiding it from the period list and taking it off
+/// today's absence board. Future absences that include it are kept so it can
+/// be restored.
+pub async fn archive_period(ctx: &mut Ctx, id: Uuid) -> sqlx::Result<Period> {
+    let archive_period_query = prepared_query!(
+        r"
+            UPDATE periods
+            SET archived = true, temp_start = null, temp_end = null
+            WHERE id = $1;
+        ";
+        {  };
+        id,
+    );
+    let clear_absences_query = prepared_query!(
+        r"
+            DELETE FROM absence_xref
+            WHERE period_id = $1;
+        ";
+        {  };
+        id,
+    );
+    let clear_coverage_query = prepared_query!(
+        r"
+            DELETE FROM coverage
+            WHERE period_id = $1;
+        ";
+        {  };
+        id,
+    );
+
+    ctx.transaction(|txn| Box::pin(async move {
+        if archive_period_query.execute(&mut **txn).await?.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        clear_absences_query.execute(&mut **txn).await?;
+        clear_coverage_query.execute(&mut **txn).await
+    })).await?;
+
+    get_period(ctx, id).await
+}
+
+pub async fn restore_period(ctx: &mut Ctx, id: Uuid) -> sqlx::Result<Period> {
+    let restore_period_query = prepared_query!(
+        r"
+            UPDATE periods
+            SET archived = false
+            WHERE id = $1;
+        ";
+        {  };
+        id,
+    );
+
+    if restore_period_query.execute(&mut *ctx).await?.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+    get_period(ctx, id).await
+}
+
+/// Gets which of `ids` belong to archived periods.
+pub async fn get_archived_periods(ctx: &mut Ctx, ids: &[Uuid]) -> sqlx::Result<Vec<Uuid>> {
+    let archived_periods_query = prepared_query!(
+        r"
+            SELECT id
+            FROM periods
+            WHERE id = ANY($1) AND archived;
+        ";
+        { id: Uuid };
+        ids,
+    );
+
+    let archived = archived_periods_query.fetch_all(&mut *ctx).await?;
+
+    Ok(archived.into_iter().map(|row| row.id).collect())
+}
+
+/// Deletes a period for good, along with its absences, coverage, and bell
+/// schedule times, and takes it out of every future and recurring absence.
+/// Returns false if it didn't exist.
+pub async fn delete_period(ctx: &mut Ctx, id: Uuid) -> sqlx::Result<bool> {
+    let remove_from_futures_query = prepared_query!(
+        r"
+            UPDATE teacher_future_schedules
+            SET periods = array_remove(periods, $1)
+            WHERE $1 = ANY(periods);
+        ";
+        {  };
+        id,
+    );
+    let remove_from_recurring_query = prepared_query!(
+        r"
+            UPDATE teacher_recurring_absences
+            SET periods = array_remove(periods, $1)
+            WHERE $1 = ANY(periods);
+        ";
+        {  };
+        id,
+    );
+    let delete_period_query = prepared_query!(
+        r"
+            DELETE FROM periods
+            WHERE id = $1;
+        ";
+        {  };
+        id,
+    );
+
+    let res = ctx.transaction(|txn| Box::pin(async move {
+        remove_from_futures_query.execute(&mut **txn).await?;
+        remove_from_recurring_query.execute(&mut **txn).await?;
+        delete_period_query.execute(&mut **txn).await
+    })).await?;
+
+    Ok(res.rows_affected() > 0)
+}
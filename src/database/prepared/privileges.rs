@@ -17,6 +17,6 @@ pub async fn get_privileges(ctx: &mut Ctx, id: Uuid) -> Result<Privileges, sqlx:
         id,
     );
 
-    let privileges = privileges_query.fetch_optional(&mut **ctx).await?;
+    let privileges = privileges_query.fetch_optional(&mut *ctx).await?;
     Ok(privileges.unwrap_or(Privileges { secretary: false, admin: false }))
 }
@@ -0,0 +1,271 @@
+use std::collections::HashSet;
+
+use chrono::{ Days, NaiveDate, Weekday };
+use uuid::Uuid;
+
+use crate::types::{ CalendarDayKind, RecurrenceRule, RecurringAbsence };
+
+use super::super::Ctx;
+use super::calendar::get_calendar_days;
+use super::future_absences::BarebonesFutureDay;
+use super::prepared_query;
+
+
+fn decode_date(column: &str, days_since_epoch: i32) -> Result<NaiveDate, sqlx::Error> {
+    u64::try_from(days_since_epoch)
+        .ok()
+        .and_then(|days| NaiveDate::default().checked_add_days(Days::new(days)))
+        .ok_or_else(|| sqlx::Error::Decode(format!("teacher_recurring_absences.{column} out of range: {days_since_epoch}").into()))
+}
+
+fn decode_weekday(iso_weekday: i16) -> Result<Weekday, sqlx::Error> {
+    iso_weekday
+        .checked_sub(1)
+        .and_then(|weekday| u8::try_from(weekday).ok())
+        .and_then(|weekday| Weekday::try_from(weekday).ok())
+        .ok_or_else(|| sqlx::Error::Decode(format!("Invalid ISO weekday: {iso_weekday}").into()))
+}
+
+/// Gets every recurring absence, optionally only for a single teacher or with
+/// a single id.
+async fn get_recurring_absences_where(
+    ctx: &mut Ctx,
+    teacher: Option<Uuid>,
+    id: Option<Uuid>,
+) -> Result<Vec<RecurringAbsence>, sqlx::Error> {
+    let get_recurrences_query = prepared_query!(
+        r#"
+            SELECT
+                ra.id,
+                ra.teacher as teacher_id,
+                ra.periods,
+                ra.fully_absent,
+                ra.comment,
+                ra.start_date - DATE '1/1/1970' as "start_date!",
+                ra.interval_weeks,
+                ra.weekdays,
+                ra.until - DATE '1/1/1970' as until,
+                ra.count,
+                ARRAY(
+                    SELECT e.date - DATE '1/1/1970'
+                    FROM teacher_recurring_absence_exceptions AS e
+                    WHERE e.recurrence_id = ra.id
+                    ORDER BY e.date
+                ) as "exceptions!"
+            FROM teacher_recurring_absences AS ra
+            WHERE
+                ($1::uuid IS NULL OR ra.teacher = $1) AND
+                ($2::uuid IS NULL OR ra.id = $2)
+            ORDER BY ra.teacher, ra.start_date;
+        "#;
+        {
+            id: Uuid,
+            teacher_id: Uuid,
+            periods: Vec<Uuid>,
+            fully_absent: bool,
+            comment: Option<String>,
+            start_date: i32,
+            interval_weeks: i32,
+            weekdays: Vec<i16>,
+            until: Option<i32>,
+            count: Option<i32>,
+            exceptions: Vec<i32>,
+        };
+        teacher,
+        id,
+    );
+
+    get_recurrences_query
+        .fetch_all(&mut *ctx)
+        .await?
+        .into_iter()
+        .map(|row| {
+            let rule = RecurrenceRule {
+                start: decode_date("start_date", row.start_date)?,
+                interval_weeks: row.interval_weeks.unsigned_abs(),
+                weekdays: row.weekdays.into_iter().map(decode_weekday).collect::<Result<_, _>>()?,
+                until: row.until.map(|until| decode_date("until", until)).transpose()?,
+                count: row.count.map(i32::unsigned_abs),
+            };
+
+            Ok(RecurringAbsence {
+                id: row.id,
+                teacher_id: row.teacher_id,
+                periods: row.periods,
+                fully_absent: row.fully_absent,
+                comment: row.comment,
+                rule,
+                exceptions: row.exceptions
+                    .into_iter()
+                    .map(|date| decode_date("exceptions", date))
+                    .collect::<Result<_, _>>()?,
+            })
+        })
+        .collect()
+}
+
+pub async fn get_recurring_absences(ctx: &mut Ctx, teacher: Option<Uuid>) -> Result<Vec<RecurringAbsence>, sqlx::Error> {
+    get_recurring_absences_where(ctx, teacher, None).await
+}
+
+pub async fn get_recurring_absence(ctx: &mut Ctx, id: Uuid) -> Result<RecurringAbsence, sqlx::Error> {
+    get_recurring_absences_where(ctx, None, Some(id))
+        .await?
+        .pop()
+        .ok_or(sqlx::Error::RowNotFound)
+}
+
+pub async fn add_recurring_absence(
+    ctx: &mut Ctx,
+
+    teacher: Uuid,
+    periods: &[Uuid],
+    fully_absent: bool,
+    comment: Option<String>,
+
+    rule: RecurrenceRule,
+) -> Result<RecurringAbsence, sqlx::Error> {
+    let days_since_epoch = |date: NaiveDate| date.signed_duration_since(NaiveDate::default()).num_days() as f64;
+
+    let weekdays: Vec<i16> = rule.weekdays
+        .iter()
+        .map(|weekday| weekday.number_from_monday() as i16)
+        .collect();
+
+    let add_recurrence_query = prepared_query!(
+        r"
+            INSERT INTO teacher_recurring_absences (
+                id, teacher,
+                periods, fully_absent, comment,
+                start_date, interval_weeks, weekdays, until, count
+            )
+            VALUES (
+                uuid_generate_v4(), $1,
+                $2, $3, $4,
+                DATE '1/1/1970' + $5 * INTERVAL '1 day',
+                $6,
+                $7,
+                DATE '1/1/1970' + $8 * INTERVAL '1 day',
+                $9
+            )
+            RETURNING id;
+        ";
+        { id: Uuid };
+        teacher,
+        periods, fully_absent, comment.as_deref(),
+        days_since_epoch(rule.start),
+        rule.interval_weeks as i32,
+        &weekdays,
+        rule.until.map(days_since_epoch),
+        rule.count.map(|count| count as i32),
+    );
+
+    let id = add_recurrence_query.fetch_one(&mut *ctx).await?.id;
+
+    Ok(RecurringAbsence {
+        id,
+        teacher_id: teacher,
+        periods: periods.to_vec(),
+        fully_absent,
+        comment,
+        rule,
+        exceptions: Vec::new(),
+    })
+}
+
+/// Deletes a recurring absence, along with its skipped occurrences.
+///
+/// Returns whether the recurring absence existed.
+pub async fn delete_recurring_absence(ctx: &mut Ctx, id: Uuid) -> Result<bool, sqlx::Error> {
+    let delete_recurrence_query = prepared_query!(
+        r"
+            DELETE FROM teacher_recurring_absences
+            WHERE id = $1;
+        ";
+        {  };
+        id,
+    );
+
+    Ok(delete_recurrence_query.execute(&mut *ctx).await?.rows_affected() > 0)
+}
+
+/// Skips a single occurrence of a recurring absence, or brings it back if
+/// `skipped` is false.
+pub async fn set_recurrence_exception(
+    ctx: &mut Ctx,
+    id: Uuid,
+    date: NaiveDate,
+    skipped: bool,
+) -> Result<(), sqlx::Error> {
+    let days_since_epoch = date.signed_duration_since(NaiveDate::default()).num_days() as f64;
+
+    if !skipped {
+        let remove_exception_query = prepared_query!(
+            r"
+                DELETE FROM teacher_recurring_absence_exceptions
+                WHERE recurrence_id = $1 AND date = DATE '1/1/1970' + $2 * INTERVAL '1 day';
+            ";
+            {  };
+            id, days_since_epoch,
+        );
+
+        remove_exception_query.execute(&mut *ctx).await?;
+        return Ok(());
+    }
+
+    let add_exception_query = prepared_query!(
+        r"
+            INSERT INTO teacher_recurring_absence_exceptions (recurrence_id, date)
+            VALUES ($1, DATE '1/1/1970' + $2 * INTERVAL '1 day')
+                ON CONFLICT DO NOTHING;
+        ";
+        {  };
+        id, days_since_epoch,
+    );
+
+    add_exception_query.execute(&mut *ctx).await?;
+    Ok(())
+}
+
+/// Expands every recurring absence, optionally only for a single teacher, into
+/// the school days from `start` to `end` (inclusive) that it happens on.
+///
+/// Fails for ranges longer than [`crate::types::MAX_OCCURRENCE_RANGE_DAYS`].
+pub (super) async fn get_recurring_days(
+    ctx: &mut Ctx,
+    teacher: Option<Uuid>,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Result<Vec<BarebonesFutureDay>, sqlx::Error> {
+    let recurrences = get_recurring_absences_where(ctx, teacher, None).await?;
+    if recurrences.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let school_days: HashSet<_> = get_calendar_days(ctx, start, end)
+        .await?
+        .into_iter()
+        .filter(|day| day.kind == CalendarDayKind::Instructional)
+        .map(|day| day.date)
+        .collect();
+
+    let mut days = Vec::new();
+    for recurrence in recurrences {
+        let occurrences = recurrence
+            .occurrences(start, end)
+            .map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+
+        days.extend(occurrences
+            .into_iter()
+            .filter(|date| school_days.contains(date))
+            .map(|date| BarebonesFutureDay {
+                teacher_id: recurrence.teacher_id,
+                date: date.signed_duration_since(NaiveDate::default()).num_days() as f64,
+                periods: recurrence.periods.clone(),
+                fully_absent: recurrence.fully_absent,
+                comment: recurrence.comment.clone(),
+            }));
+    }
+
+    Ok(days)
+}
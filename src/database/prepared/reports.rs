@@ -0,0 +1,192 @@
+//! Aggregate queries over `absence_history`, used for end-of-year reporting.
+//!
+//! Every function takes an inclusive `start` and `end` date. Archived
+//! teachers and periods still count towards the totals, since their absences
+//! happened, unless `exclude_archived` is set. Days of teachers that were
+//! deleted for good count like archived ones, except in the per-teacher
+//! monthly totals, which have nobody to list them under.
+
+use chrono::{ NaiveDate, TimeDelta };
+use uuid::Uuid;
+
+use crate::types::{
+    TeacherMonthlyAbsences,
+    PeriodAbsenceCount,
+    FullVsPartialDays,
+    WeekdayAbsenceCount,
+};
+
+use super::super::Ctx;
+use super::prepared_query;
+
+
+fn days_since_epoch(date: NaiveDate) -> f64 {
+    date.signed_duration_since(NaiveDate::default()).num_days() as f64
+}
+
+/// Dates before 1970 have a negative number of days, so this has to be
+/// signed.
+fn date_from_days_since_epoch(days: f64) -> Result<NaiveDate, sqlx::Error> {
+    TimeDelta::try_days(days as i64)
+        .and_then(|days| NaiveDate::default().checked_add_signed(days))
+        .ok_or_else(|| sqlx::Error::Decode(format!("Date out of range: {days} days since epoch").into()))
+}
+
+
+pub async fn get_monthly_absences(ctx: &mut Ctx, start: NaiveDate, end: NaiveDate, exclude_archived: bool) -> Result<Vec<TeacherMonthlyAbsences>, sqlx::Error> {
+    let monthly_absences_query = prepared_query!(
+        r#"
+            SELECT
+                ah.teacher as "teacher_id!",
+                EXTRACT(EPOCH FROM date_trunc('month', ah.date)::date)::float / 86400 as "month!",
+                count(*) as "days!",
+                count(*) FILTER (WHERE ah.fully_absent) as "full_days!",
+                count(*) FILTER (WHERE NOT ah.fully_absent) as "partial_days!",
+                sum(cardinality(ah.periods))::bigint as "periods!"
+            FROM absence_history AS ah
+            INNER JOIN teachers ON teachers.id = ah.teacher
+            WHERE
+                NOT ($3 AND teachers.archived) AND
+                DATE '1/1/1970' + $1 * INTERVAL '1 day' <= ah.date AND
+                ah.date <= DATE '1/1/1970' + $2 * INTERVAL '1 day'
+            GROUP BY ah.teacher, date_trunc('month', ah.date)
+            ORDER BY "month!", ah.teacher;
+        "#;
+        {
+            teacher_id: Uuid,
+            month: f64,
+            days: i64,
+            full_days: i64,
+            partial_days: i64,
+            periods: i64,
+        };
+        days_since_epoch(start), days_since_epoch(end), exclude_archived,
+    );
+
+    monthly_absences_query
+        .fetch_all(&mut *ctx)
+        .await?
+        .into_iter()
+        .map(|row| Ok(TeacherMonthlyAbsences {
+            teacher_id: row.teacher_id,
+            month: date_from_days_since_epoch(row.month)?,
+            days: row.days,
+            full_days: row.full_days,
+            partial_days: row.partial_days,
+            periods: row.periods,
+        }))
+        .collect()
+}
+
+/// Counts how many absence days included each period, most frequent first.
+pub async fn get_period_absence_counts(ctx: &mut Ctx, start: NaiveDate, end: NaiveDate, exclude_archived: bool, limit: i64) -> Result<Vec<PeriodAbsenceCount>, sqlx::Error> {
+    let period_counts_query = prepared_query!(
+        r#"
+            SELECT
+                period_id as "period_id!",
+                count(*) as "count!"
+            FROM absence_history AS ah
+            CROSS JOIN unnest(ah.periods) as period_id
+            LEFT JOIN teachers ON teachers.id = ah.teacher
+            INNER JOIN periods ON periods.id = period_id
+            WHERE
+                NOT ($3 AND (COALESCE(teachers.archived, true) OR periods.archived)) AND
+                DATE '1/1/1970' + $1 * INTERVAL '1 day' <= ah.date AND
+                ah.date <= DATE '1/1/1970' + $2 * INTERVAL '1 day'
+            GROUP BY period_id
+            ORDER BY "count!" DESC
+            LIMIT $4;
+        "#;
+        { period_id: Uuid, count: i64 };
+        days_since_epoch(start), days_since_epoch(end), exclude_archived, limit,
+    );
+
+    let counts = period_counts_query.fetch_all(&mut *ctx).await?;
+
+    Ok(counts.into_iter().map(|row| PeriodAbsenceCount { period_id: row.period_id, count: row.count }).collect())
+}
+
+/// Counts how many times each period was missed by an absent teacher without
+/// a substitute, most frequent first. Fully absent teachers miss every period.
+pub async fn get_uncovered_period_counts(ctx: &mut Ctx, start: NaiveDate, end: NaiveDate, exclude_archived: bool, limit: i64) -> Result<Vec<PeriodAbsenceCount>, sqlx::Error> {
+    let uncovered_counts_query = prepared_query!(
+        r#"
+            SELECT
+                absent.period_id as "period_id!",
+                count(*) as "count!"
+            FROM absence_history AS ah
+            CROSS JOIN unnest(
+                CASE WHEN ah.fully_absent THEN ARRAY(SELECT id FROM periods) ELSE ah.periods END
+            ) AS absent(period_id)
+            LEFT JOIN teachers ON teachers.id = ah.teacher
+            INNER JOIN periods ON periods.id = absent.period_id
+            WHERE
+                NOT ($3 AND (COALESCE(teachers.archived, true) OR periods.archived)) AND
+                DATE '1/1/1970' + $1 * INTERVAL '1 day' <= ah.date AND
+                ah.date <= DATE '1/1/1970' + $2 * INTERVAL '1 day' AND
+                NOT absent.period_id = ANY(ah.covered_periods) AND
+                NOT EXISTS (
+                    SELECT 1 FROM coverage
+                    WHERE
+                        coverage.teacher_id = ah.teacher AND
+                        coverage.period_id = absent.period_id AND
+                        coverage.date = ah.date
+                )
+            GROUP BY absent.period_id
+            ORDER BY "count!" DESC
+            LIMIT $4;
+        "#;
+        { period_id: Uuid, count: i64 };
+        days_since_epoch(start), days_since_epoch(end), exclude_archived, limit,
+    );
+
+    let counts = uncovered_counts_query.fetch_all(&mut *ctx).await?;
+
+    Ok(counts.into_iter().map(|row| PeriodAbsenceCount { period_id: row.period_id, count: row.count }).collect())
+}
+
+pub async fn get_full_vs_partial_days(ctx: &mut Ctx, start: NaiveDate, end: NaiveDate, exclude_archived: bool) -> Result<FullVsPartialDays, sqlx::Error> {
+    let full_vs_partial_query = prepared_query!(
+        r#"
+            SELECT
+                count(*) FILTER (WHERE ah.fully_absent) as "full!",
+                count(*) FILTER (WHERE NOT ah.fully_absent) as "partial!"
+            FROM absence_history AS ah
+            LEFT JOIN teachers ON teachers.id = ah.teacher
+            WHERE
+                NOT ($3 AND COALESCE(teachers.archived, true)) AND
+                DATE '1/1/1970' + $1 * INTERVAL '1 day' <= ah.date AND
+                ah.date <= DATE '1/1/1970' + $2 * INTERVAL '1 day';
+        "#;
+        { full: i64, partial: i64 };
+        days_since_epoch(start), days_since_epoch(end), exclude_archived,
+    );
+
+    let row = full_vs_partial_query.fetch_one(&mut *ctx).await?;
+
+    Ok(FullVsPartialDays { full: row.full, partial: row.partial })
+}
+
+pub async fn get_weekday_absence_counts(ctx: &mut Ctx, start: NaiveDate, end: NaiveDate, exclude_archived: bool) -> Result<Vec<WeekdayAbsenceCount>, sqlx::Error> {
+    let weekday_counts_query = prepared_query!(
+        r#"
+            SELECT
+                EXTRACT(ISODOW FROM ah.date)::int as "weekday!",
+                count(*) as "count!"
+            FROM absence_history AS ah
+            LEFT JOIN teachers ON teachers.id = ah.teacher
+            WHERE
+                NOT ($3 AND COALESCE(teachers.archived, true)) AND
+                DATE '1/1/1970' + $1 * INTERVAL '1 day' <= ah.date AND
+                ah.date <= DATE '1/1/1970' + $2 * INTERVAL '1 day'
+            GROUP BY "weekday!"
+            ORDER BY "weekday!";
+        "#;
+        { weekday: i32, count: i64 };
+        days_since_epoch(start), days_since_epoch(end), exclude_archived,
+    );
+
+    let counts = weekday_counts_query.fetch_all(&mut *ctx).await?;
+
+    Ok(counts.into_iter().map(|row| WeekdayAbsenceCount { weekday: row.weekday, count: row.count }).collect())
+}
@@ -0,0 +1,118 @@
+use uuid::Uuid;
+
+use crate::types::{ RolloverRun, RolloverTrigger };
+
+use super::super::Ctx;
+use super::prepared_query;
+
+
+pub async fn record_rollover(
+    ctx: &mut Ctx,
+    trigger: RolloverTrigger,
+    outcome: &Result<Vec<Uuid>, sqlx::Error>,
+) -> Result<(), sqlx::Error> {
+    let (success, teachers_updated, error) = match outcome {
+        Ok(updated) => (true, updated.len() as i32, None),
+        Err(e) => (false, 0, Some(e.to_string())),
+    };
+
+    let record_query = prepared_query!(
+        r"
+            INSERT INTO rollover_runs (id, trigger, success, teachers_updated, error)
+            VALUES (uuid_generate_v4(), $1, $2, $3, $4);
+        ";
+        {  };
+        trigger.str(), success, teachers_updated, error,
+    );
+
+    record_query.execute(&mut *ctx).await?;
+
+    Ok(())
+}
+
+pub async fn get_last_rollover(ctx: &mut Ctx) -> Result<Option<RolloverRun>, sqlx::Error> {
+    let last_rollover_query = prepared_query!(
+        r#"
+            SELECT
+                EXTRACT(EPOCH FROM ran_at)::float as "ran_at!",
+                trigger,
+                success,
+                teachers_updated,
+                error
+            FROM rollover_runs
+            ORDER BY ran_at DESC
+            LIMIT 1;
+        "#;
+        {
+            ran_at: f64,
+            trigger: String,
+            success: bool,
+            teachers_updated: i32,
+            error: Option<String>,
+        };
+    );
+
+    let Some(run) = last_rollover_query.fetch_optional(&mut *ctx).await? else {
+        return Ok(None);
+    };
+
+    let ran_at = chrono::DateTime::from_timestamp(
+        run.ran_at.floor() as i64,
+        (run.ran_at.fract() * 1_000_000_000.0) as u32,
+    ).ok_or_else(|| sqlx::Error::Decode(format!("rollover_runs.ran_at out of range: {}", run.ran_at).into()))?;
+
+    let trigger = RolloverTrigger::try_from_str(&run.trigger)
+        .ok_or_else(|| sqlx::Error::Decode(format!("Invalid rollover trigger: {}", run.trigger).into()))?;
+
+    Ok(Some(RolloverRun {
+        ran_at,
+        trigger,
+        success: run.success,
+        teachers_updated: run.teachers_updated,
+        error: run.error,
+    }))
+}
+
+/// Gets how many seconds it is until `at` (seconds since midnight) next comes
+/// around in the database's time zone. Rollovers are timed by the database's
+/// clock so that they line up with its `CURRENT_DATE`, which every query about
+/// "today" uses.
+pub async fn seconds_until_rollover(ctx: &mut Ctx, at: f64) -> Result<f64, sqlx::Error> {
+    let until_query = prepared_query!(
+        r#"
+            SELECT EXTRACT(EPOCH FROM (
+                CASE
+                    WHEN (CURRENT_DATE + $1::float * INTERVAL '1 second')::timestamptz > now()
+                        THEN (CURRENT_DATE + $1::float * INTERVAL '1 second')::timestamptz
+                    ELSE (CURRENT_DATE + 1 + $1::float * INTERVAL '1 second')::timestamptz
+                END
+            ) - now())::float as "seconds!";
+        "#;
+        { seconds: f64 };
+        at,
+    );
+
+    Ok(until_query.fetch_one(&mut *ctx).await?.seconds)
+}
+
+/// Checks whether today's rollover at `at` (seconds since midnight, in the
+/// database's time zone) is already due, with no successful rollover since.
+pub async fn rollover_missed(ctx: &mut Ctx, at: f64) -> Result<bool, sqlx::Error> {
+    let missed_query = prepared_query!(
+        r#"
+            SELECT
+                now() >= (CURRENT_DATE + $1::float * INTERVAL '1 second')::timestamptz AND
+                NOT EXISTS (
+                    SELECT 1
+                    FROM rollover_runs
+                    WHERE
+                        success AND
+                        ran_at >= (CURRENT_DATE + $1::float * INTERVAL '1 second')::timestamptz
+                ) as "missed!";
+        "#;
+        { missed: bool };
+        at,
+    );
+
+    Ok(missed_query.fetch_one(&mut *ctx).await?.missed)
+}
@@ -0,0 +1,207 @@
+use uuid::Uuid;
+
+use crate::types::{ Room, RoomAssignment };
+
+use super::super::Ctx;
+use super::prepared_query;
+
+
+pub async fn get_room(ctx: &mut Ctx, id: Uuid) -> Result<Room, sqlx::Error> {
+    let get_room_query = prepared_query!(
+        r"
+            SELECT id, name
+            FROM rooms
+            WHERE id = $1;
+        ";
+        { id: Uuid, name: String };
+        id,
+    );
+
+    let room = get_room_query.fetch_one(&mut *ctx).await?;
+
+    Ok(Room { id: room.id, name: room.name })
+}
+
+pub async fn get_all_rooms(ctx: &mut Ctx) -> Result<Vec<Room>, sqlx::Error> {
+    let get_all_rooms_query = prepared_query!(
+        r"
+            SELECT id, name
+            FROM rooms
+            ORDER BY name;
+        ";
+        { id: Uuid, name: String };
+    );
+
+    let rooms = get_all_rooms_query.fetch_all(&mut *ctx).await?;
+
+    Ok(rooms.into_iter().map(|room| Room { id: room.id, name: room.name }).collect())
+}
+
+pub async fn add_room(ctx: &mut Ctx, name: &str) -> Result<Room, sqlx::Error> {
+    let add_room_query = prepared_query!(
+        r"
+            INSERT INTO rooms (id, name)
+            VALUES (uuid_generate_v4(), $1)
+            RETURNING id;
+        ";
+        { id: Uuid };
+        name,
+    );
+
+    let id = add_room_query.fetch_one(&mut *ctx).await?.id;
+
+    Ok(Room { id, name: name.to_string() })
+}
+
+pub async fn update_room_name(ctx: &mut Ctx, id: Uuid, name: &str) -> Result<Room, sqlx::Error> {
+    let update_room_query = prepared_query!(
+        r"
+            UPDATE rooms
+            SET name = $2
+            WHERE id = $1;
+        ";
+        {  };
+        id, name,
+    );
+
+    if update_room_query.execute(&mut *ctx).await?.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    Ok(Room { id, name: name.to_string() })
+}
+
+/// Deletes a room, along with every assignment to it.
+///
+/// Returns whether the room existed.
+pub async fn delete_room(ctx: &mut Ctx, id: Uuid) -> Result<bool, sqlx::Error> {
+    let delete_room_query = prepared_query!(
+        r"
+            DELETE FROM rooms
+            WHERE id = $1;
+        ";
+        {  };
+        id,
+    );
+
+    Ok(delete_room_query.execute(&mut *ctx).await?.rows_affected() > 0)
+}
+
+/// Sets where a teacher's class reports to in a period, or removes the
+/// assignment if `room` is `None`.
+pub async fn set_room_assignment(
+    ctx: &mut Ctx,
+    teacher: Uuid,
+    period: Uuid,
+    room: Option<Uuid>,
+) -> Result<(), sqlx::Error> {
+    let Some(room) = room else {
+        let remove_assignment_query = prepared_query!(
+            r"
+                DELETE FROM room_assignments
+                WHERE teacher_id = $1 AND period_id = $2;
+            ";
+            {  };
+            teacher, period,
+        );
+
+        remove_assignment_query.execute(&mut *ctx).await?;
+        return Ok(());
+    };
+
+    let set_assignment_query = prepared_query!(
+        r"
+            INSERT INTO room_assignments (teacher_id, period_id, room_id)
+            VALUES ($1, $2, $3)
+                ON CONFLICT (teacher_id, period_id)
+                DO UPDATE SET room_id = $3;
+        ";
+        {  };
+        teacher, period, room,
+    );
+
+    set_assignment_query.execute(&mut *ctx).await?;
+    Ok(())
+}
+
+pub async fn get_room_assignments_for_teacher(ctx: &mut Ctx, teacher: Uuid) -> Result<Vec<RoomAssignment>, sqlx::Error> {
+    let get_assignments_query = prepared_query!(
+        r"
+            SELECT ra.teacher_id, ra.period_id, r.id as room_id, r.name as room_name
+            FROM room_assignments AS ra
+                INNER JOIN rooms AS r ON r.id = ra.room_id
+            WHERE ra.teacher_id = $1;
+        ";
+        {
+            teacher_id: Uuid,
+            period_id: Uuid,
+            room_id: Uuid,
+            room_name: String,
+        };
+        teacher,
+    );
+
+    let assignments = get_assignments_query.fetch_all(&mut *ctx).await?;
+
+    Ok(
+        assignments
+            .into_iter()
+            .map(|assignment| RoomAssignment {
+                teacher_id: assignment.teacher_id,
+                period_id: assignment.period_id,
+                room: Room { id: assignment.room_id, name: assignment.room_name },
+            })
+            .collect()
+    )
+}
+
+pub async fn get_all_room_assignments(ctx: &mut Ctx) -> Result<Vec<RoomAssignment>, sqlx::Error> {
+    let get_assignments_query = prepared_query!(
+        r"
+            SELECT ra.teacher_id, ra.period_id, r.id as room_id, r.name as room_name
+            FROM room_assignments AS ra
+                INNER JOIN rooms AS r ON r.id = ra.room_id;
+        ";
+        {
+            teacher_id: Uuid,
+            period_id: Uuid,
+            room_id: Uuid,
+            room_name: String,
+        };
+    );
+
+    let assignments = get_assignments_query.fetch_all(&mut *ctx).await?;
+
+    Ok(
+        assignments
+            .into_iter()
+            .map(|assignment| RoomAssignment {
+                teacher_id: assignment.teacher_id,
+                period_id: assignment.period_id,
+                room: Room { id: assignment.room_id, name: assignment.room_name },
+            })
+            .collect()
+    )
+}
+
+/// Gets where a teacher's class reports to in a period: their assigned room
+/// if they have one, otherwise the global `report_to`.
+pub async fn get_report_to_for(ctx: &mut Ctx, teacher: Uuid, period: Uuid) -> Result<String, sqlx::Error> {
+    let get_report_to_query = prepared_query!(
+        r#"
+            SELECT COALESCE(
+                (
+                    SELECT r.name
+                    FROM room_assignments AS ra
+                        INNER JOIN rooms AS r ON r.id = ra.room_id
+                    WHERE ra.teacher_id = $1 AND ra.period_id = $2
+                ),
+                (SELECT report_to FROM config)
+            ) as "report_to!";
+        "#;
+        { report_to: String };
+        teacher, period,
+    );
+
+    Ok(get_report_to_query.fetch_one(&mut *ctx).await?.report_to)
+}
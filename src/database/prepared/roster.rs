@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+
+use sqlx::{ Acquire, Connection, PgConnection };
+use uuid::Uuid;
+
+use crate::types::{ AuditOrigin, AuditTarget, OauthLink, PronounSet, RosterEntry, RosterImportReport, RosterRowError };
+
+use super::super::Ctx;
+use super::audit_log::{ record_change, snapshot };
+use super::prepared_query;
+use super::teacher::get_all_teachers;
+
+
+/// Gets every teacher along with their OAuth accounts, sorted by name.
+pub async fn get_roster(ctx: &mut Ctx) -> Result<Vec<RosterEntry>, sqlx::Error> {
+    let get_oauths_query = prepared_query!(
+        r#"
+            SELECT teacher, provider, sub
+            FROM teacher_oauths
+            ORDER BY provider;
+        "#;
+        { teacher: Uuid, provider: String, sub: String };
+    );
+
+    let mut oauths: HashMap<Uuid, Vec<OauthLink>> = HashMap::new();
+    for row in get_oauths_query.fetch_all(&mut *ctx).await? {
+        oauths
+            .entry(row.teacher)
+            .or_default()
+            .push(OauthLink { provider: row.provider, sub: row.sub });
+    }
+
+    let mut teachers = get_all_teachers(ctx).await?;
+    teachers.sort_by(|a, b| {
+        (a.get_name().get_last(), a.get_name().get_first(), a.get_id())
+            .cmp(&(b.get_name().get_last(), b.get_name().get_first(), b.get_id()))
+    });
+
+    Ok(
+        teachers
+            .into_iter()
+            .map(|teacher| RosterEntry {
+                id: Some(teacher.get_id()),
+                name: teacher.get_name().clone(),
+                pronouns: teacher.get_pronouns().clone(),
+                oauth: oauths.remove(&teacher.get_id()).unwrap_or_default(),
+            })
+            .collect()
+    )
+}
+
+
+/// Why a single roster entry couldn't be imported.
+enum EntryError {
+    /// Something wrong with the entry itself, which is reported for its row.
+    Invalid(String),
+    /// Something wrong with the database, which fails the whole import.
+    Database(sqlx::Error),
+}
+impl From<sqlx::Error> for EntryError {
+    fn from(e: sqlx::Error) -> Self {
+        let constraint = e.as_database_error().and_then(|e| e.constraint());
+        match constraint {
+            Some("no_name_duplicates") => Self::Invalid("Another teacher already has this name".to_string()),
+            Some(constraint) => Self::Invalid(format!("Conflicts with an existing teacher ({constraint})")),
+            None => Self::Database(e),
+        }
+    }
+}
+
+/// Imports a single entry, returning whether it added a new teacher. The
+/// entry's OAuth accounts replace the teacher's existing ones.
+async fn import_entry(conn: &mut PgConnection, entry: RosterEntry) -> Result<bool, EntryError> {
+    let RosterEntry { id, name, pronouns, oauth } = entry;
+    let PronounSet {
+        sub, object: obj,
+        pos_adj, pos_pro,
+        refx, gramm_plu,
+    } = pronouns;
+
+    let pronoun_set_id = prepared_query!(
+        r#"
+            INSERT INTO pronoun_sets (
+                id,
+                sub, obj,
+                pos_adj, pos_pro,
+                refx, gramm_plu
+            )
+            VALUES (uuid_generate_v4(), $1, $2, $3, $4, $5, $6)
+                ON CONFLICT
+                    ON CONSTRAINT nopronounsetduplicates
+                    DO UPDATE SET id = pronoun_sets.id
+                RETURNING id AS "id: _";
+        "#;
+        { id: Uuid };
+        sub, obj,
+        pos_adj, pos_pro,
+        refx, gramm_plu,
+    ).fetch_one(&mut *conn).await?.id;
+
+    let id = id.unwrap_or_else(Uuid::new_v4);
+    let exists = prepared_query!(
+        r#"
+            SELECT EXISTS (SELECT 1 FROM teachers WHERE id = $1) as "exists!";
+        "#;
+        { exists: bool };
+        id,
+    ).fetch_one(&mut *conn).await?.exists;
+
+    let honorific = name.get_honorific().str();
+    let middle_texts: Vec<_> = name.all_middles().map(|(_, name)| name.to_string()).collect();
+    let middle_display: Vec<_> = name.all_middles().map(|(display, _)| display).collect();
+
+    if exists {
+        sqlx::query!(
+            r#"
+                UPDATE teachers
+                SET pronouns = $2
+                WHERE id = $1;
+            "#,
+            id,
+            pronoun_set_id,
+        ).execute(&mut *conn).await?;
+
+        sqlx::query!(
+            r#"
+                UPDATE names
+                SET
+                    first = $2,
+                    last = $3,
+                    middle_texts = $4,
+                    middle_display = $5,
+                    honorific = $6
+                WHERE
+                    name_of = $1;
+            "#,
+            id,
+            name.get_first(), name.get_last(),
+            middle_texts.as_slice(), &middle_display,
+            honorific,
+        ).execute(&mut *conn).await?;
+    } else {
+        sqlx::query!(
+            r#"
+                INSERT INTO teachers (id, pronouns)
+                VALUES ($1, $2);
+            "#,
+            id,
+            pronoun_set_id,
+        ).execute(&mut *conn).await?;
+
+        sqlx::query!(
+            r#"
+                INSERT INTO names (
+                    name_of,
+                    first, last,
+                    middle_texts, middle_display,
+                    honorific
+                ) VALUES ($1, $2, $3, $4, $5, $6);
+            "#,
+            id,
+            name.get_first(), name.get_last(),
+            middle_texts.as_slice(), &middle_display,
+            honorific,
+        ).execute(&mut *conn).await?;
+    }
+
+    let providers: Vec<_> = oauth.iter().map(|link| link.provider.clone()).collect();
+    sqlx::query!(
+        r#"
+            DELETE FROM teacher_oauths
+            WHERE
+                teacher = $1 AND
+                NOT (provider = ANY($2));
+        "#,
+        id,
+        providers.as_slice(),
+    ).execute(&mut *conn).await?;
+
+    for OauthLink { provider, sub } in oauth {
+        let owner = prepared_query!(
+            r#"
+                SELECT teacher
+                FROM teacher_oauths
+                WHERE
+                    provider = $1 AND
+                    sub = $2 AND
+                    teacher <> $3
+                LIMIT 1;
+            "#;
+            { teacher: Uuid };
+            provider,
+            sub,
+            id,
+        ).fetch_optional(&mut *conn).await?;
+
+        if let Some(owner) = owner {
+            return Err(EntryError::Invalid(format!("{provider} account {sub} already belongs to teacher {}", owner.teacher)));
+        }
+
+        sqlx::query!(
+            r#"
+                INSERT INTO teacher_oauths (teacher, provider, sub)
+                VALUES ($1, $2, $3)
+                    ON CONFLICT
+                        ON CONSTRAINT unique_sub_for_provider
+                        DO UPDATE SET sub = EXCLUDED.sub;
+            "#,
+            id,
+            provider,
+            sub,
+        ).execute(&mut *conn).await?;
+    }
+
+    Ok(!exists)
+}
+
+/// Imports a roster in a single transaction, adding or updating a teacher for
+/// each entry. Each entry gets its own savepoint so that every invalid row can
+/// be reported, but nothing is committed if any row fails or if it's a dry
+/// run.
+///
+/// `errors` are the rows that couldn't be parsed, so nothing is committed if
+/// there are any.
+///
+/// An applied import is recorded in the audit log in the same transaction,
+/// with the whole roster before and after.
+pub async fn import_roster(
+    ctx: &mut Ctx,
+    origin: &AuditOrigin,
+    entries: Vec<(usize, RosterEntry)>,
+    errors: Vec<RosterRowError>,
+    dry_run: bool,
+) -> Result<RosterImportReport, sqlx::Error> {
+    let mut report = RosterImportReport { dry_run, applied: false, created: 0, updated: 0, errors };
+
+    let mut txn = Connection::begin(&mut *ctx).await?;
+    let before = snapshot(&mut txn, AuditTarget::Roster).await?;
+
+    for (row, entry) in entries {
+        let mut savepoint = txn.begin().await?;
+
+        match import_entry(&mut savepoint, entry).await {
+            Ok(created) => {
+                savepoint.commit().await?;
+                if created {
+                    report.created += 1;
+                } else {
+                    report.updated += 1;
+                }
+            },
+            Err(EntryError::Invalid(message)) => {
+                savepoint.rollback().await?;
+                report.errors.push(RosterRowError { row, message });
+            },
+            Err(EntryError::Database(e)) => return Err(e),
+        }
+    }
+
+    report.applied = !dry_run && report.errors.is_empty();
+    if report.applied {
+        let after = snapshot(&mut txn, AuditTarget::Roster).await?;
+        record_change(&mut txn, origin, AuditTarget::Roster, before, after).await?;
+        txn.commit().await?;
+    } else {
+        txn.rollback().await?;
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,86 @@
+use crate::types::{ SheetSyncDirection, SheetSyncRun };
+
+use super::super::Ctx;
+use super::prepared_query;
+
+
+pub async fn record_sheet_sync(
+    ctx: &mut Ctx,
+    sheet_id: &str,
+    direction: SheetSyncDirection,
+    attempts: u32,
+    outcome: &Result<usize, String>,
+) -> Result<(), sqlx::Error> {
+    let (success, rows, error) = match outcome {
+        Ok(rows) => (true, *rows as i32, None),
+        Err(e) => (false, 0, Some(e.as_str())),
+    };
+
+    let record_query = prepared_query!(
+        r"
+            INSERT INTO sheet_sync_runs (id, sheet_id, direction, success, rows, attempts, error)
+            VALUES (uuid_generate_v4(), $1, $2, $3, $4, $5, $6);
+        ";
+        {  };
+        sheet_id, direction.str(), success, rows, attempts as i32, error,
+    );
+
+    record_query.execute(&mut *ctx).await?;
+
+    Ok(())
+}
+
+/// Gets the most recent sync, optionally only in one direction.
+pub async fn get_last_sheet_sync(
+    ctx: &mut Ctx,
+    direction: Option<SheetSyncDirection>,
+) -> Result<Option<SheetSyncRun>, sqlx::Error> {
+    let last_sync_query = prepared_query!(
+        r#"
+            SELECT
+                EXTRACT(EPOCH FROM ran_at)::float as "ran_at!",
+                sheet_id,
+                direction,
+                success,
+                rows,
+                attempts,
+                error
+            FROM sheet_sync_runs
+            WHERE $1::varchar IS NULL OR direction = $1
+            ORDER BY ran_at DESC
+            LIMIT 1;
+        "#;
+        {
+            ran_at: f64,
+            sheet_id: String,
+            direction: String,
+            success: bool,
+            rows: i32,
+            attempts: i32,
+            error: Option<String>,
+        };
+        direction.map(|direction| direction.str()),
+    );
+
+    let Some(run) = last_sync_query.fetch_optional(&mut *ctx).await? else {
+        return Ok(None);
+    };
+
+    let ran_at = chrono::DateTime::from_timestamp(
+        run.ran_at.floor() as i64,
+        (run.ran_at.fract() * 1_000_000_000.0) as u32,
+    ).ok_or_else(|| sqlx::Error::Decode(format!("sheet_sync_runs.ran_at out of range: {}", run.ran_at).into()))?;
+
+    let direction = SheetSyncDirection::try_from_str(&run.direction)
+        .ok_or_else(|| sqlx::Error::Decode(format!("Invalid sheet sync direction: {}", run.direction).into()))?;
+
+    Ok(Some(SheetSyncRun {
+        ran_at,
+        sheet_id: run.sheet_id,
+        direction,
+        success: run.success,
+        rows: run.rows,
+        attempts: run.attempts,
+        error: run.error,
+    }))
+}
@@ -1,4 +1,3 @@
-use constant_time_eq::constant_time_eq;
 use sqlx::{ query, query_as, Connection };
 use uuid::Uuid;
 
@@ -11,6 +10,7 @@ use crate::types::{Teacher, TeacherName, PronounSet, Honorific};
 pub struct SqlTeacherInfo {
     id: Uuid,
     fully_absent: bool,
+    archived: bool,
 
     #[allow(unused)]
     pro_id: Uuid,
@@ -34,6 +34,7 @@ impl From<SqlTeacherInfo> for Option<Teacher> {
         let SqlTeacherInfo {
             id,
             fully_absent,
+            archived,
 
             pro_id: _,
             pro_sub, pro_obj,
@@ -58,7 +59,11 @@ impl From<SqlTeacherInfo> for Option<Teacher> {
             refx: pro_refx, gramm_plu: pro_gramm_plu,
         };
 
-        Some(Teacher::new(id, name, pronouns).with_fully_absence(fully_absent))
+        Some(
+            Teacher::new(id, name, pronouns)
+                .with_fully_absence(fully_absent)
+                .with_archived(archived)
+        )
     }
 }
 
@@ -67,7 +72,7 @@ pub async fn get_teacher(ctx: &mut Ctx, id: Uuid) -> Result<Teacher, sqlx::Error
         SqlTeacherInfo,
         r#"
             SELECT
-                t.id, t.fully_absent,
+                t.id, t.fully_absent, t.archived,
 
                 p.id AS pro_id,
                 p.sub AS pro_sub, p.obj AS pro_obj,
@@ -86,18 +91,23 @@ pub async fn get_teacher(ctx: &mut Ctx, id: Uuid) -> Result<Teacher, sqlx::Error
         id,
     );
 
-    let teacher_info = get_teacher_query.fetch_one(&mut **ctx).await?;
+    let teacher_info = get_teacher_query.fetch_one(&mut *ctx).await?;
 
     Option::from(teacher_info)
         .ok_or_else(|| sqlx::Error::ColumnNotFound(id.to_string()))
 }
 
+/// Gets every teacher that hasn't been archived.
 pub async fn get_all_teachers(ctx: &mut Ctx) -> Result<Vec<Teacher>, sqlx::Error> {
+    get_teachers(ctx, false).await
+}
+
+pub async fn get_teachers(ctx: &mut Ctx, include_archived: bool) -> Result<Vec<Teacher>, sqlx::Error> {
     let get_all_teachers_query = query_as!(
         SqlTeacherInfo,
         r#"
             SELECT
-                t.id, t.fully_absent,
+                t.id, t.fully_absent, t.archived,
 
                 p.id AS pro_id,
                 p.sub AS pro_sub, p.obj AS pro_obj,
@@ -110,11 +120,13 @@ pub async fn get_all_teachers(ctx: &mut Ctx) -> Result<Vec<Teacher>, sqlx::Error
                 n.middle_texts AS name_middle_texts, n.middle_display AS name_middle_display
             FROM teachers AS t
                 INNER JOIN pronoun_sets AS p ON t.pronouns = p.id
-                INNER JOIN names AS n ON t.id = n.name_of;
+                INNER JOIN names AS n ON t.id = n.name_of
+                WHERE $1 OR NOT t.archived;
         "#,
+        include_archived,
     );
 
-    let teacher_info = get_all_teachers_query.fetch_all(&mut **ctx).await?;
+    let teacher_info = get_all_teachers_query.fetch_all(&mut *ctx).await?;
 
     Ok(teacher_info.into_iter().filter_map(Option::from).collect())
 }
@@ -217,7 +229,7 @@ pub async fn update_teacher_name(ctx: &mut Ctx, id: Uuid, name: TeacherName) ->
         honorific,
     );
 
-    add_name.execute(&mut **ctx).await?;
+    add_name.execute(&mut *ctx).await?;
 
     get_teacher(ctx, id).await
 }
@@ -282,7 +294,7 @@ pub async fn update_teacher_full_absence(ctx: &mut Ctx, id: Uuid, fully_absent:
         fully_absent
     );
 
-    update_absence.execute(&mut **ctx).await?;
+    update_absence.execute(&mut *ctx).await?;
 
     get_teacher(ctx, id).await
 }
@@ -303,31 +315,10 @@ pub async fn get_teacher_by_oauth(ctx: &mut Ctx, provider: String, sub: String)
         sub,
     );
 
-    let teacher_id = teacher_oauth_query.fetch_one(&mut **ctx).await?.id;
+    let teacher_id = teacher_oauth_query.fetch_one(&mut *ctx).await?.id;
 
     get_teacher(ctx, teacher_id).await
 }
-pub async fn check_teacher_oauth(ctx: &mut Ctx, id: Uuid, provider: String, sub: String) -> Result<bool, sqlx::Error> {
-    let get_sub_query = query!(
-        r#"
-            SELECT sub
-            FROM teacher_oauths
-            WHERE
-                provider = $1 AND
-                teacher = $2;
-        "#,
-        provider,
-        id,
-    );
-
-    let db_sub = get_sub_query.fetch_one(&mut **ctx).await?.sub;
-    
-    if db_sub.len() != sub.len() {
-        return Ok(false);
-    }
-
-    Ok(constant_time_eq(db_sub.as_bytes(), sub.as_bytes()))
-}
 
 pub async fn add_teacher_oauth(ctx: &mut Ctx, teacher: Uuid, provider: String, sub: String) -> Result<(), sqlx::Error> {
     let add_teacher_oauth = query!(
@@ -340,7 +331,7 @@ pub async fn add_teacher_oauth(ctx: &mut Ctx, teacher: Uuid, provider: String, s
         sub,
     );
 
-    add_teacher_oauth.execute(&mut **ctx).await?;
+    add_teacher_oauth.execute(&mut *ctx).await?;
 
     Ok(())
 }
@@ -357,7 +348,103 @@ pub async fn remove_teacher_oauth(ctx: &mut Ctx, teacher: Uuid, provider: String
         provider,
     );
 
-    remove_teacher_oauth.execute(&mut **ctx).await?;
+    remove_teacher_oauth.execute(&mut *ctx).await?;
 
     Ok(())
 }
+
+
+/// Archives a teacher, hiding them from the teacher list and taking them off
+/// today's absence board. Their sign-ins, privileges, and absence records are
+/// kept so they can be restored.
+pub async fn archive_teacher(ctx: &mut Ctx, id: Uuid) -> Result<Teacher, sqlx::Error> {
+    let archive_teacher_query = prepared_query!(
+        r"
+            UPDATE teachers
+            SET archived = true, fully_absent = false
+            WHERE id = $1;
+        ";
+        {  };
+        id,
+    );
+    let clear_absences_query = prepared_query!(
+        r"
+            DELETE FROM absence_xref
+            WHERE teacher_id = $1;
+        ";
+        {  };
+        id,
+    );
+    let clear_coverage_query = prepared_query!(
+        r"
+            DELETE FROM coverage
+            WHERE teacher_id = $1 OR substitute_id = $1;
+        ";
+        {  };
+        id,
+    );
+
+    ctx.transaction(|txn| Box::pin(async move {
+        if archive_teacher_query.execute(&mut **txn).await?.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        clear_absences_query.execute(&mut **txn).await?;
+        clear_coverage_query.execute(&mut **txn).await
+    })).await?;
+
+    get_teacher(ctx, id).await
+}
+
+pub async fn restore_teacher(ctx: &mut Ctx, id: Uuid) -> Result<Teacher, sqlx::Error> {
+    let restore_teacher_query = prepared_query!(
+        r"
+            UPDATE teachers
+            SET archived = false
+            WHERE id = $1;
+        ";
+        {  };
+        id,
+    );
+
+    if restore_teacher_query.execute(&mut *ctx).await?.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    get_teacher(ctx, id).await
+}
+
+/// Gets which of `ids` belong to archived teachers.
+pub async fn get_archived_teachers(ctx: &mut Ctx, ids: &[Uuid]) -> Result<Vec<Uuid>, sqlx::Error> {
+    let archived_teachers_query = prepared_query!(
+        r"
+            SELECT id
+            FROM teachers
+            WHERE id = ANY($1) AND archived;
+        ";
+        { id: Uuid };
+        ids,
+    );
+
+    let archived = archived_teachers_query.fetch_all(&mut *ctx).await?;
+
+    Ok(archived.into_iter().map(|row| row.id).collect())
+}
+
+/// Deletes a teacher for good, along with their name, absences, sign-ins,
+/// privileges, and future absences. Returns false if they didn't exist.
+///
+/// Their absence history is kept, but no longer says who it was for.
+pub async fn delete_teacher(ctx: &mut Ctx, id: Uuid) -> Result<bool, sqlx::Error> {
+    let delete_teacher_query = prepared_query!(
+        r"
+            DELETE FROM teachers
+            WHERE id = $1;
+        ";
+        {  };
+        id,
+    );
+
+    let res = delete_teacher_query.execute(&mut *ctx).await?;
+
+    Ok(res.rows_affected() > 0)
+}
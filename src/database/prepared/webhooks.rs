@@ -0,0 +1,415 @@
+use chrono::{ DateTime, Utc };
+use uuid::Uuid;
+
+use crate::types::{ Webhook, WebhookDelivery, WebhookEvent };
+
+use super::super::Ctx;
+use super::prepared_query;
+
+
+fn timestamp_from_epoch(column: &str, epoch: f64) -> Result<DateTime<Utc>, sqlx::Error> {
+    DateTime::from_timestamp(
+        epoch.floor() as i64,
+        (epoch.fract() * 1_000_000_000.0) as u32,
+    ).ok_or_else(|| sqlx::Error::Decode(format!("{column} out of range: {epoch}").into()))
+}
+
+fn events_from_row(events: Vec<String>) -> Result<Vec<WebhookEvent>, sqlx::Error> {
+    events
+        .iter()
+        .map(|event| WebhookEvent::try_from_str(event)
+            .ok_or_else(|| sqlx::Error::Decode(format!("Invalid webhook event: {event}").into())))
+        .collect()
+}
+
+fn events_to_row(events: &[WebhookEvent]) -> Vec<String> {
+    events.iter().map(|event| event.str().to_string()).collect()
+}
+
+
+pub async fn get_webhook(ctx: &mut Ctx, id: Uuid) -> Result<Webhook, sqlx::Error> {
+    let get_webhook_query = prepared_query!(
+        r#"
+            SELECT
+                id, url, description, events, active,
+                EXTRACT(EPOCH FROM created_at)::float as "created_at!"
+            FROM webhooks
+            WHERE id = $1;
+        "#;
+        {
+            id: Uuid,
+            url: String,
+            description: String,
+            events: Vec<String>,
+            active: bool,
+            created_at: f64,
+        };
+        id,
+    );
+
+    let webhook = get_webhook_query.fetch_one(&mut *ctx).await?;
+
+    Ok(Webhook {
+        id: webhook.id,
+        url: webhook.url,
+        description: webhook.description,
+        events: events_from_row(webhook.events)?,
+        active: webhook.active,
+        created_at: timestamp_from_epoch("webhooks.created_at", webhook.created_at)?,
+    })
+}
+
+pub async fn get_all_webhooks(ctx: &mut Ctx) -> Result<Vec<Webhook>, sqlx::Error> {
+    let get_webhooks_query = prepared_query!(
+        r#"
+            SELECT
+                id, url, description, events, active,
+                EXTRACT(EPOCH FROM created_at)::float as "created_at!"
+            FROM webhooks
+            ORDER BY created_at, id;
+        "#;
+        {
+            id: Uuid,
+            url: String,
+            description: String,
+            events: Vec<String>,
+            active: bool,
+            created_at: f64,
+        };
+    );
+
+    get_webhooks_query
+        .fetch_all(&mut *ctx)
+        .await?
+        .into_iter()
+        .map(|webhook| Ok(Webhook {
+            id: webhook.id,
+            url: webhook.url,
+            description: webhook.description,
+            events: events_from_row(webhook.events)?,
+            active: webhook.active,
+            created_at: timestamp_from_epoch("webhooks.created_at", webhook.created_at)?,
+        }))
+        .collect()
+}
+
+pub async fn create_webhook(
+    ctx: &mut Ctx,
+    url: &str,
+    description: &str,
+    events: &[WebhookEvent],
+    secret: &str,
+) -> Result<Webhook, sqlx::Error> {
+    let create_webhook_query = prepared_query!(
+        r"
+            INSERT INTO webhooks (id, url, description, events, secret)
+            VALUES (uuid_generate_v4(), $1, $2, $3, $4)
+            RETURNING id;
+        ";
+        { id: Uuid };
+        url, description, &events_to_row(events), secret,
+    );
+
+    let id = create_webhook_query.fetch_one(&mut *ctx).await?.id;
+
+    get_webhook(ctx, id).await
+}
+
+/// Updates the parts of a webhook that are set, leaving the rest as is.
+pub async fn update_webhook(
+    ctx: &mut Ctx,
+    id: Uuid,
+    url: Option<&str>,
+    description: Option<&str>,
+    events: Option<&[WebhookEvent]>,
+    active: Option<bool>,
+) -> Result<Webhook, sqlx::Error> {
+    let events = events.map(events_to_row);
+    let update_webhook_query = prepared_query!(
+        r"
+            UPDATE webhooks
+            SET
+                url = COALESCE($2, url),
+                description = COALESCE($3, description),
+                events = COALESCE($4, events),
+                active = COALESCE($5, active)
+            WHERE id = $1;
+        ";
+        {  };
+        id, url, description, events.as_deref(), active,
+    );
+
+    update_webhook_query.execute(&mut *ctx).await?;
+
+    get_webhook(ctx, id).await
+}
+
+pub async fn set_webhook_secret(ctx: &mut Ctx, id: Uuid, secret: &str) -> Result<Webhook, sqlx::Error> {
+    let set_secret_query = prepared_query!(
+        r"
+            UPDATE webhooks
+            SET secret = $2
+            WHERE id = $1;
+        ";
+        {  };
+        id, secret,
+    );
+
+    set_secret_query.execute(&mut *ctx).await?;
+
+    get_webhook(ctx, id).await
+}
+
+/// Deletes a webhook along with its deliveries. Returns false if it didn't
+/// exist.
+pub async fn delete_webhook(ctx: &mut Ctx, id: Uuid) -> Result<bool, sqlx::Error> {
+    let delete_webhook_query = prepared_query!(
+        r"
+            DELETE FROM webhooks
+            WHERE id = $1;
+        ";
+        {  };
+        id,
+    );
+
+    let res = delete_webhook_query.execute(&mut *ctx).await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+
+/// Adds a delivery of an event to the outbox of every active webhook that
+/// wants it. Returns how many deliveries were added.
+pub async fn enqueue_webhook_event(ctx: &mut Ctx, event: WebhookEvent, payload: &str) -> Result<u64, sqlx::Error> {
+    let enqueue_query = prepared_query!(
+        r"
+            INSERT INTO webhook_deliveries (id, webhook_id, event, payload)
+            SELECT uuid_generate_v4(), id, $1::varchar, $2
+            FROM webhooks
+            WHERE
+                active AND
+                $1::varchar = ANY(events);
+        ";
+        {  };
+        event.str(), payload,
+    );
+
+    let res = enqueue_query.execute(&mut *ctx).await?;
+
+    Ok(res.rows_affected())
+}
+
+/// A delivery that's due to be sent, along with where and how to send it.
+#[derive(Debug, Clone)]
+pub struct DueDelivery {
+    pub id: Uuid,
+    pub url: String,
+    pub secret: String,
+    pub event: String,
+    pub payload: String,
+    pub attempts: i32,
+}
+
+/// Claims up to `limit` of the deliveries that are due to be sent to active
+/// webhooks, oldest first.
+///
+/// Claimed deliveries aren't due again for `lease_secs`, so other servers
+/// sharing the database skip them while they're being sent. Rows another
+/// server is claiming at the same time are skipped rather than waited on. If
+/// the server stops before recording the outcome, the delivery is retried
+/// once the lease runs out.
+pub async fn claim_due_deliveries(ctx: &mut Ctx, limit: i64, lease_secs: f64) -> Result<Vec<DueDelivery>, sqlx::Error> {
+    let claim_due_query = prepared_query!(
+        r#"
+            WITH due AS (
+                SELECT d.id
+                FROM webhook_deliveries AS d
+                    INNER JOIN webhooks AS w ON w.id = d.webhook_id
+                WHERE
+                    w.active AND
+                    d.delivered_at IS NULL AND
+                    d.failed_at IS NULL AND
+                    d.next_attempt_at <= now()
+                ORDER BY d.next_attempt_at, d.created_at
+                LIMIT $1
+                FOR UPDATE OF d SKIP LOCKED
+            )
+            UPDATE webhook_deliveries AS d
+            SET next_attempt_at = now() + $2::float * INTERVAL '1 second'
+            FROM due, webhooks AS w
+            WHERE d.id = due.id AND w.id = d.webhook_id
+            RETURNING
+                d.id, w.url, w.secret, d.event, d.payload, d.attempts,
+                EXTRACT(EPOCH FROM d.created_at)::float as "created_at!";
+        "#;
+        {
+            id: Uuid,
+            url: String,
+            secret: String,
+            event: String,
+            payload: String,
+            attempts: i32,
+            created_at: f64,
+        };
+        limit, lease_secs,
+    );
+
+    let mut claimed = claim_due_query
+        .fetch_all(&mut *ctx)
+        .await?;
+    claimed.sort_by(|a, b| a.created_at.total_cmp(&b.created_at));
+
+    Ok(
+        claimed
+            .into_iter()
+            .map(|due| DueDelivery {
+                id: due.id,
+                url: due.url,
+                secret: due.secret,
+                event: due.event,
+                payload: due.payload,
+                attempts: due.attempts,
+            })
+            .collect()
+    )
+}
+
+pub async fn mark_delivered(ctx: &mut Ctx, id: Uuid, status: i32) -> Result<(), sqlx::Error> {
+    let mark_delivered_query = prepared_query!(
+        r"
+            UPDATE webhook_deliveries
+            SET
+                attempts = attempts + 1,
+                last_status = $2,
+                last_error = NULL,
+                delivered_at = now()
+            WHERE id = $1;
+        ";
+        {  };
+        id, status,
+    );
+
+    mark_delivered_query.execute(&mut *ctx).await?;
+
+    Ok(())
+}
+
+/// Records a failed attempt, scheduling the next one in `retry_in_secs`, or
+/// giving up on the delivery if that isn't set.
+pub async fn mark_attempt_failed(
+    ctx: &mut Ctx,
+    id: Uuid,
+    status: Option<i32>,
+    error: &str,
+    retry_in_secs: Option<f64>,
+) -> Result<(), sqlx::Error> {
+    let mark_failed_query = prepared_query!(
+        r"
+            UPDATE webhook_deliveries
+            SET
+                attempts = attempts + 1,
+                last_status = $2,
+                last_error = $3,
+                next_attempt_at = now() + COALESCE($4::float, 0) * INTERVAL '1 second',
+                failed_at = CASE WHEN $4::float IS NULL THEN now() END
+            WHERE id = $1;
+        ";
+        {  };
+        id, status, error, retry_in_secs,
+    );
+
+    mark_failed_query.execute(&mut *ctx).await?;
+
+    Ok(())
+}
+
+
+/// Gets the most recent deliveries, optionally only for one webhook.
+pub async fn get_webhook_deliveries(
+    ctx: &mut Ctx,
+    webhook_id: Option<Uuid>,
+    id: Option<Uuid>,
+    limit: i64,
+) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+    let get_deliveries_query = prepared_query!(
+        r#"
+            SELECT
+                id, webhook_id, event, payload,
+                EXTRACT(EPOCH FROM created_at)::float as "created_at!",
+                attempts,
+                EXTRACT(EPOCH FROM next_attempt_at)::float as "next_attempt_at!",
+                last_status, last_error,
+                EXTRACT(EPOCH FROM delivered_at)::float as delivered_at,
+                EXTRACT(EPOCH FROM failed_at)::float as failed_at
+            FROM webhook_deliveries
+            WHERE
+                ($1::uuid IS NULL OR webhook_id = $1) AND
+                ($2::uuid IS NULL OR id = $2)
+            ORDER BY created_at DESC, id
+            LIMIT $3;
+        "#;
+        {
+            id: Uuid,
+            webhook_id: Uuid,
+            event: String,
+            payload: String,
+            created_at: f64,
+            attempts: i32,
+            next_attempt_at: f64,
+            last_status: Option<i32>,
+            last_error: Option<String>,
+            delivered_at: Option<f64>,
+            failed_at: Option<f64>,
+        };
+        webhook_id, id, limit,
+    );
+
+    get_deliveries_query
+        .fetch_all(&mut *ctx)
+        .await?
+        .into_iter()
+        .map(|delivery| Ok(WebhookDelivery {
+            id: delivery.id,
+            webhook_id: delivery.webhook_id,
+            event: WebhookEvent::try_from_str(&delivery.event)
+                .ok_or_else(|| sqlx::Error::Decode(format!("Invalid webhook event: {}", delivery.event).into()))?,
+            payload: delivery.payload,
+            created_at: timestamp_from_epoch("webhook_deliveries.created_at", delivery.created_at)?,
+            attempts: delivery.attempts,
+            next_attempt_at: timestamp_from_epoch("webhook_deliveries.next_attempt_at", delivery.next_attempt_at)?,
+            last_status: delivery.last_status,
+            last_error: delivery.last_error,
+            delivered_at: delivery.delivered_at
+                .map(|epoch| timestamp_from_epoch("webhook_deliveries.delivered_at", epoch))
+                .transpose()?,
+            failed_at: delivery.failed_at
+                .map(|epoch| timestamp_from_epoch("webhook_deliveries.failed_at", epoch))
+                .transpose()?,
+        }))
+        .collect()
+}
+
+/// Queues a delivery to be sent again right away, even if it was given up on
+/// or already delivered.
+pub async fn retry_webhook_delivery(ctx: &mut Ctx, id: Uuid) -> Result<WebhookDelivery, sqlx::Error> {
+    let retry_query = prepared_query!(
+        r"
+            UPDATE webhook_deliveries
+            SET
+                attempts = 0,
+                next_attempt_at = now(),
+                delivered_at = NULL,
+                failed_at = NULL
+            WHERE id = $1;
+        ";
+        {  };
+        id,
+    );
+
+    retry_query.execute(&mut *ctx).await?;
+
+    get_webhook_deliveries(ctx, None, Some(id), 1)
+        .await?
+        .pop()
+        .ok_or(sqlx::Error::RowNotFound)
+}
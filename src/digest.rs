@@ -0,0 +1,526 @@
+//! This module builds and emails the daily digest of who is out, for front
+//! office staff.
+//!
+//! The digest lists every absent teacher with the periods they're out for,
+//! and where their classes report to: the teacher's assigned room for the
+//! period, or the global `report_to` location. It's blind copied to the
+//! addresses in the
+//! `digestRecipients` attrib (a JSON array of strings like
+//! `"Front Office <office@school.org>"`) right after each scheduled day
+//! rollover on a school day, once today's future absences have been applied.
+//!
+//! Mail is sent over SMTP, configured with environment variables:
+//! - `SMTP_HOST`: the SMTP server. Without it, digests aren't sent.
+//! - `SMTP_PORT`: optional, defaulting to the usual port for `SMTP_SECURITY`
+//! - `SMTP_SECURITY`: `starttls` (the default), `tls`, or `none`. Use `none`
+//!   with a local SMTP sink to test.
+//! - `SMTP_USERNAME` and `SMTP_PASSWORD`: optional credentials
+//! - `DIGEST_FROM`: the address digests are sent from
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+use lettre::message::{ Mailbox, MultiPart };
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{ AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor };
+use sqlx::types::JsonValue;
+use uuid::Uuid;
+
+use crate::database::Ctx;
+use crate::logging::*;
+use crate::types::{ AbsenceDigest, DigestEntry, DigestPeriod };
+
+
+/// The attrib holding who digests are sent to.
+pub const RECIPIENTS_ATTRIB: &str = "digestRecipients";
+
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+
+#[derive(Debug)]
+pub enum DigestError {
+    Database(sqlx::Error),
+    Message(String),
+    Smtp(String),
+}
+impl std::fmt::Display for DigestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Database(e) => write!(f, "database error: {e}"),
+            Self::Message(e) => write!(f, "failed to build message: {e}"),
+            Self::Smtp(e) => write!(f, "failed to send message: {e}"),
+        }
+    }
+}
+impl From<sqlx::Error> for DigestError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Database(e)
+    }
+}
+
+
+/// Builds today's digest from the absence board. Today is the database's
+/// `CURRENT_DATE`, like the rollover and calendar feeds use.
+pub async fn build(db_conn: &mut Ctx) -> Result<AbsenceDigest, sqlx::Error> {
+    use crate::database::prepared::absences::get_all_absences;
+    use crate::database::prepared::calendar::get_today;
+    use crate::database::prepared::config::get_report_to;
+    use crate::database::prepared::period::get_all_periods;
+    use crate::database::prepared::rooms::get_all_room_assignments;
+    use crate::database::prepared::teacher::get_all_teachers;
+
+    let report_to = get_report_to(db_conn).await?;
+
+    let mut periods = get_all_periods(db_conn).await?;
+    periods.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    let mut absent_in: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+    for absence in get_all_absences(db_conn).await? {
+        absent_in.entry(absence.teacher).or_default().push(absence.period);
+    }
+
+    let rooms: HashMap<(Uuid, Uuid), String> = get_all_room_assignments(db_conn)
+        .await?
+        .into_iter()
+        .map(|assignment| ((assignment.teacher_id, assignment.period_id), assignment.room.name))
+        .collect();
+
+    let mut teachers = get_all_teachers(db_conn).await?;
+    teachers.sort_by(|a, b| {
+        (a.get_name().get_last(), a.get_name().get_first(), a.get_id())
+            .cmp(&(b.get_name().get_last(), b.get_name().get_first(), b.get_id()))
+    });
+
+    let mut entries = Vec::new();
+    for teacher in teachers {
+        let fully_absent = teacher.get_fully_absent();
+        let absent_periods = absent_in.remove(&teacher.get_id()).unwrap_or_default();
+        if absent_periods.is_empty() && !fully_absent {
+            continue;
+        }
+
+        let digest_periods = periods
+            .iter()
+            .filter(|period| fully_absent || absent_periods.contains(&period.id))
+            .map(|period| DigestPeriod {
+                name: period.name.clone(),
+                report_to: rooms
+                    .get(&(teacher.get_id(), period.id))
+                    .unwrap_or(&report_to)
+                    .clone(),
+            })
+            .collect();
+
+        entries.push(DigestEntry {
+            teacher: teacher.get_name().to_string(),
+            fully_absent,
+            periods: digest_periods,
+        });
+    }
+
+    Ok(AbsenceDigest {
+        date: get_today(db_conn).await?,
+        report_to,
+        entries,
+    })
+}
+
+pub fn subject(digest: &AbsenceDigest) -> String {
+    format!("Absences for {}", digest.date.format("%A, %B %-d"))
+}
+
+/// The periods a teacher is out for, with where their class reports to in
+/// the periods it doesn't go to the digest's `report_to`.
+fn entry_periods(entry: &DigestEntry, report_to: &str) -> String {
+    let moved = |period: &&DigestPeriod| period.report_to != report_to;
+
+    if entry.fully_absent {
+        let rooms = entry.periods
+            .iter()
+            .filter(moved)
+            .map(|period| format!("{} in {}", period.report_to, period.name))
+            .collect::<Vec<_>>();
+
+        if rooms.is_empty() {
+            "All day".to_string()
+        } else {
+            format!("All day (report to {})", rooms.join(", "))
+        }
+    } else {
+        entry.periods
+            .iter()
+            .map(|period| if moved(&period) {
+                format!("{} (report to {})", period.name, period.report_to)
+            } else {
+                period.name.clone()
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Where students should go if their teacher's class isn't listed as going
+/// somewhere else.
+fn report_to_line(digest: &AbsenceDigest) -> &'static str {
+    let any_moved = digest.entries
+        .iter()
+        .flat_map(|entry| &entry.periods)
+        .any(|period| period.report_to != digest.report_to);
+
+    if any_moved {
+        "Otherwise, students of absent teachers should report to"
+    } else {
+        "Students of absent teachers should report to"
+    }
+}
+
+/// Renders a digest as plain text.
+pub fn render_text(digest: &AbsenceDigest) -> String {
+    let mut text = format!("{}\n\n", subject(digest));
+
+    if digest.entries.is_empty() {
+        text.push_str("No teachers are out today.\n");
+        return text;
+    }
+
+    for entry in &digest.entries {
+        let _ = writeln!(text, "- {}: {}", entry.teacher, entry_periods(entry, &digest.report_to));
+    }
+    let _ = write!(text, "\n{} {}.\n", report_to_line(digest), digest.report_to);
+
+    text
+}
+
+fn escape_html(s: &str) -> String {
+    s.chars().fold(String::with_capacity(s.len()), |mut escaped, c| {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+        escaped
+    })
+}
+
+/// Renders a digest as HTML, with a table of who is out.
+pub fn render_html(digest: &AbsenceDigest) -> String {
+    let mut html = format!("<h2>{}</h2>\n", escape_html(&subject(digest)));
+
+    if digest.entries.is_empty() {
+        html.push_str("<p>No teachers are out today.</p>\n");
+        return html;
+    }
+
+    html.push_str("<table>\n<tr><th align=\"left\">Teacher</th><th align=\"left\">Out</th></tr>\n");
+    for entry in &digest.entries {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            escape_html(&entry.teacher),
+            escape_html(&entry_periods(entry, &digest.report_to)),
+        );
+    }
+    html.push_str("</table>\n");
+    let _ = writeln!(
+        html,
+        "<p>{} <b>{}</b>.</p>",
+        report_to_line(digest),
+        escape_html(&digest.report_to),
+    );
+
+    html
+}
+
+/// Gets who digests are sent to from the `digestRecipients` attrib. Addresses
+/// that can't be parsed are skipped.
+pub async fn recipients(db_conn: &mut Ctx) -> Result<Vec<Mailbox>, sqlx::Error> {
+    use crate::database::prepared::config::get_attribs;
+
+    let recipients = match get_attribs(db_conn).await?.remove(RECIPIENTS_ATTRIB) {
+        Some(JsonValue::Array(recipients)) => recipients,
+        Some(JsonValue::Null) | None => Vec::new(),
+        Some(_) => {
+            warn!("The {RECIPIENTS_ATTRIB} attrib is not an array of addresses");
+            Vec::new()
+        },
+    };
+
+    Ok(
+        recipients
+            .iter()
+            .filter_map(|recipient| match recipient.as_str().map(str::parse::<Mailbox>) {
+                Some(Ok(mailbox)) => Some(mailbox),
+                _ => {
+                    warn!("Skipping invalid digest recipient {recipient}");
+                    None
+                },
+            })
+            .collect()
+    )
+}
+
+
+/// Sends digests over SMTP.
+pub struct DigestMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+impl std::fmt::Debug for DigestMailer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DigestMailer")
+            .field("from", &self.from)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DigestMailer {
+    /// Sets up the mailer from the environment, or returns `None` (after
+    /// logging why) if digests can't be sent.
+    pub fn from_env() -> Option<Self> {
+        let Ok(host) = std::env::var("SMTP_HOST") else {
+            info!("SMTP_HOST not set, absence digests will not be sent");
+            return None;
+        };
+
+        match Self::from_env_with_host(&host) {
+            Ok(mailer) => {
+                info!("Absence digests will be sent through {host} from {}", mailer.from);
+                Some(mailer)
+            },
+            Err(e) => {
+                error!("Failed to set up SMTP, absence digests will not be sent: {e}");
+                None
+            },
+        }
+    }
+
+    fn from_env_with_host(host: &str) -> Result<Self, String> {
+        let from = std::env::var("DIGEST_FROM")
+            .map_err(|_| "DIGEST_FROM not set".to_string())?
+            .parse::<Mailbox>()
+            .map_err(|e| format!("Invalid DIGEST_FROM: {e}"))?;
+
+        let security = std::env::var("SMTP_SECURITY").unwrap_or_else(|_| "starttls".to_string());
+        let mut builder = match security.trim().to_ascii_lowercase().as_str() {
+            "starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host).map_err(|e| e.to_string())?,
+            "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(host).map_err(|e| e.to_string())?,
+            "none" => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+            other => return Err(format!("Invalid SMTP_SECURITY {other:?}, expected starttls, tls, or none")),
+        };
+
+        if let Ok(port) = std::env::var("SMTP_PORT") {
+            builder = builder.port(port.trim().parse().map_err(|e| format!("Invalid SMTP_PORT: {e}"))?);
+        }
+        if let (Ok(username), Ok(password)) = (std::env::var("SMTP_USERNAME"), std::env::var("SMTP_PASSWORD")) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(Self {
+            transport: builder.timeout(Some(SMTP_TIMEOUT)).build(),
+            from,
+        })
+    }
+
+    /// Builds today's digest and sends it to every recipient, returning how
+    /// many it was sent to.
+    pub async fn send(&self, db_conn: &mut Ctx) -> Result<usize, DigestError> {
+        let recipients = recipients(db_conn).await?;
+        if recipients.is_empty() {
+            info!("No {RECIPIENTS_ATTRIB} set, skipping the absence digest");
+            return Ok(0);
+        }
+
+        let digest = build(db_conn).await?;
+        self.send_digest(&digest, &recipients).await?;
+
+        info!("Sent the absence digest ({} absent) to {} recipients", digest.entries.len(), recipients.len());
+        Ok(recipients.len())
+    }
+
+    /// Sends a digest in one message, blind copied to every recipient so they
+    /// don't see each other's addresses.
+    async fn send_digest(&self, digest: &AbsenceDigest, recipients: &[Mailbox]) -> Result<(), DigestError> {
+        let message = recipients
+            .iter()
+            .cloned()
+            .fold(Message::builder().from(self.from.clone()), |message, recipient| message.bcc(recipient))
+            .subject(subject(digest))
+            .multipart(MultiPart::alternative_plain_html(render_text(digest), render_html(digest)))
+            .map_err(|e| DigestError::Message(e.to_string()))?;
+
+        self.transport
+            .send(message)
+            .await
+            .map_err(|e| DigestError::Smtp(e.to_string()))?;
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{ Arc, Mutex };
+
+    use chrono::NaiveDate;
+    use tokio::io::{ AsyncBufReadExt, AsyncWriteExt, BufReader };
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    /// What the SMTP sink received.
+    #[derive(Debug, Default)]
+    struct Received {
+        rcpt_to: Vec<String>,
+        messages: Vec<String>,
+    }
+
+    /// Starts an SMTP sink that accepts mail, or rejects every recipient if
+    /// `reject` is set. Returns its port and what it receives.
+    async fn smtp_sink(reject: bool) -> (u16, Arc<Mutex<Received>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("sink should bind");
+        let port = listener.local_addr().expect("sink should have an address").port();
+        let received = Arc::new(Mutex::new(Received::default()));
+
+        let log = received.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let log = log.clone();
+                tokio::spawn(async move {
+                    let (read, mut write) = stream.into_split();
+                    let mut lines = BufReader::new(read).lines();
+
+                    if write.write_all(b"220 sink ESMTP\r\n").await.is_err() {
+                        return;
+                    }
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        let command = line.to_ascii_uppercase();
+                        let reply: &[u8] = if command.starts_with("EHLO") {
+                            b"250-sink\r\n250 OK\r\n"
+                        } else if command.starts_with("RCPT TO:") {
+                            log.lock().expect("log lock").rcpt_to.push(line["RCPT TO:".len()..].to_string());
+                            if reject { b"550 No such user\r\n" } else { b"250 OK\r\n" }
+                        } else if command == "DATA" {
+                            if write.write_all(b"354 Go ahead\r\n").await.is_err() {
+                                return;
+                            }
+                            let mut message = String::new();
+                            while let Ok(Some(line)) = lines.next_line().await {
+                                if line == "." {
+                                    break;
+                                }
+                                message.push_str(&line);
+                                message.push('\n');
+                            }
+                            log.lock().expect("log lock").messages.push(message);
+                            b"250 Queued\r\n"
+                        } else if command == "QUIT" {
+                            let _ = write.write_all(b"221 Bye\r\n").await;
+                            return;
+                        } else {
+                            b"250 OK\r\n"
+                        };
+                        if write.write_all(reply).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+
+        (port, received)
+    }
+
+    fn mailer(port: u16) -> DigestMailer {
+        DigestMailer {
+            transport: AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous("127.0.0.1")
+                .port(port)
+                .timeout(Some(Duration::from_secs(5)))
+                .build(),
+            from: "Eureka <eureka@school.test>".parse().expect("from should parse"),
+        }
+    }
+
+    fn period(name: &str, report_to: &str) -> DigestPeriod {
+        DigestPeriod { name: name.to_string(), report_to: report_to.to_string() }
+    }
+
+    fn digest() -> AbsenceDigest {
+        AbsenceDigest {
+            date: NaiveDate::from_ymd_opt(2024, 9, 2).expect("date should be valid"),
+            report_to: "the library".to_string(),
+            entries: vec![
+                DigestEntry {
+                    teacher: "Ada Lovelace".to_string(),
+                    fully_absent: false,
+                    periods: vec![period("Period 1", "Room 101"), period("Period 3", "the library")],
+                },
+                DigestEntry {
+                    teacher: "Alan Turing".to_string(),
+                    fully_absent: true,
+                    periods: vec![period("Period 1", "the library"), period("Period 2", "Room 202")],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn lists_assigned_rooms() {
+        let text = render_text(&digest());
+
+        assert!(text.contains("- Ada Lovelace: Period 1 (report to Room 101), Period 3\n"));
+        assert!(text.contains("- Alan Turing: All day (report to Room 202 in Period 2)\n"));
+        assert!(text.contains("Otherwise, students of absent teachers should report to the library."));
+    }
+
+    #[test]
+    fn skips_rooms_without_assignments() {
+        let mut digest = digest();
+        for entry in &mut digest.entries {
+            for period in &mut entry.periods {
+                period.report_to = digest.report_to.clone();
+            }
+        }
+        let text = render_text(&digest);
+
+        assert!(text.contains("- Ada Lovelace: Period 1, Period 3\n"));
+        assert!(text.contains("- Alan Turing: All day\n"));
+        assert!(text.contains("\nStudents of absent teachers should report to the library."));
+    }
+
+    #[tokio::test]
+    async fn blind_copies_recipients() {
+        let (port, received) = smtp_sink(false).await;
+        let recipients = [
+            "Front Office <office@school.test>".parse().expect("recipient should parse"),
+            "principal@school.test".parse().expect("recipient should parse"),
+        ];
+
+        mailer(port).send_digest(&digest(), &recipients).await.expect("digest should send");
+
+        let received = received.lock().expect("log lock");
+        assert_eq!(received.rcpt_to, ["<office@school.test>", "<principal@school.test>"]);
+        assert_eq!(received.messages.len(), 1);
+
+        let message = &received.messages[0];
+        assert!(message.contains("Subject: Absences for Monday, September 2"));
+        assert!(!message.contains("office@school.test"));
+        assert!(!message.contains("principal@school.test"));
+        assert!(!message.to_ascii_lowercase().contains("\nbcc:"));
+    }
+
+    #[tokio::test]
+    async fn reports_smtp_errors() {
+        let (port, received) = smtp_sink(true).await;
+        let recipients = ["office@school.test".parse().expect("recipient should parse")];
+
+        let result = mailer(port).send_digest(&digest(), &recipients).await;
+
+        assert!(matches!(result, Err(DigestError::Smtp(_))), "{result:?}");
+        assert!(received.lock().expect("log lock").messages.is_empty());
+    }
+}
@@ -0,0 +1,82 @@
+//! This module contains the in-process event bus used to push changes out to
+//! GraphQL subscribers.
+//!
+//! Mutations publish an [`Event`] through the [`EventBus`] stored in
+//! [`crate::state::AppState`], and every open subscription gets its own
+//! receiver.
+
+use futures_util::Stream;
+use tokio::sync::broadcast::{ channel, Sender, error::RecvError };
+use uuid::Uuid;
+
+use crate::types::{ Teacher, Period };
+
+/// The number of events that can be buffered for a slow subscriber before it
+/// starts missing them.
+const EVENT_BUFFER_SIZE: usize = 64;
+
+/// Which part of the global config was changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, async_graphql::Enum)]
+pub enum ConfigField {
+    SpreadsheetId,
+    ReportTo,
+    Attribs,
+    Rooms,
+    Calendar,
+}
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    TeacherAbsenceChanged(Teacher),
+    PeriodTimeChanged(Period),
+    /// A teacher was deleted for good, rather than archived.
+    TeacherDeleted(Uuid),
+    /// A period was deleted for good, rather than archived.
+    PeriodDeleted(Uuid),
+    ConfigChanged(ConfigField),
+}
+
+#[derive(Debug, Clone)]
+pub struct EventBus(Sender<Event>);
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = channel(EVENT_BUFFER_SIZE);
+        Self(sender)
+    }
+
+    /// Publish an event to all current subscribers.
+    ///
+    /// Having no subscribers is not an error, the event is just dropped.
+    pub fn publish(&self, event: Event) {
+        if self.0.send(event).is_err() {
+            crate::logging::trace!("Event published with no subscribers");
+        }
+    }
+
+    /// Get a stream of every event published after this call.
+    ///
+    /// If the subscriber falls too far behind, the missed events are skipped
+    /// (and logged) instead of ending the stream.
+    pub fn stream(&self) -> impl Stream<Item = Event> {
+        let receiver = self.0.subscribe();
+
+        futures_util::stream::unfold(receiver, |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((event, receiver)),
+                    Err(RecvError::Lagged(skipped)) => {
+                        crate::logging::warn!("Subscriber lagged behind, skipped {skipped} events");
+                    },
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,352 @@
+//! This module contains the [`Audit`] extension, which records every mutation
+//! in the append-only `audit_log` table.
+//!
+//! Each entry has the request's id (the same one the logs use), the client
+//! (once its secret is verified) and teacher that made it, the mutation's path
+//! and arguments, and whether it succeeded.
+//!
+//! Mutations that change teachers, periods, absences, coverage, the roster or
+//! the config get the request's [`AuditOrigin`] with [`audit_origin`] and pass
+//! it to [`audited`][crate::database::prepared::audit_log::audited], which
+//! records the change along with its state before and after in the same
+//! transaction as the change, so it's possible to tell who removed an absence
+//! and what it was. Every other mutation, and every one that fails, is recorded
+//! by the extension once it's resolved.
+//!
+//! Every field of a `*MutationRoot` type that doesn't lead to another one is
+//! recorded, so `webhooks { create(...) }` is recorded as `webhooks.create`.
+//! Arguments holding credentials are redacted.
+
+use std::sync::{ Arc, Mutex };
+use std::sync::atomic::Ordering;
+
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory,
+    NextExecute, NextParseQuery, NextPrepareRequest, NextResolve, ResolveInfo,
+};
+use async_graphql::parser::types::{ ExecutableDocument, OperationType, Selection, SelectionSet };
+use async_graphql::{ Name, Request, Response, ServerResult, Value, Variables };
+use chrono::Utc;
+use serde_json::{ json, Map, Value as JsonValue };
+use uuid::Uuid;
+
+use crate::logging::*;
+use crate::state::AppState;
+use crate::types::{ AuditEntry, AuditOrigin };
+
+
+/// Arguments that are never written to the audit log.
+const REDACTED_ARGUMENTS: &[&str] = &["idToken"];
+
+
+/// The id of a request, shared between the logs and the audit log.
+#[derive(Debug, Clone, Copy)]
+pub struct RequestId(pub Uuid);
+
+/// What the audit log knows about a request. It's shared between the
+/// resolvers, which verify the client and find the teacher, and the
+/// extension, which can't see the data they're given.
+#[derive(Debug, Clone, Default)]
+pub struct AuditTrail(Arc<Mutex<Trail>>);
+
+#[derive(Debug, Default)]
+struct Trail {
+    /// The client making the request, once its secret is verified.
+    client_id: Option<Uuid>,
+    /// The teacher making the request, once it's known.
+    teacher_id: Option<Uuid>,
+    /// Where changes made by the mutation being resolved come from.
+    origin: Option<AuditOrigin>,
+}
+
+impl AuditTrail {
+    fn update(&self, update: impl FnOnce(&mut Trail)) {
+        if let Ok(mut trail) = self.0.lock() {
+            update(&mut trail);
+        }
+    }
+
+    fn get<T>(&self, get: impl FnOnce(&Trail) -> T) -> Option<T> {
+        self.0.lock().ok().map(|trail| get(&trail))
+    }
+}
+
+/// Notes the teacher making a request, for the audit log.
+pub (crate) fn record_actor(ctx: &async_graphql::Context<'_>, teacher_id: Uuid) {
+    if let Some(trail) = ctx.data_opt::<AuditTrail>() {
+        trail.update(|trail| trail.teacher_id = Some(teacher_id));
+    }
+}
+
+/// Notes the client making a request once its secret has been verified, for
+/// the audit log.
+pub (crate) fn record_client(ctx: &async_graphql::Context<'_>, client_id: Uuid) {
+    if let Some(trail) = ctx.data_opt::<AuditTrail>() {
+        trail.update(|trail| trail.client_id = Some(client_id));
+    }
+}
+
+/// Gets where changes made by the mutation being resolved come from, to pass
+/// to [`audited`][crate::database::prepared::audit_log::audited].
+pub (crate) fn audit_origin(ctx: &async_graphql::Context<'_>) -> AuditOrigin {
+    let origin = ctx.data_opt::<AuditTrail>().and_then(|trail| trail.get(|trail| {
+        trail.origin.clone().map(|origin| AuditOrigin {
+            client_id: trail.client_id,
+            teacher_id: trail.teacher_id,
+            ..origin
+        })
+    })).flatten();
+
+    let mut origin = origin.unwrap_or_else(|| AuditOrigin::unknown(super::req_id(ctx)));
+    if origin.teacher_id.is_none() {
+        origin.teacher_id = session_teacher(
+            ctx.data_opt::<crate::verification::SessionTokenHeader>(),
+            ctx.data_opt::<AppState>(),
+        );
+    }
+    origin
+}
+
+
+/// Records every mutation in the audit log.
+pub struct Audit;
+
+impl ExtensionFactory for Audit {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(AuditExtension {
+            request_id: Uuid::new_v4(),
+            trail: AuditTrail::default(),
+            request: Mutex::default(),
+        })
+    }
+}
+
+#[derive(Default)]
+struct ParsedRequest {
+    document: Option<ExecutableDocument>,
+    variables: Variables,
+    operation_name: Option<String>,
+}
+
+/// An instance of [`Audit`], which is made for each request.
+struct AuditExtension {
+    request_id: Uuid,
+    trail: AuditTrail,
+    request: Mutex<ParsedRequest>,
+}
+
+/// A single mutation field being resolved.
+struct MutationCall {
+    path: String,
+    arguments: Map<String, JsonValue>,
+}
+
+#[async_graphql::async_trait::async_trait]
+impl Extension for AuditExtension {
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        let request = request
+            .data(RequestId(self.request_id))
+            .data(self.trail.clone());
+
+        next.run(ctx, request).await
+    }
+
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+
+        let has_mutation = document.operations
+            .iter()
+            .any(|(_, operation)| operation.node.ty == OperationType::Mutation);
+        if has_mutation {
+            if let Ok(mut request) = self.request.lock() {
+                request.document = Some(document.clone());
+                request.variables = variables.clone();
+            }
+        }
+
+        Ok(document)
+    }
+
+    async fn execute(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        operation_name: Option<&str>,
+        next: NextExecute<'_>,
+    ) -> Response {
+        if let Ok(mut request) = self.request.lock() {
+            request.operation_name = operation_name.map(str::to_string);
+        }
+
+        next.run(ctx, operation_name).await
+    }
+
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<Value>> {
+        let is_mutation = info.parent_type.ends_with("MutationRoot")
+            && !info.return_type.trim_end_matches('!').ends_with("MutationRoot");
+        if !is_mutation {
+            return next.run(ctx, info).await;
+        }
+
+        let Some(call) = self.mutation_call(&info) else {
+            warn!("Couldn't find mutation {} in the request, it won't be audited", info.name);
+            return next.run(ctx, info).await;
+        };
+        let Some(app_state) = ctx.data_opt::<AppState>() else {
+            error!("App state missing, mutation {} won't be audited", call.path);
+            return next.run(ctx, info).await;
+        };
+
+        let origin = AuditOrigin {
+            request_id: self.request_id,
+            client_id: None,
+            teacher_id: None,
+            mutation: call.path,
+            arguments: JsonValue::Object(call.arguments),
+            recorded: Arc::default(),
+        };
+        self.trail.update(|trail| trail.origin = Some(origin.clone()));
+
+        let result = next.run(ctx, info).await;
+
+        self.trail.update(|trail| trail.origin = None);
+
+        // Successful changes were already recorded in their own transaction
+        if result.is_ok() && origin.recorded.load(Ordering::Relaxed) {
+            return result;
+        }
+
+        let (client_id, teacher_id) = self.trail
+            .get(|trail| (trail.client_id, trail.teacher_id))
+            .unwrap_or_default();
+        let entry = AuditEntry {
+            id: Uuid::new_v4(),
+            at: Utc::now(),
+            request_id: self.request_id,
+            client_id,
+            teacher_id: teacher_id.or_else(|| session_teacher(
+                ctx.data_opt::<crate::verification::SessionTokenHeader>(),
+                Some(app_state),
+            )),
+            mutation: origin.mutation,
+            arguments: origin.arguments,
+            subject: None,
+            subject_id: None,
+            before: None,
+            after: None,
+            success: result.is_ok(),
+            error: result.as_ref().err().map(|e| e.message.clone()),
+        };
+        record(app_state, &entry).await;
+
+        result
+    }
+}
+
+impl AuditExtension {
+    /// Finds the field being resolved in the request, to get its path and
+    /// arguments.
+    fn mutation_call(&self, info: &ResolveInfo<'_>) -> Option<MutationCall> {
+        let request = self.request.lock().ok()?;
+        let document = request.document.as_ref()?;
+
+        let operation = match &request.operation_name {
+            Some(name) => document.operations
+                .iter()
+                .find(|(operation_name, _)| operation_name.map(Name::as_str) == Some(name.as_str()))?
+                .1,
+            None => document.operations.iter().next()?.1,
+        };
+
+        let mut keys = info.path_node.to_string_vec();
+        let leaf_key = keys.pop()?;
+
+        let mut names = Vec::new();
+        let mut selection_set = &operation.node.selection_set.node;
+        for key in keys {
+            let field = find_field(document, selection_set, &key)?;
+            names.push(field.name.node.to_string());
+            selection_set = &field.selection_set.node;
+        }
+        let field = find_field(document, selection_set, &leaf_key)?;
+        names.push(field.name.node.to_string());
+
+        let arguments = field.arguments
+            .iter()
+            .map(|(name, value)| {
+                let value = if REDACTED_ARGUMENTS.contains(&name.node.as_str()) {
+                    json!("[redacted]")
+                } else {
+                    value.node
+                        .clone()
+                        .into_const_with(|variable| request.variables.get(&variable).cloned().ok_or(()))
+                        .ok()
+                        .and_then(|value| value.into_json().ok())
+                        .unwrap_or(JsonValue::Null)
+                };
+                (name.node.to_string(), value)
+            })
+            .collect();
+
+        Some(MutationCall { path: names.join("."), arguments })
+    }
+}
+
+/// Finds the field with a response key (its alias or name) in a selection
+/// set, looking through fragments.
+fn find_field<'a>(
+    document: &'a ExecutableDocument,
+    selection_set: &'a SelectionSet,
+    key: &str,
+) -> Option<&'a async_graphql::parser::types::Field> {
+    selection_set.items.iter().find_map(|selection| match &selection.node {
+        Selection::Field(field) => {
+            let field_key = field.node.alias.as_ref().unwrap_or(&field.node.name);
+            (field_key.node.as_str() == key).then_some(&field.node)
+        },
+        Selection::InlineFragment(fragment) => find_field(document, &fragment.node.selection_set.node, key),
+        Selection::FragmentSpread(spread) => document.fragments
+            .get(&spread.node.fragment_name.node)
+            .and_then(|fragment| find_field(document, &fragment.node.selection_set.node, key)),
+    })
+}
+
+/// Gets the teacher making the request from their session, for mutations
+/// that didn't look them up.
+fn session_teacher(
+    token: Option<&crate::verification::SessionTokenHeader>,
+    app_state: Option<&AppState>,
+) -> Option<Uuid> {
+    app_state?
+        .sessions()
+        .verify(token?.as_str())
+        .map(|session| session.teacher_id)
+}
+
+async fn record(app_state: &AppState, entry: &AuditEntry) {
+    use crate::database::prepared::audit_log::record_audit_entry;
+
+    let result = async {
+        let mut db_conn = app_state.db().acquire().await?;
+        record_audit_entry(&mut db_conn, entry).await
+    }.await;
+
+    if let Err(e) = result {
+        error!("Failed to audit mutation {} of request {}: {e}", entry.mutation, entry.request_id);
+    }
+}
@@ -9,6 +9,8 @@ pub mod structs;
 
 pub mod resolvers;
 
+pub mod audit;
+
 
 use crate::env::graphql_complexity_limit_usize_panic;
 use crate::state::AppState;
@@ -16,19 +18,17 @@ use crate::state::AppState;
 use self::{
     resolvers::query::QueryRoot,
     resolvers::mutation::MutationRoot,
+    resolvers::subscription::SubscriptionRoot,
 };
 
-use async_graphql::{
-    Schema as GenericSchema,
-    EmptySubscription,
-};
+use async_graphql::Schema as GenericSchema;
 
 
 
 
 
 /// A Schema alias type used by the `GraphQLRequest` handler to run a GraphQL query.
-pub type Schema = GenericSchema<QueryRoot, MutationRoot, EmptySubscription>;
+pub type Schema = GenericSchema<QueryRoot, MutationRoot, SubscriptionRoot>;
 
 
 // /// What is essentially the linkage between [actix_web]'s requests and [juniper]'s query execution.
@@ -66,9 +66,10 @@ pub fn schema(app_state: AppState) -> Schema {
     GenericSchema::build(
         QueryRoot,
         MutationRoot,
-        EmptySubscription,
+        SubscriptionRoot,
     )
         .data(app_state)
+        .extension(audit::Audit)
         .limit_complexity(graphql_complexity_limit_usize_panic())
         .finish()
 }
@@ -95,16 +96,49 @@ fn req_id(context: &async_graphql::Context) -> uuid::Uuid {
         context.insert_http_header(HEADER_NAME, id.hyphenated().to_string());
         id
     } else {
-        let id = uuid::Uuid::new_v4();
+        let id = context
+            .data_opt::<audit::RequestId>()
+            .map_or_else(uuid::Uuid::new_v4, |id| id.0);
         context.insert_http_header(HEADER_NAME, id.hyphenated().to_string());
         id
     }
 }
 
+/// Verifies a client's id and secret, using the scope cache when it can, and
+/// gets the scopes it's allowed, or `None` if the client couldn't be verified
+/// (it doesn't exist, the secret is wrong, or it was revoked or expired).
+pub (crate) async fn client_scopes(
+    app_state: &crate::state::AppState,
+    id: uuid::Uuid,
+    secret: &[u8],
+) -> async_graphql::Result<Option<crate::verification::scopes::Scopes>> {
+    use crate::verification::id_secret::client_allowed;
+    use async_graphql::Error as GraphQlError;
+
+    if let Some(scopes) = app_state.scope_cache().get(id, secret) {
+        crate::logging::trace!("Using cached scopes for client {id}");
+        return Ok(Some(scopes));
+    }
+
+    let mut db_pool = match app_state.db().acquire().await {
+        Ok(db_pool) => db_pool,
+        Err(e) => {
+            crate::logging::error!("DB Error: {e:?}");
+            return Err(GraphQlError::new("Internal server error (DB)"));
+        },
+    };
+
+    let scopes = client_allowed(id, secret, &mut db_pool).await;
+    if let Some(scopes) = &scopes {
+        app_state.scope_cache().insert(id, secret, scopes.clone());
+    }
+    Ok(scopes)
+}
+
 async fn get_scopes(context: &async_graphql::Context<'_>) -> async_graphql::Result<crate::verification::scopes::Scopes> {
     use crate::verification::{
         ClientIdHeader, ClientSecretHeader,
-        scopes::Scopes, id_secret::client_allowed,
+        scopes::Scopes,
     };
     use tokio::sync::OnceCell;
     use async_graphql::Error as GraphQlError;
@@ -121,13 +155,6 @@ async fn get_scopes(context: &async_graphql::Context<'_>) -> async_graphql::Resu
             crate::logging::error!("{err:?}");
             return Err(err);
         };
-        let mut db_pool = match app_state.db().acquire().await {
-            Ok(db_pool) => db_pool,
-            Err(e) => {
-                crate::logging::error!("DB Error: {e:?}");
-                return Err(GraphQlError::new("Internal server error (DB)"));
-            },
-        };
 
         let id = context.data::<ClientIdHeader>().map(|id| id.inner());
         let secret = context.data::<ClientSecretHeader>().map(|secret| secret.as_bytes());
@@ -135,11 +162,50 @@ async fn get_scopes(context: &async_graphql::Context<'_>) -> async_graphql::Resu
         let id_ok = id.is_ok();
         let secret_ok = secret.is_ok();
 
-        if let (Ok(id), Ok(secret)) = (id, secret) {
-            Ok(client_allowed(id, secret, &mut db_pool).await.clone().unwrap_or_default())
-        } else {
+        let (Ok(id), Ok(secret)) = (id, secret) else {
             crate::logging::info!("No client id or secret, id: {id_ok}, secret: {secret_ok}");
-            Ok(Scopes::new())
+            return Ok(Scopes::new());
+        };
+
+        let scopes = client_scopes(app_state, id, secret).await?;
+        if scopes.is_some() {
+            audit::record_client(context, id);
+        }
+        Ok(scopes.unwrap_or_default())
+    }).await.cloned()
+}
+
+/// Gets the teacher session from the `session-token` header (or the
+/// `sessionToken` connection payload field), if there is a valid one. The
+/// session is kept for the whole request (or websocket connection), so its
+/// expiry is checked every time.
+async fn get_session(context: &async_graphql::Context<'_>) -> async_graphql::Result<Option<crate::types::Session>> {
+    use crate::types::Session;
+    use crate::verification::SessionTokenHeader;
+    use tokio::sync::OnceCell;
+    use async_graphql::Error as GraphQlError;
+
+
+    let Ok(session_cell) = context.data::<OnceCell<Option<Session>>>() else {
+        crate::logging::error!("Session OnceCell Missing from context!");
+        return Ok(None);
+    };
+
+    session_cell.get_or_try_init(|| async {
+        let Ok(app_state) = context.data::<crate::state::AppState>() else {
+            let err = GraphQlError::new("Internal server error (App State)");
+            crate::logging::error!("{err:?}");
+            return Err(err);
+        };
+
+        let Ok(token) = context.data::<SessionTokenHeader>() else {
+            return Ok(None);
+        };
+
+        let session = app_state.sessions().verify(token.as_str());
+        if session.is_none() {
+            crate::logging::debug!("Invalid or expired session token");
         }
-    }).await.map(Clone::clone)
+        Ok(session)
+    }).await.map(|session| session.filter(|session| session.expires_at > chrono::Utc::now()))
 }
@@ -0,0 +1,21 @@
+#![allow(unused_braces)]
+
+use async_graphql::Object;
+use chrono::{ DateTime, Utc };
+use uuid::Uuid;
+
+use crate::types::{ AbsenceRequest, AbsenceRequestStatus, PackedAbsenceState };
+
+
+#[Object]
+impl AbsenceRequest {
+    async fn absence(&self) -> &PackedAbsenceState { &self.absence }
+
+    async fn status(&self) -> AbsenceRequestStatus { self.status }
+    async fn requested_at(&self) -> DateTime<Utc> { self.requested_at }
+
+    /// The secretary or admin who reviewed the request, or null if it hasn't
+    /// been reviewed or was approved by a trusted client.
+    async fn reviewed_by(&self) -> Option<Uuid> { self.reviewed_by }
+    async fn reviewed_at(&self) -> Option<DateTime<Utc>> { self.reviewed_at }
+}
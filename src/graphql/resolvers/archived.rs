@@ -0,0 +1,42 @@
+//! Guards that keep writes away from archived teachers and periods.
+
+use async_graphql::{ Context, Error as GraphQlError, Result as GraphQlResult };
+use uuid::Uuid;
+
+use crate::database::Ctx;
+use crate::graphql::req_id;
+use crate::logs_env::logging::fmt_req_id;
+
+
+/// Fails if any of `teachers` or `periods` has been archived, so nothing new
+/// is attached to them until they're restored.
+pub (crate) async fn ensure_not_archived(
+    ctx: &Context<'_>,
+    db_conn: &mut Ctx,
+    teachers: &[Uuid],
+    periods: &[Uuid],
+) -> GraphQlResult<()> {
+    use crate::database::prepared::teacher::get_archived_teachers as get_archived_teachers_from_db;
+    use crate::database::prepared::period::get_archived_periods as get_archived_periods_from_db;
+
+    let db_error = |e: sqlx::Error| {
+        crate::logging::error!("{} - Failed to check for archived rows: {e}", fmt_req_id(req_id(ctx)));
+        GraphQlError::new(format!("Failed to check for archived rows: {e}"))
+    };
+
+    if !teachers.is_empty() {
+        let archived = get_archived_teachers_from_db(db_conn, teachers).await.map_err(db_error)?;
+        if let Some(id) = archived.first() {
+            return Err(GraphQlError::new(format!("Teacher {id} is archived")));
+        }
+    }
+
+    if !periods.is_empty() {
+        let archived = get_archived_periods_from_db(db_conn, periods).await.map_err(db_error)?;
+        if let Some(id) = archived.first() {
+            return Err(GraphQlError::new(format!("Period {id} is archived")));
+        }
+    }
+
+    Ok(())
+}
@@ -28,6 +28,19 @@ impl Attribs {
         }
     }
 
+    /// Who the daily absence digest is emailed to.
+    async fn digest_recipients<'a>(&'a self, ctx: &Context<'_>) -> GraphQlResult<Vec<&'a str>> {
+        ensure_auth!(ctx, [admin]);
+        match self.0.get(crate::digest::RECIPIENTS_ATTRIB) {
+            Some(JsonValue::Array(recipients)) => recipients
+                .iter()
+                .map(|recipient| recipient.as_str().ok_or_else(|| GraphQlError::new("Digest recipients are not all strings")))
+                .collect(),
+            Some(JsonValue::Null) | None => Ok(Vec::new()),
+            Some(_) => Err(GraphQlError::new("Digest recipients are not set to an array")),
+        }
+    }
+
     async fn raw<'a>(&'a self, ctx: &Context<'_>) -> GraphQlResult<RawAttribs<'a>> {
         ensure_auth!(ctx, [admin]);
         Ok(RawAttribs(self))
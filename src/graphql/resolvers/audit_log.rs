@@ -0,0 +1,33 @@
+#![allow(unused_braces)]
+
+use async_graphql::Object;
+use chrono::{DateTime, Utc};
+use serde_json::Value as JsonValue;
+use uuid::Uuid;
+
+use crate::types::{ AuditEntry, AuditLogPage, AuditSubject };
+
+
+#[Object]
+impl AuditEntry {
+    async fn id(&self) -> Uuid { self.id }
+    async fn at(&self) -> DateTime<Utc> { self.at }
+    async fn request_id(&self) -> Uuid { self.request_id }
+    async fn client_id(&self) -> Option<Uuid> { self.client_id }
+    async fn teacher_id(&self) -> Option<Uuid> { self.teacher_id }
+    async fn mutation(&self) -> &str { &self.mutation }
+    async fn arguments(&self) -> &JsonValue { &self.arguments }
+    async fn subject(&self) -> Option<AuditSubject> { self.subject }
+    async fn subject_id(&self) -> Option<Uuid> { self.subject_id }
+    async fn before(&self) -> Option<&JsonValue> { self.before.as_ref() }
+    async fn after(&self) -> Option<&JsonValue> { self.after.as_ref() }
+    async fn success(&self) -> bool { self.success }
+    async fn error(&self) -> Option<&str> { self.error.as_deref() }
+}
+
+#[Object]
+impl AuditLogPage {
+    async fn entries(&self) -> &[AuditEntry] { &self.entries }
+    /// How many entries match the filter, across every page.
+    async fn total(&self) -> i64 { self.total }
+}
@@ -0,0 +1,59 @@
+#![allow(unused_braces)]
+
+use async_graphql::{ Context, Object, Result as GraphQlResult };
+use chrono::NaiveDate;
+use uuid::Uuid;
+
+use crate::graphql::req_id;
+use crate::types::{ BellSchedule, BellSchedulePeriod, Period, ScheduleDay };
+
+use super::{ get_db, run_query, ensure_auth, TimeRange };
+
+
+#[Object]
+impl BellSchedule {
+    async fn id(&self) -> Uuid { self.id }
+    async fn name(&self) -> &str { &self.name }
+
+    /// The periods with times in this schedule. Periods that aren't in it
+    /// use their default time on days with this schedule.
+    async fn periods(&self, ctx: &Context<'_>) -> GraphQlResult<Vec<BellSchedulePeriod>> {
+        use crate::database::prepared::bell_schedules::get_bell_schedule_periods;
+
+        ensure_auth!(ctx, [read_period]);
+
+        let mut db_conn = get_db!(ctx);
+        let id = self.id;
+
+        run_query!(
+            db_conn.get_bell_schedule_periods(id)
+            else (req_id(ctx)) "Failed to get periods of bell schedule {id}: {}"
+        )
+    }
+}
+
+#[Object]
+impl BellSchedulePeriod {
+    async fn period_id(&self) -> Uuid { self.period_id }
+    async fn time_range(&self) -> TimeRange { (self.start, self.end).into() }
+
+    async fn period(&self, ctx: &Context<'_>) -> GraphQlResult<Period> {
+        use crate::database::prepared::period::get_period;
+
+        ensure_auth!(ctx, [read_period]);
+
+        let mut db_conn = get_db!(ctx);
+        let id = self.period_id;
+
+        run_query!(
+            db_conn.get_period(id)
+            else (req_id(ctx)) "Failed to get period {id}: {}"
+        )
+    }
+}
+
+#[Object]
+impl ScheduleDay {
+    async fn date(&self) -> NaiveDate { self.date }
+    async fn schedule(&self) -> &BellSchedule { &self.schedule }
+}
@@ -0,0 +1,15 @@
+#![allow(unused_braces)]
+
+use async_graphql::Object;
+use chrono::NaiveDate;
+
+use crate::types::{ CalendarDay, CalendarDayKind };
+
+
+#[Object]
+impl CalendarDay {
+    async fn date(&self) -> NaiveDate { self.date }
+    async fn kind(&self) -> CalendarDayKind { self.kind }
+    async fn name(&self) -> Option<&str> { self.name.as_deref() }
+    async fn is_school_day(&self) -> bool { self.kind == CalendarDayKind::Instructional }
+}
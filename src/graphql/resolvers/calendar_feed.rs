@@ -0,0 +1,31 @@
+#![allow(unused_braces)]
+
+use async_graphql::Object;
+use chrono::{ DateTime, Utc };
+use uuid::Uuid;
+
+use crate::types::{ CalendarFeed, CalendarFeedCredentials };
+
+
+#[Object]
+impl CalendarFeed {
+    async fn id(&self) -> Uuid { self.id }
+    /// The teacher whose absences are in the feed, or null for the staff-wide
+    /// feed.
+    async fn teacher_id(&self) -> Option<Uuid> { self.teacher_id }
+    /// The teacher who created the feed. The feed stops working if they're
+    /// archived, or if it's someone else's feed and they're no longer a
+    /// secretary or admin.
+    async fn created_by(&self) -> Uuid { self.created_by }
+    async fn created_at(&self) -> DateTime<Utc> { self.created_at }
+}
+
+#[Object]
+impl CalendarFeedCredentials {
+    /// The feed's token. It can't be recovered later, so revoke the feed and
+    /// create a new one if it's lost.
+    async fn token(&self) -> &str { &self.token }
+    /// The path to subscribe to, relative to the server.
+    async fn path(&self) -> String { crate::calendar_feed::feed_path(&self.token) }
+    async fn feed(&self) -> &CalendarFeed { &self.feed }
+}
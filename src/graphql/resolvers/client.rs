@@ -0,0 +1,28 @@
+#![allow(unused_braces)]
+
+use async_graphql::Object;
+use chrono::{ DateTime, Utc };
+use uuid::Uuid;
+
+use crate::types::{ Client, ClientCredentials };
+
+
+#[Object]
+impl Client {
+    async fn id(&self) -> Uuid { self.id }
+    async fn scopes(&self) -> Vec<String> { self.scopes.to_string().split_whitespace().map(str::to_string).collect() }
+    async fn description(&self) -> &str { &self.description }
+
+    async fn expires_at(&self) -> Option<DateTime<Utc>> { self.expires_at }
+    async fn revoked_at(&self) -> Option<DateTime<Utc>> { self.revoked_at }
+    async fn last_seen_at(&self) -> Option<DateTime<Utc>> { self.last_seen_at }
+
+    /// Requests per minute, or null for the server default. 0 means unlimited.
+    async fn rate_limit(&self) -> Option<i32> { self.rate_limit }
+}
+
+#[Object]
+impl ClientCredentials {
+    async fn client(&self) -> &Client { &self.client }
+    async fn secret(&self) -> &str { &self.secret }
+}
@@ -0,0 +1,95 @@
+#![allow(unused_braces)]
+
+use async_graphql::{ Context, Object, Result as GraphQlResult };
+use uuid::Uuid;
+
+use crate::graphql::req_id;
+use crate::types::{ Coverage, Period, Substitute, Teacher };
+
+use super::{ get_db, run_query, ensure_auth };
+
+
+#[Object]
+impl Coverage {
+    /// The absent teacher being covered.
+    async fn teacher_id(&self) -> Uuid { self.teacher_id }
+    async fn period_id(&self) -> Uuid { self.period_id }
+
+    /// The covering teacher's id, or null for an external substitute.
+    async fn substitute_id(&self) -> Option<Uuid> {
+        match &self.substitute {
+            Substitute::Teacher(id) => Some(*id),
+            Substitute::External(_) => None,
+        }
+    }
+
+    /// The external substitute's name, or null if a teacher is covering.
+    async fn external_name(&self) -> Option<&str> {
+        match &self.substitute {
+            Substitute::Teacher(_) => None,
+            Substitute::External(name) => Some(name),
+        }
+    }
+
+    async fn teacher(&self, ctx: &Context<'_>) -> GraphQlResult<Teacher> {
+        use crate::database::prepared::teacher::get_teacher;
+
+        ensure_auth!(ctx, [read_teacher]);
+
+        let mut db_conn = get_db!(ctx);
+        let id = self.teacher_id;
+
+        run_query!(
+            db_conn.get_teacher(id)
+            else (req_id(ctx)) "Failed to get covered teacher {id}: {}"
+        )
+    }
+
+    async fn period(&self, ctx: &Context<'_>) -> GraphQlResult<Period> {
+        use crate::database::prepared::period::get_period;
+
+        ensure_auth!(ctx, [read_period]);
+
+        let mut db_conn = get_db!(ctx);
+        let id = self.period_id;
+
+        run_query!(
+            db_conn.get_period(id)
+            else (req_id(ctx)) "Failed to get covered period {id}: {}"
+        )
+    }
+
+    /// Where the covered class reports to: the absent teacher's assigned room,
+    /// or the global `reportTo`.
+    async fn report_to(&self, ctx: &Context<'_>) -> GraphQlResult<String> {
+        use crate::database::prepared::rooms::get_report_to_for;
+
+        ensure_auth!(ctx, [read_teacher, read_period]);
+
+        let mut db_conn = get_db!(ctx);
+        let (teacher, period) = (self.teacher_id, self.period_id);
+
+        run_query!(
+            db_conn.get_report_to_for(teacher, period)
+            else (req_id(ctx)) "Failed to get \"report to\" location of teacher {teacher} from database: {}"
+        )
+    }
+
+    /// The covering teacher, or null for an external substitute.
+    async fn substitute(&self, ctx: &Context<'_>) -> GraphQlResult<Option<Teacher>> {
+        use crate::database::prepared::teacher::get_teacher;
+
+        let Substitute::Teacher(id) = self.substitute else {
+            return Ok(None);
+        };
+
+        ensure_auth!(ctx, [read_teacher]);
+
+        let mut db_conn = get_db!(ctx);
+
+        run_query!(
+            db_conn.get_teacher(id)
+            else (req_id(ctx)) "Failed to get substitute teacher {id}: {}"
+        ).map(Some)
+    }
+}
@@ -0,0 +1,31 @@
+#![allow(unused_braces)]
+
+use async_graphql::Object;
+use chrono::NaiveDate;
+
+use crate::types::{ AbsenceDigest, DigestEntry, DigestPeriod };
+
+
+#[Object]
+impl AbsenceDigest {
+    async fn date(&self) -> NaiveDate { self.date }
+    async fn report_to(&self) -> &str { &self.report_to }
+    async fn entries(&self) -> &[DigestEntry] { &self.entries }
+
+    async fn subject(&self) -> String { crate::digest::subject(self) }
+    async fn text(&self) -> String { crate::digest::render_text(self) }
+    async fn html(&self) -> String { crate::digest::render_html(self) }
+}
+
+#[Object]
+impl DigestEntry {
+    async fn teacher(&self) -> &str { &self.teacher }
+    async fn fully_absent(&self) -> bool { self.fully_absent }
+    async fn periods(&self) -> &[DigestPeriod] { &self.periods }
+}
+
+#[Object]
+impl DigestPeriod {
+    async fn name(&self) -> &str { &self.name }
+    async fn report_to(&self) -> &str { &self.report_to }
+}
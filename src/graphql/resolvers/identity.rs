@@ -0,0 +1,151 @@
+//! Helpers for working out which teacher is making a request.
+
+use async_graphql::{ Context, Error as GraphQlError, Result as GraphQlResult };
+
+use crate::database::Ctx;
+use crate::graphql::req_id;
+use crate::logs_env::logging::fmt_req_id;
+use crate::state::AppState;
+use crate::types::{ Privileges, Teacher };
+
+
+/// Verify an OIDC ID token and get the teacher linked to its identity in
+/// `teacher_oauths`. Archived teachers are rejected, like they are for
+/// sessions.
+pub (crate) async fn teacher_from_id_token(
+    ctx: &Context<'_>,
+    db_conn: &mut Ctx,
+    id_token: &str,
+) -> GraphQlResult<Teacher> {
+    use crate::database::prepared::teacher::get_teacher_by_oauth;
+
+    let identity = match ctx.data::<AppState>()?.oidc().verify(id_token).await {
+        Ok(identity) => identity,
+        Err(e) => {
+            crate::logging::warn!("{} - Rejected ID token: {e}", fmt_req_id(req_id(ctx)));
+            return Err(GraphQlError::new("Invalid ID token"));
+        },
+    };
+
+    let teacher = get_teacher_by_oauth(db_conn, identity.provider.clone(), identity.sub)
+        .await
+        .map_err(|e| {
+            crate::logging::info!(
+                "{} - No teacher for verified {} identity: {e}",
+                fmt_req_id(req_id(ctx)),
+                identity.provider,
+            );
+            GraphQlError::new("This oauth user doesn't exist")
+        })?;
+
+    crate::graphql::audit::record_actor(ctx, teacher.get_id());
+    if teacher.get_archived() {
+        return Err(GraphQlError::new("This oauth user's teacher has been archived"));
+    }
+    Ok(teacher)
+}
+
+/// Get the teacher making the request, from their session token if there is
+/// one, otherwise from `id_token`.
+pub (crate) async fn current_teacher(
+    ctx: &Context<'_>,
+    db_conn: &mut Ctx,
+    id_token: Option<&str>,
+) -> GraphQlResult<Teacher> {
+    use crate::database::prepared::teacher::get_teacher;
+
+    if let Some(session) = crate::graphql::get_session(ctx).await? {
+        crate::graphql::audit::record_actor(ctx, session.teacher_id);
+        let teacher = get_teacher(db_conn, session.teacher_id)
+            .await
+            .map_err(|e| {
+                crate::logging::info!(
+                    "{} - No teacher for session of {}: {e}",
+                    fmt_req_id(req_id(ctx)),
+                    session.teacher_id,
+                );
+                GraphQlError::new("This session's teacher doesn't exist")
+            })?;
+
+        if teacher.get_archived() {
+            return Err(GraphQlError::new("This session's teacher has been archived"));
+        }
+        return Ok(teacher);
+    }
+
+    match id_token {
+        Some(id_token) => teacher_from_id_token(ctx, db_conn, id_token).await,
+        None => Err(GraphQlError::new("Not signed in, pass a session token or an ID token")),
+    }
+}
+
+/// Get the privileges of the teacher making the request.
+/// 
+/// Sessions carry the privileges the teacher had when they logged in, so the
+/// database is only checked for requests made with an ID token.
+pub (crate) async fn current_privileges(
+    ctx: &Context<'_>,
+    db_conn: &mut Ctx,
+    teacher: &Teacher,
+) -> GraphQlResult<Privileges> {
+    use crate::database::prepared::privileges::get_privileges;
+
+    if let Some(session) = crate::graphql::get_session(ctx).await? {
+        if session.teacher_id == teacher.get_id() {
+            return Ok(session.privileges);
+        }
+    }
+
+    get_privileges(db_conn, teacher.get_id())
+        .await
+        .map_err(|e| {
+            crate::logging::error!("{} - Failed to get permissions for teacher: {e}", fmt_req_id(req_id(ctx)));
+            GraphQlError::new(format!("Failed to get permissions for teacher: {e}"))
+        })
+}
+
+/// Make sure the teacher making the request may act for `teacher_id`, which
+/// they can if it's themselves, or if they're a secretary or admin.
+pub (crate) async fn ensure_acting_for(
+    ctx: &Context<'_>,
+    db_conn: &mut Ctx,
+    teacher_id: uuid::Uuid,
+    id_token: Option<&str>,
+) -> GraphQlResult<()> {
+    let teacher = current_teacher(ctx, db_conn, id_token).await?;
+    if teacher.get_id() == teacher_id {
+        return Ok(());
+    }
+
+    let privileges = current_privileges(ctx, db_conn, &teacher).await?;
+    if privileges.secretary || privileges.admin {
+        return Ok(());
+    }
+
+    crate::logging::info!(
+        "{} - Teacher {} tried to act for teacher {teacher_id}",
+        fmt_req_id(req_id(ctx)),
+        teacher.get_id(),
+    );
+    Err(GraphQlError::new("Not permitted to access this resource"))
+}
+
+/// Make sure the teacher making the request is a secretary or admin.
+pub (crate) async fn ensure_staff(
+    ctx: &Context<'_>,
+    db_conn: &mut Ctx,
+    id_token: Option<&str>,
+) -> GraphQlResult<()> {
+    let teacher = current_teacher(ctx, db_conn, id_token).await?;
+    let privileges = current_privileges(ctx, db_conn, &teacher).await?;
+    if privileges.secretary || privileges.admin {
+        return Ok(());
+    }
+
+    crate::logging::info!(
+        "{} - Teacher {} tried to access a staff-only resource",
+        fmt_req_id(req_id(ctx)),
+        teacher.get_id(),
+    );
+    Err(GraphQlError::new("Not permitted to access this resource"))
+}
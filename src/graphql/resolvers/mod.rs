@@ -1,16 +1,39 @@
 pub mod query;
 pub mod mutation;
+pub mod subscription;
 
 mod teacher;
 mod period;
 
 mod packed_absence_state;
+mod absence_request;
+mod recurring_absence;
+mod coverage;
+mod room;
+mod bell_schedule;
+mod calendar;
 mod time_range;
 mod pronoun_set;
 mod teacher_name;
 mod privileges;
 mod sparse_metrics_view;
 mod attribs;
+mod rollover_run;
+mod sheet_sync_run;
+mod reports;
+mod client;
+mod webhook;
+mod identity;
+mod archived;
+mod session;
+mod calendar_feed;
+mod roster;
+mod digest;
+mod audit_log;
+
+pub (crate) use identity::{ teacher_from_id_token, current_teacher, current_privileges, ensure_acting_for, ensure_staff };
+pub (crate) use archived::ensure_not_archived;
+pub (crate) use crate::graphql::audit::audit_origin;
 
 pub use {
     // teacher::TeacherMetadata,
@@ -88,3 +111,30 @@ macro_rules! ensure_auth {
     };
 }
 pub (crate) use ensure_auth;
+
+
+/// Publish an event to every subscriber listening on the
+/// [`EventBus`][crate::events::EventBus].
+pub (crate) fn publish_event(ctx: &async_graphql::Context<'_>, event: crate::events::Event) {
+    match ctx.data::<crate::state::AppState>() {
+        Ok(app_state) => app_state.events().publish(event),
+        Err(e) => crate::logging::error!("Failed to publish event, app state missing: {e:?}"),
+    }
+}
+
+
+/// The most days a query or mutation taking a date range covers at once, so a
+/// huge range can't make the database generate or scan millions of rows.
+pub (crate) const MAX_DATE_SPAN_DAYS: i64 = 366;
+
+/// Fails if `start` to `end` (inclusive) is longer than
+/// [`MAX_DATE_SPAN_DAYS`].
+pub (crate) fn ensure_date_span(start: chrono::NaiveDate, end: chrono::NaiveDate) -> async_graphql::Result<()> {
+    let days = end.signed_duration_since(start).num_days() + 1;
+    if days > MAX_DATE_SPAN_DAYS {
+        return Err(async_graphql::Error::new(format!(
+            "Date ranges can't be longer than {MAX_DATE_SPAN_DAYS} days, but {start} to {end} is {days}",
+        )));
+    }
+    Ok(())
+}
@@ -5,8 +5,11 @@ use sqlx::types::JsonValue;
 
 
 use crate::graphql::resolvers::attribs::Attribs;
-use crate::graphql::resolvers::{ensure_auth, get_db, run_query};
+use crate::events::{Event, ConfigField};
+use crate::database::prepared::audit_log::audited;
+use crate::graphql::resolvers::{ensure_auth, get_db, run_query, publish_event, audit_origin};
 use crate::graphql::req_id;
+use crate::types::AuditTarget;
 
 
 use async_graphql::Result as GraphQlResult;
@@ -65,6 +68,25 @@ impl AttribMutationRoot {
         ensure_auth!(ctx, [write_config]);
         set_single_attrib(ctx, "supportFormUrl", new_value).await
     }
+
+    /// Sets who the daily absence digest is emailed to, like
+    /// `Front Office <office@school.org>`.
+    async fn set_digest_recipients(
+        &self,
+        ctx: &Context<'_>,
+        recipients: Vec<String>,
+    ) -> GraphQlResult<Attribs> {
+        ensure_auth!(ctx, [write_config]);
+
+        for recipient in &recipients {
+            if let Err(e) = recipient.parse::<lettre::message::Mailbox>() {
+                return Err(async_graphql::Error::new(format!("Invalid digest recipient {recipient:?}: {e}")));
+            }
+        }
+
+        let recipients = recipients.into_iter().map(JsonValue::String).collect();
+        set_single_attrib(ctx, crate::digest::RECIPIENTS_ATTRIB, JsonValue::Array(recipients)).await
+    }
 }
 
 
@@ -79,9 +101,13 @@ pub async fn set_single_attrib(
     };
 
     let mut db_conn = get_db!(ctx);
+    let origin = audit_origin(ctx);
 
+    let key = key.to_owned();
     run_query!(
-        db_conn.set_single_attrib_db(key, &new_value)
+        db_conn.audited(&origin, AuditTarget::Config, move |txn| Box::pin(async move {
+            set_single_attrib_db(txn, &key, &new_value).await
+        }))
         else (req_id(ctx)) "Database error: {}"
     )?;
 
@@ -90,6 +116,7 @@ pub async fn set_single_attrib(
         else (req_id(ctx)) "Database error: {}"
     )?;
 
+    publish_event(ctx, Event::ConfigChanged(ConfigField::Attribs));
     Ok(Attribs(map))
 }
 
@@ -103,9 +130,13 @@ pub async fn clear_single_attrib(
     };
 
     let mut db_conn = get_db!(ctx);
+    let origin = audit_origin(ctx);
 
+    let key = key.to_owned();
     run_query!(
-        db_conn.clear_single_attrib_db(key)
+        db_conn.audited(&origin, AuditTarget::Config, move |txn| Box::pin(async move {
+            clear_single_attrib_db(txn, &key).await
+        }))
         else (req_id(ctx)) "Database error: {}"
     )?;
 
@@ -114,6 +145,7 @@ pub async fn clear_single_attrib(
         else (req_id(ctx)) "Database error: {}"
     )?;
 
+    publish_event(ctx, Event::ConfigChanged(ConfigField::Attribs));
     Ok(Attribs(map))
 }
 
@@ -127,9 +159,10 @@ pub async fn set_attribs(
     };
 
     let mut db_conn = get_db!(ctx);
+    let origin = audit_origin(ctx);
 
     run_query!(
-        db_conn.set_attribs_db(attribs)
+        db_conn.audited(&origin, AuditTarget::Config, |txn| Box::pin(set_attribs_db(txn, attribs)))
         else (req_id(ctx)) "Database error: {}"
     )?;
 
@@ -138,6 +171,7 @@ pub async fn set_attribs(
         else (req_id(ctx)) "Database error: {}"
     )?;
 
+    publish_event(ctx, Event::ConfigChanged(ConfigField::Attribs));
     Ok(Attribs(map))
 }
 
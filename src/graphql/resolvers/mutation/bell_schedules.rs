@@ -0,0 +1,136 @@
+use async_graphql::Context;
+use chrono::NaiveDate;
+use uuid::Uuid;
+
+
+use crate::database::Ctx;
+use crate::events::Event;
+use crate::graphql::resolvers::{get_db, run_query, publish_event};
+use crate::graphql::req_id;
+use crate::logs_env::logging::fmt_req_id;
+
+use crate::graphql::structs::TimeRangeInput;
+use crate::types::BellSchedule;
+
+use async_graphql::{ Error as GraphQlError, Result as GraphQlResult };
+
+/// Republishes every period's time, since changing a bell schedule or the
+/// calendar can change the times of any of them.
+async fn publish_all_period_times(ctx: &Context<'_>, db_conn: &mut Ctx) -> GraphQlResult<()> {
+    use crate::database::prepared::period::get_all_periods as get_all_periods_from_db;
+
+    let periods = get_all_periods_from_db(db_conn)
+        .await
+        .map_err(|e| {
+            crate::logging::error!("{} - Failed to refetch periods: {e}", fmt_req_id(req_id(ctx)));
+            GraphQlError::new(format!("Failed to refetch periods: {e}"))
+        })?;
+    for period in periods {
+        publish_event(ctx, Event::PeriodTimeChanged(period));
+    }
+    Ok(())
+}
+
+pub async fn add_bell_schedule(
+    ctx: &Context<'_>,
+    name: String,
+) -> GraphQlResult<BellSchedule> {
+    use crate::database::prepared::bell_schedules::add_bell_schedule as add_bell_schedule_in_db;
+
+    let mut db_conn = get_db!(ctx);
+
+    run_query!(
+        db_conn.add_bell_schedule_in_db(&name)
+        else (req_id(ctx)) "Failed to add bell schedule {name}: {}"
+    )
+}
+
+pub async fn update_bell_schedule_name(
+    ctx: &Context<'_>,
+    id: Uuid,
+    name: String,
+) -> GraphQlResult<BellSchedule> {
+    use crate::database::prepared::bell_schedules::update_bell_schedule_name as update_bell_schedule_name_in_db;
+
+    let mut db_conn = get_db!(ctx);
+
+    run_query!(
+        db_conn.update_bell_schedule_name_in_db(id, &name)
+        else (req_id(ctx)) "Failed to rename bell schedule {id}: {}"
+    )
+}
+
+pub async fn delete_bell_schedule(
+    ctx: &Context<'_>,
+    id: Uuid,
+) -> GraphQlResult<bool> {
+    use crate::database::prepared::bell_schedules::delete_bell_schedule as delete_bell_schedule_in_db;
+
+    let mut db_conn = get_db!(ctx);
+
+    let deleted = run_query!(
+        db_conn.delete_bell_schedule_in_db(id)
+        else (req_id(ctx)) "Failed to delete bell schedule {id}: {}"
+    )?;
+
+    if deleted {
+        publish_all_period_times(ctx, &mut db_conn).await?;
+    }
+    Ok(deleted)
+}
+
+pub async fn set_bell_schedule_period_time(
+    ctx: &Context<'_>,
+    schedule_id: Uuid,
+    period_id: Uuid,
+    time: TimeRangeInput,
+) -> GraphQlResult<bool> {
+    use crate::database::prepared::bell_schedules::set_bell_schedule_period_time as set_bell_schedule_period_time_in_db;
+
+    let mut db_conn = get_db!(ctx);
+
+    run_query!(
+        db_conn.set_bell_schedule_period_time_in_db(schedule_id, period_id, [time.start, time.end])
+        else (req_id(ctx)) "Failed to set time of period {period_id} in bell schedule {schedule_id}: {}"
+    )?;
+
+    publish_all_period_times(ctx, &mut db_conn).await?;
+    Ok(true)
+}
+
+pub async fn clear_bell_schedule_period_time(
+    ctx: &Context<'_>,
+    schedule_id: Uuid,
+    period_id: Uuid,
+) -> GraphQlResult<bool> {
+    use crate::database::prepared::bell_schedules::clear_bell_schedule_period_time as clear_bell_schedule_period_time_in_db;
+
+    let mut db_conn = get_db!(ctx);
+
+    run_query!(
+        db_conn.clear_bell_schedule_period_time_in_db(schedule_id, period_id)
+        else (req_id(ctx)) "Failed to clear time of period {period_id} in bell schedule {schedule_id}: {}"
+    )?;
+
+    publish_all_period_times(ctx, &mut db_conn).await?;
+    Ok(true)
+}
+
+pub async fn set_schedule_days(
+    ctx: &Context<'_>,
+    start: NaiveDate,
+    end: NaiveDate,
+    schedule_id: Option<Uuid>,
+) -> GraphQlResult<bool> {
+    use crate::database::prepared::bell_schedules::set_schedule_days as set_schedule_days_in_db;
+
+    let mut db_conn = get_db!(ctx);
+
+    run_query!(
+        db_conn.set_schedule_days_in_db(start, end, schedule_id)
+        else (req_id(ctx)) "Failed to set bell schedule from {start} to {end}: {}"
+    )?;
+
+    publish_all_period_times(ctx, &mut db_conn).await?;
+    Ok(true)
+}
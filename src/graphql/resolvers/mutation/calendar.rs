@@ -0,0 +1,35 @@
+use async_graphql::{ Context, Error as GraphQlError };
+use chrono::NaiveDate;
+
+
+use crate::events::{Event, ConfigField};
+use crate::graphql::resolvers::{get_db, run_query, publish_event, ensure_date_span};
+use crate::graphql::req_id;
+use crate::types::CalendarDayKind;
+
+use async_graphql::Result as GraphQlResult;
+
+pub async fn set_calendar_days(
+    ctx: &Context<'_>,
+    start: NaiveDate,
+    end: NaiveDate,
+    kind: Option<CalendarDayKind>,
+    name: Option<String>,
+) -> GraphQlResult<bool> {
+    use crate::database::prepared::calendar::set_calendar_days as set_calendar_days_in_db;
+
+    if kind == Some(CalendarDayKind::Weekend) {
+        return Err(GraphQlError::new("Days can't be set to WEEKEND, clear their kind instead"));
+    }
+    ensure_date_span(start, end)?;
+
+    let mut db_conn = get_db!(ctx);
+
+    run_query!(
+        db_conn.set_calendar_days_in_db(start, end, kind, name.as_deref())
+        else (req_id(ctx)) "Failed to set calendar from {start} to {end}: {}"
+    )?;
+
+    publish_event(ctx, Event::ConfigChanged(ConfigField::Calendar));
+    Ok(true)
+}
@@ -0,0 +1,70 @@
+use async_graphql::Context;
+use uuid::Uuid;
+
+
+use crate::database::Ctx;
+use crate::graphql::resolvers::{get_db, run_query, current_teacher, ensure_acting_for, ensure_staff, ensure_not_archived};
+use crate::graphql::req_id;
+use crate::types::CalendarFeedCredentials;
+
+use async_graphql::Result as GraphQlResult;
+
+/// Teachers can manage their own feeds, and secretaries and admins can manage
+/// anyone's along with the staff-wide feeds.
+async fn ensure_feed_access(
+    ctx: &Context<'_>,
+    db_conn: &mut Ctx,
+    teacher_id: Option<Uuid>,
+    id_token: Option<&str>,
+) -> GraphQlResult<()> {
+    match teacher_id {
+        Some(teacher_id) => ensure_acting_for(ctx, db_conn, teacher_id, id_token).await,
+        None => ensure_staff(ctx, db_conn, id_token).await,
+    }
+}
+
+pub async fn create_calendar_feed(
+    ctx: &Context<'_>,
+    teacher_id: Option<Uuid>,
+    id_token: Option<String>,
+) -> GraphQlResult<CalendarFeedCredentials> {
+    use crate::database::prepared::calendar_feeds::add_calendar_feed as add_calendar_feed_in_db;
+
+    let mut db_conn = get_db!(ctx);
+    ensure_feed_access(ctx, &mut db_conn, teacher_id, id_token.as_deref()).await?;
+    ensure_not_archived(ctx, &mut db_conn, teacher_id.as_slice(), &[]).await?;
+    let created_by = current_teacher(ctx, &mut db_conn, id_token.as_deref()).await?.get_id();
+
+    let (token, token_hash) = crate::calendar_feed::new_token();
+
+    let feed = run_query!(
+        db_conn.add_calendar_feed_in_db(teacher_id, created_by, &token_hash)
+        else (req_id(ctx)) "Failed to add calendar feed: {}"
+    )?;
+
+    Ok(CalendarFeedCredentials { token, feed })
+}
+
+pub async fn revoke_calendar_feed(
+    ctx: &Context<'_>,
+    id: Uuid,
+    id_token: Option<String>,
+) -> GraphQlResult<bool> {
+    use crate::database::prepared::calendar_feeds::{
+        delete_calendar_feed as delete_calendar_feed_in_db,
+        get_calendar_feed as get_calendar_feed_from_db,
+    };
+
+    let mut db_conn = get_db!(ctx);
+
+    let feed = run_query!(
+        db_conn.get_calendar_feed_from_db(id)
+        else (req_id(ctx)) "Failed to get calendar feed {id}: {}"
+    )?;
+    ensure_feed_access(ctx, &mut db_conn, feed.teacher_id, id_token.as_deref()).await?;
+
+    run_query!(
+        db_conn.delete_calendar_feed_in_db(id)
+        else (req_id(ctx)) "Failed to revoke calendar feed {id}: {}"
+    )
+}
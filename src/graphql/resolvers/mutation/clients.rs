@@ -0,0 +1,196 @@
+use async_graphql::{ Context, Error as GraphQlError, Result as GraphQlResult };
+use chrono::{ DateTime, Utc };
+use uuid::Uuid;
+
+use crate::graphql::resolvers::{ get_db, run_query };
+use crate::graphql::req_id;
+use crate::state::AppState;
+use crate::types::{ Client, ClientCredentials };
+use crate::verification::id_secret::{ generate_client_keystr, generate_client_secret };
+use crate::verification::scopes::{ Scopes, SCOPES };
+
+
+/// Every client mutation. Access to this is gated behind the `admin` scope in
+/// [`MutationRoot::clients`][super::MutationRoot].
+#[derive(Debug, Clone)]
+pub struct ClientMutationRoot;
+
+#[async_graphql::Object]
+impl ClientMutationRoot {
+    /// Create a new client with a freshly generated secret.
+    ///
+    /// The secret is only ever returned here, so it must be saved right away.
+    async fn create(
+        &self,
+        ctx: &Context<'_>,
+        scopes: Vec<String>,
+        description: String,
+        #[graphql(desc = "When the client stops working, never if not set")] expires_at: Option<DateTime<Utc>>,
+    ) -> GraphQlResult<ClientCredentials> {
+        use crate::database::prepared::clients::create_client as create_client_in_db;
+
+        let scopes = parse_scopes(&scopes)?;
+        let (secret, keystr) = new_secret().await?;
+        let id = Uuid::new_v4();
+
+        let mut db_conn = get_db!(ctx);
+
+        let client = run_query!(
+            db_conn.create_client_in_db(id, &keystr, &scopes, &description, expires_at)
+            else (req_id(ctx)) "Failed to create client: {}"
+        )?;
+
+        Ok(ClientCredentials { client, secret })
+    }
+
+    /// Replace a client's secret with a freshly generated one. The old secret
+    /// stops working immediately.
+    async fn rotate_secret(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+    ) -> GraphQlResult<ClientCredentials> {
+        use crate::database::prepared::clients::set_client_key as set_client_key_in_db;
+
+        let (secret, keystr) = new_secret().await?;
+
+        let mut db_conn = get_db!(ctx);
+
+        let client = run_query!(
+            db_conn.set_client_key_in_db(id, &keystr)
+            else (req_id(ctx)) "Failed to rotate secret for client {id}: {}"
+        )?;
+        invalidate_cached_scopes(ctx, id)?;
+
+        Ok(ClientCredentials { client, secret })
+    }
+
+    async fn set_scopes(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        scopes: Vec<String>,
+    ) -> GraphQlResult<Client> {
+        use crate::database::prepared::clients::set_client_scopes as set_client_scopes_in_db;
+
+        let scopes = parse_scopes(&scopes)?;
+
+        let mut db_conn = get_db!(ctx);
+
+        let res = run_query!(
+            db_conn.set_client_scopes_in_db(id, &scopes)
+            else (req_id(ctx)) "Failed to set scopes for client {id}: {}"
+        )?;
+        invalidate_cached_scopes(ctx, id)?;
+
+        Ok(res)
+    }
+
+    async fn set_description(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        description: String,
+    ) -> GraphQlResult<Client> {
+        use crate::database::prepared::clients::set_client_description as set_client_description_in_db;
+
+        let mut db_conn = get_db!(ctx);
+
+        run_query!(
+            db_conn.set_client_description_in_db(id, &description)
+            else (req_id(ctx)) "Failed to set description for client {id}: {}"
+        )
+    }
+
+    async fn set_expiry(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        #[graphql(desc = "When the client stops working, never if not set")] expires_at: Option<DateTime<Utc>>,
+    ) -> GraphQlResult<Client> {
+        use crate::database::prepared::clients::set_client_expiry as set_client_expiry_in_db;
+
+        let mut db_conn = get_db!(ctx);
+
+        let res = run_query!(
+            db_conn.set_client_expiry_in_db(id, expires_at)
+            else (req_id(ctx)) "Failed to set expiry for client {id}: {}"
+        )?;
+        invalidate_cached_scopes(ctx, id)?;
+
+        Ok(res)
+    }
+
+    async fn set_rate_limit(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        #[graphql(desc = "Requests per minute, 0 for unlimited, or unset for the server default")] per_minute: Option<i32>,
+    ) -> GraphQlResult<Client> {
+        use crate::database::prepared::clients::set_client_rate_limit as set_client_rate_limit_in_db;
+
+        if per_minute.is_some_and(|limit| limit < 0) {
+            return Err(GraphQlError::new("Rate limit can't be negative"));
+        }
+
+        let mut db_conn = get_db!(ctx);
+
+        let res = run_query!(
+            db_conn.set_client_rate_limit_in_db(id, per_minute)
+            else (req_id(ctx)) "Failed to set rate limit for client {id}: {}"
+        )?;
+        ctx.data::<AppState>()?.rate_limits().forget_client(id);
+
+        Ok(res)
+    }
+
+    /// Revoke a client, so its id and secret stop working immediately.
+    /// Returns false if it was already revoked.
+    async fn revoke(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+    ) -> GraphQlResult<bool> {
+        use crate::database::prepared::clients::revoke_client as revoke_client_in_db;
+
+        let mut db_conn = get_db!(ctx);
+
+        let res = run_query!(
+            db_conn.revoke_client_in_db(id)
+            else (req_id(ctx)) "Failed to revoke client {id}: {}"
+        )?;
+        invalidate_cached_scopes(ctx, id)?;
+
+        Ok(res)
+    }
+}
+
+
+/// Make sure the next request from a changed client is verified against the
+/// database instead of using cached scopes or a stale rate limit.
+fn invalidate_cached_scopes(ctx: &Context<'_>, id: Uuid) -> GraphQlResult<()> {
+    let app_state = ctx.data::<AppState>()?;
+    app_state.scope_cache().invalidate(id);
+    app_state.rate_limits().forget_client(id);
+    Ok(())
+}
+
+fn parse_scopes(scopes: &[String]) -> GraphQlResult<Scopes> {
+    let Some(scopes) = Scopes::try_from_str(&scopes.join(" ")) else {
+        return Err(GraphQlError::new(format!("Invalid scope(s). Valid scopes: {}", SCOPES.join(", "))));
+    };
+
+    Ok(scopes)
+}
+
+async fn new_secret() -> GraphQlResult<(String, String)> {
+    let secret = generate_client_secret();
+
+    let hash_secret = secret.clone();
+    let keystr = tokio::task::spawn_blocking(move || generate_client_keystr(hash_secret.as_bytes())).await;
+    let Ok(Some(keystr)) = keystr else {
+        return Err(GraphQlError::new("Failed to generate client secret"));
+    };
+
+    Ok((secret, keystr))
+}
@@ -0,0 +1,88 @@
+use async_graphql::Context;
+use uuid::Uuid;
+
+
+use crate::database::prepared::audit_log::audited;
+use crate::events::Event;
+use crate::graphql::resolvers::{get_db, run_query, publish_event, ensure_not_archived, audit_origin};
+use crate::graphql::req_id;
+use crate::types::{ AuditTarget, Coverage, Substitute };
+
+use async_graphql::Result as GraphQlResult;
+
+pub async fn set_coverage(
+    ctx: &Context<'_>,
+    teacher_id: Uuid,
+    period_id: Uuid,
+    substitute_id: Option<Uuid>,
+    external_name: Option<String>,
+) -> GraphQlResult<Coverage> {
+    use crate::database::prepared::coverage::{
+        find_coverage_conflict as find_coverage_conflict_in_db,
+        set_coverage as set_coverage_in_db,
+    };
+    use crate::database::prepared::teacher::get_teacher as get_teacher_from_db;
+
+    let substitute = match (substitute_id, external_name) {
+        (Some(id), None) => Substitute::Teacher(id),
+        (None, Some(name)) if !name.trim().is_empty() => Substitute::External(name.trim().to_string()),
+        (None, Some(_)) => return Err(async_graphql::Error::new("External substitute name can't be empty")),
+        _ => return Err(async_graphql::Error::new("Exactly one of substituteId or externalName is required")),
+    };
+
+    let mut db_conn = get_db!(ctx);
+    let teachers: Vec<_> = [Some(teacher_id), substitute_id].into_iter().flatten().collect();
+    ensure_not_archived(ctx, &mut db_conn, &teachers, &[period_id]).await?;
+
+    let conflict = run_query!(
+        db_conn.find_coverage_conflict_in_db(teacher_id, period_id, substitute_id)
+        else (req_id(ctx)) "Failed to check coverage of teacher {teacher_id} in period {period_id}: {}"
+    )?;
+    if let Some(conflict) = conflict {
+        return Err(async_graphql::Error::new(conflict.to_string()));
+    }
+
+    let origin = audit_origin(ctx);
+    let target = AuditTarget::Coverage { teacher: teacher_id, period: period_id };
+
+    let coverage = run_query!(
+        db_conn.audited(&origin, target, |txn| Box::pin(set_coverage_in_db(txn, teacher_id, period_id, substitute)))
+        else (req_id(ctx)) "Failed to set coverage of teacher {teacher_id} in period {period_id}: {}"
+    )?;
+
+    let teacher = run_query!(
+        db_conn.get_teacher_from_db(teacher_id)
+        else (req_id(ctx)) "Failed to refetch covered teacher {teacher_id}: {}"
+    )?;
+    publish_event(ctx, Event::TeacherAbsenceChanged(teacher));
+
+    Ok(coverage)
+}
+
+pub async fn clear_coverage(
+    ctx: &Context<'_>,
+    teacher_id: Uuid,
+    period_id: Uuid,
+) -> GraphQlResult<bool> {
+    use crate::database::prepared::coverage::clear_coverage as clear_coverage_in_db;
+    use crate::database::prepared::teacher::get_teacher as get_teacher_from_db;
+
+    let mut db_conn = get_db!(ctx);
+    let origin = audit_origin(ctx);
+    let target = AuditTarget::Coverage { teacher: teacher_id, period: period_id };
+
+    let cleared = run_query!(
+        db_conn.audited(&origin, target, |txn| Box::pin(clear_coverage_in_db(txn, teacher_id, period_id)))
+        else (req_id(ctx)) "Failed to clear coverage of teacher {teacher_id} in period {period_id}: {}"
+    )?;
+
+    if cleared {
+        let teacher = run_query!(
+            db_conn.get_teacher_from_db(teacher_id)
+            else (req_id(ctx)) "Failed to refetch covered teacher {teacher_id}: {}"
+        )?;
+        publish_event(ctx, Event::TeacherAbsenceChanged(teacher));
+    }
+
+    Ok(cleared)
+}
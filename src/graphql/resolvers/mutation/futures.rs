@@ -1,14 +1,57 @@
 use async_graphql::Context;
-use chrono::NaiveDate;
+use chrono::{ Datelike, NaiveDate, Weekday };
+use serde_json::json;
+use sqlx::Connection;
 use uuid::Uuid;
 
 
-use crate::graphql::resolvers::{get_db, run_query};
+use crate::database::Ctx;
+use crate::database::prepared::audit_log::{ audited, record_change, snapshot };
+use crate::graphql::resolvers::{get_db, run_query, current_teacher, current_privileges, ensure_not_archived, audit_origin};
 use crate::graphql::req_id;
+use crate::graphql::structs::RecurrenceRuleInput;
+use crate::types::{ AbsenceRequestStatus, AuditOrigin, AuditTarget, RecurrenceRule, RecurringAbsence, RolloverTrigger, WebhookEvent };
 
 
 use async_graphql::Result as GraphQlResult;
 
+/// Who is editing a teacher's future absences, which decides whether their
+/// changes need to be approved.
+enum FutureEditor {
+    /// A client with the `experimental` and `admin` scopes.
+    TrustedClient,
+    /// A secretary or admin, whose changes are approved by them.
+    Reviewer(Uuid),
+    /// The teacher themselves, whose changes need to be approved.
+    Teacher,
+}
+
+/// Clients with the `experimental` and `admin` scopes can edit anyone's future
+/// absences. Otherwise, the signed-in teacher can only edit their own, unless
+/// they're a secretary or admin.
+async fn future_editor(
+    ctx: &Context<'_>,
+    db_conn: &mut Ctx,
+    id: Uuid,
+    id_token: Option<&str>,
+) -> GraphQlResult<FutureEditor> {
+    let scopes = crate::graphql::get_scopes(ctx).await?;
+    if scopes.experimental && scopes.admin {
+        return Ok(FutureEditor::TrustedClient);
+    }
+
+    let teacher = current_teacher(ctx, db_conn, id_token).await?;
+    let privileges = current_privileges(ctx, db_conn, &teacher).await?;
+
+    if privileges.secretary || privileges.admin {
+        Ok(FutureEditor::Reviewer(teacher.get_id()))
+    } else if teacher.get_id() == id {
+        Ok(FutureEditor::Teacher)
+    } else {
+        Err(async_graphql::Error::new("Not permitted to access this resource"))
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 pub async fn set_teacher_future_absence(
     ctx: &Context<'_>,
@@ -18,15 +61,23 @@ pub async fn set_teacher_future_absence(
     periods: Vec<Uuid>,
     fully_absent: bool,
     comment: Option<String>,
+    id_token: Option<String>,
 ) -> GraphQlResult<bool> {
-    use crate::database::prepared::future_absences::set_future_day as set_future_absence_in_db;
-
     let mut db_conn = get_db!(ctx);
+    let (status, reviewed_by) = match future_editor(ctx, &mut db_conn, id, id_token.as_deref()).await? {
+        FutureEditor::TrustedClient => (AbsenceRequestStatus::Approved, None),
+        FutureEditor::Reviewer(reviewer) => (AbsenceRequestStatus::Approved, Some(reviewer)),
+        FutureEditor::Teacher => (AbsenceRequestStatus::Pending, None),
+    };
+    ensure_not_archived(ctx, &mut db_conn, &[id], &periods).await?;
+    let origin = audit_origin(ctx);
 
     run_query!(
-        db_conn.set_future_absence_in_db(
+        db_conn.set_future_absence_and_notify(
+            &origin,
             start, end.unwrap_or(start), id,
             &periods, fully_absent, comment,
+            status, reviewed_by,
         )
         else (req_id(ctx)) "Failed to set future absence in the database for teacher {id}: {}"
     )?;
@@ -34,35 +85,391 @@ pub async fn set_teacher_future_absence(
     Ok(true)
 }
 
+/// Sets a future absence and, if it's approved, queues the
+/// `future_absence_created` webhooks for it in the same transaction. Pending
+/// requests send `future_absence_approved` once they're reviewed instead.
+#[allow(clippy::too_many_arguments)]
+async fn set_future_absence_and_notify(
+    db_conn: &mut Ctx,
+    origin: &AuditOrigin,
+    start: NaiveDate,
+    end: NaiveDate,
+    id: Uuid,
+    periods: &[Uuid],
+    fully_absent: bool,
+    comment: Option<String>,
+    status: AbsenceRequestStatus,
+    reviewed_by: Option<Uuid>,
+) -> Result<(), sqlx::Error> {
+    use crate::database::prepared::future_absences::set_future_day;
+
+    let webhook_data = json!({
+        "teacherId": id,
+        "start": start,
+        "end": end,
+        "periods": periods,
+        "fullyAbsent": fully_absent,
+        "comment": comment,
+        "status": status.str(),
+    });
+
+    let periods = periods.to_vec();
+    audited(db_conn, origin, AuditTarget::FutureAbsences { teacher: id, start, end }, move |txn| Box::pin(async move {
+        set_future_day(txn, start, end, id, &periods, fully_absent, comment, status, reviewed_by).await?;
+        if status == AbsenceRequestStatus::Approved {
+            crate::webhooks::notify(txn, WebhookEvent::FutureAbsenceCreated, webhook_data).await?;
+        }
+        Ok(())
+    })).await
+}
+
 pub async fn clear_teacher_future_absence(
     ctx: &Context<'_>,
     start: NaiveDate,
     end: Option<NaiveDate>,
     id: Uuid,
+    id_token: Option<String>,
 ) -> GraphQlResult<bool> {
-    use crate::database::prepared::future_absences::clear_future_day as clear_future_absence_in_db;
-
     let mut db_conn = get_db!(ctx);
+    future_editor(ctx, &mut db_conn, id, id_token.as_deref()).await?;
+    let origin = audit_origin(ctx);
 
     run_query!(
-        db_conn.clear_future_absence_in_db(start, end.unwrap_or(start), id)
+        db_conn.clear_future_absence_and_notify(&origin, start, end.unwrap_or(start), id)
         else (req_id(ctx)) "Failed to clear future absence in the database for teacher {id}: {}"
     )?;
     
     Ok(true)
 }
 
-pub async fn sync_and_flush_futures(
+/// Cancels a teacher's future absences and queues the
+/// `future_absence_cancelled` webhooks for them in the same transaction, if
+/// any days were cancelled.
+async fn clear_future_absence_and_notify(
+    db_conn: &mut Ctx,
+    origin: &AuditOrigin,
+    start: NaiveDate,
+    end: NaiveDate,
+    id: Uuid,
+) -> Result<(), sqlx::Error> {
+    use crate::database::prepared::future_absences::clear_future_day;
+
+    audited(db_conn, origin, AuditTarget::FutureAbsences { teacher: id, start, end }, |txn| Box::pin(async move {
+        let cancelled = clear_future_day(txn, start, end, id).await?;
+        if cancelled > 0 {
+            crate::webhooks::notify(txn, WebhookEvent::FutureAbsenceCancelled, json!({
+                "teacherId": id,
+                "start": start,
+                "end": end,
+                "daysCancelled": cancelled,
+            })).await?;
+        }
+        Ok(())
+    })).await
+}
+
+fn recurrence_rule(rule: RecurrenceRuleInput) -> GraphQlResult<RecurrenceRule> {
+    let interval_weeks = u32::try_from(rule.interval_weeks)
+        .ok()
+        .filter(|interval_weeks| *interval_weeks > 0)
+        .ok_or_else(|| async_graphql::Error::new("intervalWeeks must be at least 1"))?;
+
+    let count = match rule.count {
+        Some(count) => Some(
+            u32::try_from(count)
+                .ok()
+                .filter(|count| *count > 0)
+                .ok_or_else(|| async_graphql::Error::new("count must be at least 1"))?
+        ),
+        None => None,
+    };
+
+    if rule.until.is_some_and(|until| until < rule.start) {
+        return Err(async_graphql::Error::new("until can't be before start"));
+    }
+
+    let weekdays = match rule.weekdays {
+        Some(weekdays) if weekdays.is_empty() => return Err(async_graphql::Error::new("weekdays can't be empty")),
+        Some(weekdays) => weekdays
+            .into_iter()
+            .map(|weekday| {
+                weekday
+                    .checked_sub(1)
+                    .and_then(|weekday| u8::try_from(weekday).ok())
+                    .and_then(|weekday| Weekday::try_from(weekday).ok())
+                    .ok_or_else(|| async_graphql::Error::new(format!("Invalid ISO weekday {weekday}")))
+            })
+            .collect::<GraphQlResult<Vec<_>>>()?,
+        None => vec![rule.start.weekday()],
+    };
+
+    Ok(RecurrenceRule { start: rule.start, interval_weeks, weekdays, until: rule.until, count })
+}
+
+/// Sets a future absence that repeats. Only secretaries, admins and
+/// `experimental` + `admin` clients can set recurring absences, since they
+/// can't go through the approval workflow.
+#[allow(clippy::too_many_arguments)]
+pub async fn add_recurring_teacher_absence(
     ctx: &Context<'_>,
+    id: Uuid,
+    periods: Vec<Uuid>,
+    fully_absent: bool,
+    comment: Option<String>,
+    rule: RecurrenceRuleInput,
+    id_token: Option<String>,
+) -> GraphQlResult<RecurringAbsence> {
+    let rule = recurrence_rule(rule)?;
+
+    let mut db_conn = get_db!(ctx);
+    if let FutureEditor::Teacher = future_editor(ctx, &mut db_conn, id, id_token.as_deref()).await? {
+        return Err(async_graphql::Error::new("Only secretaries and admins can set recurring absences"));
+    }
+    ensure_not_archived(ctx, &mut db_conn, &[id], &periods).await?;
+    let origin = audit_origin(ctx);
+
+    run_query!(
+        db_conn.add_recurring_absence_and_notify(&origin, id, &periods, fully_absent, comment, rule)
+        else (req_id(ctx)) "Failed to add recurring absence in the database for teacher {id}: {}"
+    )
+}
+
+/// Adds a recurring absence and queues the `future_absence_created` webhooks
+/// for it in the same transaction. Its ID isn't known until it's added, so
+/// it's audited by hand rather than through `audited`.
+async fn add_recurring_absence_and_notify(
+    db_conn: &mut Ctx,
+    origin: &AuditOrigin,
+    id: Uuid,
+    periods: &[Uuid],
+    fully_absent: bool,
+    comment: Option<String>,
+    rule: RecurrenceRule,
+) -> Result<RecurringAbsence, sqlx::Error> {
+    use crate::database::prepared::recurring_absences::add_recurring_absence;
+
+    let mut txn = db_conn.begin().await?;
+    let recurrence = add_recurring_absence(&mut txn, id, periods, fully_absent, comment, rule).await?;
+    crate::webhooks::notify(&mut txn, WebhookEvent::FutureAbsenceCreated, json!({
+        "teacherId": id,
+        "recurrenceId": recurrence.id,
+        "start": recurrence.rule.start,
+        "until": recurrence.rule.until,
+        "periods": recurrence.periods,
+        "fullyAbsent": recurrence.fully_absent,
+        "comment": recurrence.comment,
+        "status": AbsenceRequestStatus::Approved.str(),
+    })).await?;
+
+    let target = AuditTarget::RecurringAbsence(recurrence.id);
+    let after = snapshot(&mut txn, target).await?;
+    record_change(&mut txn, origin, target, None, after).await?;
+    txn.commit().await?;
+
+    Ok(recurrence)
+}
+
+/// Teachers can delete their own recurring absences. Secretaries, admins and
+/// `experimental` + `admin` clients can delete anyone's.
+pub async fn delete_recurring_teacher_absence(
+    ctx: &Context<'_>,
+    recurrence_id: Uuid,
+    id_token: Option<String>,
 ) -> GraphQlResult<bool> {
-    use crate::database::prepared::future_absences::flush_today as sync_and_flush_in_db;
+    use crate::database::prepared::recurring_absences::get_recurring_absence as get_recurring_absence_from_db;
+
+    let mut db_conn = get_db!(ctx);
+
+    let recurrence = run_query!(
+        db_conn.get_recurring_absence_from_db(recurrence_id)
+        else (req_id(ctx)) "Failed to get recurring absence {recurrence_id}: {}"
+    )?;
+    future_editor(ctx, &mut db_conn, recurrence.teacher_id, id_token.as_deref()).await?;
+    let origin = audit_origin(ctx);
+
+    run_query!(
+        db_conn.delete_recurring_absence_and_notify(&origin, recurrence.teacher_id, recurrence_id)
+        else (req_id(ctx)) "Failed to delete recurring absence {recurrence_id}: {}"
+    )
+}
+
+/// Deletes a recurring absence and queues the `future_absence_cancelled`
+/// webhooks for it in the same transaction, if it was deleted.
+async fn delete_recurring_absence_and_notify(
+    db_conn: &mut Ctx,
+    origin: &AuditOrigin,
+    teacher_id: Uuid,
+    recurrence_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    use crate::database::prepared::recurring_absences::delete_recurring_absence;
+
+    audited(db_conn, origin, AuditTarget::RecurringAbsence(recurrence_id), |txn| Box::pin(async move {
+        let deleted = delete_recurring_absence(txn, recurrence_id).await?;
+        if deleted {
+            crate::webhooks::notify(txn, WebhookEvent::FutureAbsenceCancelled, json!({
+                "teacherId": teacher_id,
+                "recurrenceId": recurrence_id,
+            })).await?;
+        }
+        Ok(deleted)
+    })).await
+}
+
+/// Skips one occurrence of a recurring absence, or brings it back. Teachers
+/// can skip occurrences of their own recurring absences, but only
+/// secretaries, admins and `experimental` + `admin` clients can bring them
+/// back.
+pub async fn skip_recurring_teacher_absence(
+    ctx: &Context<'_>,
+    recurrence_id: Uuid,
+    date: NaiveDate,
+    skipped: bool,
+    id_token: Option<String>,
+) -> GraphQlResult<RecurringAbsence> {
+    use crate::database::prepared::recurring_absences::get_recurring_absence as get_recurring_absence_from_db;
 
     let mut db_conn = get_db!(ctx);
 
+    let recurrence = run_query!(
+        db_conn.get_recurring_absence_from_db(recurrence_id)
+        else (req_id(ctx)) "Failed to get recurring absence {recurrence_id}: {}"
+    )?;
+    let editor = future_editor(ctx, &mut db_conn, recurrence.teacher_id, id_token.as_deref()).await?;
+    if !skipped && matches!(editor, FutureEditor::Teacher) {
+        return Err(async_graphql::Error::new("Only secretaries and admins can bring back skipped absences"));
+    }
+    let origin = audit_origin(ctx);
+
     run_query!(
-        db_conn.sync_and_flush_in_db()
-        else (req_id(ctx)) "Failed in syncing and flushing futures at {}: {}", chrono::Utc::now().to_rfc2822()
+        db_conn.skip_recurrence_and_notify(&origin, &recurrence, date, skipped)
+        else (req_id(ctx)) "Failed to skip {date} of recurring absence {recurrence_id}: {}"
     )?;
-    
+
+    run_query!(
+        db_conn.get_recurring_absence_from_db(recurrence_id)
+        else (req_id(ctx)) "Failed to refetch recurring absence {recurrence_id}: {}"
+    )
+}
+
+/// Skips one occurrence of a recurring absence, or brings it back, and queues
+/// the `future_absence_cancelled` or `future_absence_created` webhooks for it
+/// in the same transaction.
+async fn skip_recurrence_and_notify(
+    db_conn: &mut Ctx,
+    origin: &AuditOrigin,
+    recurrence: &RecurringAbsence,
+    date: NaiveDate,
+    skipped: bool,
+) -> Result<(), sqlx::Error> {
+    use crate::database::prepared::recurring_absences::set_recurrence_exception;
+
+    let (event, data) = if skipped {
+        (WebhookEvent::FutureAbsenceCancelled, json!({
+            "teacherId": recurrence.teacher_id,
+            "recurrenceId": recurrence.id,
+            "start": date,
+            "end": date,
+        }))
+    } else {
+        (WebhookEvent::FutureAbsenceCreated, json!({
+            "teacherId": recurrence.teacher_id,
+            "recurrenceId": recurrence.id,
+            "start": date,
+            "end": date,
+            "periods": recurrence.periods,
+            "fullyAbsent": recurrence.fully_absent,
+            "comment": recurrence.comment,
+            "status": AbsenceRequestStatus::Approved.str(),
+        }))
+    };
+
+    let recurrence_id = recurrence.id;
+    audited(db_conn, origin, AuditTarget::RecurringAbsence(recurrence_id), move |txn| Box::pin(async move {
+        set_recurrence_exception(txn, recurrence_id, date, skipped).await?;
+        crate::webhooks::notify(txn, event, data).await
+    })).await
+}
+
+/// Approves or rejects a teacher's pending future absences. Only secretaries
+/// and admins can review requests.
+pub async fn review_teacher_future_absence(
+    ctx: &Context<'_>,
+    start: NaiveDate,
+    end: Option<NaiveDate>,
+    id: Uuid,
+    status: AbsenceRequestStatus,
+    id_token: Option<String>,
+) -> GraphQlResult<i32> {
+    let mut db_conn = get_db!(ctx);
+
+    let reviewer = current_teacher(ctx, &mut db_conn, id_token.as_deref()).await?;
+    let privileges = current_privileges(ctx, &mut db_conn, &reviewer).await?;
+    if !privileges.secretary && !privileges.admin {
+        return Err(async_graphql::Error::new("Not permitted to access this resource"));
+    }
+    if status == AbsenceRequestStatus::Approved {
+        ensure_not_archived(ctx, &mut db_conn, &[id], &[]).await?;
+    }
+
+    let origin = audit_origin(ctx);
+
+    let reviewed = run_query!(
+        db_conn.review_future_absence_and_notify(&origin, start, end.unwrap_or(start), id, status, reviewer.get_id())
+        else (req_id(ctx)) "Failed to review future absence in the database for teacher {id}: {}"
+    )?;
+
+    i32::try_from(reviewed)
+        .map_err(|_| async_graphql::Error::new(format!("Reviewed {reviewed} days, which is too many to count")))
+}
+
+/// Reviews a teacher's pending future absences and queues the
+/// `future_absence_approved` or `future_absence_rejected` webhooks for them
+/// in the same transaction, if any days were reviewed.
+async fn review_future_absence_and_notify(
+    db_conn: &mut Ctx,
+    origin: &AuditOrigin,
+    start: NaiveDate,
+    end: NaiveDate,
+    id: Uuid,
+    status: AbsenceRequestStatus,
+    reviewer: Uuid,
+) -> Result<u64, sqlx::Error> {
+    use crate::database::prepared::absence_requests::review_future_days;
+
+    let event = match status {
+        AbsenceRequestStatus::Approved => Some(WebhookEvent::FutureAbsenceApproved),
+        AbsenceRequestStatus::Rejected => Some(WebhookEvent::FutureAbsenceRejected),
+        _ => None,
+    };
+
+    audited(db_conn, origin, AuditTarget::FutureAbsences { teacher: id, start, end }, |txn| Box::pin(async move {
+        let reviewed = review_future_days(txn, start, end, id, status, reviewer).await?;
+        if let Some(event) = event.filter(|_| reviewed > 0) {
+            crate::webhooks::notify(txn, event, json!({
+                "teacherId": id,
+                "start": start,
+                "end": end,
+                "daysReviewed": reviewed,
+                "reviewedBy": reviewer,
+            })).await?;
+        }
+        Ok(reviewed)
+    })).await
+}
+
+pub async fn sync_and_flush_futures(
+    ctx: &Context<'_>,
+) -> GraphQlResult<bool> {
+    let app_state = ctx.data::<crate::state::AppState>()?;
+
+    crate::scheduler::run(app_state, RolloverTrigger::Manual, &audit_origin(ctx))
+        .await
+        .map_err(|e| {
+            let e = e.to_string();
+            let now = chrono::Utc::now().to_rfc2822();
+            crate::logging::error!("{} - Failed in syncing and flushing futures at {now}: {e}", crate::logging::fmt_req_id(req_id(ctx)));
+            async_graphql::Error::new(format!("Failed in syncing and flushing futures at {now}: {e}"))
+        })?;
+
     Ok(true)
 }
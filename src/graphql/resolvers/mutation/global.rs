@@ -1,7 +1,10 @@
 use async_graphql::Context;
 
-use crate::graphql::resolvers::{get_db, run_query};
+use crate::database::prepared::audit_log::audited;
+use crate::events::{Event, ConfigField};
+use crate::graphql::resolvers::{get_db, run_query, publish_event, audit_origin};
 use crate::graphql::req_id;
+use crate::types::AuditTarget;
 
 use async_graphql::Result as GraphQlResult;
 
@@ -12,12 +15,16 @@ pub async fn set_spreadsheet_id(
     use crate::database::prepared::config::set_sheet_id as set_sheet_id_in_db;
 
     let mut db_conn = get_db!(ctx);
+    let origin = audit_origin(ctx);
 
     run_query!(
-        db_conn.set_sheet_id_in_db(&id)
+        db_conn.audited(&origin, AuditTarget::Config, move |txn| Box::pin(async move {
+            set_sheet_id_in_db(txn, &id).await
+        }))
         else (req_id(ctx)) "Database error: {}"
     )?;
-    
+
+    publish_event(ctx, Event::ConfigChanged(ConfigField::SpreadsheetId));
     Ok(true)
 }
 
@@ -28,11 +35,15 @@ pub async fn set_report_to(
     use crate::database::prepared::config::set_report_to as set_report_to_in_db;
 
     let mut db_conn = get_db!(ctx);
+    let origin = audit_origin(ctx);
 
     run_query!(
-        db_conn.set_report_to_in_db(&report_to)
+        db_conn.audited(&origin, AuditTarget::Config, move |txn| Box::pin(async move {
+            set_report_to_in_db(txn, &report_to).await
+        }))
         else (req_id(ctx)) "Database error: {}"
     )?;
-    
+
+    publish_event(ctx, Event::ConfigChanged(ConfigField::ReportTo));
     Ok(true)
 }
@@ -4,6 +4,14 @@ mod oauth;
 mod period_management;
 mod teacher_management;
 mod attribs;
+mod clients;
+mod coverage;
+mod rooms;
+mod bell_schedules;
+mod calendar;
+mod calendar_feeds;
+mod roster;
+mod webhooks;
 
 use async_graphql::{
     Object,
@@ -14,16 +22,19 @@ use async_graphql::{
 use chrono::NaiveDate;
 use uuid::Uuid;
 
+use crate::database::Ctx;
+use crate::database::prepared::audit_log::audited;
 use crate::database::prepared::teacher::get_teacher;
+use crate::events::Event;
 use crate::graphql::req_id;
-use crate::types::{ Teacher, Period };
+use crate::types::{ AuditOrigin, AuditTarget, Teacher, Period, SessionCredentials, AbsenceRequestStatus, Coverage, Room, BellSchedule, RecurringAbsence, CalendarDayKind, CalendarFeedCredentials, RosterFormat, RosterImportReport };
 
 use crate::graphql::structs::{
     GraphQlTeacherName,
-    GraphQlPronounSet, TimeRangeInput,
+    GraphQlPronounSet, TimeRangeInput, RecurrenceRuleInput,
 };
 
-use super::{ get_db, run_query, ensure_auth };
+use super::{ get_db, run_query, ensure_auth, publish_event, ensure_not_archived, audit_origin };
 
 /// This is a memberless struct implementing all the mutations for `improved-eureka`.
 /// This includes:
@@ -46,6 +57,23 @@ impl MutationRoot {
 
         teacher_management::add_teacher(ctx, name, pronouns).await
     }
+
+    /// Adds and updates teachers in bulk from a CSV or JSON roster, like the
+    /// one from `exportTeachers`. Rows with the id of an existing teacher
+    /// update them, and their OAuth accounts replace the teacher's existing
+    /// ones. The import is all or nothing, so if any row has an error,
+    /// nothing is applied and every error is reported.
+    async fn import_teachers(
+        &self,
+        ctx: &Context<'_>,
+        data: String,
+        #[graphql(default_with = "RosterFormat::Csv")] format: RosterFormat,
+        #[graphql(default = false, desc = "Check the roster for errors without applying it")] dry_run: bool,
+    ) -> GraphQlResult<RosterImportReport> {
+        ensure_auth!(ctx, [create_teacher, write_teacher_name, write_teacher_pronouns, admin]);
+
+        roster::import_teachers(ctx, data, format, dry_run).await
+    }
     
     async fn update_teacher_name(
         &self,
@@ -76,19 +104,53 @@ impl MutationRoot {
         periods: Vec<Uuid>,
         fully_absent: bool,
     ) -> GraphQlResult<Teacher> {
-        use crate::database::prepared::absences::update_absences_for_teacher as update_absences_for_teacher_in_db;
+        ensure_auth!(ctx, [write_teacher_absence]);
 
         let mut db_conn = get_db!(ctx);
-        ensure_auth!(ctx, [write_teacher_absence]);
+        ensure_not_archived(ctx, &mut db_conn, &[id], &periods).await?;
+        let origin = audit_origin(ctx);
 
         run_query!(
-            db_conn.update_absences_for_teacher_in_db(id, &periods, fully_absent)
+            db_conn.update_absences_and_notify(&origin, id, periods, fully_absent)
             else (req_id(ctx)) "Failed to update absence for teacher {id}: {}"
         )?;
-        run_query!(
+        let teacher = run_query!(
             db_conn.get_teacher(id)
             else (req_id(ctx)) "Failed to refetch updated teacher {id}: {}"
-        )
+        )?;
+
+        publish_event(ctx, Event::TeacherAbsenceChanged(teacher.clone()));
+        Ok(teacher)
+    }
+
+    /// Assigns a substitute to cover an absent teacher in a period, either
+    /// another teacher (`substituteId`) or an external substitute
+    /// (`externalName`).
+    /// 
+    /// Fails if the substitute is absent or already covering someone else in
+    /// that period.
+    async fn set_coverage(
+        &self,
+        ctx: &Context<'_>,
+        teacher_id: Uuid,
+        period_id: Uuid,
+        substitute_id: Option<Uuid>,
+        external_name: Option<String>,
+    ) -> GraphQlResult<Coverage> {
+        ensure_auth!(ctx, [write_teacher_absence]);
+
+        coverage::set_coverage(ctx, teacher_id, period_id, substitute_id, external_name).await
+    }
+
+    async fn clear_coverage(
+        &self,
+        ctx: &Context<'_>,
+        teacher_id: Uuid,
+        period_id: Uuid,
+    ) -> GraphQlResult<bool> {
+        ensure_auth!(ctx, [write_teacher_absence]);
+
+        coverage::clear_coverage(ctx, teacher_id, period_id).await
     }
 
     async fn add_teacher_associated_oauth(
@@ -114,7 +176,23 @@ impl MutationRoot {
         oauth::remove_teacher_associated_oauth(ctx, id, provider).await
     }
 
+    /// Exchange an OIDC ID token for a session token, so later requests don't
+    /// need to pass the ID token.
+    async fn login(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "Signed OIDC ID token of the teacher")] id_token: String,
+    ) -> GraphQlResult<SessionCredentials> {
+        ensure_auth!(ctx, [read_teacher]);
 
+        oauth::login(ctx, id_token).await
+    }
+
+
+    /// Teachers can request their own future absences, which then need to be
+    /// approved. Only school days in the range are set. Secretaries, admins
+    /// and `experimental` + `admin` clients can set anyone's, and their
+    /// changes are approved straight away.
     #[allow(clippy::too_many_arguments)]
     async fn set_teacher_future_absence(
         &self,
@@ -125,38 +203,189 @@ impl MutationRoot {
         periods: Vec<Uuid>,
         fully_absent: bool,
         comment: Option<String>,
+        #[graphql(desc = "Signed OIDC ID token of the teacher, if there's no session")] id_token: Option<String>,
     ) -> GraphQlResult<bool> {
-        ensure_auth!(ctx, [experimental, admin]);
+        ensure_auth!(ctx, [write_teacher_futures]);
 
         futures::set_teacher_future_absence(
             ctx,
             start, end, id,
             periods, fully_absent, comment,
+            id_token,
         ).await
     }
 
+    /// Teachers can cancel their own future absences. Secretaries, admins and
+    /// `experimental` + `admin` clients can cancel anyone's.
     async fn clear_teacher_future_absence(
         &self,
         ctx: &Context<'_>,
         start: NaiveDate,
         end: Option<NaiveDate>,
         id: Uuid,
+        #[graphql(desc = "Signed OIDC ID token of the teacher, if there's no session")] id_token: Option<String>,
     ) -> GraphQlResult<bool> {
-        ensure_auth!(ctx, [experimental, admin]);
+        ensure_auth!(ctx, [write_teacher_futures]);
 
         futures::clear_teacher_future_absence(
             ctx,
             start, end, id,
+            id_token,
+        ).await
+    }
+
+    /// Approves a teacher's pending future absences, returning how many days
+    /// were approved. Only secretaries and admins can approve absences.
+    async fn approve_teacher_future_absence(
+        &self,
+        ctx: &Context<'_>,
+        start: NaiveDate,
+        end: Option<NaiveDate>,
+        id: Uuid,
+        #[graphql(desc = "Signed OIDC ID token of the reviewer, if there's no session")] id_token: Option<String>,
+    ) -> GraphQlResult<i32> {
+        ensure_auth!(ctx, [write_teacher_futures]);
+
+        futures::review_teacher_future_absence(
+            ctx,
+            start, end, id,
+            AbsenceRequestStatus::Approved,
+            id_token,
+        ).await
+    }
+
+    /// Rejects a teacher's pending future absences, returning how many days
+    /// were rejected. Only secretaries and admins can reject absences.
+    async fn reject_teacher_future_absence(
+        &self,
+        ctx: &Context<'_>,
+        start: NaiveDate,
+        end: Option<NaiveDate>,
+        id: Uuid,
+        #[graphql(desc = "Signed OIDC ID token of the reviewer, if there's no session")] id_token: Option<String>,
+    ) -> GraphQlResult<i32> {
+        ensure_auth!(ctx, [write_teacher_futures]);
+
+        futures::review_teacher_future_absence(
+            ctx,
+            start, end, id,
+            AbsenceRequestStatus::Rejected,
+            id_token,
         ).await
     }
 
+    /// Sets a future absence that repeats weekly or every few weeks. Only
+    /// secretaries, admins and `experimental` + `admin` clients can set
+    /// recurring absences.
+    #[allow(clippy::too_many_arguments)]
+    async fn add_recurring_teacher_absence(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        periods: Vec<Uuid>,
+        fully_absent: bool,
+        comment: Option<String>,
+        rule: RecurrenceRuleInput,
+        #[graphql(desc = "Signed OIDC ID token of the teacher, if there's no session")] id_token: Option<String>,
+    ) -> GraphQlResult<RecurringAbsence> {
+        ensure_auth!(ctx, [write_teacher_futures]);
+
+        futures::add_recurring_teacher_absence(
+            ctx,
+            id, periods, fully_absent, comment,
+            rule,
+            id_token,
+        ).await
+    }
+
+    /// Teachers can delete their own recurring absences. Secretaries, admins
+    /// and `experimental` + `admin` clients can delete anyone's.
+    async fn delete_recurring_teacher_absence(
+        &self,
+        ctx: &Context<'_>,
+        recurrence_id: Uuid,
+        #[graphql(desc = "Signed OIDC ID token of the teacher, if there's no session")] id_token: Option<String>,
+    ) -> GraphQlResult<bool> {
+        ensure_auth!(ctx, [write_teacher_futures]);
+
+        futures::delete_recurring_teacher_absence(ctx, recurrence_id, id_token).await
+    }
+
+    /// Skips one occurrence of a recurring absence, or brings it back if
+    /// `skipped` is false. Teachers can skip their own occurrences, but only
+    /// secretaries, admins and `experimental` + `admin` clients can bring
+    /// them back.
+    async fn skip_recurring_teacher_absence(
+        &self,
+        ctx: &Context<'_>,
+        recurrence_id: Uuid,
+        date: NaiveDate,
+        #[graphql(default = true)] skipped: bool,
+        #[graphql(desc = "Signed OIDC ID token of the teacher, if there's no session")] id_token: Option<String>,
+    ) -> GraphQlResult<RecurringAbsence> {
+        ensure_auth!(ctx, [write_teacher_futures]);
+
+        futures::skip_recurring_teacher_absence(ctx, recurrence_id, date, skipped, id_token).await
+    }
+
+    /// Creates an iCalendar feed of a teacher's future absences, or the
+    /// staff-wide feed of everyone's without a `teacherId`. Teachers can
+    /// create their own feeds, and only secretaries and admins can create
+    /// anyone else's or staff-wide ones.
+    async fn create_calendar_feed(
+        &self,
+        ctx: &Context<'_>,
+        teacher_id: Option<Uuid>,
+        #[graphql(desc = "Signed OIDC ID token of the teacher, if there's no session")] id_token: Option<String>,
+    ) -> GraphQlResult<CalendarFeedCredentials> {
+        ensure_auth!(ctx, [write_teacher_futures]);
+
+        calendar_feeds::create_calendar_feed(ctx, teacher_id, id_token).await
+    }
+    /// Revokes a calendar feed, so its token stops working.
+    async fn revoke_calendar_feed(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        #[graphql(desc = "Signed OIDC ID token of the teacher, if there's no session")] id_token: Option<String>,
+    ) -> GraphQlResult<bool> {
+        ensure_auth!(ctx, [write_teacher_futures]);
+
+        calendar_feeds::revoke_calendar_feed(ctx, id, id_token).await
+    }
+
+    /// Runs a full day rollover now, exactly like the scheduled one.
     async fn sync_and_flush_futures(
         &self,
         ctx: &Context<'_>,
     ) -> GraphQlResult<bool> {
+        ensure_auth!(ctx, [experimental, admin]);
+
         futures::sync_and_flush_futures(ctx).await
     }
 
+    /// Emails today's absence digest to the `digestRecipients` now, instead
+    /// of waiting for the next day rollover. Returns how many it was sent to.
+    async fn send_absence_digest(
+        &self,
+        ctx: &Context<'_>,
+    ) -> GraphQlResult<i32> {
+        ensure_auth!(ctx, [admin]);
+
+        let Some(mailer) = ctx.data::<crate::state::AppState>()?.digest() else {
+            return Err(async_graphql::Error::new("Absence digests can't be sent, SMTP isn't set up"));
+        };
+
+        let mut db_conn = get_db!(ctx);
+
+        let sent = mailer.send(&mut db_conn).await.map_err(|e| {
+            crate::logging::error!("{} - Failed to send absence digest: {e}", crate::logging::fmt_req_id(req_id(ctx)));
+            async_graphql::Error::new(format!("Failed to send absence digest: {e}"))
+        })?;
+
+        Ok(sent as i32)
+    }
+
     // Global config/settings
     async fn set_spreadsheet_id(
         &self,
@@ -178,18 +407,28 @@ impl MutationRoot {
     }
 
 
-    // async fn delete_teacher(
-    //     ctx: &Context,
-    //     id: TeacherId,
-    // ) -> juniper::FieldResult<bool> {
-    //     let mut db_context_mut = ctx.get_db_mut().await;
+    /// Archives a teacher, hiding them from `allTeachers` until they're
+    /// restored. With `hard`, deletes them and everything about them instead,
+    /// except their absence history, which is kept for reports without them.
+    async fn delete_teacher(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        #[graphql(default = false)] hard: bool,
+    ) -> GraphQlResult<bool> {
+        ensure_auth!(ctx, [delete_teacher]);
 
-    //     delete_teacher
-    //         ::delete_teacher(&mut db_context_mut.client, id)
-    //         .await
-    //         .map_err(IntoFieldError::into_field_error)?;
-    //     Ok(true)
-    // }
+        teacher_management::delete_teacher(ctx, id, hard).await
+    }
+    async fn restore_teacher(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+    ) -> GraphQlResult<Teacher> {
+        ensure_auth!(ctx, [delete_teacher]);
+
+        teacher_management::restore_teacher(ctx, id).await
+    }
 
 
     async fn add_period(
@@ -257,6 +496,136 @@ impl MutationRoot {
         Ok(true)
     }
 
+    async fn add_bell_schedule(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+    ) -> GraphQlResult<BellSchedule> {
+        ensure_auth!(ctx, [write_period_time]);
+
+        bell_schedules::add_bell_schedule(ctx, name).await
+    }
+    async fn update_bell_schedule_name(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        name: String,
+    ) -> GraphQlResult<BellSchedule> {
+        ensure_auth!(ctx, [write_period_time]);
+
+        bell_schedules::update_bell_schedule_name(ctx, id, name).await
+    }
+    /// Deletes a bell schedule. Days that used it go back to the periods'
+    /// default times.
+    async fn delete_bell_schedule(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+    ) -> GraphQlResult<bool> {
+        ensure_auth!(ctx, [write_period_time]);
+
+        bell_schedules::delete_bell_schedule(ctx, id).await
+    }
+    /// Sets a period's time in a bell schedule, adding it to the schedule if
+    /// it isn't already in it.
+    async fn set_bell_schedule_period_time(
+        &self,
+        ctx: &Context<'_>,
+        schedule_id: Uuid,
+        period_id: Uuid,
+        time: TimeRangeInput,
+    ) -> GraphQlResult<bool> {
+        ensure_auth!(ctx, [write_period_time]);
+
+        bell_schedules::set_bell_schedule_period_time(ctx, schedule_id, period_id, time).await
+    }
+    /// Removes a period from a bell schedule, so it keeps its default time on
+    /// days with that schedule.
+    async fn clear_bell_schedule_period_time(
+        &self,
+        ctx: &Context<'_>,
+        schedule_id: Uuid,
+        period_id: Uuid,
+    ) -> GraphQlResult<bool> {
+        ensure_auth!(ctx, [write_period_time]);
+
+        bell_schedules::clear_bell_schedule_period_time(ctx, schedule_id, period_id).await
+    }
+    /// Sets the bell schedule used from `start` to `end` (or just on `start`).
+    /// Without a `scheduleId`, those days go back to the periods' default
+    /// times.
+    async fn set_schedule_days(
+        &self,
+        ctx: &Context<'_>,
+        start: NaiveDate,
+        end: Option<NaiveDate>,
+        schedule_id: Option<Uuid>,
+    ) -> GraphQlResult<bool> {
+        ensure_auth!(ctx, [write_period_time]);
+
+        bell_schedules::set_schedule_days(ctx, start, end.unwrap_or(start), schedule_id).await
+    }
+
+    /// Marks every day from `start` to `end` (or just `start`) as an
+    /// `INSTRUCTIONAL` day, a `HOLIDAY` or a `BREAK`. Without a `kind`, those
+    /// days go back to the default, where only weekdays are school days.
+    /// Ranges can be up to 366 days long.
+    async fn set_calendar_days(
+        &self,
+        ctx: &Context<'_>,
+        start: NaiveDate,
+        end: Option<NaiveDate>,
+        kind: Option<CalendarDayKind>,
+        name: Option<String>,
+    ) -> GraphQlResult<bool> {
+        ensure_auth!(ctx, [write_config]);
+
+        calendar::set_calendar_days(ctx, start, end.unwrap_or(start), kind, name).await
+    }
+
+    async fn add_room(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+    ) -> GraphQlResult<Room> {
+        ensure_auth!(ctx, [write_config]);
+
+        rooms::add_room(ctx, name).await
+    }
+    async fn update_room_name(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        name: String,
+    ) -> GraphQlResult<Room> {
+        ensure_auth!(ctx, [write_config]);
+
+        rooms::update_room_name(ctx, id, name).await
+    }
+    async fn delete_room(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+    ) -> GraphQlResult<bool> {
+        ensure_auth!(ctx, [write_config]);
+
+        rooms::delete_room(ctx, id).await
+    }
+
+    /// Sets the room a teacher's class reports to in a period when they're
+    /// absent. Without a `roomId`, the class reports to the global `reportTo`.
+    async fn set_room_assignment(
+        &self,
+        ctx: &Context<'_>,
+        teacher_id: Uuid,
+        period_id: Uuid,
+        room_id: Option<Uuid>,
+    ) -> GraphQlResult<bool> {
+        ensure_auth!(ctx, [write_config]);
+
+        rooms::set_room_assignment(ctx, teacher_id, period_id, room_id).await
+    }
+
     async fn clear_metrics(
         &self,
         ctx: &Context<'_>,
@@ -276,18 +645,46 @@ impl MutationRoot {
         attribs::AttribMutationRoot
     }
 
-    // async fn delete_period(
-    //     ctx: &Context,
-    //     id: PeriodId,
-    // ) -> juniper::FieldResult<bool> {
-    //     let mut db_context_mut = ctx.get_db_mut().await;
+    async fn clients(
+        &self,
+        ctx: &Context<'_>,
+    ) -> GraphQlResult<clients::ClientMutationRoot> {
+        ensure_auth!(ctx, [admin]);
 
-    //     delete_period
-    //         ::delete_period(&mut db_context_mut.client, id)
-    //         .await
-    //         .map_err(IntoFieldError::into_field_error)?;
-    //     Ok(true)
-    // }
+        Ok(clients::ClientMutationRoot)
+    }
+
+    async fn webhooks(
+        &self,
+        ctx: &Context<'_>,
+    ) -> GraphQlResult<webhooks::WebhookMutationRoot> {
+        ensure_auth!(ctx, [admin]);
+
+        Ok(webhooks::WebhookMutationRoot)
+    }
+
+    /// Archives a period, hiding it from `allPeriods` until it's restored.
+    /// With `hard`, deletes it and takes it out of every future absence
+    /// instead.
+    async fn delete_period(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        #[graphql(default = false)] hard: bool,
+    ) -> GraphQlResult<bool> {
+        ensure_auth!(ctx, [delete_period]);
+
+        period_management::delete_period(ctx, id, hard).await
+    }
+    async fn restore_period(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+    ) -> GraphQlResult<Period> {
+        ensure_auth!(ctx, [delete_period]);
+
+        period_management::restore_period(ctx, id).await
+    }
 
     // async fn clear_absences(
     //     ctx: &Context,
@@ -313,3 +710,20 @@ impl MutationRoot {
     //     Ok(true)
     // }
 }
+
+/// Sets a teacher's absences for today and queues the `absence_set` webhooks
+/// for them in one transaction.
+async fn update_absences_and_notify(
+    db_conn: &mut Ctx,
+    origin: &AuditOrigin,
+    id: Uuid,
+    periods: Vec<Uuid>,
+    fully_absent: bool,
+) -> Result<(), sqlx::Error> {
+    use crate::database::prepared::absences::update_absences_for_teacher;
+
+    audited(db_conn, origin, AuditTarget::Teacher(id), move |txn| Box::pin(async move {
+        update_absences_for_teacher(txn, id, &periods, fully_absent, None).await?;
+        crate::webhooks::notify_absence_set(txn, id, "api").await
+    })).await
+}
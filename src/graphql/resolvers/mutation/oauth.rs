@@ -2,10 +2,11 @@ use async_graphql::Context;
 use uuid::Uuid;
 
 
-use crate::graphql::resolvers::{get_db, run_query};
+use crate::database::prepared::audit_log::audited;
+use crate::graphql::resolvers::{get_db, run_query, audit_origin};
 use crate::graphql::req_id;
 
-use crate::types::Teacher;
+use crate::types::{ AuditTarget, SessionCredentials, Teacher };
 
 use async_graphql::Result as GraphQlResult;
 
@@ -19,9 +20,11 @@ pub async fn add_teacher_associated_oauth(
     use crate::database::prepared::teacher::get_teacher as get_teacher_from_db;
 
     let mut db_conn = get_db!(ctx);
+    let origin = audit_origin(ctx);
 
+    let oauth_provider = provider.clone();
     run_query!(
-        db_conn.add_teacher_associated_oauth_in_db(id, provider.clone(), sub)
+        db_conn.audited(&origin, AuditTarget::Teacher(id), |txn| Box::pin(add_teacher_associated_oauth_in_db(txn, id, oauth_provider, sub)))
         else (req_id(ctx)) "Failed to add {provider} oauth for teacher {id}: {}"
     )?;
     run_query!(
@@ -39,9 +42,11 @@ pub async fn remove_teacher_associated_oauth(
     use crate::database::prepared::teacher::get_teacher as get_teacher_from_db;
 
     let mut db_conn = get_db!(ctx);
+    let origin = audit_origin(ctx);
 
+    let oauth_provider = provider.clone();
     run_query!(
-        db_conn.remove_teacher_associated_oauth_in_db(id, provider.clone())
+        db_conn.audited(&origin, AuditTarget::Teacher(id), |txn| Box::pin(remove_teacher_associated_oauth_in_db(txn, id, oauth_provider)))
         else (req_id(ctx)) "Failed to remove {provider} oauth for teacher {id}: {}"
     )?;
     run_query!(
@@ -49,3 +54,27 @@ pub async fn remove_teacher_associated_oauth(
         else (req_id(ctx)) "Failed to refetch updated teacher {id}: {}"
     )
 }
+
+
+pub async fn login(
+    ctx: &Context<'_>,
+    id_token: String,
+) -> GraphQlResult<SessionCredentials> {
+    use crate::database::prepared::privileges::get_privileges as get_privileges_from_db;
+    use crate::graphql::resolvers::teacher_from_id_token;
+
+    let mut db_conn = get_db!(ctx);
+
+    let teacher = teacher_from_id_token(ctx, &mut db_conn, &id_token).await?;
+    let privileges = run_query!(
+        db_conn.get_privileges_from_db(teacher.get_id())
+        else (req_id(ctx)) "Failed to get permissions for teacher: {}"
+    )?;
+
+    let Some((token, session)) = ctx.data::<crate::state::AppState>()?.sessions().issue(teacher.get_id(), privileges) else {
+        crate::logging::error!("{} - Failed to sign session token", crate::logs_env::logging::fmt_req_id(req_id(ctx)));
+        return Err(async_graphql::Error::new("Failed to create session"));
+    };
+
+    Ok(SessionCredentials { token, session })
+}
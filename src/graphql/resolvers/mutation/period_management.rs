@@ -2,11 +2,13 @@ use async_graphql::Context;
 use uuid::Uuid;
 
 
-use crate::graphql::resolvers::{get_db, run_query};
+use crate::database::prepared::audit_log::audited;
+use crate::events::Event;
+use crate::graphql::resolvers::{get_db, run_query, publish_event, audit_origin};
 use crate::graphql::req_id;
 
 use crate::graphql::structs::TimeRangeInput;
-use crate::types::Period;
+use crate::types::{ AuditTarget, Period };
 
 use async_graphql::Result as GraphQlResult;
 
@@ -35,9 +37,12 @@ pub async fn update_period_name(
     use crate::database::prepared::period::update_period_name as update_period_name_in_db;
 
     let mut db_conn = get_db!(ctx);
+    let origin = audit_origin(ctx);
 
     run_query!(
-        db_conn.update_period_name_in_db(id, &name)
+        db_conn.audited(&origin, AuditTarget::Period(id), move |txn| Box::pin(async move {
+            update_period_name_in_db(txn, id, &name).await
+        }))
         else (req_id(ctx)) "Database error: {}"
     )
 }
@@ -51,11 +56,15 @@ pub async fn update_period_time(
     use crate::database::prepared::period::update_period_time as update_period_time_in_db;
 
     let mut db_conn = get_db!(ctx);
+    let origin = audit_origin(ctx);
 
-    run_query!(
-        db_conn.update_period_time_in_db(id, [time.start, time.end])
+    let period = run_query!(
+        db_conn.audited(&origin, AuditTarget::Period(id), |txn| Box::pin(update_period_time_in_db(txn, id, [time.start, time.end])))
         else (req_id(ctx)) "Failed to get : {}"
-    )
+    )?;
+
+    publish_event(ctx, Event::PeriodTimeChanged(period.clone()));
+    Ok(period)
 }
 pub async fn set_period_temp_time(
     ctx: &Context<'_>,
@@ -66,11 +75,15 @@ pub async fn set_period_temp_time(
     use crate::database::prepared::period::set_period_temp_time as set_period_temp_time_in_db;
 
     let mut db_conn = get_db!(ctx);
+    let origin = audit_origin(ctx);
 
-    run_query!(
-        db_conn.set_period_temp_time_in_db(id, [temp_time.start, temp_time.end])
+    let period = run_query!(
+        db_conn.audited(&origin, AuditTarget::Period(id), |txn| Box::pin(set_period_temp_time_in_db(txn, id, [temp_time.start, temp_time.end])))
         else (req_id(ctx)) "Database error: {}"
-    )
+    )?;
+
+    publish_event(ctx, Event::PeriodTimeChanged(period.clone()));
+    Ok(period)
 }
 pub async fn clear_period_temp_time(
     ctx: &Context<'_>,
@@ -80,23 +93,107 @@ pub async fn clear_period_temp_time(
     use crate::database::prepared::period::clear_period_temp_time as clear_period_temp_time_in_db;
 
     let mut db_conn = get_db!(ctx);
+    let origin = audit_origin(ctx);
 
-    run_query!(
-        db_conn.clear_period_temp_time_in_db(id)
+    let period = run_query!(
+        db_conn.audited(&origin, AuditTarget::Period(id), |txn| Box::pin(clear_period_temp_time_in_db(txn, id)))
         else (req_id(ctx)) "Database error: {}"
-    )
+    )?;
+
+    publish_event(ctx, Event::PeriodTimeChanged(period.clone()));
+    Ok(period)
 }
 pub async fn clear_all_temp_times(
     ctx: &Context<'_>,
 ) -> GraphQlResult<()> {
-    use crate::database::prepared::period::flush_all_temp_times as clear_all_temp_times_in_db;
+    use crate::database::prepared::period::{
+        flush_all_temp_times as clear_all_temp_times_in_db,
+        get_all_periods as get_all_periods_from_db,
+    };
 
     let mut db_conn = get_db!(ctx);
+    let origin = audit_origin(ctx);
 
     run_query!(
-        db_conn.clear_all_temp_times_in_db()
+        db_conn.audited(&origin, AuditTarget::AllPeriods, |txn| Box::pin(clear_all_temp_times_in_db(txn)))
         else (req_id(ctx)) "Database error: {}"
-    )
+    )?;
+
+    let periods = run_query!(
+        db_conn.get_all_periods_from_db()
+        else (req_id(ctx)) "Failed to refetch cleared periods: {}"
+    )?;
+    for period in periods {
+        publish_event(ctx, Event::PeriodTimeChanged(period));
+    }
+    Ok(())
 }
 
+/// Archives a period, or deletes it for good if `hard` is set.
+pub async fn delete_period(
+    ctx: &Context<'_>,
 
+    id: Uuid,
+    hard: bool,
+) -> GraphQlResult<bool> {
+    use crate::database::prepared::absences::get_all_absences_for_period as get_all_absences_for_period_from_db;
+    use crate::database::prepared::period::{
+        archive_period as archive_period_in_db,
+        delete_period as delete_period_from_db,
+    };
+    use crate::database::prepared::teacher::get_teacher as get_teacher_from_db;
+
+    let mut db_conn = get_db!(ctx);
+    let origin = audit_origin(ctx);
+
+    // Teachers absent in the period lose that absence either way
+    let absences = run_query!(
+        db_conn.get_all_absences_for_period_from_db(id)
+        else (req_id(ctx)) "Database error: {}"
+    )?;
+
+    let deleted = if hard {
+        let deleted = run_query!(
+            db_conn.audited(&origin, AuditTarget::Period(id), |txn| Box::pin(delete_period_from_db(txn, id)))
+            else (req_id(ctx)) "Database error: {}"
+        )?;
+        if deleted {
+            publish_event(ctx, Event::PeriodDeleted(id));
+        }
+        deleted
+    } else {
+        let period = run_query!(
+            db_conn.audited(&origin, AuditTarget::Period(id), |txn| Box::pin(archive_period_in_db(txn, id)))
+            else (req_id(ctx)) "Database error: {}"
+        )?;
+        publish_event(ctx, Event::PeriodTimeChanged(period));
+        true
+    };
+
+    for absence in absences {
+        let teacher = run_query!(
+            db_conn.get_teacher_from_db(absence.teacher)
+            else (req_id(ctx)) "Failed to refetch teacher {}: {}", absence.teacher
+        )?;
+        publish_event(ctx, Event::TeacherAbsenceChanged(teacher));
+    }
+    Ok(deleted)
+}
+pub async fn restore_period(
+    ctx: &Context<'_>,
+
+    id: Uuid,
+) -> GraphQlResult<Period> {
+    use crate::database::prepared::period::restore_period as restore_period_in_db;
+
+    let mut db_conn = get_db!(ctx);
+    let origin = audit_origin(ctx);
+
+    let period = run_query!(
+        db_conn.audited(&origin, AuditTarget::Period(id), |txn| Box::pin(restore_period_in_db(txn, id)))
+        else (req_id(ctx)) "Database error: {}"
+    )?;
+
+    publish_event(ctx, Event::PeriodTimeChanged(period.clone()));
+    Ok(period)
+}
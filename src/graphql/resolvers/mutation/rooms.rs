@@ -0,0 +1,83 @@
+use async_graphql::Context;
+use uuid::Uuid;
+
+
+use crate::events::{Event, ConfigField};
+use crate::graphql::resolvers::{get_db, run_query, publish_event};
+use crate::graphql::req_id;
+use crate::types::Room;
+
+use async_graphql::Result as GraphQlResult;
+
+pub async fn add_room(
+    ctx: &Context<'_>,
+    name: String,
+) -> GraphQlResult<Room> {
+    use crate::database::prepared::rooms::add_room as add_room_in_db;
+
+    let mut db_conn = get_db!(ctx);
+
+    let room = run_query!(
+        db_conn.add_room_in_db(&name)
+        else (req_id(ctx)) "Failed to add room {name}: {}"
+    )?;
+
+    publish_event(ctx, Event::ConfigChanged(ConfigField::Rooms));
+    Ok(room)
+}
+
+pub async fn update_room_name(
+    ctx: &Context<'_>,
+    id: Uuid,
+    name: String,
+) -> GraphQlResult<Room> {
+    use crate::database::prepared::rooms::update_room_name as update_room_name_in_db;
+
+    let mut db_conn = get_db!(ctx);
+
+    let room = run_query!(
+        db_conn.update_room_name_in_db(id, &name)
+        else (req_id(ctx)) "Failed to rename room {id}: {}"
+    )?;
+
+    publish_event(ctx, Event::ConfigChanged(ConfigField::Rooms));
+    Ok(room)
+}
+
+pub async fn delete_room(
+    ctx: &Context<'_>,
+    id: Uuid,
+) -> GraphQlResult<bool> {
+    use crate::database::prepared::rooms::delete_room as delete_room_in_db;
+
+    let mut db_conn = get_db!(ctx);
+
+    let deleted = run_query!(
+        db_conn.delete_room_in_db(id)
+        else (req_id(ctx)) "Failed to delete room {id}: {}"
+    )?;
+
+    if deleted {
+        publish_event(ctx, Event::ConfigChanged(ConfigField::Rooms));
+    }
+    Ok(deleted)
+}
+
+pub async fn set_room_assignment(
+    ctx: &Context<'_>,
+    teacher_id: Uuid,
+    period_id: Uuid,
+    room_id: Option<Uuid>,
+) -> GraphQlResult<bool> {
+    use crate::database::prepared::rooms::set_room_assignment as set_room_assignment_in_db;
+
+    let mut db_conn = get_db!(ctx);
+
+    run_query!(
+        db_conn.set_room_assignment_in_db(teacher_id, period_id, room_id)
+        else (req_id(ctx)) "Failed to set room of teacher {teacher_id} in period {period_id}: {}"
+    )?;
+
+    publish_event(ctx, Event::ConfigChanged(ConfigField::Rooms));
+    Ok(true)
+}
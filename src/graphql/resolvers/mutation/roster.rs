@@ -0,0 +1,34 @@
+use async_graphql::Context;
+
+
+use crate::graphql::resolvers::{get_db, run_query, audit_origin};
+use crate::graphql::req_id;
+use crate::types::{ RosterFormat, RosterImportReport };
+
+use async_graphql::Result as GraphQlResult;
+
+/// Imports a roster of teachers. If any row can't be parsed, the rest are
+/// still checked against the database but nothing is applied, so every
+/// problem can be fixed at once.
+pub async fn import_teachers(
+    ctx: &Context<'_>,
+    data: String,
+    format: RosterFormat,
+    dry_run: bool,
+) -> GraphQlResult<RosterImportReport> {
+    use crate::database::prepared::roster::import_roster as import_roster_in_db;
+
+    let (entries, parse_errors) = crate::roster::parse(format, &data);
+
+    let mut db_conn = get_db!(ctx);
+    let origin = audit_origin(ctx);
+
+    let mut report = run_query!(
+        db_conn.import_roster_in_db(&origin, entries, parse_errors, dry_run)
+        else (req_id(ctx)) "Failed to import roster: {}"
+    )?;
+
+    report.errors.sort_by_key(|error| error.row);
+
+    Ok(report)
+}
@@ -2,11 +2,13 @@ use async_graphql::Context;
 use uuid::Uuid;
 
 
-use crate::graphql::resolvers::{get_db, run_query};
+use crate::database::prepared::audit_log::audited;
+use crate::events::Event;
+use crate::graphql::resolvers::{get_db, run_query, publish_event, audit_origin};
 use crate::graphql::req_id;
 
 use crate::graphql::structs::{GraphQlTeacherName, GraphQlPronounSet};
-use crate::types::Teacher;
+use crate::types::{ AuditTarget, Teacher };
 
 use async_graphql::Result as GraphQlResult;
 
@@ -25,9 +27,10 @@ pub async fn add_teacher(
         pronouns.into(),
     );
     let teacher_id = teacher.get_id();
+    let origin = audit_origin(ctx);
 
     run_query!(
-        db_conn.add_teacher_to_db(teacher)
+        db_conn.audited(&origin, AuditTarget::Teacher(teacher_id), |txn| Box::pin(add_teacher_to_db(txn, teacher)))
         else (req_id(ctx)) "Failed to add teacher under ID {teacher_id}: {}"
     )
 }
@@ -41,9 +44,10 @@ pub async fn update_teacher_name(
     use crate::database::prepared::teacher::update_teacher_name as update_teacher_name_in_db;
 
     let mut db_conn = get_db!(ctx);
+    let origin = audit_origin(ctx);
 
     run_query!(
-        db_conn.update_teacher_name_in_db(id, name.into())
+        db_conn.audited(&origin, AuditTarget::Teacher(id), |txn| Box::pin(update_teacher_name_in_db(txn, id, name.into())))
         else (req_id(ctx)) "Failed to update name of teacher {id}: {}"
     )
 }
@@ -56,9 +60,64 @@ pub async fn update_teacher_pronouns(
     use crate::database::prepared::teacher::update_teacher_pronouns as update_teacher_pronouns_in_db;
 
     let mut db_conn = get_db!(ctx);
+    let origin = audit_origin(ctx);
 
     run_query!(
-        db_conn.update_teacher_pronouns_in_db(id, pronouns.into())
+        db_conn.audited(&origin, AuditTarget::Teacher(id), |txn| Box::pin(update_teacher_pronouns_in_db(txn, id, pronouns.into())))
         else (req_id(ctx)) "Failed to update pronouns of teacher {id}: {}"
     )
 }
+
+/// Archives a teacher, or deletes them for good if `hard` is set. Deleting
+/// them detaches their absence history in the same transaction, so it's kept
+/// for reports.
+pub async fn delete_teacher(
+    ctx: &Context<'_>,
+    id: Uuid,
+    hard: bool,
+) -> GraphQlResult<bool> {
+    use crate::database::prepared::teacher::{
+        archive_teacher as archive_teacher_in_db,
+        delete_teacher as delete_teacher_from_db,
+    };
+
+    let mut db_conn = get_db!(ctx);
+    let origin = audit_origin(ctx);
+
+    if hard {
+        let deleted = run_query!(
+            db_conn.audited(&origin, AuditTarget::Teacher(id), |txn| Box::pin(delete_teacher_from_db(txn, id)))
+            else (req_id(ctx)) "Failed to delete teacher {id}: {}"
+        )?;
+        if deleted {
+            publish_event(ctx, Event::TeacherDeleted(id));
+        }
+        return Ok(deleted);
+    }
+
+    let teacher = run_query!(
+        db_conn.audited(&origin, AuditTarget::Teacher(id), |txn| Box::pin(archive_teacher_in_db(txn, id)))
+        else (req_id(ctx)) "Failed to archive teacher {id}: {}"
+    )?;
+
+    publish_event(ctx, Event::TeacherAbsenceChanged(teacher));
+    Ok(true)
+}
+
+pub async fn restore_teacher(
+    ctx: &Context<'_>,
+    id: Uuid,
+) -> GraphQlResult<Teacher> {
+    use crate::database::prepared::teacher::restore_teacher as restore_teacher_in_db;
+
+    let mut db_conn = get_db!(ctx);
+    let origin = audit_origin(ctx);
+
+    let teacher = run_query!(
+        db_conn.audited(&origin, AuditTarget::Teacher(id), |txn| Box::pin(restore_teacher_in_db(txn, id)))
+        else (req_id(ctx)) "Failed to restore teacher {id}: {}"
+    )?;
+
+    publish_event(ctx, Event::TeacherAbsenceChanged(teacher.clone()));
+    Ok(teacher)
+}
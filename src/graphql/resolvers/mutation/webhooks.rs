@@ -0,0 +1,145 @@
+use async_graphql::{ Context, Error as GraphQlError, Result as GraphQlResult };
+use uuid::Uuid;
+
+use crate::graphql::resolvers::{ get_db, run_query };
+use crate::graphql::req_id;
+use crate::types::{ Webhook, WebhookCredentials, WebhookDelivery, WebhookEvent };
+
+
+/// Every webhook mutation. Access to this is gated behind the `admin` scope in
+/// [`MutationRoot::webhooks`][super::MutationRoot].
+#[derive(Debug, Clone)]
+pub struct WebhookMutationRoot;
+
+#[async_graphql::Object]
+impl WebhookMutationRoot {
+    /// Register an endpoint to be sent `events`, with a freshly generated
+    /// signing secret.
+    ///
+    /// The secret is only ever returned here, so it must be saved right away.
+    async fn create(
+        &self,
+        ctx: &Context<'_>,
+        url: String,
+        events: Vec<WebhookEvent>,
+        #[graphql(default)] description: String,
+    ) -> GraphQlResult<WebhookCredentials> {
+        use crate::database::prepared::webhooks::create_webhook as create_webhook_in_db;
+
+        validate_url(&url)?;
+        let events = validate_events(events)?;
+        let secret = crate::webhooks::new_secret();
+
+        let mut db_conn = get_db!(ctx);
+
+        let webhook = run_query!(
+            db_conn.create_webhook_in_db(&url, &description, &events, &secret)
+            else (req_id(ctx)) "Failed to create webhook: {}"
+        )?;
+
+        Ok(WebhookCredentials { webhook, secret })
+    }
+
+    /// Change the parts of a webhook that are set, leaving the rest as is.
+    async fn update(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        url: Option<String>,
+        events: Option<Vec<WebhookEvent>>,
+        description: Option<String>,
+        active: Option<bool>,
+    ) -> GraphQlResult<Webhook> {
+        use crate::database::prepared::webhooks::update_webhook as update_webhook_in_db;
+
+        if let Some(url) = &url {
+            validate_url(url)?;
+        }
+        let events = events.map(validate_events).transpose()?;
+
+        let mut db_conn = get_db!(ctx);
+
+        run_query!(
+            db_conn.update_webhook_in_db(id, url.as_deref(), description.as_deref(), events.as_deref(), active)
+            else (req_id(ctx)) "Failed to update webhook {id}: {}"
+        )
+    }
+
+    /// Replace a webhook's signing secret with a freshly generated one.
+    /// Deliveries are signed with the new secret immediately, including
+    /// retries of earlier ones.
+    async fn rotate_secret(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+    ) -> GraphQlResult<WebhookCredentials> {
+        use crate::database::prepared::webhooks::set_webhook_secret as set_webhook_secret_in_db;
+
+        let secret = crate::webhooks::new_secret();
+
+        let mut db_conn = get_db!(ctx);
+
+        let webhook = run_query!(
+            db_conn.set_webhook_secret_in_db(id, &secret)
+            else (req_id(ctx)) "Failed to rotate secret for webhook {id}: {}"
+        )?;
+
+        Ok(WebhookCredentials { webhook, secret })
+    }
+
+    /// Delete a webhook along with its pending deliveries. Returns false if
+    /// it didn't exist.
+    async fn delete(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+    ) -> GraphQlResult<bool> {
+        use crate::database::prepared::webhooks::delete_webhook as delete_webhook_in_db;
+
+        let mut db_conn = get_db!(ctx);
+
+        run_query!(
+            db_conn.delete_webhook_in_db(id)
+            else (req_id(ctx)) "Failed to delete webhook {id}: {}"
+        )
+    }
+
+    /// Send a delivery again right away, with a fresh set of attempts, even
+    /// if it was given up on or already delivered.
+    async fn retry_delivery(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+    ) -> GraphQlResult<WebhookDelivery> {
+        use crate::database::prepared::webhooks::retry_webhook_delivery as retry_webhook_delivery_in_db;
+
+        let mut db_conn = get_db!(ctx);
+
+        run_query!(
+            db_conn.retry_webhook_delivery_in_db(id)
+            else (req_id(ctx)) "Failed to retry webhook delivery {id}: {}"
+        )
+    }
+}
+
+
+fn validate_url(url: &str) -> GraphQlResult<()> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| GraphQlError::new(format!("Invalid webhook url: {e}")))?;
+
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(GraphQlError::new("Webhook urls must be http or https"));
+    }
+
+    Ok(())
+}
+
+fn validate_events(mut events: Vec<WebhookEvent>) -> GraphQlResult<Vec<WebhookEvent>> {
+    if events.is_empty() {
+        return Err(GraphQlError::new("Webhooks need at least one event"));
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    events.retain(|event| seen.insert(*event));
+
+    Ok(events)
+}
@@ -9,12 +9,12 @@ use uuid::Uuid;
 
 
 use crate::graphql::req_id;
-use crate::types::{Period, Teacher};
+use crate::types::{Period, Teacher, AbsentPeriod, AbsentTeacher, Coverage};
 use crate::database::Ctx;
 use crate::logging::*;
 
 use super::TimeRange;
-use super::{ get_db, ensure_auth };
+use super::{ get_db, run_query, ensure_auth };
 
 #[Object]
 impl Period {
@@ -24,9 +24,23 @@ impl Period {
     async fn default_time_range(&self) -> TimeRange {
         (self.start, self.end).into()
     }
+    /// The period's time today: its temporary time if it has one, otherwise
+    /// its time in today's bell schedule, otherwise its default time.
     async fn time_range(&self) -> TimeRange {
-        (self.temp_start.unwrap_or(self.start), self.temp_end.unwrap_or(self.end)).into()
+        let (start, end) = match (self.temp_start, self.temp_end, self.schedule_start, self.schedule_end) {
+            (Some(start), Some(end), _, _) => (start, end),
+            (_, _, Some(start), Some(end)) => (start, end),
+            _ => (self.start, self.end),
+        };
+        (start, end).into()
     }
+    /// The period's time in today's bell schedule, if today has one that
+    /// includes this period.
+    async fn schedule_time_range(&self) -> Option<TimeRange> {
+        Some((self.schedule_start?, self.schedule_end?).into())
+    }
+    /// Whether the period was soft deleted with `deletePeriod`.
+    async fn archived(&self) -> bool { self.archived }
 
 
     // Assuming during every period, 1/10 of the teachers are out (way
@@ -38,7 +52,7 @@ impl Period {
     async fn teachers_absent(
         &self,
         ctx: &Context<'_>,
-    ) -> GraphQlResult<Vec<Teacher>> {
+    ) -> GraphQlResult<Vec<AbsentTeacher>> {
         ensure_auth!(ctx, [read_teacher, read_teacher_absence]);
 
         let mut db_conn = get_db!(ctx);
@@ -52,13 +66,56 @@ impl Period {
                 GraphQlError::new(format!("Failed to get absent teacher ids from database {e}"))
             })?;
 
-        ids.get_teachers(req_id, &mut db_conn)
+        let teachers = ids.get_teachers(req_id, &mut db_conn)
             .await
             .map_err(|e| {
                 let e = e.to_string();
                 error!("{} - Failed to get absent teacher data from database {e}", fmt_req_id(req_id));
                 GraphQlError::new(format!("Failed to get absent teachers from database {e}"))
-            })
+            })?;
+
+        let period_id = self.id;
+        Ok(teachers.into_iter().map(|teacher| AbsentTeacher { teacher, period_id }).collect())
+    }
+}
+
+
+/// A period in `Teacher.absence`, which has the same fields as a `Period` along
+/// with the substitute covering that teacher.
+#[Object]
+impl AbsentPeriod {
+    async fn id(&self) -> Uuid { self.period.id }
+    async fn name(&self) -> &str { &self.period.name }
+
+    async fn default_time_range(&self, ctx: &Context<'_>) -> GraphQlResult<TimeRange> {
+        self.period.default_time_range(ctx).await
+    }
+    async fn time_range(&self, ctx: &Context<'_>) -> GraphQlResult<TimeRange> {
+        self.period.time_range(ctx).await
+    }
+    async fn schedule_time_range(&self, ctx: &Context<'_>) -> GraphQlResult<Option<TimeRange>> {
+        self.period.schedule_time_range(ctx).await
+    }
+    async fn archived(&self) -> bool { self.period.archived }
+
+    #[graphql(complexity = 5 + 10 * child_complexity)]
+    async fn teachers_absent(&self, ctx: &Context<'_>) -> GraphQlResult<Vec<AbsentTeacher>> {
+        self.period.teachers_absent(ctx).await?
+    }
+
+    /// Today's substitute for the teacher in this period, if they have one.
+    async fn covered_by(&self, ctx: &Context<'_>) -> GraphQlResult<Option<Coverage>> {
+        use crate::database::prepared::coverage::get_coverage;
+
+        ensure_auth!(ctx, [read_teacher_absence, read_period]);
+
+        let mut db_conn = get_db!(ctx);
+        let (teacher, period) = (self.teacher_id, self.period.id);
+
+        run_query!(
+            db_conn.get_coverage(teacher, period)
+            else (req_id(ctx)) "Failed to get coverage of teacher {teacher} in period {period} from database: {}"
+        )
     }
 }
 
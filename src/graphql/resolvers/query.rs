@@ -5,17 +5,31 @@
 // mod all_teachers;
 // mod all_periods;
 
-use crate::database::prepared::privileges::get_privileges;
-
 use crate::graphql::req_id;
 use crate::metrics::SparseMetricsView;
 use crate::types::Privileges;
 use crate::types::Teacher;
 use crate::types::Period;
 use crate::types::PackedAbsenceState;
+use crate::types::{ AbsenceRequest, AbsenceRequestStatus };
+use crate::types::RecurringAbsence;
+use crate::types::CalendarFeed;
+use crate::types::RosterFormat;
+use crate::types::RolloverRun;
+use crate::types::{ SheetSyncDirection, SheetSyncRun };
+use crate::types::Reports;
+use crate::types::AbsenceDigest;
+use crate::types::AuditLogPage;
+use crate::graphql::structs::{ AuditLogFilter, PageInput };
+use crate::types::Client;
+use crate::types::{ Webhook, WebhookDelivery };
+use crate::types::Room;
+use crate::types::{ BellSchedule, ScheduleDay };
+use crate::types::CalendarDay;
+use crate::types::Session;
 use crate::types::TeacherAbsenceStateList;
 
-use super::{ get_db, run_query, ensure_auth };
+use super::{ get_db, run_query, ensure_auth, current_teacher, current_privileges, ensure_acting_for, ensure_staff, ensure_date_span };
 
 use async_graphql::{
     Object,
@@ -66,14 +80,15 @@ impl QueryRoot {
     async fn all_teachers(
         &self,
         ctx: &Context<'_>,
+        #[graphql(default = false, desc = "Include teachers that were soft deleted")] include_archived: bool,
     ) -> GraphQlResult<Vec<Teacher>> {
-        use crate::database::prepared::teacher::get_all_teachers as get_all_teachers_from_db;        
+        use crate::database::prepared::teacher::get_teachers as get_teachers_from_db;        
 
         ensure_auth!(ctx, [read_teacher]);
 
         let mut db_conn = get_db!(ctx);
 
-        get_all_teachers_from_db(&mut db_conn)
+        get_teachers_from_db(&mut db_conn, include_archived)
             .await
             .map_err(|e| {
                 if matches!(e, sqlx::Error::RowNotFound) {
@@ -88,44 +103,34 @@ impl QueryRoot {
     async fn get_teacher_by_oauth(
         &self,
         ctx: &Context<'_>,
-        #[graphql(desc = "Provider of OAuth")] provider: String,
-        #[graphql(desc = "Sub of OAuth")] sub: String,
+        #[graphql(desc = "Signed OIDC ID token of the teacher, if there's no session")] id_token: Option<String>,
     ) -> GraphQlResult<Teacher> {
-        use crate::database::prepared::teacher::get_teacher_by_oauth as get_teacher_by_oauth_from_db;
-
         ensure_auth!(ctx, [read_teacher, admin, experimental]);
 
         let mut db_conn = get_db!(ctx);
 
-        run_query!(
-            db_conn.get_teacher_by_oauth_from_db(provider, sub)
-            else (req_id(ctx)) "Failed to get teacher from database: {}"
-        )
+        current_teacher(ctx, &mut db_conn, id_token.as_deref()).await
     }
 
+    /// The approved future absences of a teacher. See `absenceRequests` for
+    /// pending, rejected and cancelled ones. Ranges can be up to 366 days
+    /// long.
     async fn get_teacher_futures(
         &self,
         ctx: &Context<'_>,
         id: Uuid,
         start: NaiveDate,
         end: NaiveDate,
-        #[graphql(desc = "Provider of OAuth")] provider: String,
-        #[graphql(desc = "Sub of OAuth")] sub: String,
+        #[graphql(desc = "Signed OIDC ID token of the teacher, if there's no session")] id_token: Option<String>,
     ) -> GraphQlResult<Vec<PackedAbsenceState>> {
         use crate::database::prepared::future_absences::get_future_days_for_teacher as get_futures_from_db;
-        use crate::database::prepared::teacher::check_teacher_oauth as check_oauth_db;
 
-        ensure_auth!(ctx, [read_teacher, admin, experimental]);
+        ensure_auth!(ctx, [read_teacher]);
+        ensure_date_span(start, end)?;
 
         let mut db_conn = get_db!(ctx);
 
-        let oauth_res = run_query!(
-            db_conn.check_oauth_db(id, provider, sub)
-            else (req_id(ctx)) "Not permitted to access this resource {:.0}"
-        )?;
-        if !oauth_res {
-            return Err(GraphQlError::new("Not permitted to access this resource"));
-        }
+        ensure_acting_for(ctx, &mut db_conn, id, id_token.as_deref()).await?;
 
         run_query!(
             db_conn.get_futures_from_db(id, start, end)
@@ -137,24 +142,17 @@ impl QueryRoot {
         ctx: &Context<'_>,
         start: NaiveDate,
         end: NaiveDate,
-        #[graphql(desc = "Provider of OAuth")] provider: String,
-        #[graphql(desc = "Sub of OAuth")] sub: String,
+        #[graphql(desc = "Signed OIDC ID token of the teacher, if there's no session")] id_token: Option<String>,
     ) -> GraphQlResult<Vec<TeacherAbsenceStateList>> {
         use crate::database::prepared::future_absences::get_all_future_days as get_all_futures_from_db;
-        use crate::database::prepared::teacher::get_teacher_by_oauth as get_teacher_db;
 
         ensure_auth!(ctx, [read_teacher, admin, experimental]);
+        ensure_date_span(start, end)?;
 
         let mut db_conn = get_db!(ctx);
 
-        let teacher = run_query!(
-            db_conn.get_teacher_db(provider.clone(), sub.clone())
-            else (req_id(ctx)) "This oauth user doesn't exist {:.0}"
-        )?;
-        let teacher_perms = run_query!(
-            db_conn.get_privileges(teacher.get_id())
-            else (req_id(ctx)) "Not permitted to access this resource {:.0}"
-        )?;
+        let teacher = current_teacher(ctx, &mut db_conn, id_token.as_deref()).await?;
+        let teacher_perms = current_privileges(ctx, &mut db_conn, &teacher).await?;
 
         if !teacher_perms.secretary && !teacher_perms.admin {
             return Err(GraphQlError::new("Not permitted to access this resource"));
@@ -166,17 +164,286 @@ impl QueryRoot {
         )
     }
 
+    /// Every future absence between `start` and `end` with its review state,
+    /// including pending, rejected and cancelled ones. Ranges can be up to 366
+    /// days long.
+    /// 
+    /// Teachers can only see their own requests, secretaries and admins can
+    /// see anyone's.
+    async fn absence_requests(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "Only get the requests of this teacher")] teacher_id: Option<Uuid>,
+        start: NaiveDate,
+        end: NaiveDate,
+        #[graphql(desc = "Only get requests with this status")] status: Option<AbsenceRequestStatus>,
+        #[graphql(desc = "Signed OIDC ID token of the teacher, if there's no session")] id_token: Option<String>,
+    ) -> GraphQlResult<Vec<AbsenceRequest>> {
+        use crate::database::prepared::absence_requests::get_absence_requests as get_absence_requests_from_db;
+
+        ensure_auth!(ctx, [read_teacher, read_teacher_absence]);
+        ensure_date_span(start, end)?;
+
+        let mut db_conn = get_db!(ctx);
+
+        if let Some(teacher_id) = teacher_id {
+            ensure_acting_for(ctx, &mut db_conn, teacher_id, id_token.as_deref()).await?;
+        } else {
+            let teacher = current_teacher(ctx, &mut db_conn, id_token.as_deref()).await?;
+            let teacher_perms = current_privileges(ctx, &mut db_conn, &teacher).await?;
+
+            if !teacher_perms.secretary && !teacher_perms.admin {
+                return Err(GraphQlError::new("Not permitted to access this resource"));
+            }
+        }
+
+        run_query!(
+            db_conn.get_absence_requests_from_db(teacher_id, start, end, status)
+            else (req_id(ctx)) "Failed to get absence requests from database: {}"
+        )
+    }
+
+    /// Teachers can get their own recurring absences. Secretaries and admins
+    /// can get anyone's, or everyone's without a `teacherId`.
+    async fn recurring_absences(
+        &self,
+        ctx: &Context<'_>,
+        teacher_id: Option<Uuid>,
+        #[graphql(desc = "Signed OIDC ID token of the teacher, if there's no session")] id_token: Option<String>,
+    ) -> GraphQlResult<Vec<RecurringAbsence>> {
+        use crate::database::prepared::recurring_absences::get_recurring_absences as get_recurring_absences_from_db;
+
+        ensure_auth!(ctx, [read_teacher, read_teacher_absence]);
+
+        let mut db_conn = get_db!(ctx);
+
+        if let Some(teacher_id) = teacher_id {
+            ensure_acting_for(ctx, &mut db_conn, teacher_id, id_token.as_deref()).await?;
+        } else {
+            let teacher = current_teacher(ctx, &mut db_conn, id_token.as_deref()).await?;
+            let teacher_perms = current_privileges(ctx, &mut db_conn, &teacher).await?;
+
+            if !teacher_perms.secretary && !teacher_perms.admin {
+                return Err(GraphQlError::new("Not permitted to access this resource"));
+            }
+        }
+
+        run_query!(
+            db_conn.get_recurring_absences_from_db(teacher_id)
+            else (req_id(ctx)) "Failed to get recurring absences from database: {}"
+        )
+    }
+
+    /// A teacher's calendar feeds, or the staff-wide feeds without a
+    /// `teacherId`. Tokens aren't included, since only their hashes are kept.
+    async fn calendar_feeds(
+        &self,
+        ctx: &Context<'_>,
+        teacher_id: Option<Uuid>,
+        #[graphql(desc = "Signed OIDC ID token of the teacher, if there's no session")] id_token: Option<String>,
+    ) -> GraphQlResult<Vec<CalendarFeed>> {
+        use crate::database::prepared::calendar_feeds::get_calendar_feeds as get_calendar_feeds_from_db;
+
+        ensure_auth!(ctx, [read_teacher]);
+
+        let mut db_conn = get_db!(ctx);
+
+        match teacher_id {
+            Some(teacher_id) => ensure_acting_for(ctx, &mut db_conn, teacher_id, id_token.as_deref()).await?,
+            None => ensure_staff(ctx, &mut db_conn, id_token.as_deref()).await?,
+        }
+
+        run_query!(
+            db_conn.get_calendar_feeds_from_db(teacher_id)
+            else (req_id(ctx)) "Failed to get calendar feeds from database: {}"
+        )
+    }
+
+    /// The whole roster of teachers, with their names, pronouns and OAuth
+    /// accounts, in a format that `importTeachers` can read back.
+    async fn export_teachers(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default_with = "RosterFormat::Csv")] format: RosterFormat,
+    ) -> GraphQlResult<String> {
+        use crate::database::prepared::roster::get_roster as get_roster_from_db;
+
+        ensure_auth!(ctx, [read_teacher, read_teacher_name, read_teacher_pronouns, admin]);
+
+        let mut db_conn = get_db!(ctx);
+        let roster = run_query!(
+            db_conn.get_roster_from_db()
+            else (req_id(ctx)) "Failed to get roster from database: {}"
+        )?;
+
+        crate::roster::write(format, &roster).map_err(|e| {
+            crate::logging::error!("{} - Failed to write roster: {e}", crate::logging::fmt_req_id(req_id(ctx)));
+            GraphQlError::new(format!("Failed to write roster: {e}"))
+        })
+    }
+
+    /// Every pending future absence between `start` and `end`, for
+    /// secretaries and admins to review. Ranges can be up to 366 days long.
+    async fn pending_absence_requests(
+        &self,
+        ctx: &Context<'_>,
+        start: NaiveDate,
+        end: NaiveDate,
+        #[graphql(desc = "Signed OIDC ID token of the teacher, if there's no session")] id_token: Option<String>,
+    ) -> GraphQlResult<Vec<AbsenceRequest>> {
+        use crate::database::prepared::absence_requests::get_absence_requests as get_absence_requests_from_db;
+
+        ensure_auth!(ctx, [read_teacher, read_teacher_absence]);
+        ensure_date_span(start, end)?;
+
+        let mut db_conn = get_db!(ctx);
+
+        let teacher = current_teacher(ctx, &mut db_conn, id_token.as_deref()).await?;
+        let teacher_perms = current_privileges(ctx, &mut db_conn, &teacher).await?;
+
+        if !teacher_perms.secretary && !teacher_perms.admin {
+            return Err(GraphQlError::new("Not permitted to access this resource"));
+        }
+
+        run_query!(
+            db_conn.get_absence_requests_from_db(None, start, end, Some(AbsenceRequestStatus::Pending))
+            else (req_id(ctx)) "Failed to get pending absence requests from database: {}"
+        )
+    }
+
+    /// The absences that were applied between `start` and `end`. Ranges can
+    /// be up to 366 days long.
+    async fn absence_history(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "Only get the history of this teacher")] teacher_id: Option<Uuid>,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> GraphQlResult<Vec<PackedAbsenceState>> {
+        use crate::database::prepared::absence_history::get_absence_history as get_absence_history_from_db;
+
+        ensure_auth!(ctx, [read_teacher_absence, admin]);
+        ensure_date_span(start, end)?;
+
+        let mut db_conn = get_db!(ctx);
+
+        run_query!(
+            db_conn.get_absence_history_from_db(teacher_id, start, end)
+            else (req_id(ctx)) "Failed to get absence history from database: {}"
+        )
+    }
+
+    /// Absence statistics for secretaries and admins. Ranges can be up to
+    /// 366 days long.
+    async fn reports(
+        &self,
+        ctx: &Context<'_>,
+        start: NaiveDate,
+        end: NaiveDate,
+        #[graphql(default = false, desc = "Leave archived teachers and periods out of the totals")] exclude_archived: bool,
+        #[graphql(desc = "Signed OIDC ID token of the teacher, if there's no session")] id_token: Option<String>,
+    ) -> GraphQlResult<Reports> {
+
+        ensure_auth!(ctx, [read_teacher, read_teacher_absence]);
+        ensure_date_span(start, end)?;
+
+        let mut db_conn = get_db!(ctx);
+
+        let teacher = current_teacher(ctx, &mut db_conn, id_token.as_deref()).await?;
+        let teacher_perms = current_privileges(ctx, &mut db_conn, &teacher).await?;
+
+        if !teacher_perms.secretary && !teacher_perms.admin {
+            return Err(GraphQlError::new("Not permitted to access this resource"));
+        }
+
+        Ok(Reports { start, end, exclude_archived })
+    }
+
+    async fn clients(
+        &self,
+        ctx: &Context<'_>,
+    ) -> GraphQlResult<Vec<Client>> {
+        use crate::database::prepared::clients::get_all_clients as get_all_clients_from_db;
+
+        ensure_auth!(ctx, [admin]);
+
+        let mut db_conn = get_db!(ctx);
+
+        run_query!(
+            db_conn.get_all_clients_from_db()
+            else (req_id(ctx)) "Failed to get clients from database: {}"
+        )
+    }
+
+    async fn webhooks(
+        &self,
+        ctx: &Context<'_>,
+    ) -> GraphQlResult<Vec<Webhook>> {
+        use crate::database::prepared::webhooks::get_all_webhooks as get_all_webhooks_from_db;
+
+        ensure_auth!(ctx, [admin]);
+
+        let mut db_conn = get_db!(ctx);
+
+        run_query!(
+            db_conn.get_all_webhooks_from_db()
+            else (req_id(ctx)) "Failed to get webhooks from database: {}"
+        )
+    }
+
+    /// The most recent webhook deliveries, newest first, optionally only for
+    /// one webhook. `limit` is at most 500.
+    async fn webhook_deliveries(
+        &self,
+        ctx: &Context<'_>,
+        webhook_id: Option<Uuid>,
+        #[graphql(default = 50)] limit: u32,
+    ) -> GraphQlResult<Vec<WebhookDelivery>> {
+        use crate::database::prepared::webhooks::get_webhook_deliveries as get_webhook_deliveries_from_db;
+
+        ensure_auth!(ctx, [admin]);
+
+        if !(1..=500).contains(&limit) {
+            return Err(GraphQlError::new("Limit must be from 1 to 500"));
+        }
+
+        let mut db_conn = get_db!(ctx);
+
+        run_query!(
+            db_conn.get_webhook_deliveries_from_db(webhook_id, None, limit.into())
+            else (req_id(ctx)) "Failed to get webhook deliveries from database: {}"
+        )
+    }
+
+    async fn stale_clients(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "How long a client can go unused before it's stale", default = 30)] unused_for_days: i32,
+    ) -> GraphQlResult<Vec<Client>> {
+        use crate::database::prepared::clients::get_stale_clients as get_stale_clients_from_db;
+
+        ensure_auth!(ctx, [admin]);
+
+        let mut db_conn = get_db!(ctx);
+
+        run_query!(
+            db_conn.get_stale_clients_from_db(unused_for_days)
+            else (req_id(ctx)) "Failed to get stale clients from database: {}"
+        )
+    }
+
     async fn all_periods(
         &self,
         ctx: &Context<'_>,
+        #[graphql(default = false, desc = "Include periods that were soft deleted")] include_archived: bool,
     ) -> GraphQlResult<Vec<Period>> {
-        use crate::database::prepared::period::get_all_periods as get_all_periods_from_db;        
+        use crate::database::prepared::period::get_periods as get_periods_from_db;        
 
         ensure_auth!(ctx, [read_period]);
 
         let mut db_conn = get_db!(ctx);
 
-        get_all_periods_from_db(&mut db_conn)
+        get_periods_from_db(&mut db_conn, include_archived)
             .await
             .map_err(|e| {
                 if matches!(e, sqlx::Error::RowNotFound) {
@@ -194,25 +461,24 @@ impl QueryRoot {
     async fn get_privs(
         &self,
         ctx: &Context<'_>,
-        #[graphql(desc = "Provider of OAuth")] provider: String,
-        #[graphql(desc = "Sub of OAuth")] sub: String,
+        #[graphql(desc = "Signed OIDC ID token of the teacher, if there's no session")] id_token: Option<String>,
     ) -> GraphQlResult<Privileges> {
-        use crate::database::prepared::privileges::get_privileges as get_privs_from_db;
-        use crate::database::prepared::teacher::get_teacher_by_oauth as get_teacher_db;
-
         ensure_auth!(ctx, [read_teacher, admin, experimental]);
 
         let mut db_conn = get_db!(ctx);
 
-        let teacher = run_query!(
-            db_conn.get_teacher_db(provider.clone(), sub.clone())
-            else (req_id(ctx)) "This oauth user doesn't exist {:.0}"
-        )?;
+        let teacher = current_teacher(ctx, &mut db_conn, id_token.as_deref()).await?;
         
-        run_query!(
-            db_conn.get_privs_from_db(teacher.get_id())
-            else (req_id(ctx)) "Failed to get permissions for oauth user: {}"
-        )
+        current_privileges(ctx, &mut db_conn, &teacher).await
+    }
+
+    /// The session from the `session-token` header, or null if there isn't a
+    /// valid one.
+    async fn current_session(
+        &self,
+        ctx: &Context<'_>,
+    ) -> GraphQlResult<Option<Session>> {
+        crate::graphql::get_session(ctx).await
     }
 
     async fn curr_spreadsheet_id(
@@ -231,6 +497,95 @@ impl QueryRoot {
         )
     }
 
+    async fn all_rooms(
+        &self,
+        ctx: &Context<'_>,
+    ) -> GraphQlResult<Vec<Room>> {
+        use crate::database::prepared::rooms::get_all_rooms as get_all_rooms_from_db;
+
+        ensure_auth!(ctx, [read_period]);
+
+        let mut db_conn = get_db!(ctx);
+
+        run_query!(
+            db_conn.get_all_rooms_from_db()
+            else (req_id(ctx)) "Failed to get rooms from database: {}"
+        )
+    }
+
+    async fn all_bell_schedules(
+        &self,
+        ctx: &Context<'_>,
+    ) -> GraphQlResult<Vec<BellSchedule>> {
+        use crate::database::prepared::bell_schedules::get_all_bell_schedules as get_all_bell_schedules_from_db;
+
+        ensure_auth!(ctx, [read_period]);
+
+        let mut db_conn = get_db!(ctx);
+
+        run_query!(
+            db_conn.get_all_bell_schedules_from_db()
+            else (req_id(ctx)) "Failed to get bell schedules from database: {}"
+        )
+    }
+
+    /// The bell schedule of every day from `start` to `end` that has one.
+    /// Days that aren't listed use the periods' default times.
+    async fn schedule_calendar(
+        &self,
+        ctx: &Context<'_>,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> GraphQlResult<Vec<ScheduleDay>> {
+        use crate::database::prepared::bell_schedules::get_schedule_days as get_schedule_days_from_db;
+
+        ensure_auth!(ctx, [read_period]);
+
+        let mut db_conn = get_db!(ctx);
+
+        run_query!(
+            db_conn.get_schedule_days_from_db(start, end)
+            else (req_id(ctx)) "Failed to get bell schedule calendar from database: {}"
+        )
+    }
+
+    /// Every day from `start` to `end` in the school calendar, including the
+    /// ones that aren't school days. Ranges can be up to 366 days long.
+    async fn school_days(
+        &self,
+        ctx: &Context<'_>,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> GraphQlResult<Vec<CalendarDay>> {
+        use crate::database::prepared::calendar::get_calendar_days as get_calendar_days_from_db;
+
+        ensure_auth!(ctx, [read_period]);
+        ensure_date_span(start, end)?;
+
+        let mut db_conn = get_db!(ctx);
+
+        run_query!(
+            db_conn.get_calendar_days_from_db(start, end)
+            else (req_id(ctx)) "Failed to get school calendar from database: {}"
+        )
+    }
+
+    async fn curr_bell_schedule(
+        &self,
+        ctx: &Context<'_>,
+    ) -> GraphQlResult<Option<BellSchedule>> {
+        use crate::database::prepared::bell_schedules::get_todays_bell_schedule as get_todays_bell_schedule_from_db;
+
+        ensure_auth!(ctx, [read_period]);
+
+        let mut db_conn = get_db!(ctx);
+
+        run_query!(
+            db_conn.get_todays_bell_schedule_from_db()
+            else (req_id(ctx)) "Failed to get today's bell schedule from database: {}"
+        )
+    }
+
     async fn curr_report_to(
         &self,
         ctx: &Context<'_>,
@@ -247,6 +602,80 @@ impl QueryRoot {
         )
     }
 
+    async fn last_rollover(
+        &self,
+        ctx: &Context<'_>,
+    ) -> GraphQlResult<Option<RolloverRun>> {
+        use crate::database::prepared::rollovers::get_last_rollover as get_last_rollover_from_db;
+
+        ensure_auth!(ctx, [admin]);
+
+        let mut db_conn = get_db!(ctx);
+
+        run_query!(
+            db_conn.get_last_rollover_from_db()
+            else (req_id(ctx)) "Failed to get last day rollover from database: {}"
+        )
+    }
+
+    /// The most recent push to or pull from the mirrored spreadsheet,
+    /// optionally only in one `direction`.
+    async fn last_sheet_sync(
+        &self,
+        ctx: &Context<'_>,
+        direction: Option<SheetSyncDirection>,
+    ) -> GraphQlResult<Option<SheetSyncRun>> {
+        use crate::database::prepared::sheet_syncs::get_last_sheet_sync as get_last_sheet_sync_from_db;
+
+        ensure_auth!(ctx, [admin]);
+
+        let mut db_conn = get_db!(ctx);
+
+        run_query!(
+            db_conn.get_last_sheet_sync_from_db(direction)
+            else (req_id(ctx)) "Failed to get last spreadsheet sync from database: {}"
+        )
+    }
+
+    /// Today's absence digest, as it would be emailed after the next day
+    /// rollover.
+    async fn absence_digest(&self, ctx: &Context<'_>) -> GraphQlResult<AbsenceDigest> {
+        use crate::digest::build as build_digest;
+
+        ensure_auth!(ctx, [admin]);
+
+        let mut db_conn = get_db!(ctx);
+
+        run_query!(
+            db_conn.build_digest()
+            else (req_id(ctx)) "Failed to build absence digest: {}"
+        )
+    }
+
+    /// The record of every mutation, newest first. `page.limit` is at most
+    /// 500.
+    async fn audit_log(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] filter: AuditLogFilter,
+        #[graphql(default)] page: PageInput,
+    ) -> GraphQlResult<AuditLogPage> {
+        use crate::database::prepared::audit_log::get_audit_log as get_audit_log_from_db;
+
+        ensure_auth!(ctx, [admin]);
+
+        if page.offset < 0 || !(1..=500).contains(&page.limit) {
+            return Err(GraphQlError::new("Page offset must not be negative, and its limit must be from 1 to 500"));
+        }
+
+        let mut db_conn = get_db!(ctx);
+
+        run_query!(
+            db_conn.get_audit_log_from_db(&filter.into(), page.offset.into(), page.limit.into())
+            else (req_id(ctx)) "Failed to get audit log from database: {}"
+        )
+    }
+
     async fn get_metrics(
         &self,
         ctx: &Context<'_>,
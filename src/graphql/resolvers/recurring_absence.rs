@@ -0,0 +1,65 @@
+#![allow(unused_braces)]
+
+use async_graphql::{ Context, Object, Result as GraphQlResult };
+use chrono::NaiveDate;
+use uuid::Uuid;
+
+use crate::graphql::req_id;
+use crate::types::{ Period, RecurringAbsence, Teacher };
+
+use super::{ get_db, run_query, ensure_auth };
+
+
+#[Object]
+impl RecurringAbsence {
+    async fn id(&self) -> Uuid { self.id }
+    async fn teacher_id(&self) -> Uuid { self.teacher_id }
+
+    async fn teacher(&self, ctx: &Context<'_>) -> GraphQlResult<Teacher> {
+        use crate::database::prepared::teacher::get_teacher;
+
+        ensure_auth!(ctx, [read_teacher]);
+
+        let mut db_conn = get_db!(ctx);
+        let id = self.teacher_id;
+
+        run_query!(
+            db_conn.get_teacher(id)
+            else (req_id(ctx)) "Failed to get teacher {id}: {}"
+        )
+    }
+
+    async fn periods(&self, ctx: &Context<'_>) -> GraphQlResult<Vec<Period>> {
+        use crate::database::prepared::period::get_all_periods;
+
+        ensure_auth!(ctx, [read_period]);
+
+        let mut db_conn = get_db!(ctx);
+
+        let periods = run_query!(
+            db_conn.get_all_periods()
+            else (req_id(ctx)) "Failed to get periods of recurring absence: {}"
+        )?;
+
+        Ok(periods.into_iter().filter(|period| self.periods.contains(&period.id)).collect())
+    }
+    async fn fully_absent(&self) -> bool { self.fully_absent }
+    async fn comment(&self) -> Option<&str> { self.comment.as_deref() }
+
+    async fn start(&self) -> NaiveDate { self.rule.start }
+    async fn interval_weeks(&self) -> u32 { self.rule.interval_weeks }
+    /// ISO weekdays, where 1 is Monday.
+    async fn weekdays(&self) -> Vec<u32> { self.rule.weekdays.iter().map(|weekday| weekday.number_from_monday()).collect() }
+    async fn until(&self) -> Option<NaiveDate> { self.rule.until }
+    async fn count(&self) -> Option<u32> { self.rule.count }
+
+    /// Occurrences that were skipped.
+    async fn exceptions(&self) -> &[NaiveDate] { &self.exceptions }
+
+    /// Every date from `start` to `end` the absence happens on, leaving out
+    /// skipped occurrences. Days that aren't school days are still included.
+    /// Ranges can be up to 366 days long.
+    async fn dates(&self, start: NaiveDate, end: NaiveDate) -> GraphQlResult<Vec<NaiveDate>> {
+        Ok(self.occurrences(start, end)?)
+    }
+}
@@ -0,0 +1,140 @@
+#![allow(unused_braces)]
+
+use async_graphql::{ Object, Context, Result as GraphQlResult };
+use chrono::NaiveDate;
+use uuid::Uuid;
+
+use crate::graphql::req_id;
+use crate::types::{
+    Reports,
+    Period,
+    TeacherMonthlyAbsences,
+    PeriodAbsenceCount,
+    FullVsPartialDays,
+    WeekdayAbsenceCount,
+};
+
+use super::{ get_db, run_query, ensure_auth };
+
+
+/// The most periods `mostAbsentPeriods` and `mostUncoveredPeriods` can
+/// return.
+const MAX_PERIOD_COUNT_LIMIT: i32 = 100;
+
+#[Object]
+impl Reports {
+    async fn start(&self) -> NaiveDate { self.start }
+    async fn end(&self) -> NaiveDate { self.end }
+    async fn exclude_archived(&self) -> bool { self.exclude_archived }
+
+    async fn monthly_absences(&self, ctx: &Context<'_>) -> GraphQlResult<Vec<TeacherMonthlyAbsences>> {
+        use crate::database::prepared::reports::get_monthly_absences as get_monthly_absences_from_db;
+
+        let mut db_conn = get_db!(ctx);
+
+        run_query!(
+            db_conn.get_monthly_absences_from_db(self.start, self.end, self.exclude_archived)
+            else (req_id(ctx)) "Failed to get monthly absences from database: {}"
+        )
+    }
+
+    /// The periods teachers were most often absent from, most frequent
+    /// first. `limit` can be at most 100.
+    #[graphql(complexity = "(limit.clamp(1, MAX_PERIOD_COUNT_LIMIT) as usize).saturating_mul(child_complexity)")]
+    async fn most_absent_periods(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 10, validator(minimum = 1, maximum = 100))] limit: i32,
+    ) -> GraphQlResult<Vec<PeriodAbsenceCount>> {
+        use crate::database::prepared::reports::get_period_absence_counts as get_period_absence_counts_from_db;
+
+        let mut db_conn = get_db!(ctx);
+
+        run_query!(
+            db_conn.get_period_absence_counts_from_db(self.start, self.end, self.exclude_archived, i64::from(limit))
+            else (req_id(ctx)) "Failed to get period absence counts from database: {}"
+        )
+    }
+
+    /// The periods absent teachers most often had no substitute for, most
+    /// frequent first. `limit` can be at most 100.
+    #[graphql(complexity = "(limit.clamp(1, MAX_PERIOD_COUNT_LIMIT) as usize).saturating_mul(child_complexity)")]
+    async fn most_uncovered_periods(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 10, validator(minimum = 1, maximum = 100))] limit: i32,
+    ) -> GraphQlResult<Vec<PeriodAbsenceCount>> {
+        use crate::database::prepared::reports::get_uncovered_period_counts as get_uncovered_period_counts_from_db;
+
+        let mut db_conn = get_db!(ctx);
+
+        run_query!(
+            db_conn.get_uncovered_period_counts_from_db(self.start, self.end, self.exclude_archived, i64::from(limit))
+            else (req_id(ctx)) "Failed to get uncovered period counts from database: {}"
+        )
+    }
+
+    async fn full_vs_partial_days(&self, ctx: &Context<'_>) -> GraphQlResult<FullVsPartialDays> {
+        use crate::database::prepared::reports::get_full_vs_partial_days as get_full_vs_partial_days_from_db;
+
+        let mut db_conn = get_db!(ctx);
+
+        run_query!(
+            db_conn.get_full_vs_partial_days_from_db(self.start, self.end, self.exclude_archived)
+            else (req_id(ctx)) "Failed to get full and partial absence days from database: {}"
+        )
+    }
+
+    async fn weekday_distribution(&self, ctx: &Context<'_>) -> GraphQlResult<Vec<WeekdayAbsenceCount>> {
+        use crate::database::prepared::reports::get_weekday_absence_counts as get_weekday_absence_counts_from_db;
+
+        let mut db_conn = get_db!(ctx);
+
+        run_query!(
+            db_conn.get_weekday_absence_counts_from_db(self.start, self.end, self.exclude_archived)
+            else (req_id(ctx)) "Failed to get weekday absence counts from database: {}"
+        )
+    }
+}
+
+#[Object]
+impl TeacherMonthlyAbsences {
+    async fn teacher_id(&self) -> Uuid { self.teacher_id }
+    async fn month(&self) -> NaiveDate { self.month }
+    async fn days(&self) -> i64 { self.days }
+    async fn full_days(&self) -> i64 { self.full_days }
+    async fn partial_days(&self) -> i64 { self.partial_days }
+    async fn periods(&self) -> i64 { self.periods }
+}
+
+#[Object]
+impl PeriodAbsenceCount {
+    async fn period_id(&self) -> Uuid { self.period_id }
+    async fn count(&self) -> i64 { self.count }
+
+    async fn period(&self, ctx: &Context<'_>) -> GraphQlResult<Period> {
+        use crate::database::prepared::period::get_period as get_period_from_db;
+
+        ensure_auth!(ctx, [read_period]);
+
+        let mut db_conn = get_db!(ctx);
+
+        run_query!(
+            db_conn.get_period_from_db(self.period_id)
+            else (req_id(ctx)) "Failed to get period from database: {}"
+        )
+    }
+}
+
+#[Object]
+impl FullVsPartialDays {
+    async fn full(&self) -> i64 { self.full }
+    async fn partial(&self) -> i64 { self.partial }
+}
+
+#[Object]
+impl WeekdayAbsenceCount {
+    /// ISO weekday, 1 is Monday and 7 is Sunday
+    async fn weekday(&self) -> i32 { self.weekday }
+    async fn count(&self) -> i64 { self.count }
+}
@@ -0,0 +1,16 @@
+#![allow(unused_braces)]
+
+use async_graphql::Object;
+use chrono::{DateTime, Utc};
+
+use crate::types::RolloverRun;
+
+
+#[Object]
+impl RolloverRun {
+    async fn ran_at(&self) -> DateTime<Utc> { self.ran_at }
+    async fn trigger(&self) -> &str { self.trigger.str() }
+    async fn success(&self) -> bool { self.success }
+    async fn teachers_updated(&self) -> i32 { self.teachers_updated }
+    async fn error(&self) -> Option<&str> { self.error.as_deref() }
+}
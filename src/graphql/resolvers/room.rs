@@ -0,0 +1,37 @@
+#![allow(unused_braces)]
+
+use async_graphql::{ Context, Object, Result as GraphQlResult };
+use uuid::Uuid;
+
+use crate::graphql::req_id;
+use crate::types::{ Period, Room, RoomAssignment };
+
+use super::{ get_db, run_query, ensure_auth };
+
+
+#[Object]
+impl Room {
+    async fn id(&self) -> Uuid { self.id }
+    async fn name(&self) -> &str { &self.name }
+}
+
+#[Object]
+impl RoomAssignment {
+    async fn teacher_id(&self) -> Uuid { self.teacher_id }
+    async fn period_id(&self) -> Uuid { self.period_id }
+    async fn room(&self) -> &Room { &self.room }
+
+    async fn period(&self, ctx: &Context<'_>) -> GraphQlResult<Period> {
+        use crate::database::prepared::period::get_period;
+
+        ensure_auth!(ctx, [read_period]);
+
+        let mut db_conn = get_db!(ctx);
+        let id = self.period_id;
+
+        run_query!(
+            db_conn.get_period(id)
+            else (req_id(ctx)) "Failed to get period {id}: {}"
+        )
+    }
+}
@@ -0,0 +1,27 @@
+#![allow(unused_braces)]
+
+use async_graphql::Object;
+
+use crate::types::{ RosterImportReport, RosterRowError };
+
+
+#[Object]
+impl RosterImportReport {
+    /// Whether only a dry run was asked for.
+    async fn dry_run(&self) -> bool { self.dry_run }
+    /// Whether the roster was actually imported.
+    async fn applied(&self) -> bool { self.applied }
+    /// How many teachers were, or would be, added.
+    async fn created(&self) -> u32 { self.created }
+    /// How many existing teachers were, or would be, updated.
+    async fn updated(&self) -> u32 { self.updated }
+    async fn errors(&self) -> &[RosterRowError] { &self.errors }
+}
+
+#[Object]
+impl RosterRowError {
+    /// The 1-based row in the roster, not counting the CSV header. Errors
+    /// with the whole file are on row 0.
+    async fn row(&self) -> usize { self.row }
+    async fn message(&self) -> &str { &self.message }
+}
@@ -0,0 +1,37 @@
+#![allow(unused_braces)]
+
+use async_graphql::{ Context, Object, Result as GraphQlResult };
+use chrono::{ DateTime, Utc };
+use uuid::Uuid;
+
+use crate::types::{ Privileges, Session, SessionCredentials, Teacher };
+
+use super::{ get_db, ensure_auth };
+
+
+#[Object]
+impl Session {
+    async fn teacher_id(&self) -> Uuid { self.teacher_id }
+    async fn privileges(&self) -> Privileges { self.privileges }
+    async fn expires_at(&self) -> DateTime<Utc> { self.expires_at }
+
+    async fn teacher(&self, ctx: &Context<'_>) -> GraphQlResult<Teacher> {
+        use crate::database::prepared::teacher::get_teacher;
+
+        ensure_auth!(ctx, [read_teacher]);
+
+        let mut db_conn = get_db!(ctx);
+
+        get_teacher(&mut db_conn, self.teacher_id)
+            .await
+            .map_err(|e| async_graphql::Error::new(format!("Failed to get teacher from database {e}")))
+    }
+}
+
+#[Object]
+impl SessionCredentials {
+    /// Pass this as the `session-token` header (or `sessionToken` in a
+    /// websocket's `connection_init` payload) to act as this teacher.
+    async fn token(&self) -> &str { &self.token }
+    async fn session(&self) -> &Session { &self.session }
+}
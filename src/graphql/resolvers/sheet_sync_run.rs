@@ -0,0 +1,19 @@
+#![allow(unused_braces)]
+
+use async_graphql::Object;
+use chrono::{DateTime, Utc};
+
+use crate::types::{ SheetSyncDirection, SheetSyncRun };
+
+
+#[Object]
+impl SheetSyncRun {
+    async fn ran_at(&self) -> DateTime<Utc> { self.ran_at }
+    async fn sheet_id(&self) -> &str { &self.sheet_id }
+    async fn direction(&self) -> SheetSyncDirection { self.direction }
+    async fn success(&self) -> bool { self.success }
+    /// Teacher rows written for a push, or teachers updated from a pull.
+    async fn rows(&self) -> i32 { self.rows }
+    async fn attempts(&self) -> i32 { self.attempts }
+    async fn error(&self) -> Option<&str> { self.error.as_deref() }
+}
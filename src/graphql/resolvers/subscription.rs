@@ -0,0 +1,221 @@
+//! This module contains solely the [`SubscriptionRoot`] struct.
+//! It only exists for organizational purposes.
+
+use std::future::ready;
+use std::sync::{ Arc, Mutex };
+use std::time::{ Duration, Instant };
+
+use async_graphql::{
+    Subscription,
+
+    Context,
+};
+use chrono::Utc;
+use futures_util::{ Stream, StreamExt };
+use uuid::Uuid;
+
+use crate::events::{ Event, ConfigField };
+use crate::state::AppState;
+use crate::types::{ Teacher, Period };
+use crate::verification::{ ClientIdHeader, ClientSecretHeader, SessionTokenHeader, scopes::Scopes };
+
+use super::ensure_auth;
+
+
+/// How often an open subscription checks that its client still has the
+/// scopes it subscribed with. Scopes are otherwise only checked once, when
+/// the subscription starts, so without this a revoked client would keep
+/// getting events for as long as its socket stays open.
+const SCOPE_RECHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Ends `stream` at the first item after the subscribing client stops
+/// passing `allowed` (or is revoked). The client is checked again before an
+/// item is sent if it was last checked over [`SCOPE_RECHECK_INTERVAL`] ago.
+///
+/// If the subscription was made with a session, it also ends as soon as the
+/// session expires, whether or not anything was sent.
+fn while_allowed<T: Send + 'static>(
+    ctx: &Context<'_>,
+    allowed: fn(&Scopes) -> bool,
+    stream: impl Stream<Item = T> + Send + 'static,
+) -> async_graphql::Result<impl Stream<Item = T>> {
+    let app_state = ctx.data::<AppState>()?.clone();
+    let client = ctx.data_opt::<ClientIdHeader>()
+        .zip(ctx.data_opt::<ClientSecretHeader>())
+        .map(|(id, secret)| (id.inner(), secret.as_bytes().to_vec()));
+    let session_expiry = ctx.data_opt::<SessionTokenHeader>()
+        .and_then(|token| app_state.sessions().verify(token.as_str()))
+        .map(|session| session.expires_at);
+    let last_checked = Arc::new(Mutex::new(Instant::now()));
+
+    let expired = async move {
+        match session_expiry {
+            Some(expires_at) => tokio::time::sleep((expires_at - Utc::now()).to_std().unwrap_or_default()).await,
+            None => std::future::pending().await,
+        }
+    };
+
+    Ok(
+        stream
+            .take_until(expired)
+            .then(move |item| {
+                let app_state = app_state.clone();
+                let client = client.clone();
+                let last_checked = last_checked.clone();
+                async move {
+                    if session_expiry.is_some_and(|expires_at| expires_at <= Utc::now()) {
+                        return None;
+                    }
+
+                    let due = last_checked
+                        .lock()
+                        .map_or(true, |last_checked| last_checked.elapsed() >= SCOPE_RECHECK_INTERVAL);
+                    if !due {
+                        return Some(item);
+                    }
+
+                    let still_allowed = match client {
+                        // A database hiccup shouldn't drop every open socket,
+                        // the next re-check will catch a revoked client
+                        Some((id, secret)) => match crate::graphql::client_scopes(&app_state, id, &secret).await {
+                            Ok(scopes) => scopes.is_some_and(|scopes| allowed(&scopes)),
+                            Err(_) => true,
+                        },
+                        None => allowed(&Scopes::new()),
+                    };
+                    if let Ok(mut last_checked) = last_checked.lock() {
+                        *last_checked = Instant::now();
+                    }
+
+                    still_allowed.then_some(item)
+                }
+            })
+            .take_while(|item| ready(item.is_some()))
+            .filter_map(ready)
+    )
+}
+
+
+/// This is a memberless struct implementing all the subscriptions for `improved-eureka`.
+/// This includes:
+/// - `teacher_absence_changed(id?) -> Teacher`
+/// - `period_time_changed(id?) -> Period`
+/// - `teacher_deleted(id?) -> ID`
+/// - `period_deleted(id?) -> ID`
+/// - `config_changed() -> ConfigField`
+///
+/// Every stream is fed by the [`EventBus`][crate::events::EventBus], which
+/// the mutations in [`super::mutation`] publish to.
+#[derive(Debug, Clone, Copy)]
+pub struct SubscriptionRoot;
+
+#[Subscription]
+impl SubscriptionRoot {
+    async fn teacher_absence_changed(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "Only send changes for the teacher with this id")] id: Option<Uuid>,
+    ) -> async_graphql::Result<impl Stream<Item = Teacher>> {
+        ensure_auth!(ctx, [read_teacher, read_teacher_absence]);
+
+        let events = ctx.data::<AppState>()?.events().stream();
+
+        while_allowed(
+            ctx,
+            |scopes| scopes.read_teacher && scopes.read_teacher_absence,
+            events.filter_map(move |event| async move {
+                match event {
+                    Event::TeacherAbsenceChanged(teacher) if id.is_none_or(|id| id == teacher.get_id()) => Some(teacher),
+                    _ => None,
+                }
+            }),
+        )
+    }
+
+    async fn period_time_changed(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "Only send changes for the period with this id")] id: Option<Uuid>,
+    ) -> async_graphql::Result<impl Stream<Item = Period>> {
+        ensure_auth!(ctx, [read_period]);
+
+        let events = ctx.data::<AppState>()?.events().stream();
+
+        while_allowed(
+            ctx,
+            |scopes| scopes.read_period,
+            events.filter_map(move |event| async move {
+                match event {
+                    Event::PeriodTimeChanged(period) if id.is_none_or(|id| id == period.id) => Some(period),
+                    _ => None,
+                }
+            }),
+        )
+    }
+
+    /// Sends the id of every teacher that's deleted for good. Archived
+    /// teachers come through `teacherAbsenceChanged` instead.
+    async fn teacher_deleted(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "Only send the deletion of the teacher with this id")] id: Option<Uuid>,
+    ) -> async_graphql::Result<impl Stream<Item = Uuid>> {
+        ensure_auth!(ctx, [read_teacher]);
+
+        let events = ctx.data::<AppState>()?.events().stream();
+
+        while_allowed(
+            ctx,
+            |scopes| scopes.read_teacher,
+            events.filter_map(move |event| async move {
+                match event {
+                    Event::TeacherDeleted(deleted) if id.is_none_or(|id| id == deleted) => Some(deleted),
+                    _ => None,
+                }
+            }),
+        )
+    }
+
+    /// Sends the id of every period that's deleted for good. Archived
+    /// periods come through `periodTimeChanged` instead.
+    async fn period_deleted(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "Only send the deletion of the period with this id")] id: Option<Uuid>,
+    ) -> async_graphql::Result<impl Stream<Item = Uuid>> {
+        ensure_auth!(ctx, [read_period]);
+
+        let events = ctx.data::<AppState>()?.events().stream();
+
+        while_allowed(
+            ctx,
+            |scopes| scopes.read_period,
+            events.filter_map(move |event| async move {
+                match event {
+                    Event::PeriodDeleted(deleted) if id.is_none_or(|id| id == deleted) => Some(deleted),
+                    _ => None,
+                }
+            }),
+        )
+    }
+
+    async fn config_changed(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<impl Stream<Item = ConfigField>> {
+        ensure_auth!(ctx, [read_teacher, read_period]);
+
+        let events = ctx.data::<AppState>()?.events().stream();
+
+        while_allowed(
+            ctx,
+            |scopes| scopes.read_teacher && scopes.read_period,
+            events.filter_map(|event| async move {
+                match event {
+                    Event::ConfigChanged(field) => Some(field),
+                    _ => None,
+                }
+            }),
+        )
+    }
+}
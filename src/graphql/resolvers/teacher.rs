@@ -1,9 +1,10 @@
 use async_graphql::Object;
 use async_graphql::{ Error as GraphQlError, Result as GraphQlResult, Context };
 
-use crate::types::{Teacher, PronounSet, TeacherName, Period};
+use crate::types::{Teacher, PronounSet, TeacherName, Period, AbsentPeriod, AbsentTeacher, Coverage, RoomAssignment};
 
-use super::{ get_db, ensure_auth };
+use super::{ get_db, run_query, ensure_auth };
+use crate::graphql::req_id;
 
 use uuid::Uuid;
 
@@ -36,24 +37,27 @@ impl Teacher {
     async fn absence(
         &self,
         ctx: &Context<'_>,
-    ) -> GraphQlResult<Vec<Period>> {
+    ) -> GraphQlResult<Vec<AbsentPeriod>> {
         ensure_auth!(ctx, [read_teacher_absence, read_period]);
 
         let mut db_conn = get_db!(ctx);
+        let teacher_id = self.get_id();
 
-        let ids = PeriodList::get_by_teacher(self.get_id(), &mut db_conn)
+        let ids = PeriodList::get_by_teacher(teacher_id, &mut db_conn)
             .await
             .map_err(|e| {
                 let e = e.to_string();
                 GraphQlError::new(format!("Failed to get periods ids this teacher is absent from database {e}"))
             })?;
 
-        ids.get_periods(&mut db_conn)
+        let periods = ids.get_periods(&mut db_conn)
             .await
             .map_err(|e| {
                 let e = e.to_string();
                 GraphQlError::new(format!("Failed to get periods this teacher is absent from database {e}"))
-            })
+            })?;
+
+        Ok(periods.into_iter().map(|period| AbsentPeriod { period, teacher_id }).collect())
     }
 
     async fn fully_absent(&self, ctx: &Context<'_>) -> GraphQlResult<bool> {
@@ -61,10 +65,105 @@ impl Teacher {
 
         Ok(self.get_fully_absent())
     }
+
+    /// Whether the teacher was soft deleted with `deleteTeacher`.
+    async fn archived(&self) -> bool {
+        self.get_archived()
+    }
+
+    /// The rooms this teacher's classes report to when they're absent.
+    async fn room_assignments(
+        &self,
+        ctx: &Context<'_>,
+    ) -> GraphQlResult<Vec<RoomAssignment>> {
+        use crate::database::prepared::rooms::get_room_assignments_for_teacher;
+
+        ensure_auth!(ctx, [read_teacher, read_period]);
+
+        let mut db_conn = get_db!(ctx);
+        let id = self.get_id();
+
+        run_query!(
+            db_conn.get_room_assignments_for_teacher(id)
+            else (req_id(ctx)) "Failed to get room assignments of teacher {id} from database: {}"
+        )
+    }
+
+    /// Where this teacher's class reports to in a period when they're absent:
+    /// their assigned room, or the global `reportTo` if they don't have one.
+    async fn report_to(
+        &self,
+        ctx: &Context<'_>,
+        period_id: Uuid,
+    ) -> GraphQlResult<String> {
+        use crate::database::prepared::rooms::get_report_to_for;
+
+        ensure_auth!(ctx, [read_teacher, read_period]);
+
+        let mut db_conn = get_db!(ctx);
+        let id = self.get_id();
+
+        run_query!(
+            db_conn.get_report_to_for(id, period_id)
+            else (req_id(ctx)) "Failed to get \"report to\" location of teacher {id} from database: {}"
+        )
+    }
 }
 
 
 
+/// A teacher in `Period.teachersAbsent`, which has the same fields as a
+/// `Teacher` along with the substitute covering them in that period.
+#[Object]
+impl AbsentTeacher {
+    async fn id(&self) -> Uuid { self.teacher.get_id() }
+
+    #[graphql(complexity = 3)]
+    async fn pronouns(&self, ctx: &Context<'_>) -> GraphQlResult<&PronounSet> {
+        self.teacher.pronouns(ctx).await?
+    }
+
+    #[graphql(complexity = 3)]
+    async fn name(&self, ctx: &Context<'_>) -> GraphQlResult<&TeacherName> {
+        self.teacher.name(ctx).await?
+    }
+
+    #[graphql(complexity = 10 + 5 * child_complexity)]
+    async fn absence(&self, ctx: &Context<'_>) -> GraphQlResult<Vec<AbsentPeriod>> {
+        self.teacher.absence(ctx).await?
+    }
+
+    async fn fully_absent(&self, ctx: &Context<'_>) -> GraphQlResult<bool> {
+        self.teacher.fully_absent(ctx).await?
+    }
+
+    async fn archived(&self) -> bool { self.teacher.get_archived() }
+
+    async fn room_assignments(&self, ctx: &Context<'_>) -> GraphQlResult<Vec<RoomAssignment>> {
+        self.teacher.room_assignments(ctx).await?
+    }
+
+    async fn report_to(&self, ctx: &Context<'_>, period_id: Uuid) -> GraphQlResult<String> {
+        self.teacher.report_to(ctx, period_id).await?
+    }
+
+    /// Today's substitute for this teacher in the period, if they have one.
+    async fn covered_by(&self, ctx: &Context<'_>) -> GraphQlResult<Option<Coverage>> {
+        use crate::database::prepared::coverage::get_coverage;
+
+        ensure_auth!(ctx, [read_teacher, read_teacher_absence]);
+
+        let mut db_conn = get_db!(ctx);
+        let (teacher, period) = (self.teacher.get_id(), self.period_id);
+
+        run_query!(
+            db_conn.get_coverage(teacher, period)
+            else (req_id(ctx)) "Failed to get coverage of teacher {teacher} in period {period} from database: {}"
+        )
+    }
+}
+
+
 #[derive(Debug, Clone)]
 pub struct PeriodList(Vec<Uuid>);
 
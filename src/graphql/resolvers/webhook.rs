@@ -0,0 +1,48 @@
+#![allow(unused_braces)]
+
+use async_graphql::Object;
+use chrono::{ DateTime, Utc };
+use uuid::Uuid;
+
+use crate::types::{ Webhook, WebhookCredentials, WebhookDelivery, WebhookDeliveryStatus, WebhookEvent };
+
+
+#[Object]
+impl Webhook {
+    async fn id(&self) -> Uuid { self.id }
+    async fn url(&self) -> &str { &self.url }
+    async fn description(&self) -> &str { &self.description }
+    async fn events(&self) -> &[WebhookEvent] { &self.events }
+    /// Inactive webhooks aren't sent new events, and their pending deliveries
+    /// are held until they're active again.
+    async fn active(&self) -> bool { self.active }
+    async fn created_at(&self) -> DateTime<Utc> { self.created_at }
+}
+
+#[Object]
+impl WebhookCredentials {
+    async fn webhook(&self) -> &Webhook { &self.webhook }
+    /// The key deliveries are signed with. It's only returned here, so it
+    /// must be saved right away.
+    async fn secret(&self) -> &str { &self.secret }
+}
+
+#[Object]
+impl WebhookDelivery {
+    async fn id(&self) -> Uuid { self.id }
+    async fn webhook_id(&self) -> Uuid { self.webhook_id }
+    async fn event(&self) -> WebhookEvent { self.event }
+    /// The exact JSON body that's sent.
+    async fn payload(&self) -> &str { &self.payload }
+    async fn status(&self) -> WebhookDeliveryStatus { self.delivery_status() }
+    async fn created_at(&self) -> DateTime<Utc> { self.created_at }
+
+    async fn attempts(&self) -> i32 { self.attempts }
+    async fn next_attempt_at(&self) -> DateTime<Utc> { self.next_attempt_at }
+    /// The HTTP status of the last attempt, if the endpoint responded.
+    async fn last_status(&self) -> Option<i32> { self.last_status }
+    async fn last_error(&self) -> Option<&str> { self.last_error.as_deref() }
+
+    async fn delivered_at(&self) -> Option<DateTime<Utc>> { self.delivered_at }
+    async fn failed_at(&self) -> Option<DateTime<Utc>> { self.failed_at }
+}
@@ -17,9 +17,73 @@ pub use {
 use std::fmt::Debug;
 
 use async_graphql::InputObject;
+use chrono::{ DateTime, NaiveDate, Utc };
+use uuid::Uuid;
+
+use crate::types::{ AuditFilter, AuditSubject };
 
 #[derive(Debug, Clone, Copy, InputObject)]
 pub struct TimeRangeInput {
     pub start: f64,
     pub end: f64,
 }
+
+/// When a recurring absence happens. Skipped occurrences still count towards
+/// `count`.
+#[derive(Debug, Clone, InputObject)]
+pub struct RecurrenceRuleInput {
+    pub start: NaiveDate,
+    /// 1 for every week, 2 for every other week, and so on.
+    #[graphql(default = 1)]
+    pub interval_weeks: i32,
+    /// ISO weekdays, where 1 is Monday. Defaults to the weekday of `start`.
+    pub weekdays: Option<Vec<i32>>,
+    pub until: Option<NaiveDate>,
+    pub count: Option<i32>,
+}
+
+/// What to narrow the audit log down to. Every filter that's set has to
+/// match.
+#[derive(Debug, Clone, Default, InputObject)]
+pub struct AuditLogFilter {
+    /// A mutation's path, like `updateTeacherAbsence`. This also matches the
+    /// mutations nested under it, so `webhooks` matches `webhooks.create`.
+    pub mutation: Option<String>,
+    pub request_id: Option<Uuid>,
+    pub client_id: Option<Uuid>,
+    pub teacher_id: Option<Uuid>,
+    pub subject: Option<AuditSubject>,
+    pub subject_id: Option<Uuid>,
+    pub success: Option<bool>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+impl From<AuditLogFilter> for AuditFilter {
+    fn from(filter: AuditLogFilter) -> Self {
+        Self {
+            mutation: filter.mutation,
+            request_id: filter.request_id,
+            client_id: filter.client_id,
+            teacher_id: filter.teacher_id,
+            subject: filter.subject,
+            subject_id: filter.subject_id,
+            success: filter.success,
+            since: filter.since,
+            until: filter.until,
+        }
+    }
+}
+
+/// Which page of a long list to get.
+#[derive(Debug, Clone, Copy, InputObject)]
+pub struct PageInput {
+    #[graphql(default = 0)]
+    pub offset: i32,
+    #[graphql(default = 50)]
+    pub limit: i32,
+}
+impl Default for PageInput {
+    fn default() -> Self {
+        Self { offset: 0, limit: 50 }
+    }
+}
@@ -38,15 +38,19 @@
 //!       Periods, Absences, etc)
 //!     - [`state`] for a way to globally store the Schema and the database
 //!       connection pool
+//!     - [`events`] for pushing changes out to GraphQL subscribers
+//!     - [`scheduler`] for the daily absence board rollover
+//!     - [`sheet_sync`] for mirroring the absence board to a spreadsheet
+//!     - [`webhooks`] for notifying other services of events
+//!     - [`calendar_feed`] for the iCalendar feeds of future absences
+//!     - [`roster`] for bulk importing and exporting teachers
+//!     - [`digest`] for the daily email digest of absences
 //!     - [`logs_env::logging`] for all logging in the crate
 //!     - [`logs_env::env`] for pre-checking all of the environment variables on
 //!       server startup
 //! 
 //! 
 //! ## Things Left to Do
-//! - Outward communication for notifs
-//!     - Redis?
-//!     - `POST` request to separate web server?
 //! - Mirroring to the school server
 //!     - Internal?
 //!     - Redis?
@@ -67,3 +71,10 @@ pub mod logs_env;
 pub use logs_env::*;
 
 pub mod metrics;
+pub mod events;
+pub mod scheduler;
+pub mod sheet_sync;
+pub mod webhooks;
+pub mod calendar_feed;
+pub mod roster;
+pub mod digest;
@@ -17,7 +17,7 @@ pub mod logging {
     //! - [`shortened`] for displayable shortened strings
     //! 
     //! Usually you should just import all of it with
-    //! ```no_run
+    //! ```ignore
     //! use crate::logging::*;
     //! ```
 
@@ -30,7 +30,7 @@ pub mod logging {
     /// 
     /// This function is relatively fault-tolerant, and will default to the full
     /// string if it can't shorten it correctly.
-    pub fn shortened(string: &str, max_len: usize) -> Shortened {
+    pub fn shortened(string: &str, max_len: usize) -> Shortened<'_> {
         let (display_name, shortened) =  if string.chars().count() >= max_len {
             if let Some((idx, _)) = string.char_indices().nth(max_len-3) {
                 (&string[..idx], true)
@@ -119,6 +119,177 @@ pub mod env {
             PORT, GRAPHQL_COMPLEXITY_LIMIT
     );
 
+    /// Get the time of day, in the database's time zone, to run the day
+    /// rollover at from the optional `ROLLOVER_TIME` environment variable
+    /// (`HH:MM`, 24 hour).
+    /// 
+    /// Defaults to 05:00 if unset, and returns `None` if it is set to `off` to
+    /// disable the scheduled rollover entirely.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if it is set to anything else that isn't a valid time, so a typo
+    /// can't quietly move the rollover.
+    pub fn rollover_time() -> Option<chrono::NaiveTime> {
+        const DEFAULT: chrono::NaiveTime = match chrono::NaiveTime::from_hms_opt(5, 0, 0) {
+            Some(time) => time,
+            None => chrono::NaiveTime::MIN,
+        };
+
+        let Ok(time) = std::env::var("ROLLOVER_TIME") else {
+            return Some(DEFAULT);
+        };
+        if time.trim().eq_ignore_ascii_case("off") {
+            return None;
+        }
+
+        match chrono::NaiveTime::parse_from_str(time.trim(), "%H:%M") {
+            Ok(time) => Some(time),
+            Err(e) => {
+                crate::logging::error!("Failed to parse ROLLOVER_TIME as HH:MM or off");
+                crate::logging::debug!("ROLLOVER_TIME: {time:?} ({e})");
+                panic!("Failed to parse ROLLOVER_TIME as HH:MM or off");
+            },
+        }
+    }
+
+    /// Parse an optional environment variable, falling back to `default` if it
+    /// is unset or invalid.
+    fn parse_or<T>(name: &str, default: T) -> T
+    where
+        T: std::str::FromStr + std::fmt::Display,
+        T::Err: std::fmt::Display,
+    {
+        let Ok(value) = std::env::var(name) else {
+            return default;
+        };
+
+        match value.trim().parse() {
+            Ok(value) => value,
+            Err(e) => {
+                crate::logging::warn!("Failed to parse {name} {value:?} ({e}), using default of {default}");
+                default
+            },
+        }
+    }
+
+    /// How long a verified client's scopes are cached for, from the optional
+    /// `SCOPE_CACHE_TTL_SECS` environment variable. Defaults to 60 seconds,
+    /// and 0 disables the cache.
+    pub fn scope_cache_ttl() -> std::time::Duration {
+        std::time::Duration::from_secs(parse_or("SCOPE_CACHE_TTL_SECS", 60))
+    }
+
+    /// The most clients to cache scopes for at once, from the optional
+    /// `SCOPE_CACHE_CAPACITY` environment variable. Defaults to 1024.
+    pub fn scope_cache_capacity() -> usize {
+        parse_or("SCOPE_CACHE_CAPACITY", 1024)
+    }
+
+    /// The default number of requests per minute a client may make, from the
+    /// optional `RATE_LIMIT_PER_MINUTE` environment variable. Defaults to 300,
+    /// and 0 disables the limit.
+    /// 
+    /// This can be overridden for each client.
+    pub fn rate_limit_per_minute() -> u32 {
+        parse_or("RATE_LIMIT_PER_MINUTE", 300)
+    }
+
+    /// The number of requests per minute a single IP may make without a
+    /// verified client id and secret, from the optional `RATE_LIMIT_ANONYMOUS_PER_MINUTE` environment
+    /// variable. Defaults to 60, and 0 disables the limit.
+    pub fn rate_limit_anonymous_per_minute() -> u32 {
+        parse_or("RATE_LIMIT_ANONYMOUS_PER_MINUTE", 60)
+    }
+
+    /// Whether to trust `Forwarded`/`X-Forwarded-For` headers for the IP of
+    /// anonymous requests, from the optional `RATE_LIMIT_TRUST_PROXY`
+    /// environment variable. Only enable this behind a reverse proxy that sets
+    /// them, otherwise they can be spoofed. Defaults to false.
+    pub fn rate_limit_trust_proxy() -> bool {
+        parse_or("RATE_LIMIT_TRUST_PROXY", false)
+    }
+
+    /// How long a session token from `login` is valid for, from the optional
+    /// `SESSION_TTL_MINUTES` environment variable. Defaults to 60 minutes.
+    pub fn session_ttl() -> std::time::Duration {
+        std::time::Duration::from_secs(60 * parse_or("SESSION_TTL_MINUTES", 60))
+    }
+
+    /// How many days after today calendar feeds include, from the optional
+    /// `CALENDAR_FEED_DAYS` environment variable. Defaults to 365, which is
+    /// also the most it can be.
+    pub fn calendar_feed_days() -> u64 {
+        parse_or("CALENDAR_FEED_DAYS", 365).min(365)
+    }
+
+    /// Whether to mirror the absence board to the configured spreadsheet,
+    /// from the optional `SHEET_SYNC_ENABLED` environment variable. Defaults
+    /// to false.
+    pub fn sheet_sync_enabled() -> bool {
+        parse_or("SHEET_SYNC_ENABLED", false)
+    }
+
+    /// Whether to also apply edits made in the spreadsheet back to the
+    /// absence board, from the optional `SHEET_SYNC_PULL` environment
+    /// variable. Defaults to false.
+    pub fn sheet_sync_pull() -> bool {
+        parse_or("SHEET_SYNC_PULL", false)
+    }
+
+    /// How often the spreadsheet is synced even without any changes, from the
+    /// optional `SHEET_SYNC_INTERVAL_SECS` environment variable. Defaults to 5
+    /// minutes.
+    pub fn sheet_sync_interval() -> std::time::Duration {
+        std::time::Duration::from_secs(parse_or("SHEET_SYNC_INTERVAL_SECS", 300).max(1))
+    }
+
+    /// How long the absence board has to go without changes before it's
+    /// pushed, from the optional `SHEET_SYNC_DEBOUNCE_SECS` environment
+    /// variable. Defaults to 5 seconds.
+    pub fn sheet_sync_debounce() -> std::time::Duration {
+        std::time::Duration::from_secs(parse_or("SHEET_SYNC_DEBOUNCE_SECS", 5))
+    }
+
+    /// The longest a burst of changes can hold back a push, from the optional
+    /// `SHEET_SYNC_DEBOUNCE_MAX_SECS` environment variable. Defaults to 60
+    /// seconds.
+    pub fn sheet_sync_debounce_max() -> std::time::Duration {
+        std::time::Duration::from_secs(parse_or("SHEET_SYNC_DEBOUNCE_MAX_SECS", 60))
+    }
+
+    /// How long to wait for each spreadsheet request, from the optional
+    /// `SHEET_SYNC_TIMEOUT_SECS` environment variable. Defaults to 30 seconds.
+    pub fn sheet_sync_timeout() -> std::time::Duration {
+        std::time::Duration::from_secs(parse_or("SHEET_SYNC_TIMEOUT_SECS", 30))
+    }
+
+    /// How many times a failed push or pull is retried, from the optional
+    /// `SHEET_SYNC_RETRIES` environment variable. Defaults to 3.
+    pub fn sheet_sync_retries() -> u32 {
+        parse_or("SHEET_SYNC_RETRIES", 3)
+    }
+
+    /// How often the webhook outbox is checked for deliveries to send, from
+    /// the optional `WEBHOOK_POLL_SECS` environment variable. Defaults to 2
+    /// seconds.
+    pub fn webhook_poll_interval() -> std::time::Duration {
+        std::time::Duration::from_secs(parse_or("WEBHOOK_POLL_SECS", 2).max(1))
+    }
+
+    /// How many times a webhook delivery is attempted before it's given up
+    /// on, from the optional `WEBHOOK_MAX_ATTEMPTS` environment variable.
+    /// Defaults to 8, which keeps retrying for about 2 hours.
+    pub fn webhook_max_attempts() -> u32 {
+        parse_or("WEBHOOK_MAX_ATTEMPTS", 8).max(1)
+    }
+
+    /// How long to wait for a webhook endpoint to respond, from the optional
+    /// `WEBHOOK_TIMEOUT_SECS` environment variable. Defaults to 10 seconds.
+    pub fn webhook_timeout() -> std::time::Duration {
+        std::time::Duration::from_secs(parse_or("WEBHOOK_TIMEOUT_SECS", 10))
+    }
+
     pub mod sql {
         use arcs_env_rs::*;
 
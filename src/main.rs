@@ -1,9 +1,10 @@
 use actix_web::web::Header;
-use actix_web::{HttpServer, web, HttpResponse, http::header::ContentType, Responder};
+use actix_web::{HttpServer, HttpRequest, web, HttpResponse, http::header::ContentType, Responder};
 
 use async_graphql::http::{playground_source, GraphQLPlaygroundConfig};
-use improved_eureka::verification::{ClientSecretHeader, ClientIdHeader};
+use improved_eureka::verification::{ClientSecretHeader, ClientIdHeader, SessionTokenHeader};
 use improved_eureka::graphql::Schema;
+use improved_eureka::state::AppState;
 
 use improved_eureka::logging::*;
 
@@ -15,15 +16,20 @@ async fn main() -> std::io::Result<()> {
     let sender = setup::metrics();
 
     let clean_up_logging = setup::env_and_logging();
+    let state = setup::state(sender.clone()).await;
+    setup::scheduler(state.clone());
+    setup::sheet_sync(state.clone());
+    setup::webhooks(state.clone());
+
     let schema = setup::data(
         Some("./schema.graphql"),
-        sender.clone(),
+        state.clone(),
     ).await;
     let bind_to = setup::get_bind().await;
 
 
     let server = HttpServer::new(
-        move || setup::app(schema.clone(), None, sender.clone())
+        move || setup::app(schema.clone(), None, sender.clone(), state.clone())
     ).bind(bind_to)?.run();
 
     let (result, _) = tokio::join!(
@@ -35,7 +41,7 @@ async fn main() -> std::io::Result<()> {
     result
 }
 
-use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse};
+use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse, GraphQLSubscription};
 
 /// This route handles all of the GraphQL requests. It's essentially the basis
 /// of the API.
@@ -50,21 +56,90 @@ async fn graphql_handler(
 
     client_id: Option<Header<ClientIdHeader>>,
     client_secret: Option<Header<ClientSecretHeader>>,
+    session_token: Option<Header<SessionTokenHeader>>,
 ) -> GraphQLResponse {    
-    let request = augment_request(request.into_inner(), client_id, client_secret).await;
+    let request = augment_request(request.into_inner(), client_id, client_secret, session_token).await;
     schema.execute(request).await.into()
 }
 
 
+/// This route handles GraphQL subscriptions over a websocket, using either the
+/// `graphql-ws` or `graphql-transport-ws` protocol.
+/// 
+/// Browsers can't set headers on websocket requests, so the client id, secret
+/// and session token can also be passed as `clientId`, `clientSecret` and
+/// `sessionToken` in the `connection_init` payload.
+#[actix_web::get("/graphql", name = "graphql_subscription_handler")]
+async fn graphql_subscription_handler(
+    request: HttpRequest,
+    payload: web::Payload,
+    schema: web::Data<Schema>,
+
+    client_id: Option<Header<ClientIdHeader>>,
+    client_secret: Option<Header<ClientSecretHeader>>,
+    session_token: Option<Header<SessionTokenHeader>>,
+) -> actix_web::Result<HttpResponse> {
+    let mut data = async_graphql::Data::default();
+    if let (Some(id), Some(secret)) = (client_id, client_secret) {
+        data.insert(id.0);
+        data.insert(secret.0);
+    }
+    if let Some(token) = session_token {
+        data.insert(token.0);
+    }
+
+    GraphQLSubscription::new(Schema::clone(&schema))
+        .with_data(data)
+        .on_connection_init(subscription_connection_init)
+        .start(&request, payload)
+}
+
+async fn subscription_connection_init(payload: serde_json::Value) -> async_graphql::Result<async_graphql::Data> {
+    use tokio::sync::OnceCell;
+    use improved_eureka::types::Session;
+    use improved_eureka::verification::scopes::Scopes;
+
+    let mut data = async_graphql::Data::default();
+    data.insert(OnceCell::<Scopes>::new());
+    data.insert(OnceCell::<Option<Session>>::new());
+
+    if let Some(token) = payload.get("sessionToken").and_then(serde_json::Value::as_str) {
+        data.insert(SessionTokenHeader::new(token.to_string()));
+    }
+
+    let (Some(id), Some(secret)) = (
+        payload.get("clientId").and_then(serde_json::Value::as_str),
+        payload.get("clientSecret").and_then(serde_json::Value::as_str),
+    ) else {
+        return Ok(data);
+    };
+
+    let Ok(id) = uuid::Uuid::parse_str(id) else {
+        return Err(async_graphql::Error::new("Invalid clientId in connection payload"));
+    };
+
+    data.insert(ClientIdHeader::new(id));
+    data.insert(ClientSecretHeader::new(secret.as_bytes().to_vec()));
+    Ok(data)
+}
+
+
 pub async fn augment_request(
     request: async_graphql::Request,
     client_id: Option<Header<ClientIdHeader>>,
     client_secret: Option<Header<ClientSecretHeader>>,
+    session_token: Option<Header<SessionTokenHeader>>,
 ) -> async_graphql::Request {
     use tokio::sync::OnceCell;
+    use improved_eureka::types::Session;
     use improved_eureka::verification::scopes::Scopes;
     let scopes_once_cell: OnceCell<Scopes> = OnceCell::new();
-    let request = request.data(scopes_once_cell);
+    let session_once_cell: OnceCell<Option<Session>> = OnceCell::new();
+    let mut request = request.data(scopes_once_cell).data(session_once_cell);
+
+    if let Some(token) = session_token {
+        request = request.data(token.0);
+    }
 
     if let (Some(id), Some(secret)) = (client_id, client_secret) {
         request.data(id.0).data(secret.0)
@@ -74,6 +149,26 @@ pub async fn augment_request(
 }
 
 
+/// This route serves the iCalendar feed with a token, for calendar apps to
+/// subscribe to. See `improved_eureka::calendar_feed` for how feeds are built.
+#[actix_web::get("/calendar/{token}.ics", name = "calendar_feed_handler")]
+async fn calendar_feed_handler(
+    token: web::Path<String>,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    match improved_eureka::calendar_feed::render(&state, &token).await {
+        Ok(Some(calendar)) => HttpResponse::Ok()
+            .content_type("text/calendar; charset=utf-8")
+            .body(calendar),
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            error!("Failed to build calendar feed: {e}");
+            HttpResponse::InternalServerError().finish()
+        },
+    }
+}
+
+
 /// This endpoint (`/`) handles serving the
 /// [`graphiql`][https://www.gatsbyjs.com/docs/how-to/querying-data/running-queries-with-graphiql/]
 /// interface for testing queries with Tablejet's API.
@@ -84,6 +179,7 @@ pub async fn augment_request(
 async fn interactive() -> impl Responder {
 
     let config = GraphQLPlaygroundConfig::new("/graphql")
+        .subscription_endpoint("/graphql")
         .title("TableJet Interactive GraphQL API");
 
     let html_response = playground_source(config);
@@ -99,6 +195,7 @@ async fn interactive() -> impl Responder {
 mod setup {
     use arcs_logging_rs::default_logging_targets_with_size_limit;
     use improved_eureka::graphql::Schema;
+    use improved_eureka::state::AppState;
 
     /// This function sets up the environment using dotenvy and initializes the
     /// logging system.
@@ -149,13 +246,48 @@ mod setup {
         unwrap_connection(db_conn)
     }
 
-    /// Gets the graphql schema (with the associated db context) for the server
-    fn schema(db: sqlx::PgPool, metrics: improved_eureka::metrics::MetricProducer) -> improved_eureka::graphql::Schema {
-        use improved_eureka::state::AppState;
-        use improved_eureka::graphql::schema;
+    /// Gets the shared app state (the db pool, metrics, and event bus) for the
+    /// server
+    pub async fn state(metrics: improved_eureka::metrics::MetricProducer) -> AppState {
+        let db = db().await;
+        AppState::new(db, metrics)
+    }
+
+    /// Starts the daily rollover task, unless `ROLLOVER_TIME` is set to `off`
+    pub fn scheduler(state: AppState) {
+        use improved_eureka::scheduler::DayRollover;
+
+        if let Some(at) = improved_eureka::env::rollover_time() {
+            DayRollover::new(state, at).spawn();
+        } else {
+            improved_eureka::logging::info!("Scheduled day rollover is disabled");
+        }
+    }
+
+    /// Starts mirroring the absence board to the configured spreadsheet, if
+    /// `SHEET_SYNC_ENABLED` is set
+    pub fn sheet_sync(state: AppState) {
+        use improved_eureka::sheet_sync::{ GoogleSheetsSink, SheetSync, SheetSyncConfig };
 
-        let ctx: AppState = AppState::new(db, metrics);
-        schema(ctx)
+        if !improved_eureka::env::sheet_sync_enabled() {
+            improved_eureka::logging::info!("Spreadsheet sync is disabled");
+            return;
+        }
+
+        match GoogleSheetsSink::from_env() {
+            Ok(sink) => SheetSync::new(state, std::sync::Arc::new(sink), SheetSyncConfig::from_env()).spawn(),
+            Err(e) => improved_eureka::logging::error!("Spreadsheet sync is disabled, failed to set up Google Sheets: {e}"),
+        }
+    }
+
+    /// Starts sending the deliveries in the webhook outbox
+    pub fn webhooks(state: AppState) {
+        use improved_eureka::webhooks::WebhookDispatcher;
+
+        match WebhookDispatcher::new(state) {
+            Ok(dispatcher) => dispatcher.spawn(),
+            Err(e) => improved_eureka::logging::error!("Webhooks are disabled, failed to set up the dispatcher: {e}"),
+        }
     }
 
 
@@ -163,10 +295,9 @@ mod setup {
     /// the application builder.
     pub async fn data(
         save_schema: Option<&str>,
-        metrics: improved_eureka::metrics::MetricProducer,
+        state: AppState,
     ) -> actix_web::web::Data<Schema> {
-        let db = db().await;
-        let schema = schema(db, metrics);
+        let schema = improved_eureka::graphql::schema(state);
         if let Some(path) = save_schema {
             improved_eureka::graphql::save_schema(&schema, path);
         }
@@ -200,12 +331,14 @@ mod setup {
     use actix_web::dev::{ ServiceFactory, ServiceRequest, ServiceResponse };
     use actix_web::body::MessageBody;
     use improved_eureka::metrics::{ MetricProducer, middleware::ResponseTimeRecorder };
+    use improved_eureka::verification::rate_limit::RateLimiter;
 
     /// This function creates an instance of an actix App
     pub fn app(
         schema: actix_web::web::Data<Schema>,
         cors: Option<actix_cors::Cors>,
         metrics: MetricProducer,
+        state: AppState,
     ) -> App<impl ServiceFactory<
         ServiceRequest,
         Response = ServiceResponse<impl MessageBody>,
@@ -215,9 +348,13 @@ mod setup {
     >> {
         actix_web::App::new()
             .wrap(cors.unwrap_or_else(default_cors))
+            .wrap(RateLimiter::new(state.clone()))
             .wrap(ResponseTimeRecorder::new(metrics))
             .app_data(schema)
+            .app_data(actix_web::web::Data::new(state))
             .service(super::graphql_handler)
+            .service(super::graphql_subscription_handler)
+            .service(super::calendar_feed_handler)
             .service(super::interactive)
     }
 }
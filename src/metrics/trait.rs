@@ -69,7 +69,7 @@ impl Metrics for ResponseTimeMap {
         let p = p.clamp(0.0, 1.0);
 
         let mut sorted: Vec<_> = self.iter().collect();
-        sorted.sort_by(|(a, _), (b, _)| a.cmp(b));
+        sorted.sort_by_key(|(a, _)| *a);
 
         let index = if p == 1.0 {
             self.recorded().saturating_sub(1)
@@ -0,0 +1,435 @@
+//! This module reads and writes whole teacher rosters as CSV or JSON, for the
+//! `importTeachers` mutation and `exportTeachers` query.
+//!
+//! A CSV roster has these columns:
+//! - `id`: an existing teacher's id to update them, or empty to add a teacher
+//! - `honorific`, `first` and `last`
+//! - `middle`: middle names separated by `;`, with hidden ones in parentheses,
+//!   like `Ann;(Marie)`
+//! - `pronouns`: `sub/obj/pos_adj/pos_pro/refx`, like `they/them/their/theirs/themself`
+//! - `plural`: whether the pronouns are grammatically plural
+//! - `oauth`: `provider:sub` accounts separated by `;`
+//!
+//! A JSON roster is an array of objects shaped like the `addTeacher` inputs,
+//! with an optional `id` and an `oauth` array of `{ provider, sub }`.
+
+use std::collections::HashSet;
+
+use serde::{ Deserialize, Serialize };
+use uuid::Uuid;
+
+use crate::types::{ Honorific, OauthLink, PronounSet, RosterEntry, RosterFormat, RosterRowError, TeacherName };
+
+
+/// The longest pronoun or OAuth provider the database can store.
+const MAX_SHORT_FIELD_LEN: usize = 63;
+/// The longest OAuth subject the database can store.
+const MAX_SUB_LEN: usize = 255;
+
+#[derive(Serialize, Deserialize)]
+struct CsvRow {
+    #[serde(default)]
+    id: Option<Uuid>,
+    honorific: String,
+    first: String,
+    #[serde(default)]
+    middle: String,
+    last: String,
+    pronouns: String,
+    #[serde(default)]
+    plural: bool,
+    #[serde(default)]
+    oauth: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonMiddleName {
+    name: String,
+    vis: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonPronounSet {
+    sub: String,
+    obj: String,
+    pos_adj: String,
+    pos_pro: String,
+    refx: String,
+    gramm_plu: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonOauthLink {
+    provider: String,
+    sub: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonEntry {
+    #[serde(default)]
+    id: Option<Uuid>,
+    honorific: String,
+    first: String,
+    #[serde(default)]
+    middle: Vec<JsonMiddleName>,
+    last: String,
+    pronouns: JsonPronounSet,
+    #[serde(default)]
+    oauth: Vec<JsonOauthLink>,
+}
+
+
+/// Parses a roster, returning every valid entry along with its row, and an
+/// error for every invalid row.
+pub fn parse(format: RosterFormat, data: &str) -> (Vec<(usize, RosterEntry)>, Vec<RosterRowError>) {
+    let rows = match format {
+        RosterFormat::Csv => parse_csv(data),
+        RosterFormat::Json => parse_json(data),
+    };
+
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+    let mut ids = HashSet::new();
+    let mut accounts = HashSet::new();
+
+    for (row, entry) in rows {
+        let entry = entry.and_then(|entry| {
+            if let Some(id) = entry.id {
+                if !ids.insert(id) {
+                    return Err(format!("Teacher {id} is in the roster more than once"));
+                }
+            }
+            for link in &entry.oauth {
+                if !accounts.insert((link.provider.clone(), link.sub.clone())) {
+                    return Err(format!("{} account {} is in the roster more than once", link.provider, link.sub));
+                }
+            }
+            Ok(entry)
+        });
+
+        match entry {
+            Ok(entry) => entries.push((row, entry)),
+            Err(message) => errors.push(RosterRowError { row, message }),
+        }
+    }
+
+    (entries, errors)
+}
+
+fn parse_csv(data: &str) -> Vec<(usize, Result<RosterEntry, String>)> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data.as_bytes());
+
+    reader
+        .deserialize::<CsvRow>()
+        .enumerate()
+        .map(|(idx, row)| {
+            let entry = row
+                .map_err(|e| e.to_string())
+                .and_then(entry_from_csv);
+            (idx + 1, entry)
+        })
+        .collect()
+}
+
+fn entry_from_csv(row: CsvRow) -> Result<RosterEntry, String> {
+    let middle = row.middle
+        .split(';')
+        .map(str::trim)
+        .filter(|middle| !middle.is_empty())
+        .map(|middle| match middle.strip_prefix('(').and_then(|middle| middle.strip_suffix(')')) {
+            Some(hidden) => (false, hidden.trim().to_string()),
+            None => (true, middle.to_string()),
+        })
+        .collect();
+
+    let pronouns: Vec<_> = row.pronouns.split('/').map(str::trim).collect();
+    let [sub, obj, pos_adj, pos_pro, refx] = pronouns.as_slice() else {
+        return Err(format!("Pronouns must be sub/obj/pos_adj/pos_pro/refx, got {}", row.pronouns));
+    };
+    let pronouns = PronounSet::new(
+        sub.to_string(), obj.to_string(),
+        pos_adj.to_string(), pos_pro.to_string(),
+        refx.to_string(), row.plural,
+    );
+
+    let oauth = row.oauth
+        .split(';')
+        .map(str::trim)
+        .filter(|link| !link.is_empty())
+        .map(|link| match link.split_once(':') {
+            Some((provider, sub)) => Ok(OauthLink { provider: provider.trim().to_string(), sub: sub.trim().to_string() }),
+            None => Err(format!("OAuth accounts must be provider:sub, got {link}")),
+        })
+        .collect::<Result<_, _>>()?;
+
+    validate_entry(row.id, &row.honorific, row.first, middle, row.last, pronouns, oauth)
+}
+
+fn parse_json(data: &str) -> Vec<(usize, Result<RosterEntry, String>)> {
+    let values: Vec<serde_json::Value> = match serde_json::from_str(data) {
+        Ok(values) => values,
+        Err(e) => return vec![(0, Err(format!("Roster must be a JSON array: {e}")))],
+    };
+
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(idx, value)| {
+            let entry = serde_json::from_value::<JsonEntry>(value)
+                .map_err(|e| e.to_string())
+                .and_then(|entry| {
+                    let JsonPronounSet { sub, obj, pos_adj, pos_pro, refx, gramm_plu } = entry.pronouns;
+
+                    validate_entry(
+                        entry.id,
+                        &entry.honorific,
+                        entry.first,
+                        entry.middle.into_iter().map(|JsonMiddleName { name, vis }| (vis, name)).collect(),
+                        entry.last,
+                        PronounSet::new(sub, obj, pos_adj, pos_pro, refx, gramm_plu),
+                        entry.oauth.into_iter().map(|JsonOauthLink { provider, sub }| OauthLink { provider, sub }).collect(),
+                    )
+                });
+            (idx + 1, entry)
+        })
+        .collect()
+}
+
+fn validate_entry(
+    id: Option<Uuid>,
+    honorific: &str,
+    first: String,
+    middle: Vec<(bool, String)>,
+    last: String,
+    pronouns: PronounSet,
+    oauth: Vec<OauthLink>,
+) -> Result<RosterEntry, String> {
+    let honorific = Honorific::try_from_str(honorific.trim().trim_end_matches('.'))
+        .ok_or_else(|| format!("Invalid honorific {honorific}"))?;
+
+    if first.trim().is_empty() || last.trim().is_empty() {
+        return Err("First and last names can't be empty".to_string());
+    }
+    if middle.iter().any(|(_, name)| name.trim().is_empty()) {
+        return Err("Middle names can't be empty".to_string());
+    }
+
+    let PronounSet { sub, object, pos_adj, pos_pro, refx, .. } = &pronouns;
+    for pronoun in [sub, object, pos_adj, pos_pro, refx] {
+        if pronoun.is_empty() || pronoun.chars().count() > MAX_SHORT_FIELD_LEN {
+            return Err(format!("Pronouns must be 1 to {MAX_SHORT_FIELD_LEN} characters, got {pronoun:?}"));
+        }
+    }
+
+    let mut providers = HashSet::new();
+    for OauthLink { provider, sub } in &oauth {
+        if provider.is_empty() || provider.chars().count() > MAX_SHORT_FIELD_LEN {
+            return Err(format!("OAuth providers must be 1 to {MAX_SHORT_FIELD_LEN} characters, got {provider:?}"));
+        }
+        if sub.is_empty() || sub.chars().count() > MAX_SUB_LEN {
+            return Err(format!("OAuth subjects must be 1 to {MAX_SUB_LEN} characters, got {sub:?}"));
+        }
+        if !providers.insert(provider) {
+            return Err(format!("Teachers can only have one {provider} account"));
+        }
+    }
+
+    Ok(RosterEntry {
+        id,
+        name: TeacherName::new(honorific, first.trim().to_string(), last.trim().to_string(), middle),
+        pronouns,
+        oauth,
+    })
+}
+
+
+/// Writes a roster in a format that [`parse`] can read back.
+pub fn write(format: RosterFormat, entries: &[RosterEntry]) -> Result<String, String> {
+    match format {
+        RosterFormat::Csv => write_csv(entries),
+        RosterFormat::Json => write_json(entries),
+    }
+}
+
+fn write_csv(entries: &[RosterEntry]) -> Result<String, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+
+    for entry in entries {
+        let middle: Vec<_> = entry.name
+            .all_middles()
+            .map(|(visible, name)| if visible { name.to_string() } else { format!("({name})") })
+            .collect();
+        let PronounSet { sub, object, pos_adj, pos_pro, refx, gramm_plu } = &entry.pronouns;
+        let oauth: Vec<_> = entry.oauth
+            .iter()
+            .map(|OauthLink { provider, sub }| format!("{provider}:{sub}"))
+            .collect();
+
+        writer
+            .serialize(CsvRow {
+                id: entry.id,
+                honorific: entry.name.get_honorific().str().to_string(),
+                first: entry.name.get_first().to_string(),
+                middle: middle.join(";"),
+                last: entry.name.get_last().to_string(),
+                pronouns: format!("{sub}/{object}/{pos_adj}/{pos_pro}/{refx}"),
+                plural: *gramm_plu,
+                oauth: oauth.join(";"),
+            })
+            .map_err(|e| e.to_string())?;
+    }
+
+    let data = writer.into_inner().map_err(|e| e.to_string())?;
+    String::from_utf8(data).map_err(|e| e.to_string())
+}
+
+fn write_json(entries: &[RosterEntry]) -> Result<String, String> {
+    let entries: Vec<_> = entries
+        .iter()
+        .map(|entry| {
+            let PronounSet { sub, object, pos_adj, pos_pro, refx, gramm_plu } = entry.pronouns.clone();
+
+            JsonEntry {
+                id: entry.id,
+                honorific: entry.name.get_honorific().str().to_string(),
+                first: entry.name.get_first().to_string(),
+                middle: entry.name
+                    .all_middles()
+                    .map(|(vis, name)| JsonMiddleName { name: name.to_string(), vis })
+                    .collect(),
+                last: entry.name.get_last().to_string(),
+                pronouns: JsonPronounSet { sub, obj: object, pos_adj, pos_pro, refx, gramm_plu },
+                oauth: entry.oauth
+                    .iter()
+                    .map(|OauthLink { provider, sub }| JsonOauthLink { provider: provider.clone(), sub: sub.clone() })
+                    .collect(),
+            }
+        })
+        .collect();
+
+    serde_json::to_string_pretty(&entries).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries() -> Vec<RosterEntry> {
+        vec![
+            RosterEntry {
+                id: Some(Uuid::from_u128(1)),
+                name: TeacherName::new(
+                    Honorific::Mx,
+                    "Alex".to_string(),
+                    "O'Brien, Jr".to_string(),
+                    vec![(true, "Ann".to_string()), (false, "Marie".to_string())],
+                ),
+                pronouns: PronounSet::new(
+                    "they".to_string(), "them".to_string(),
+                    "their".to_string(), "theirs".to_string(),
+                    "themself".to_string(), true,
+                ),
+                oauth: vec![
+                    OauthLink { provider: "google".to_string(), sub: "1234".to_string() },
+                    OauthLink { provider: "microsoft".to_string(), sub: "abc:def".to_string() },
+                ],
+            },
+            RosterEntry {
+                id: None,
+                name: TeacherName::new(Honorific::Dr, "Sam".to_string(), "Lee".to_string(), Vec::new()),
+                pronouns: PronounSet::new(
+                    "she".to_string(), "her".to_string(),
+                    "her".to_string(), "hers".to_string(),
+                    "herself".to_string(), false,
+                ),
+                oauth: Vec::new(),
+            },
+        ]
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let entries = entries();
+        let data = write(RosterFormat::Csv, &entries).expect("roster should write");
+
+        let (parsed, errors) = parse(RosterFormat::Csv, &data);
+
+        assert_eq!(errors, Vec::new());
+        assert_eq!(parsed, entries.into_iter().enumerate().map(|(idx, entry)| (idx + 1, entry)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let entries = entries();
+        let data = write(RosterFormat::Json, &entries).expect("roster should write");
+
+        let (parsed, errors) = parse(RosterFormat::Json, &data);
+
+        assert_eq!(errors, Vec::new());
+        assert_eq!(parsed, entries.into_iter().enumerate().map(|(idx, entry)| (idx + 1, entry)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn parses_csv() {
+        let data = "\
+id,honorific,first,middle,last,pronouns,plural,oauth
+,Ms.,  Jo  ,Kay; (Lynn),Smith,she/her/her/hers/herself,false,google:42
+";
+
+        let (parsed, errors) = parse(RosterFormat::Csv, data);
+
+        assert_eq!(errors, Vec::new());
+        let [(row, entry)] = parsed.as_slice() else { panic!("expected one entry, got {parsed:?}") };
+        assert_eq!(*row, 1);
+        assert_eq!(entry.id, None);
+        assert_eq!(entry.name.get_honorific(), Honorific::Ms);
+        assert_eq!(entry.name.get_first(), "Jo");
+        assert_eq!(
+            entry.name.all_middles().map(|(visible, name)| (visible, name.to_string())).collect::<Vec<_>>(),
+            vec![(true, "Kay".to_string()), (false, "Lynn".to_string())],
+        );
+        assert_eq!(entry.pronouns.refx, "herself");
+        assert!(!entry.pronouns.gramm_plu);
+        assert_eq!(entry.oauth, vec![OauthLink { provider: "google".to_string(), sub: "42".to_string() }]);
+    }
+
+    #[test]
+    fn reports_every_bad_csv_row() {
+        let id = Uuid::from_u128(7);
+        let data = format!("\
+id,honorific,first,middle,last,pronouns,plural,oauth
+,Mx,Robin,,Hood,they/them,true,
+,Captain,Robin,,Hood,they/them/their/theirs/themself,true,
+{id},Mx,Robin,,Hood,they/them/their/theirs/themself,true,google
+{id},Mx,Robin,,Hood,they/them/their/theirs/themself,true,
+,Mx,Robin,,Hood,they/them/their/theirs/themself,true,
+");
+
+        let (parsed, errors) = parse(RosterFormat::Csv, &data);
+
+        assert_eq!(errors.iter().map(|error| error.row).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(parsed.iter().map(|(row, _)| *row).collect::<Vec<_>>(), vec![4, 5]);
+        assert!(errors[0].message.contains("Pronouns"));
+        assert!(errors[1].message.contains("honorific"));
+        assert!(errors[2].message.contains("provider:sub"));
+    }
+
+    #[test]
+    fn rejects_duplicate_ids_and_accounts() {
+        let id = Uuid::from_u128(7);
+        let data = format!("\
+id,honorific,first,middle,last,pronouns,plural,oauth
+{id},Mx,Robin,,Hood,they/them/their/theirs/themself,true,google:1
+{id},Mx,Robin,,Hood,they/them/their/theirs/themself,true,
+,Mx,Robin,,Hood,they/them/their/theirs/themself,true,google:1
+");
+
+        let (parsed, errors) = parse(RosterFormat::Csv, &data);
+
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(errors.iter().map(|error| error.row).collect::<Vec<_>>(), vec![2, 3]);
+    }
+}
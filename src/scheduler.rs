@@ -0,0 +1,209 @@
+//! This module contains the day rollover task, which keeps the absence board
+//! correct for each new school day without needing an external cron job.
+//!
+//! Each rollover, in a single transaction:
+//! - clears every period's temporary time
+//! - clears yesterday's substitute coverage
+//! - applies today's approved future absences if it's a school day (see
+//!   [`flush_today`][crate::database::prepared::future_absences::flush_today])
+//! - records the absence board before and after in the audit log
+//!
+//! Then it records the outcome in `rollover_runs`, sends it to webhooks, and
+//! publishes the changes to subscribers. The manual `syncAndFlushFutures`
+//! mutation runs exactly the same [`run`].
+//!
+//! The rollover time is read on the database's clock, since that's what
+//! decides `CURRENT_DATE` for every query about today. If the server was down
+//! when a rollover was due, it catches up as soon as it starts.
+//!
+//! After a successful scheduled rollover on a school day, the absence digest
+//! is emailed (see [`digest`][crate::digest]).
+
+use std::time::Duration;
+
+use chrono::{ NaiveTime, Timelike };
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::database::Ctx;
+use crate::database::prepared::audit_log::audited;
+use crate::events::Event;
+use crate::logging::*;
+use crate::state::AppState;
+use crate::types::{ AuditOrigin, AuditTarget, RolloverTrigger };
+
+
+/// How long to wait before trying again when the next rollover time can't be
+/// read from the database.
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+
+
+/// A background task that runs a day rollover at the same local time every
+/// day.
+#[derive(Clone)]
+pub struct DayRollover {
+    state: AppState,
+    at: NaiveTime,
+}
+
+impl DayRollover {
+    pub fn new(state: AppState, at: NaiveTime) -> Self {
+        Self { state, at }
+    }
+
+    pub fn spawn(self) {
+        tokio::spawn(self.start());
+    }
+
+    pub async fn start(self) {
+        info!("Day rollover scheduled for {} database time", self.at);
+
+        match self.missed().await {
+            Ok(true) => {
+                info!("Today's day rollover was missed, catching up");
+                if let Err(e) = run(&self.state, RolloverTrigger::CatchUp, &system_origin(RolloverTrigger::CatchUp)).await {
+                    error!("Catch-up day rollover failed: {e}");
+                }
+            },
+            Ok(false) => (),
+            Err(e) => error!("Failed to check for a missed day rollover: {e}"),
+        }
+
+        loop {
+            let wait = match self.until_next().await {
+                Ok(wait) => wait,
+                Err(e) => {
+                    error!("Failed to get the time until the next day rollover, retrying in a minute: {e}");
+                    tokio::time::sleep(RETRY_DELAY).await;
+                    continue;
+                },
+            };
+            debug!("Next day rollover in {:.0} seconds", wait.as_secs_f64());
+            tokio::time::sleep(wait).await;
+
+            match run(&self.state, RolloverTrigger::Scheduled, &system_origin(RolloverTrigger::Scheduled)).await {
+                Ok(_) => self.send_digest().await,
+                Err(e) => error!("Scheduled day rollover failed: {e}"),
+            }
+        }
+    }
+
+    fn at_seconds(&self) -> f64 {
+        f64::from(self.at.num_seconds_from_midnight())
+    }
+
+    async fn missed(&self) -> Result<bool, sqlx::Error> {
+        use crate::database::prepared::rollovers::rollover_missed;
+
+        let mut db_conn = self.state.db().acquire().await?;
+        rollover_missed(&mut db_conn, self.at_seconds()).await
+    }
+
+    /// Gets the time until the next rollover on the database's clock. The
+    /// wait is at least a second, so a rollover can't run twice in a row.
+    async fn until_next(&self) -> Result<Duration, sqlx::Error> {
+        use crate::database::prepared::rollovers::seconds_until_rollover;
+
+        let mut db_conn = self.state.db().acquire().await?;
+        let seconds = seconds_until_rollover(&mut db_conn, self.at_seconds()).await?;
+
+        Ok(Duration::from_secs_f64(seconds.max(1.0)))
+    }
+
+    /// Emails today's absence digest, if SMTP is set up and today is a school
+    /// day.
+    async fn send_digest(&self) {
+        use crate::database::prepared::calendar::is_school_day_today;
+
+        let Some(mailer) = self.state.digest() else {
+            return;
+        };
+
+        let result = async {
+            let mut db_conn = self.state.db().acquire().await?;
+
+            if !is_school_day_today(&mut db_conn).await? {
+                debug!("Not sending the absence digest, today isn't a school day");
+                return Ok(0);
+            }
+
+            mailer.send(&mut db_conn).await
+        }.await;
+
+        if let Err(e) = result {
+            error!("Failed to send the absence digest: {e}");
+        }
+    }
+}
+
+/// Who to audit a rollover the scheduler runs on its own as.
+fn system_origin(trigger: RolloverTrigger) -> AuditOrigin {
+    AuditOrigin::system("rollover", json!({ "trigger": trigger.str() }))
+}
+
+/// Run a full day rollover, record its outcome, and publish the resulting
+/// changes to subscribers. `origin` is who the change is audited as.
+///
+/// Returns the ids of the teachers whose absences were updated.
+pub async fn run(state: &AppState, trigger: RolloverTrigger, origin: &AuditOrigin) -> Result<Vec<Uuid>, sqlx::Error> {
+    use crate::database::prepared::rollovers::record_rollover;
+
+    let mut db_conn = state.db().acquire().await?;
+
+    info!("Starting {} day rollover", trigger.str());
+    let outcome = rollover(&mut db_conn, trigger, origin).await;
+
+    match &outcome {
+        Ok(updated) => info!("Day rollover finished, {} teachers updated", updated.len()),
+        Err(e) => error!("Day rollover failed: {e}"),
+    }
+
+    if let Err(e) = record_rollover(&mut db_conn, trigger, &outcome).await {
+        error!("Failed to record day rollover outcome: {e}");
+    }
+    if let Err(rollover_error) = &outcome {
+        if let Err(e) = crate::webhooks::notify_flush_failed(&mut db_conn, trigger, rollover_error).await {
+            error!("Failed to queue webhooks for the failed day rollover: {e}");
+        }
+    }
+
+    let updated = outcome?;
+    if let Err(e) = publish_changes(&mut db_conn, state, &updated).await {
+        error!("Failed to publish day rollover changes: {e}");
+    }
+
+    Ok(updated)
+}
+
+/// Applies the rollover in one transaction, so a failure part way through
+/// leaves the board as it was. Its webhook events and audit log entry are
+/// written in the same transaction.
+async fn rollover(db_conn: &mut Ctx, trigger: RolloverTrigger, origin: &AuditOrigin) -> Result<Vec<Uuid>, sqlx::Error> {
+    use crate::database::prepared::period::flush_all_temp_times;
+    use crate::database::prepared::future_absences::flush_today;
+    use crate::database::prepared::coverage::clear_all_coverage;
+
+    audited(db_conn, origin, AuditTarget::AbsenceBoard, |txn| Box::pin(async move {
+        flush_all_temp_times(txn).await?;
+        clear_all_coverage(txn).await?;
+        let updated = flush_today(txn).await?;
+        crate::webhooks::notify_flush_completed(txn, trigger, &updated).await?;
+
+        Ok(updated)
+    })).await
+}
+
+async fn publish_changes(db_conn: &mut Ctx, state: &AppState, updated: &[Uuid]) -> Result<(), sqlx::Error> {
+    use crate::database::prepared::period::get_all_periods;
+    use crate::database::prepared::teacher::get_teacher;
+
+    for period in get_all_periods(db_conn).await? {
+        state.events().publish(Event::PeriodTimeChanged(period));
+    }
+    for id in updated.iter().copied() {
+        let teacher = get_teacher(db_conn, id).await?;
+        state.events().publish(Event::TeacherAbsenceChanged(teacher));
+    }
+
+    Ok(())
+}
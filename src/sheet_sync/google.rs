@@ -0,0 +1,408 @@
+//! This module contains [`GoogleSheetsSink`], which mirrors the absence board
+//! through the Google Sheets API.
+//!
+//! It's configured with optional environment variables:
+//! - `SHEETS_API_URL`: where the Sheets API is, defaulting to
+//!   `https://sheets.googleapis.com`. Point this at a local stand-in to test.
+//! - `SHEET_SYNC_RANGE`: the sheet (tab) to write to, defaulting to
+//!   `Absences`
+//! - `GOOGLE_SERVICE_ACCOUNT_FILE`: a service account's JSON key, which the
+//!   spreadsheet needs to be shared with. Without it, requests are sent
+//!   without credentials, which only works with a stand-in.
+//!
+//! Requests time out after [`sheet_sync_timeout`][crate::env::sheet_sync_timeout].
+
+use std::time::{ Duration, Instant };
+
+use futures_util::future::BoxFuture;
+use jsonwebtoken::{ Algorithm, EncodingKey, Header };
+use reqwest::{ RequestBuilder, Response, Url };
+use serde::{ Deserialize, Serialize };
+use tokio::sync::Mutex;
+
+use crate::logging::*;
+
+use super::{ SheetSink, SheetSyncError };
+
+
+const DEFAULT_API_URL: &str = "https://sheets.googleapis.com";
+const DEFAULT_RANGE: &str = "Absences";
+const SHEETS_SCOPE: &str = "https://www.googleapis.com/auth/spreadsheets";
+
+/// How long before an access token expires that it's replaced.
+const TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+
+#[derive(Deserialize)]
+struct ServiceAccountKey {
+    client_email: String,
+    private_key: String,
+    token_uri: String,
+}
+
+struct ServiceAccount {
+    client_email: String,
+    token_uri: String,
+    key: EncodingKey,
+}
+
+#[derive(Serialize)]
+struct TokenClaims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: i64,
+    exp: i64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ValueRange<'a> {
+    major_dimension: &'a str,
+    values: &'a [Vec<String>],
+}
+
+#[derive(Deserialize)]
+struct ValueRangeResponse {
+    #[serde(default)]
+    values: Vec<Vec<serde_json::Value>>,
+}
+
+
+pub struct GoogleSheetsSink {
+    client: reqwest::Client,
+    api_url: String,
+    range: String,
+    account: Option<ServiceAccount>,
+    token: Mutex<Option<(String, Instant)>>,
+}
+
+impl GoogleSheetsSink {
+    pub fn from_env() -> Result<Self, String> {
+        let api_url = std::env::var("SHEETS_API_URL").unwrap_or_else(|_| DEFAULT_API_URL.to_string());
+        let range = std::env::var("SHEET_SYNC_RANGE").unwrap_or_else(|_| DEFAULT_RANGE.to_string());
+
+        let account = match std::env::var("GOOGLE_SERVICE_ACCOUNT_FILE") {
+            Ok(path) => Some(load_service_account(&path)?),
+            Err(_) => {
+                warn!("GOOGLE_SERVICE_ACCOUNT_FILE not set, spreadsheet requests will be sent without credentials");
+                None
+            },
+        };
+
+        Self::new(api_url, range, account, crate::env::sheet_sync_timeout())
+    }
+
+    fn new(api_url: String, range: String, account: Option<ServiceAccount>, timeout: Duration) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            client,
+            api_url: api_url.trim_end_matches('/').to_string(),
+            range,
+            account,
+            token: Mutex::new(None),
+        })
+    }
+
+    /// Gets the URL of a range's values, with an optional `:method` suffix.
+    fn values_url(&self, sheet_id: &str, range: &str, method: Option<&str>) -> Result<Url, SheetSyncError> {
+        let mut url = Url::parse(&self.api_url)
+            .map_err(|e| SheetSyncError::Request(format!("Invalid SHEETS_API_URL: {e}")))?;
+
+        let last = match method {
+            Some(method) => format!("{range}:{method}"),
+            None => range.to_string(),
+        };
+
+        url.path_segments_mut()
+            .map_err(|_| SheetSyncError::Request("Invalid SHEETS_API_URL".to_string()))?
+            .extend(["v4", "spreadsheets", sheet_id, "values", &last]);
+
+        Ok(url)
+    }
+
+    /// Gets an access token for the service account, reusing the last one
+    /// until it's about to expire.
+    async fn access_token(&self) -> Result<Option<String>, SheetSyncError> {
+        let Some(account) = &self.account else {
+            return Ok(None);
+        };
+
+        let mut cached = self.token.lock().await;
+        if let Some((token, expires)) = cached.as_ref() {
+            if Instant::now() + TOKEN_EXPIRY_MARGIN < *expires {
+                return Ok(Some(token.clone()));
+            }
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let claims = TokenClaims {
+            iss: &account.client_email,
+            scope: SHEETS_SCOPE,
+            aud: &account.token_uri,
+            iat: now,
+            exp: now + 60 * 60,
+        };
+        let assertion = jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &account.key)
+            .map_err(|e| SheetSyncError::Auth(e.to_string()))?;
+
+        let response = self.client
+            .post(&account.token_uri)
+            .form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", &assertion),
+            ])
+            .send()
+            .await
+            .map_err(|e| SheetSyncError::Auth(e.to_string()))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(SheetSyncError::Auth(format!("Token exchange failed with {status}: {body}")));
+        }
+
+        let TokenResponse { access_token, expires_in } = response
+            .json()
+            .await
+            .map_err(|e| SheetSyncError::Auth(e.to_string()))?;
+
+        *cached = Some((access_token.clone(), Instant::now() + Duration::from_secs(expires_in)));
+        Ok(Some(access_token))
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<Response, SheetSyncError> {
+        let request = match self.access_token().await? {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        };
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| SheetSyncError::Request(e.to_string()))?;
+
+        if response.status().is_success() {
+            Ok(response)
+        } else {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            Err(SheetSyncError::Request(format!("{status}: {body}")))
+        }
+    }
+}
+
+fn load_service_account(path: &str) -> Result<ServiceAccount, String> {
+    let contents = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {path}: {e}"))?;
+    let key: ServiceAccountKey = serde_json::from_str(&contents).map_err(|e| format!("Failed to parse {path}: {e}"))?;
+
+    Ok(ServiceAccount {
+        key: EncodingKey::from_rsa_pem(key.private_key.as_bytes()).map_err(|e| format!("Invalid private key in {path}: {e}"))?,
+        client_email: key.client_email,
+        token_uri: key.token_uri,
+    })
+}
+
+impl SheetSink for GoogleSheetsSink {
+    fn push<'a>(&'a self, sheet_id: &'a str, rows: &'a [Vec<String>]) -> BoxFuture<'a, Result<(), SheetSyncError>> {
+        Box::pin(async move {
+            let clear_url = self.values_url(sheet_id, &self.range, Some("clear"))?;
+            self.send(self.client.post(clear_url).json(&serde_json::json!({}))).await?;
+
+            let mut update_url = self.values_url(sheet_id, &format!("{}!A1", self.range), None)?;
+            update_url.query_pairs_mut().append_pair("valueInputOption", "RAW");
+            let body = ValueRange { major_dimension: "ROWS", values: rows };
+            self.send(self.client.put(update_url).json(&body)).await?;
+
+            Ok(())
+        })
+    }
+
+    fn pull<'a>(&'a self, sheet_id: &'a str) -> BoxFuture<'a, Result<Vec<Vec<String>>, SheetSyncError>> {
+        Box::pin(async move {
+            let url = self.values_url(sheet_id, &self.range, None)?;
+            let response: ValueRangeResponse = self.send(self.client.get(url))
+                .await?
+                .json()
+                .await
+                .map_err(|e| SheetSyncError::Request(e.to_string()))?;
+
+            let rows = response.values
+                .into_iter()
+                .map(|row| row
+                    .into_iter()
+                    .map(|cell| match cell {
+                        serde_json::Value::String(cell) => cell,
+                        cell => cell.to_string(),
+                    })
+                    .collect()
+                )
+                .collect();
+
+            Ok(rows)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::io::{ AsyncReadExt, AsyncWriteExt };
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    /// A request the stand-in received.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct Received {
+        method: String,
+        path: String,
+        body: String,
+    }
+
+    /// Starts a stand-in for the Sheets API that answers every request with
+    /// `status` and `body`, or never answers if `status` is `None`. Returns
+    /// its URL and the requests it receives.
+    async fn stand_in(status: Option<u16>, body: &'static str) -> (String, Arc<std::sync::Mutex<Vec<Received>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("stand-in should bind");
+        let url = format!("http://{}", listener.local_addr().expect("stand-in should have an address"));
+        let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        let log = received.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut data = Vec::new();
+                let mut buf = [0; 4096];
+
+                let (head, body_start) = loop {
+                    let Ok(read) = stream.read(&mut buf).await else { return };
+                    if read == 0 {
+                        return;
+                    }
+                    data.extend_from_slice(&buf[..read]);
+                    if let Some(end) = data.windows(4).position(|window| window == b"\r\n\r\n") {
+                        break (String::from_utf8_lossy(&data[..end]).to_string(), end + 4);
+                    }
+                };
+
+                let content_length = head
+                    .lines()
+                    .filter_map(|line| line.split_once(':'))
+                    .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                    .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+                    .unwrap_or(0);
+                while data.len() < body_start + content_length {
+                    let Ok(read) = stream.read(&mut buf).await else { return };
+                    if read == 0 {
+                        break;
+                    }
+                    data.extend_from_slice(&buf[..read]);
+                }
+
+                let mut request_line = head.lines().next().unwrap_or_default().split(' ');
+                log.lock().expect("log lock").push(Received {
+                    method: request_line.next().unwrap_or_default().to_string(),
+                    path: request_line.next().unwrap_or_default().to_string(),
+                    body: String::from_utf8_lossy(&data[body_start..]).to_string(),
+                });
+
+                match status {
+                    Some(status) => {
+                        let response = format!(
+                            "HTTP/1.1 {status} Stand-in\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                            body.len(),
+                        );
+                        let _ = stream.write_all(response.as_bytes()).await;
+                    },
+                    // Hold the connection open without answering
+                    None => tokio::time::sleep(Duration::from_secs(60)).await,
+                }
+            }
+        });
+
+        (url, received)
+    }
+
+    fn sink(url: String, timeout: Duration) -> GoogleSheetsSink {
+        GoogleSheetsSink::new(url, "Absences".to_string(), None, timeout).expect("client should build")
+    }
+
+    #[tokio::test]
+    async fn push_clears_then_writes_rows() {
+        let (url, received) = stand_in(Some(200), "{}").await;
+        let rows = vec![
+            vec!["Teacher ID".to_string(), "Name".to_string()],
+            vec!["1".to_string(), "Mx. Doe".to_string()],
+        ];
+
+        sink(url, Duration::from_secs(5)).push("sheet-1", &rows).await.expect("push should succeed");
+
+        let received = received.lock().expect("log lock").clone();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].method, "POST");
+        assert_eq!(received[0].path, "/v4/spreadsheets/sheet-1/values/Absences:clear");
+        assert_eq!(received[1].method, "PUT");
+        assert_eq!(received[1].path, "/v4/spreadsheets/sheet-1/values/Absences!A1?valueInputOption=RAW");
+
+        let body: serde_json::Value = serde_json::from_str(&received[1].body).expect("body should be JSON");
+        assert_eq!(body, serde_json::json!({
+            "majorDimension": "ROWS",
+            "values": [["Teacher ID", "Name"], ["1", "Mx. Doe"]],
+        }));
+    }
+
+    #[tokio::test]
+    async fn pull_reads_cells_as_text() {
+        let (url, received) = stand_in(Some(200), r#"{"range":"Absences!A1:D2","values":[["Teacher ID","Fully Absent"],["1",true,3]]}"#).await;
+
+        let rows = sink(url, Duration::from_secs(5)).pull("sheet-1").await.expect("pull should succeed");
+
+        assert_eq!(rows, vec![
+            vec!["Teacher ID".to_string(), "Fully Absent".to_string()],
+            vec!["1".to_string(), "true".to_string(), "3".to_string()],
+        ]);
+        let received = received.lock().expect("log lock").clone();
+        assert_eq!(received[0].method, "GET");
+        assert_eq!(received[0].path, "/v4/spreadsheets/sheet-1/values/Absences");
+    }
+
+    #[tokio::test]
+    async fn pull_of_empty_sheet() {
+        let (url, _) = stand_in(Some(200), r#"{"range":"Absences!A1:Z1000"}"#).await;
+
+        let rows = sink(url, Duration::from_secs(5)).pull("sheet-1").await.expect("pull should succeed");
+
+        assert!(rows.is_empty());
+    }
+
+    #[tokio::test]
+    async fn error_responses_fail() {
+        let (url, _) = stand_in(Some(403), r#"{"error":"forbidden"}"#).await;
+
+        let error = sink(url, Duration::from_secs(5)).push("sheet-1", &[]).await.expect_err("push should fail");
+
+        assert!(matches!(&error, SheetSyncError::Request(message) if message.contains("403")), "{error}");
+    }
+
+    #[tokio::test]
+    async fn requests_time_out() {
+        let (url, _) = stand_in(None, "").await;
+
+        let started = Instant::now();
+        let error = sink(url, Duration::from_millis(200)).pull("sheet-1").await.expect_err("pull should time out");
+
+        assert!(matches!(error, SheetSyncError::Request(_)), "{error}");
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
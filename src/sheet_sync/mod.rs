@@ -0,0 +1,432 @@
+//! This module contains the spreadsheet mirror, which keeps the spreadsheet in
+//! `config.sheet_id` in sync with the absence board.
+//!
+//! The board is written as one row per teacher, under a header row:
+//!
+//! | Teacher ID | Name | Fully Absent | Periods |
+//! |------------|------|--------------|---------|
+//! | `<uuid>` | Mx. Jane Doe | FALSE | 1st, 3rd |
+//!
+//! The [`SheetSync`] worker pushes the board a few seconds after it changes
+//! (see [`sheet_sync_debounce`][crate::env::sheet_sync_debounce]), or at most
+//! [`sheet_sync_debounce_max`][crate::env::sheet_sync_debounce_max] after the
+//! first change if it keeps changing, and on a fixed interval. With `SHEET_SYNC_PULL` enabled, it also pulls the sheet on
+//! that interval, and applies rows that were edited since the last push. Rows
+//! that weren't edited are left alone, so a pull can't undo changes that
+//! haven't been pushed yet.
+//!
+//! Spreadsheets are reached through a [`SheetSink`], so the worker doesn't
+//! depend on Google. [`GoogleSheetsSink`] talks to the Sheets API, or to a
+//! local stand-in at `SHEETS_API_URL`.
+//!
+//! Failed pushes and pulls are retried with exponential backoff, and every
+//! outcome is recorded in `sheet_sync_runs` for the `lastSheetSync` query.
+
+mod google;
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+use futures_util::StreamExt;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::database::Ctx;
+use crate::database::prepared::audit_log::audited;
+use crate::events::{ ConfigField, Event };
+use crate::logging::*;
+use crate::state::AppState;
+use crate::types::{ AuditOrigin, AuditTarget, SheetSyncDirection };
+
+pub use google::GoogleSheetsSink;
+
+
+/// The header row of the mirrored sheet.
+pub const HEADER: [&str; 4] = ["Teacher ID", "Name", "Fully Absent", "Periods"];
+
+/// How long to wait before the first retry. Each retry after that waits twice
+/// as long as the last.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+
+#[derive(Debug)]
+pub enum SheetSyncError {
+    /// The spreadsheet couldn't be reached, or rejected the request.
+    Request(String),
+    /// Credentials for the spreadsheet couldn't be loaded or exchanged.
+    Auth(String),
+    Database(sqlx::Error),
+}
+
+impl std::fmt::Display for SheetSyncError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Request(e) => write!(f, "Spreadsheet request failed: {e}"),
+            Self::Auth(e) => write!(f, "Spreadsheet authentication failed: {e}"),
+            Self::Database(e) => write!(f, "Database error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for SheetSyncError {}
+
+impl From<sqlx::Error> for SheetSyncError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Database(e)
+    }
+}
+
+
+/// Somewhere the absence board can be mirrored to, as rows of cells.
+pub trait SheetSink: Send + Sync {
+    /// Replaces every row of the sheet with `rows`.
+    fn push<'a>(&'a self, sheet_id: &'a str, rows: &'a [Vec<String>]) -> BoxFuture<'a, Result<(), SheetSyncError>>;
+
+    /// Reads every row of the sheet. Rows may be missing trailing empty cells.
+    fn pull<'a>(&'a self, sheet_id: &'a str) -> BoxFuture<'a, Result<Vec<Vec<String>>, SheetSyncError>>;
+}
+
+
+/// How the [`SheetSync`] worker behaves.
+#[derive(Debug, Clone, Copy)]
+pub struct SheetSyncConfig {
+    pub pull: bool,
+    pub interval: Duration,
+    pub debounce: Duration,
+    /// The longest a push waits for changes to stop.
+    pub debounce_max: Duration,
+    pub retries: u32,
+}
+
+impl SheetSyncConfig {
+    pub fn from_env() -> Self {
+        use crate::env::{ sheet_sync_pull, sheet_sync_interval, sheet_sync_debounce, sheet_sync_debounce_max, sheet_sync_retries };
+
+        Self {
+            pull: sheet_sync_pull(),
+            interval: sheet_sync_interval(),
+            debounce: sheet_sync_debounce(),
+            debounce_max: sheet_sync_debounce_max(),
+            retries: sheet_sync_retries(),
+        }
+    }
+}
+
+
+/// A background task that mirrors the absence board to the configured
+/// spreadsheet.
+pub struct SheetSync {
+    state: AppState,
+    sink: Arc<dyn SheetSink>,
+    config: SheetSyncConfig,
+
+    /// The rows last pushed to each sheet, keyed by teacher id, which pulls
+    /// are compared against to find edited rows.
+    last_pushed: Option<(String, HashMap<Uuid, Vec<String>>)>,
+}
+
+impl SheetSync {
+    pub fn new(state: AppState, sink: Arc<dyn SheetSink>, config: SheetSyncConfig) -> Self {
+        Self { state, sink, config, last_pushed: None }
+    }
+
+    pub fn spawn(self) {
+        tokio::spawn(self.start());
+    }
+
+    pub async fn start(mut self) {
+        info!(
+            "Spreadsheet sync started, every {} seconds{}",
+            self.config.interval.as_secs(),
+            if self.config.pull { " with pulls" } else { "" },
+        );
+
+        let mut events = std::pin::pin!(self.state.events().stream());
+        let mut interval = tokio::time::interval(self.config.interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    if self.config.pull {
+                        log_failure(SheetSyncDirection::Pull, self.pull().await);
+                    }
+                    log_failure(SheetSyncDirection::Push, self.push().await);
+                },
+                Some(event) = events.next() => {
+                    if !changes_board(&event) {
+                        continue;
+                    }
+
+                    // Wait for a quiet moment so a burst of changes is only
+                    // pushed once, but don't let constant changes hold the
+                    // push back forever.
+                    let deadline = tokio::time::Instant::now() + self.config.debounce_max;
+                    while let Ok(Ok(Some(_))) = tokio::time::timeout_at(
+                        deadline,
+                        tokio::time::timeout(self.config.debounce, events.next()),
+                    ).await {}
+
+                    log_failure(SheetSyncDirection::Push, self.push().await);
+                },
+            }
+        }
+    }
+
+    /// Writes the current absence board to the spreadsheet, retrying if it
+    /// fails. Does nothing if no spreadsheet is configured.
+    ///
+    /// No database connection is held while the spreadsheet is being written,
+    /// so slow or failing requests don't tie up the pool.
+    pub async fn push(&mut self) -> Result<(), SheetSyncError> {
+        let (sheet_id, rows) = {
+            let mut db_conn = self.state.db().acquire().await?;
+
+            let Some(sheet_id) = configured_sheet(&mut db_conn).await? else {
+                return Ok(());
+            };
+            (sheet_id, board_rows(&mut db_conn).await?)
+        };
+
+        let (attempts, outcome) = self.retrying(|| self.sink.push(&sheet_id, &rows)).await;
+        let outcome = outcome.map(|_| rows.len() - 1);
+
+        let mut db_conn = self.state.db().acquire().await?;
+        record(&mut db_conn, &sheet_id, SheetSyncDirection::Push, attempts, &outcome).await;
+
+        if outcome.is_ok() {
+            let pushed = rows.into_iter().skip(1).filter_map(keyed_row).collect();
+            self.last_pushed = Some((sheet_id, pushed));
+        }
+
+        outcome.map(|_| ())
+    }
+
+    /// Reads the spreadsheet and applies every row that was edited since the
+    /// last push, retrying if it can't be read. Does nothing if no spreadsheet
+    /// is configured or nothing has been pushed to it yet.
+    ///
+    /// Like [`push`][Self::push], no database connection is held while the
+    /// spreadsheet is being read.
+    pub async fn pull(&self) -> Result<(), SheetSyncError> {
+        let sheet_id = {
+            let mut db_conn = self.state.db().acquire().await?;
+
+            let Some(sheet_id) = configured_sheet(&mut db_conn).await? else {
+                return Ok(());
+            };
+            sheet_id
+        };
+        let Some(last_pushed) = self.last_pushed
+            .as_ref()
+            .filter(|(pushed_to, _)| *pushed_to == sheet_id)
+            .map(|(_, rows)| rows)
+        else {
+            debug!("Skipping spreadsheet pull, nothing has been pushed to {sheet_id} yet");
+            return Ok(());
+        };
+
+        let (attempts, rows) = self.retrying(|| self.sink.pull(&sheet_id)).await;
+
+        let mut db_conn = self.state.db().acquire().await?;
+        let outcome = match rows {
+            Ok(rows) => apply_rows(&self.state, &mut db_conn, last_pushed, rows).await,
+            Err(e) => Err(e),
+        };
+        record(&mut db_conn, &sheet_id, SheetSyncDirection::Pull, attempts, &outcome).await;
+
+        outcome.map(|_| ())
+    }
+
+    /// Runs a request to the sink, retrying with backoff if it fails. Returns
+    /// the number of attempts made along with the final outcome.
+    async fn retrying<T, F, Fut>(&self, mut request: F) -> (u32, Result<T, SheetSyncError>)
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, SheetSyncError>>,
+    {
+        let mut attempts = 0;
+
+        loop {
+            attempts += 1;
+
+            match request().await {
+                Err(e) if attempts <= self.config.retries => {
+                    let wait = RETRY_BASE_DELAY * 2u32.saturating_pow(attempts - 1);
+                    warn!("Spreadsheet request failed, retrying in {} seconds: {e}", wait.as_secs());
+                    tokio::time::sleep(wait).await;
+                },
+                outcome => return (attempts, outcome),
+            }
+        }
+    }
+}
+
+fn log_failure(direction: SheetSyncDirection, outcome: Result<(), SheetSyncError>) {
+    if let Err(e) = outcome {
+        error!("Spreadsheet {} failed: {e}", direction.str());
+    }
+}
+
+/// Whether an event might change what's on the sheet.
+fn changes_board(event: &Event) -> bool {
+    matches!(event, Event::TeacherAbsenceChanged(_) | Event::ConfigChanged(ConfigField::SpreadsheetId))
+}
+
+async fn configured_sheet(db_conn: &mut Ctx) -> Result<Option<String>, sqlx::Error> {
+    use crate::database::prepared::config::get_sheet_id;
+
+    let sheet_id = get_sheet_id(db_conn).await?;
+    Ok(Some(sheet_id.trim().to_string()).filter(|sheet_id| !sheet_id.is_empty()))
+}
+
+async fn record(
+    db_conn: &mut Ctx,
+    sheet_id: &str,
+    direction: SheetSyncDirection,
+    attempts: u32,
+    outcome: &Result<usize, SheetSyncError>,
+) {
+    use crate::database::prepared::sheet_syncs::record_sheet_sync;
+
+    let outcome = outcome.as_ref().map(|rows| *rows).map_err(|e| e.to_string());
+    if let Err(e) = record_sheet_sync(db_conn, sheet_id, direction, attempts, &outcome).await {
+        error!("Failed to record spreadsheet {} outcome: {e}", direction.str());
+    }
+}
+
+
+/// Pads a row out to the full width of the sheet and trims its cells, so rows
+/// read back from the sheet can be compared with pushed ones.
+fn normalize_row(row: Vec<String>) -> Vec<String> {
+    let mut row: Vec<_> = row.into_iter().map(|cell| cell.trim().to_string()).collect();
+    row.resize(HEADER.len(), String::new());
+    row
+}
+
+fn keyed_row(row: Vec<String>) -> Option<(Uuid, Vec<String>)> {
+    let row = normalize_row(row);
+    let id = Uuid::parse_str(&row[0]).ok()?;
+    Some((id, row))
+}
+
+/// Gets the absence board as rows for the sheet, starting with the header.
+async fn board_rows(db_conn: &mut Ctx) -> Result<Vec<Vec<String>>, sqlx::Error> {
+    use crate::database::prepared::absences::get_all_absences;
+    use crate::database::prepared::period::get_all_periods;
+    use crate::database::prepared::teacher::get_all_teachers;
+
+    let mut periods = get_all_periods(db_conn).await?;
+    periods.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    let mut absent_in: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+    for absence in get_all_absences(db_conn).await? {
+        absent_in.entry(absence.teacher).or_default().push(absence.period);
+    }
+
+    let mut teachers = get_all_teachers(db_conn).await?;
+    teachers.sort_by(|a, b| {
+        (a.get_name().get_last(), a.get_name().get_first(), a.get_id())
+            .cmp(&(b.get_name().get_last(), b.get_name().get_first(), b.get_id()))
+    });
+
+    let header = HEADER.iter().map(|cell| cell.to_string()).collect();
+    let rows = teachers.into_iter().map(|teacher| {
+        let absent_periods = absent_in.remove(&teacher.get_id()).unwrap_or_default();
+        let period_names: Vec<_> = periods
+            .iter()
+            .filter(|period| absent_periods.contains(&period.id))
+            .map(|period| period.name.as_str())
+            .collect();
+
+        vec![
+            teacher.get_id().to_string(),
+            teacher.get_name().to_string(),
+            if teacher.get_fully_absent() { "TRUE" } else { "FALSE" }.to_string(),
+            period_names.join(", "),
+        ]
+    });
+
+    Ok(std::iter::once(header).chain(rows).collect())
+}
+
+fn parse_fully_absent(cell: &str) -> Option<bool> {
+    match cell.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "y" | "x" | "1" => Some(true),
+        "false" | "no" | "n" | "" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Applies the rows pulled from the sheet that differ from the ones last
+/// pushed, returning how many teachers were updated. Rows that can't be
+/// understood are skipped. Each row is applied and audited in its own
+/// transaction.
+async fn apply_rows(
+    state: &AppState,
+    db_conn: &mut Ctx,
+    last_pushed: &HashMap<Uuid, Vec<String>>,
+    rows: Vec<Vec<String>>,
+) -> Result<usize, SheetSyncError> {
+    use crate::database::prepared::absences::update_absences_for_teacher;
+    use crate::database::prepared::period::get_all_periods;
+    use crate::database::prepared::teacher::get_teacher;
+
+    let periods = get_all_periods(db_conn).await?;
+    let period_id = |name: &str| periods
+        .iter()
+        .find(|period| {
+            period.name.eq_ignore_ascii_case(name) ||
+            period.short_name.as_deref().is_some_and(|short| short.eq_ignore_ascii_case(name))
+        })
+        .map(|period| period.id);
+
+    // Every row applied by one pull is audited under the same request ID
+    let origin = AuditOrigin::system("sheetSync", json!({ "direction": "pull" }));
+    let mut updated = 0;
+
+    for (id, row) in rows.into_iter().skip(1).filter_map(keyed_row) {
+        let Some(pushed) = last_pushed.get(&id) else {
+            warn!("Skipping spreadsheet row for unknown teacher {id}");
+            continue;
+        };
+        if pushed[2..] == row[2..] {
+            continue;
+        }
+
+        let Some(fully_absent) = parse_fully_absent(&row[2]) else {
+            warn!("Skipping spreadsheet row for teacher {id}, can't read {:?} as fully absent", row[2]);
+            continue;
+        };
+
+        let period_ids: Option<Vec<_>> = row[3]
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(period_id)
+            .collect();
+        let Some(period_ids) = period_ids else {
+            warn!("Skipping spreadsheet row for teacher {id}, unknown period in {:?}", row[3]);
+            continue;
+        };
+
+        audited(db_conn, &origin, AuditTarget::Teacher(id), move |txn| Box::pin(async move {
+            update_absences_for_teacher(txn, id, &period_ids, fully_absent, None).await?;
+            crate::webhooks::notify_absence_set(txn, id, "spreadsheet").await
+        })).await?;
+
+        let teacher = get_teacher(db_conn, id).await?;
+        state.events().publish(Event::TeacherAbsenceChanged(teacher));
+
+        updated += 1;
+    }
+
+    if updated > 0 {
+        info!("Applied {updated} edited rows from the spreadsheet");
+    }
+
+    Ok(updated)
+}
@@ -1,15 +1,45 @@
 use sqlx::PgPool;
 
+use crate::digest::DigestMailer;
+use crate::events::EventBus;
 use crate::metrics::MetricProducer;
+use crate::verification::oidc::OidcVerifier;
+use crate::verification::rate_limit::RateLimits;
+use crate::verification::scope_cache::ScopeCache;
+use crate::verification::session::SessionSigner;
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct WebContext {
     db: PgPool,
     metrics: MetricProducer,
+    events: EventBus,
+    scope_cache: ScopeCache,
+    rate_limits: RateLimits,
+    oidc: OidcVerifier,
+    sessions: SessionSigner,
+    digest: Option<DigestMailer>,
 }
 impl WebContext {
     pub fn new(db: PgPool, metrics: MetricProducer) -> Self {
-        Self { db, metrics }
+        use crate::env::{
+            scope_cache_ttl, scope_cache_capacity,
+            rate_limit_per_minute, rate_limit_anonymous_per_minute, rate_limit_trust_proxy,
+        };
+
+        Self {
+            db,
+            metrics,
+            events: EventBus::new(),
+            scope_cache: ScopeCache::new(scope_cache_ttl(), scope_cache_capacity()),
+            rate_limits: RateLimits::new(
+                rate_limit_per_minute(),
+                rate_limit_anonymous_per_minute(),
+                rate_limit_trust_proxy(),
+            ),
+            oidc: OidcVerifier::from_env(),
+            sessions: SessionSigner::from_env(),
+            digest: DigestMailer::from_env(),
+        }
     }
 }
 
@@ -27,6 +57,30 @@ impl AppState {
     pub fn metrics(&self) -> &MetricProducer {
         &self.0.metrics
     }
-}
 
+    pub fn events(&self) -> &EventBus {
+        &self.0.events
+    }
+
+    pub fn scope_cache(&self) -> &ScopeCache {
+        &self.0.scope_cache
+    }
+
+    pub fn rate_limits(&self) -> &RateLimits {
+        &self.0.rate_limits
+    }
+
+    pub fn oidc(&self) -> &OidcVerifier {
+        &self.0.oidc
+    }
+
+    pub fn sessions(&self) -> &SessionSigner {
+        &self.0.sessions
+    }
+
+    /// Gets the digest mailer, if SMTP is set up.
+    pub fn digest(&self) -> Option<&DigestMailer> {
+        self.0.digest.as_ref()
+    }
+}
 
@@ -0,0 +1,45 @@
+use chrono::{ DateTime, Utc };
+use uuid::Uuid;
+
+use super::PackedAbsenceState;
+
+/// Where a future absence is in the approval workflow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, async_graphql::Enum)]
+pub enum AbsenceRequestStatus {
+    Pending,
+    Approved,
+    Rejected,
+    Cancelled,
+}
+impl AbsenceRequestStatus {
+    pub fn try_from_str(s: &str) -> Option<Self> {
+        match s {
+            "pending" => Some(Self::Pending),
+            "approved" => Some(Self::Approved),
+            "rejected" => Some(Self::Rejected),
+            "cancelled" => Some(Self::Cancelled),
+            _ => None,
+        }
+    }
+    pub fn str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Approved => "approved",
+            Self::Rejected => "rejected",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// A single day of a teacher's future absence, along with its review state.
+/// 
+/// `reviewed_by` is `None` for requests approved by a trusted client rather
+/// than a secretary or admin.
+#[derive(Debug, Clone)]
+pub struct AbsenceRequest {
+    pub absence: PackedAbsenceState,
+    pub status: AbsenceRequestStatus,
+    pub requested_at: DateTime<Utc>,
+    pub reviewed_by: Option<Uuid>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+}
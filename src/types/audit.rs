@@ -0,0 +1,170 @@
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde_json::{json, Value as JsonValue};
+use uuid::Uuid;
+
+/// What kind of thing a mutation changed, for the audit log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, async_graphql::Enum)]
+pub enum AuditSubject {
+    Teacher,
+    Period,
+    AllPeriods,
+    AbsenceBoard,
+    Config,
+    FutureAbsences,
+    RecurringAbsence,
+    Coverage,
+    Roster,
+}
+impl AuditSubject {
+    pub fn try_from_str(s: &str) -> Option<Self> {
+        match s {
+            "teacher" => Some(Self::Teacher),
+            "period" => Some(Self::Period),
+            "all_periods" => Some(Self::AllPeriods),
+            "absence_board" => Some(Self::AbsenceBoard),
+            "config" => Some(Self::Config),
+            "future_absences" => Some(Self::FutureAbsences),
+            "recurring_absence" => Some(Self::RecurringAbsence),
+            "coverage" => Some(Self::Coverage),
+            "roster" => Some(Self::Roster),
+            _ => None,
+        }
+    }
+    pub fn str(&self) -> &'static str {
+        match self {
+            Self::Teacher => "teacher",
+            Self::Period => "period",
+            Self::AllPeriods => "all_periods",
+            Self::AbsenceBoard => "absence_board",
+            Self::Config => "config",
+            Self::FutureAbsences => "future_absences",
+            Self::RecurringAbsence => "recurring_absence",
+            Self::Coverage => "coverage",
+            Self::Roster => "roster",
+        }
+    }
+}
+
+/// Exactly what a change is made to, so its state before and after can be
+/// kept in the audit log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditTarget {
+    Teacher(Uuid),
+    Period(Uuid),
+    AllPeriods,
+    AbsenceBoard,
+    Config,
+    /// A teacher's future absences from `start` to `end` (inclusive).
+    FutureAbsences { teacher: Uuid, start: NaiveDate, end: NaiveDate },
+    RecurringAbsence(Uuid),
+    /// Who is covering a teacher in a period today.
+    Coverage { teacher: Uuid, period: Uuid },
+    Roster,
+}
+impl AuditTarget {
+    pub fn subject(&self) -> AuditSubject {
+        match self {
+            Self::Teacher(_) => AuditSubject::Teacher,
+            Self::Period(_) => AuditSubject::Period,
+            Self::AllPeriods => AuditSubject::AllPeriods,
+            Self::AbsenceBoard => AuditSubject::AbsenceBoard,
+            Self::Config => AuditSubject::Config,
+            Self::FutureAbsences { .. } => AuditSubject::FutureAbsences,
+            Self::RecurringAbsence(_) => AuditSubject::RecurringAbsence,
+            Self::Coverage { .. } => AuditSubject::Coverage,
+            Self::Roster => AuditSubject::Roster,
+        }
+    }
+
+    /// The id the entry is filed under. Future absences and coverage are
+    /// filed under their teacher.
+    pub fn subject_id(&self) -> Option<Uuid> {
+        match self {
+            Self::Teacher(id) | Self::Period(id) | Self::RecurringAbsence(id) => Some(*id),
+            Self::FutureAbsences { teacher, .. } | Self::Coverage { teacher, .. } => Some(*teacher),
+            Self::AllPeriods | Self::AbsenceBoard | Self::Config | Self::Roster => None,
+        }
+    }
+}
+
+/// Who made a change and how, for the audit log.
+#[derive(Debug, Clone)]
+pub struct AuditOrigin {
+    pub request_id: Uuid,
+    /// The client that made the request, once its secret was verified.
+    pub client_id: Option<Uuid>,
+    pub teacher_id: Option<Uuid>,
+    /// The mutation that made the change, or what made it on its own, like
+    /// `rollover`.
+    pub mutation: String,
+    pub arguments: JsonValue,
+    /// Set once a change is written to the audit log, so the mutation isn't
+    /// recorded a second time when it finishes.
+    pub recorded: Arc<AtomicBool>,
+}
+impl AuditOrigin {
+    /// A change the server made on its own, like a day rollover.
+    pub fn system(source: &str, arguments: JsonValue) -> Self {
+        Self {
+            request_id: Uuid::new_v4(),
+            client_id: None,
+            teacher_id: None,
+            mutation: source.to_string(),
+            arguments,
+            recorded: Arc::default(),
+        }
+    }
+
+    /// A change made by a mutation that couldn't be traced back to its
+    /// request.
+    pub fn unknown(request_id: Uuid) -> Self {
+        Self { request_id, ..Self::system("unknown", json!({})) }
+    }
+}
+
+/// A record of a single mutation.
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    pub id: Uuid,
+    pub at: DateTime<Utc>,
+    pub request_id: Uuid,
+    pub client_id: Option<Uuid>,
+    /// The teacher who made the request, if they signed in.
+    pub teacher_id: Option<Uuid>,
+    /// The path of the mutation field, like `updateTeacherAbsence` or
+    /// `webhooks.create`.
+    pub mutation: String,
+    pub arguments: JsonValue,
+    pub subject: Option<AuditSubject>,
+    pub subject_id: Option<Uuid>,
+    pub before: Option<JsonValue>,
+    pub after: Option<JsonValue>,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+/// What to narrow the audit log down to. Every filter that's set has to
+/// match.
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    pub mutation: Option<String>,
+    pub request_id: Option<Uuid>,
+    pub client_id: Option<Uuid>,
+    pub teacher_id: Option<Uuid>,
+    pub subject: Option<AuditSubject>,
+    pub subject_id: Option<Uuid>,
+    pub success: Option<bool>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+/// One page of the audit log, newest first.
+#[derive(Debug, Clone)]
+pub struct AuditLogPage {
+    pub entries: Vec<AuditEntry>,
+    /// How many entries match the filter, across every page.
+    pub total: i64,
+}
@@ -0,0 +1,26 @@
+use chrono::NaiveDate;
+use uuid::Uuid;
+
+/// A named set of period times, like "Regular" or "Early Dismissal".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BellSchedule {
+    pub id: Uuid,
+    pub name: String,
+}
+
+/// The time of one period in a bell schedule, in seconds since midnight.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BellSchedulePeriod {
+    pub schedule_id: Uuid,
+    pub period_id: Uuid,
+
+    pub start: f64,
+    pub end: f64,
+}
+
+/// The bell schedule that's used on a date.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduleDay {
+    pub date: NaiveDate,
+    pub schedule: BellSchedule,
+}
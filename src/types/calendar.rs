@@ -0,0 +1,40 @@
+use chrono::NaiveDate;
+
+/// What kind of day a date is in the school calendar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, async_graphql::Enum)]
+pub enum CalendarDayKind {
+    Instructional,
+    Holiday,
+    Break,
+    /// A Saturday or Sunday that hasn't been made an instructional day. This
+    /// is never stored, it's only the default for weekends.
+    Weekend,
+}
+impl CalendarDayKind {
+    pub fn try_from_str(s: &str) -> Option<Self> {
+        match s {
+            "instructional" => Some(Self::Instructional),
+            "holiday" => Some(Self::Holiday),
+            "break" => Some(Self::Break),
+            "weekend" => Some(Self::Weekend),
+            _ => None,
+        }
+    }
+    pub fn str(&self) -> &'static str {
+        match self {
+            Self::Instructional => "instructional",
+            Self::Holiday => "holiday",
+            Self::Break => "break",
+            Self::Weekend => "weekend",
+        }
+    }
+}
+
+/// A date in the school calendar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CalendarDay {
+    pub date: NaiveDate,
+    pub kind: CalendarDayKind,
+    /// The name of the holiday or break, like "Thanksgiving".
+    pub name: Option<String>,
+}
@@ -0,0 +1,22 @@
+use chrono::{ DateTime, Utc };
+use uuid::Uuid;
+
+/// A subscribable iCalendar feed of future absences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CalendarFeed {
+    pub id: Uuid,
+    /// The teacher whose absences are in the feed, or `None` for the
+    /// staff-wide feed of everyone's absences.
+    pub teacher_id: Option<Uuid>,
+    /// The teacher who created the feed. It only works while they could still
+    /// create it.
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A new calendar feed along with its token, which is only ever shown once.
+#[derive(Debug, Clone)]
+pub struct CalendarFeedCredentials {
+    pub token: String,
+    pub feed: CalendarFeed,
+}
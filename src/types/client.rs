@@ -0,0 +1,30 @@
+use chrono::{ DateTime, Utc };
+use uuid::Uuid;
+
+use crate::verification::scopes::Scopes;
+
+/// An API client, as stored in the `clients` table. The client key is never
+/// exposed.
+#[derive(Debug, Clone)]
+pub struct Client {
+    pub id: Uuid,
+    pub scopes: Scopes,
+    pub description: String,
+
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub last_seen_at: Option<DateTime<Utc>>,
+
+    /// Requests per minute, or `None` for the server default.
+    pub rate_limit: Option<i32>,
+}
+
+/// A client along with its plaintext secret.
+/// 
+/// This is only ever returned right after a secret is generated, since only
+/// its hash is stored.
+#[derive(Debug, Clone)]
+pub struct ClientCredentials {
+    pub client: Client,
+    pub secret: String,
+}
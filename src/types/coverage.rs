@@ -0,0 +1,58 @@
+use uuid::Uuid;
+
+use super::{ Period, Teacher };
+
+/// Who is covering an absent teacher's period.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Substitute {
+    /// Another teacher at the school.
+    Teacher(Uuid),
+    /// A substitute from outside the school, who isn't in `teachers`.
+    External(String),
+}
+
+/// A substitute assigned to one period of a teacher's absence today.
+#[derive(Debug, Clone)]
+pub struct Coverage {
+    pub teacher_id: Uuid,
+    pub period_id: Uuid,
+    pub substitute: Substitute,
+}
+
+/// A period a teacher is absent in, so it can say who's covering them.
+#[derive(Debug, Clone)]
+pub struct AbsentPeriod {
+    pub period: Period,
+    pub teacher_id: Uuid,
+}
+
+/// A teacher absent in a period, so it can say who's covering them.
+#[derive(Debug, Clone)]
+pub struct AbsentTeacher {
+    pub teacher: Teacher,
+    pub period_id: Uuid,
+}
+
+/// Why a substitute can't be assigned to a period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoverageConflict {
+    /// The teacher isn't absent in that period, so there's nothing to cover.
+    NotAbsent,
+    /// A teacher can't cover their own absence.
+    SelfCoverage,
+    /// The substitute is absent in that period themselves.
+    SubstituteAbsent,
+    /// The substitute is already covering another teacher in that period.
+    DoubleBooked(Uuid),
+}
+
+impl std::fmt::Display for CoverageConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotAbsent => write!(f, "Teacher isn't absent in this period"),
+            Self::SelfCoverage => write!(f, "Teachers can't cover their own absence"),
+            Self::SubstituteAbsent => write!(f, "Substitute is absent in this period"),
+            Self::DoubleBooked(teacher) => write!(f, "Substitute is already covering teacher {teacher} in this period"),
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use chrono::NaiveDate;
+
+/// A period an absent teacher is out for, and where their class reports to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DigestPeriod {
+    pub name: String,
+    /// The teacher's assigned room for the period, or the global `report_to`
+    /// if they don't have one.
+    pub report_to: String,
+}
+
+/// A teacher who is out on the day of a digest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DigestEntry {
+    pub teacher: String,
+    pub fully_absent: bool,
+    /// The periods they're out for, in the order of the day. Teachers who are
+    /// out all day are out for every period.
+    pub periods: Vec<DigestPeriod>,
+}
+
+/// The daily email digest of who is out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AbsenceDigest {
+    pub date: NaiveDate,
+    /// Where students of absent teachers should go, unless the teacher has a
+    /// room assigned for the period.
+    pub report_to: String,
+    pub entries: Vec<DigestEntry>,
+}
@@ -1,10 +1,42 @@
 mod absence;
+mod absence_request;
+mod recurring_absence;
+mod coverage;
+mod room;
+mod bell_schedule;
+mod calendar;
+mod calendar_feed;
 mod period;
 mod teacher;
+mod roster;
+mod rollover;
+mod sheet_sync;
+mod report;
+mod digest;
+mod audit;
+mod client;
+mod webhook;
+mod session;
 
 pub use teacher::{ Teacher, TeacherName, Honorific, pronouns::PronounSet };
+pub use roster::{ RosterFormat, OauthLink, RosterEntry, RosterRowError, RosterImportReport };
 pub use period::Period;
 pub use absence::{ Absence, PackedAbsenceState, TeacherAbsenceStateList };
+pub use absence_request::{ AbsenceRequest, AbsenceRequestStatus };
+pub use recurring_absence::{ RecurrenceRule, RecurringAbsence, RangeTooLong, MAX_OCCURRENCE_RANGE_DAYS };
+pub use coverage::{ AbsentPeriod, AbsentTeacher, Coverage, CoverageConflict, Substitute };
+pub use room::{ Room, RoomAssignment };
+pub use bell_schedule::{ BellSchedule, BellSchedulePeriod, ScheduleDay };
+pub use calendar::{ CalendarDay, CalendarDayKind };
+pub use calendar_feed::{ CalendarFeed, CalendarFeedCredentials };
+pub use rollover::{ RolloverRun, RolloverTrigger };
+pub use sheet_sync::{ SheetSyncDirection, SheetSyncRun };
+pub use client::{ Client, ClientCredentials };
+pub use webhook::{ Webhook, WebhookCredentials, WebhookEvent, WebhookDelivery, WebhookDeliveryStatus };
+pub use session::{ Session, SessionCredentials };
+pub use audit::{ AuditSubject, AuditTarget, AuditOrigin, AuditEntry, AuditFilter, AuditLogPage };
+pub use digest::{ AbsenceDigest, DigestEntry, DigestPeriod };
+pub use report::{ Reports, TeacherMonthlyAbsences, PeriodAbsenceCount, FullVsPartialDays, WeekdayAbsenceCount };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Privileges {
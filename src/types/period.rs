@@ -16,6 +16,14 @@ pub struct Period {
     
     pub temp_start: Option<f64>,
     pub temp_end: Option<f64>,
+
+    /// The period's time in today's bell schedule, if today has one that
+    /// includes this period.
+    pub schedule_start: Option<f64>,
+    pub schedule_end: Option<f64>,
+
+    /// Whether the period was soft deleted.
+    pub archived: bool,
 }
 
 impl Debug for Period {
@@ -0,0 +1,297 @@
+use chrono::{ Datelike, Days, NaiveDate, Weekday };
+use uuid::Uuid;
+
+/// When a recurring absence happens, like "every other Friday until June".
+///
+/// Like an RRULE, `count` limits how many times the rule happens, and skipped
+/// occurrences still count towards it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecurrenceRule {
+    pub start: NaiveDate,
+    /// 1 for every week, 2 for every other week, and so on.
+    pub interval_weeks: u32,
+    pub weekdays: Vec<Weekday>,
+    pub until: Option<NaiveDate>,
+    pub count: Option<u32>,
+}
+impl RecurrenceRule {
+    /// Every date from `from` to `to` (inclusive) that the rule happens on.
+    ///
+    /// Ranges longer than [`MAX_OCCURRENCE_RANGE_DAYS`] are rejected.
+    pub fn occurrences(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<NaiveDate>, RangeTooLong> {
+        let days = to.signed_duration_since(from).num_days() + 1;
+        if days > MAX_OCCURRENCE_RANGE_DAYS {
+            return Err(RangeTooLong { days });
+        }
+
+        let last = match self.until {
+            Some(until) => until.min(to),
+            None => to,
+        };
+        let begin = from.max(self.start);
+        if begin > last {
+            return Ok(Vec::new());
+        }
+
+        let mut weekdays = self.weekdays.clone();
+        weekdays.sort_by_key(|weekday| weekday.num_days_from_monday());
+        weekdays.dedup();
+
+        let interval = i64::from(self.interval_weeks.max(1));
+        let first_week = self.start.week(Weekday::Mon).first_day();
+
+        // Jump straight to the first week the rule happens in that could have
+        // dates from `begin` on, counting the occurrences skipped over so
+        // `count` still applies.
+        let begin_week = begin.signed_duration_since(first_week).num_days() / 7;
+        let mut week = (begin_week + interval - 1) / interval * interval;
+        let mut seen = if week == 0 {
+            0
+        } else {
+            let before_start = weekdays
+                .iter()
+                .filter(|weekday| weekday.num_days_from_monday() < self.start.weekday().num_days_from_monday())
+                .count() as i64;
+            (week / interval) * weekdays.len() as i64 - before_start
+        };
+        let count = self.count.map(i64::from);
+
+        let mut occurrences = Vec::new();
+        'weeks: while let Some(monday) = first_week
+            .checked_add_days(Days::new(week as u64 * 7))
+            .filter(|monday| *monday <= last)
+        {
+            for weekday in &weekdays {
+                if count.is_some_and(|count| seen >= count) {
+                    break 'weeks;
+                }
+
+                let Some(date) = monday.checked_add_days(Days::new(u64::from(weekday.num_days_from_monday()))) else {
+                    break 'weeks;
+                };
+                if date > last {
+                    break 'weeks;
+                }
+                if date < self.start {
+                    continue;
+                }
+
+                seen += 1;
+                if date >= from {
+                    occurrences.push(date);
+                }
+            }
+
+            week += interval;
+        }
+
+        Ok(occurrences)
+    }
+}
+
+/// The most days recurrences can be expanded over at once.
+pub const MAX_OCCURRENCE_RANGE_DAYS: i64 = 366;
+
+/// A range of dates longer than [`MAX_OCCURRENCE_RANGE_DAYS`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RangeTooLong {
+    pub days: i64,
+}
+
+impl std::fmt::Display for RangeTooLong {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Recurrences can't be expanded over more than {MAX_OCCURRENCE_RANGE_DAYS} days, but this range is {}", self.days)
+    }
+}
+
+impl std::error::Error for RangeTooLong {}
+
+/// A future absence that repeats, set once instead of day by day.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecurringAbsence {
+    pub id: Uuid,
+    pub teacher_id: Uuid,
+
+    pub periods: Vec<Uuid>,
+    pub fully_absent: bool,
+    pub comment: Option<String>,
+
+    pub rule: RecurrenceRule,
+    /// Occurrences that were skipped.
+    pub exceptions: Vec<NaiveDate>,
+}
+impl RecurringAbsence {
+    /// Every date from `from` to `to` (inclusive) that the absence happens on,
+    /// leaving out skipped occurrences.
+    pub fn occurrences(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<NaiveDate>, RangeTooLong> {
+        Ok(self.rule
+            .occurrences(from, to)?
+            .into_iter()
+            .filter(|date| !self.exceptions.contains(date))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).expect("valid test date")
+    }
+
+    fn rule(start: NaiveDate, interval_weeks: u32, weekdays: &[Weekday]) -> RecurrenceRule {
+        RecurrenceRule {
+            start,
+            interval_weeks,
+            weekdays: weekdays.to_vec(),
+            until: None,
+            count: None,
+        }
+    }
+
+    /// The same as [`RecurrenceRule::occurrences`], stepping a day at a time.
+    fn occurrences_by_day(rule: &RecurrenceRule, from: NaiveDate, to: NaiveDate) -> Vec<NaiveDate> {
+        let first_week = rule.start.week(Weekday::Mon).first_day();
+        let last = rule.until.map_or(to, |until| until.min(to));
+
+        let mut occurrences = Vec::new();
+        let mut seen = 0;
+        let mut date = rule.start;
+        while date <= last && rule.count.is_none_or(|count| seen < count) {
+            let weeks_since_start = date.signed_duration_since(first_week).num_days() / 7;
+            if rule.weekdays.contains(&date.weekday()) && weeks_since_start % i64::from(rule.interval_weeks) == 0 {
+                seen += 1;
+                if date >= from {
+                    occurrences.push(date);
+                }
+            }
+            date = date.succ_opt().expect("valid test date");
+        }
+        occurrences
+    }
+
+    #[test]
+    fn weekly() {
+        // 2026-01-05 is a Monday
+        let rule = rule(date(2026, 1, 5), 1, &[Weekday::Wed]);
+
+        assert_eq!(
+            rule.occurrences(date(2026, 1, 1), date(2026, 1, 31)),
+            Ok(vec![date(2026, 1, 7), date(2026, 1, 14), date(2026, 1, 21), date(2026, 1, 28)]),
+        );
+    }
+
+    #[test]
+    fn every_other_week() {
+        let rule = rule(date(2026, 1, 5), 2, &[Weekday::Fri]);
+
+        assert_eq!(
+            rule.occurrences(date(2026, 1, 1), date(2026, 2, 28)),
+            Ok(vec![date(2026, 1, 9), date(2026, 1, 23), date(2026, 2, 6), date(2026, 2, 20)]),
+        );
+        assert_eq!(
+            rule.occurrences(date(2026, 1, 10), date(2026, 1, 22)),
+            Ok(vec![]),
+        );
+    }
+
+    #[test]
+    fn several_weekdays_starting_mid_week() {
+        // Starts on a Wednesday, so that week's Monday doesn't count
+        let rule = rule(date(2026, 1, 7), 1, &[Weekday::Fri, Weekday::Mon, Weekday::Mon]);
+
+        assert_eq!(
+            rule.occurrences(date(2026, 1, 1), date(2026, 1, 19)),
+            Ok(vec![date(2026, 1, 9), date(2026, 1, 12), date(2026, 1, 16), date(2026, 1, 19)]),
+        );
+    }
+
+    #[test]
+    fn until() {
+        let rule = RecurrenceRule {
+            until: Some(date(2026, 1, 20)),
+            ..rule(date(2026, 1, 5), 1, &[Weekday::Tue])
+        };
+
+        assert_eq!(
+            rule.occurrences(date(2026, 1, 1), date(2026, 3, 1)),
+            Ok(vec![date(2026, 1, 6), date(2026, 1, 13), date(2026, 1, 20)]),
+        );
+        assert_eq!(rule.occurrences(date(2026, 1, 21), date(2026, 3, 1)), Ok(vec![]));
+    }
+
+    #[test]
+    fn count_includes_occurrences_before_the_range() {
+        let rule = RecurrenceRule {
+            count: Some(5),
+            ..rule(date(2026, 1, 7), 2, &[Weekday::Mon, Weekday::Thu])
+        };
+
+        // Jan 8, 19, 22, Feb 2 and 5
+        assert_eq!(
+            rule.occurrences(date(2026, 1, 20), date(2026, 12, 31)),
+            Ok(vec![date(2026, 1, 22), date(2026, 2, 2), date(2026, 2, 5)]),
+        );
+        assert_eq!(rule.occurrences(date(2026, 2, 6), date(2026, 12, 31)), Ok(vec![]));
+    }
+
+    #[test]
+    fn matches_stepping_day_by_day() {
+        let rules = [
+            rule(date(2026, 1, 7), 1, &[Weekday::Mon, Weekday::Wed, Weekday::Sun]),
+            rule(date(2026, 1, 10), 3, &[Weekday::Tue, Weekday::Sat]),
+            RecurrenceRule { count: Some(9), ..rule(date(2026, 1, 8), 2, &[Weekday::Mon, Weekday::Fri]) },
+            RecurrenceRule { until: Some(date(2026, 6, 1)), ..rule(date(2026, 2, 1), 4, &[Weekday::Thu]) },
+        ];
+
+        for rule in &rules {
+            for (from, to) in [
+                (date(2026, 1, 1), date(2026, 12, 31)),
+                (date(2026, 3, 4), date(2026, 5, 17)),
+                (date(2026, 2, 2), date(2026, 2, 2)),
+            ] {
+                assert_eq!(rule.occurrences(from, to), Ok(occurrences_by_day(rule, from, to)), "{rule:?} from {from} to {to}");
+            }
+        }
+    }
+
+    #[test]
+    fn range_too_long() {
+        let rule = rule(date(2026, 1, 5), 1, &[Weekday::Mon]);
+
+        assert!(rule.occurrences(date(2026, 1, 1), date(2027, 1, 1)).is_ok());
+        assert_eq!(
+            rule.occurrences(date(2026, 1, 1), date(2027, 1, 2)),
+            Err(RangeTooLong { days: 367 }),
+        );
+    }
+
+    #[test]
+    fn far_from_start() {
+        let rule = rule(date(2000, 1, 3), 1, &[Weekday::Mon]);
+
+        assert_eq!(
+            rule.occurrences(date(2026, 1, 1), date(2026, 1, 12)),
+            Ok(vec![date(2026, 1, 5), date(2026, 1, 12)]),
+        );
+    }
+
+    #[test]
+    fn skipped_occurrences() {
+        let absence = RecurringAbsence {
+            id: Uuid::nil(),
+            teacher_id: Uuid::nil(),
+            periods: Vec::new(),
+            fully_absent: true,
+            comment: None,
+            rule: rule(date(2026, 1, 5), 1, &[Weekday::Mon]),
+            exceptions: vec![date(2026, 1, 12)],
+        };
+
+        assert_eq!(
+            absence.occurrences(date(2026, 1, 1), date(2026, 1, 19)),
+            Ok(vec![date(2026, 1, 5), date(2026, 1, 19)]),
+        );
+    }
+}
@@ -0,0 +1,45 @@
+use chrono::NaiveDate;
+use uuid::Uuid;
+
+/// The date range a set of reports covers. Every report under it is
+/// aggregated over this range, inclusive.
+#[derive(Debug, Clone, Copy)]
+pub struct Reports {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    /// Leave archived teachers and periods out of the totals.
+    pub exclude_archived: bool,
+}
+
+/// How often a single teacher was out during a single month.
+#[derive(Debug, Clone)]
+pub struct TeacherMonthlyAbsences {
+    pub teacher_id: Uuid,
+    pub month: NaiveDate,
+    pub days: i64,
+    pub full_days: i64,
+    pub partial_days: i64,
+    pub periods: i64,
+}
+
+/// How many absence days included a given period, or how many of them had no
+/// substitute.
+#[derive(Debug, Clone, Copy)]
+pub struct PeriodAbsenceCount {
+    pub period_id: Uuid,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FullVsPartialDays {
+    pub full: i64,
+    pub partial: i64,
+}
+
+/// How many absence days fell on a given ISO weekday (1 is Monday, 7 is
+/// Sunday).
+#[derive(Debug, Clone, Copy)]
+pub struct WeekdayAbsenceCount {
+    pub weekday: i32,
+    pub count: i64,
+}
@@ -0,0 +1,38 @@
+use chrono::{DateTime, Utc};
+
+/// What caused a day rollover to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RolloverTrigger {
+    Scheduled,
+    /// Run at startup because the server was down when the scheduled
+    /// rollover was due.
+    CatchUp,
+    Manual,
+}
+impl RolloverTrigger {
+    pub fn try_from_str(s: &str) -> Option<Self> {
+        match s {
+            "scheduled" => Some(Self::Scheduled),
+            "catch_up" => Some(Self::CatchUp),
+            "manual" => Some(Self::Manual),
+            _ => None,
+        }
+    }
+    pub fn str(&self) -> &'static str {
+        match self {
+            Self::Scheduled => "scheduled",
+            Self::CatchUp => "catch_up",
+            Self::Manual => "manual",
+        }
+    }
+}
+
+/// The recorded outcome of a single day rollover.
+#[derive(Debug, Clone)]
+pub struct RolloverRun {
+    pub ran_at: DateTime<Utc>,
+    pub trigger: RolloverTrigger,
+    pub success: bool,
+    pub teachers_updated: i32,
+    pub error: Option<String>,
+}
@@ -0,0 +1,16 @@
+use uuid::Uuid;
+
+/// A room that classes can report to when their teacher is absent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Room {
+    pub id: Uuid,
+    pub name: String,
+}
+
+/// Where one of a teacher's classes reports to when they're absent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoomAssignment {
+    pub teacher_id: Uuid,
+    pub period_id: Uuid,
+    pub room: Room,
+}
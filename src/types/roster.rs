@@ -0,0 +1,49 @@
+use uuid::Uuid;
+
+use super::{ PronounSet, TeacherName };
+
+/// The file formats a roster can be imported from and exported to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, async_graphql::Enum)]
+pub enum RosterFormat {
+    Csv,
+    Json,
+}
+
+/// A teacher's OAuth account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OauthLink {
+    pub provider: String,
+    pub sub: String,
+}
+
+/// One teacher in an imported or exported roster.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RosterEntry {
+    /// Entries with the id of an existing teacher update them, and the rest
+    /// add new teachers.
+    pub id: Option<Uuid>,
+    pub name: TeacherName,
+    pub pronouns: PronounSet,
+    pub oauth: Vec<OauthLink>,
+}
+
+/// Why a row of an imported roster couldn't be imported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RosterRowError {
+    /// The 1-based row in the file, not counting the CSV header.
+    pub row: usize,
+    pub message: String,
+}
+
+/// The outcome of a roster import.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RosterImportReport {
+    /// Whether only a dry run was asked for.
+    pub dry_run: bool,
+    /// Whether the roster was actually imported. Imports are all or nothing,
+    /// so they're only applied if it isn't a dry run and there are no errors.
+    pub applied: bool,
+    pub created: u32,
+    pub updated: u32,
+    pub errors: Vec<RosterRowError>,
+}
@@ -0,0 +1,22 @@
+use chrono::{ DateTime, Utc };
+use uuid::Uuid;
+
+use super::Privileges;
+
+/// A signed-in teacher, as proven by a session token.
+/// 
+/// Privileges are a snapshot from when the session was created, so changes to
+/// them only apply to new sessions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Session {
+    pub teacher_id: Uuid,
+    pub privileges: Privileges,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A new session along with its token, returned by `login`.
+#[derive(Debug, Clone)]
+pub struct SessionCredentials {
+    pub token: String,
+    pub session: Session,
+}
@@ -0,0 +1,36 @@
+use chrono::{DateTime, Utc};
+
+/// Whether a sync wrote the absence board to the spreadsheet or read it back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, async_graphql::Enum)]
+pub enum SheetSyncDirection {
+    Push,
+    Pull,
+}
+impl SheetSyncDirection {
+    pub fn try_from_str(s: &str) -> Option<Self> {
+        match s {
+            "push" => Some(Self::Push),
+            "pull" => Some(Self::Pull),
+            _ => None,
+        }
+    }
+    pub fn str(&self) -> &'static str {
+        match self {
+            Self::Push => "push",
+            Self::Pull => "pull",
+        }
+    }
+}
+
+/// The recorded outcome of a single push to or pull from the spreadsheet.
+#[derive(Debug, Clone)]
+pub struct SheetSyncRun {
+    pub ran_at: DateTime<Utc>,
+    pub sheet_id: String,
+    pub direction: SheetSyncDirection,
+    pub success: bool,
+    /// Teacher rows written for a push, or teachers updated from a pull.
+    pub rows: i32,
+    pub attempts: i32,
+    pub error: Option<String>,
+}
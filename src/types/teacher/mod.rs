@@ -171,18 +171,23 @@ pub struct Teacher {
     pub (super) name: TeacherName,
     pub (super) pronouns: PronounSet,
     pub (super) fully_absent: bool,
+    pub (super) archived: bool,
 }
 
 impl Teacher {
     pub fn new(id: Uuid, name: TeacherName, pronouns: PronounSet) -> Self {
-        Self { id, name, pronouns, fully_absent: false }
+        Self { id, name, pronouns, fully_absent: false, archived: false }
     }
     pub fn with_fully_absence(self, fully_absent: bool) -> Self {
         Self { fully_absent, ..self }
     }
+    pub fn with_archived(self, archived: bool) -> Self {
+        Self { archived, ..self }
+    }
 
     pub fn get_id(&self) -> Uuid { self.id }
     pub fn get_name(&self) -> &TeacherName { &self.name }
     pub fn get_pronouns(&self) -> &PronounSet { &self.pronouns }
     pub fn get_fully_absent(&self) -> bool { self.fully_absent }
+    pub fn get_archived(&self) -> bool { self.archived }
 }
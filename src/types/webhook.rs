@@ -0,0 +1,102 @@
+use chrono::{ DateTime, Utc };
+use uuid::Uuid;
+
+/// The kinds of events webhooks can be sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, async_graphql::Enum)]
+pub enum WebhookEvent {
+    /// A teacher's absences for today were set.
+    AbsenceSet,
+    /// An approved future absence, one-off or recurring, was set for a
+    /// teacher. Requests that still need to be reviewed don't send this.
+    FutureAbsenceCreated,
+    /// A teacher's pending future absences were approved.
+    FutureAbsenceApproved,
+    /// A teacher's pending future absences were rejected.
+    FutureAbsenceRejected,
+    /// A teacher's future absences, one-off or recurring, were cancelled.
+    FutureAbsenceCancelled,
+    /// A day rollover (or manual flush) finished, successfully or not.
+    FlushCompleted,
+}
+impl WebhookEvent {
+    pub fn try_from_str(s: &str) -> Option<Self> {
+        match s {
+            "absence_set" => Some(Self::AbsenceSet),
+            "future_absence_created" => Some(Self::FutureAbsenceCreated),
+            "future_absence_approved" => Some(Self::FutureAbsenceApproved),
+            "future_absence_rejected" => Some(Self::FutureAbsenceRejected),
+            "future_absence_cancelled" => Some(Self::FutureAbsenceCancelled),
+            "flush_completed" => Some(Self::FlushCompleted),
+            _ => None,
+        }
+    }
+    pub fn str(&self) -> &'static str {
+        match self {
+            Self::AbsenceSet => "absence_set",
+            Self::FutureAbsenceCreated => "future_absence_created",
+            Self::FutureAbsenceApproved => "future_absence_approved",
+            Self::FutureAbsenceRejected => "future_absence_rejected",
+            Self::FutureAbsenceCancelled => "future_absence_cancelled",
+            Self::FlushCompleted => "flush_completed",
+        }
+    }
+}
+
+/// An endpoint that's sent events, as stored in the `webhooks` table. The
+/// signing secret is never exposed after it's created.
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub url: String,
+    pub description: String,
+    pub events: Vec<WebhookEvent>,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A webhook along with its signing secret.
+///
+/// This is only ever returned right after a secret is generated.
+#[derive(Debug, Clone)]
+pub struct WebhookCredentials {
+    pub webhook: Webhook,
+    pub secret: String,
+}
+
+/// Where a delivery is in the outbox.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, async_graphql::Enum)]
+pub enum WebhookDeliveryStatus {
+    Pending,
+    Delivered,
+    /// Every attempt failed, so it won't be retried again.
+    Failed,
+}
+
+/// One event sent (or to be sent) to a webhook.
+#[derive(Debug, Clone)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event: WebhookEvent,
+    pub payload: String,
+    pub created_at: DateTime<Utc>,
+
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_status: Option<i32>,
+    pub last_error: Option<String>,
+
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub failed_at: Option<DateTime<Utc>>,
+}
+impl WebhookDelivery {
+    pub fn delivery_status(&self) -> WebhookDeliveryStatus {
+        if self.delivered_at.is_some() {
+            WebhookDeliveryStatus::Delivered
+        } else if self.failed_at.is_some() {
+            WebhookDeliveryStatus::Failed
+        } else {
+            WebhookDeliveryStatus::Pending
+        }
+    }
+}
@@ -4,50 +4,169 @@ use crate::database::{self, Ctx};
 
 use super::scopes::Scopes;
 
+/// Client keys come in two formats:
+/// - `$argon2id$...`, a PHC string from argon2id. Every new key uses this.
+/// - `hash:salt`, the legacy format, where `hash` is `sha256(secret:salt)`.
+///
+/// Legacy keys are still accepted, and get re-hashed to argon2id the next time
+/// they're used successfully.
+const ARGON2ID_PREFIX: &str = "$argon2id$";
+
 pub async fn client_allowed(client_id: Uuid, provided_secret: &[u8], ctx: &mut Ctx) -> Option<Scopes> {
-    let Ok(Some(secret)) = database::prepared::clients::get_client_secret(ctx, client_id).await else {
+    let Ok(Some(keystr)) = database::prepared::clients::get_client_secret(ctx, client_id).await else {
         return None;
     };
 
-    let (hash, salt) = secret.rsplit_once(':')?;
+    if keystr.starts_with(ARGON2ID_PREFIX) {
+        let secret = provided_secret.to_vec();
+        let verify_keystr = keystr.clone();
+        let verified = tokio::task::spawn_blocking(move || verify_argon2id(&secret, &verify_keystr))
+            .await
+            .unwrap_or(false);
+
+        if !verified {
+            return None;
+        }
+    } else {
+        if !verify_legacy(provided_secret, &keystr) {
+            return None;
+        }
+        upgrade_legacy_keystr(client_id, provided_secret, &keystr, ctx).await;
+    }
+
+    if let Err(e) = database::prepared::clients::mark_client_seen(ctx, client_id).await {
+        crate::logging::warn!("Failed to update last seen time for client {client_id}: {e}");
+    }
+
+    database::prepared::clients::get_client_scopes(ctx, client_id).await.ok().flatten()
+}
+
+fn verify_argon2id(secret: &[u8], keystr: &str) -> bool {
+    use argon2::{ Argon2, PasswordHash, PasswordVerifier };
+
+    let Ok(hash) = PasswordHash::new(keystr) else {
+        crate::logging::warn!("Malformed argon2id client key");
+        return false;
+    };
+
+    Argon2::default().verify_password(secret, &hash).is_ok()
+}
 
-    let value_to_hash: Vec<u8> = provided_secret
+fn verify_legacy(secret: &[u8], keystr: &str) -> bool {
+    let Some((hash, salt)) = keystr.rsplit_once(':') else {
+        return false;
+    };
+
+    let value_to_hash: Vec<u8> = secret
         .iter()
         .copied()
         .chain(std::iter::once(b':'))
         .chain(salt.as_bytes().iter().copied())
         .collect();
 
-
     let test_hash = sha256::digest(value_to_hash);
 
-    if !constant_time_eq::constant_time_eq(test_hash.as_bytes(), hash.as_bytes()) {
-        return None;
-    }
+    constant_time_eq::constant_time_eq(test_hash.as_bytes(), hash.as_bytes())
+}
 
-    database::prepared::clients::get_client_scopes(ctx, client_id).await.ok().flatten()
+/// Replaces a verified legacy key with an argon2id one.
+///
+/// Failing to upgrade isn't fatal, the legacy key keeps working and the
+/// upgrade is tried again on the next login.
+async fn upgrade_legacy_keystr(client_id: Uuid, secret: &[u8], old_keystr: &str, ctx: &mut Ctx) {
+    let secret = secret.to_vec();
+    let Ok(Some(new_keystr)) = tokio::task::spawn_blocking(move || generate_client_keystr(&secret)).await else {
+        crate::logging::warn!("Failed to generate argon2id key to upgrade client {client_id}");
+        return;
+    };
+
+    match database::prepared::clients::replace_client_key(ctx, client_id, old_keystr, &new_keystr).await {
+        Ok(true) => crate::logging::info!("Upgraded client {client_id} key to argon2id"),
+        Ok(false) => crate::logging::debug!("Client {client_id} key changed before it could be upgraded"),
+        Err(e) => crate::logging::warn!("Failed to upgrade client {client_id} key to argon2id: {e}"),
+    }
 }
 
+/// Hashes a client secret into an argon2id client key.
+///
+/// This is deliberately slow, so avoid calling it directly from async code.
 pub fn generate_client_keystr(secret: &[u8]) -> Option<String> {
+    use argon2::{ Argon2, PasswordHasher, password_hash::SaltString };
     use rand::RngCore;
     let mut rng = rand::thread_rng();
 
-    let mut salt_bytes = [0; 32];
+    let mut salt_bytes = [0; 16];
     if rng.try_fill_bytes(&mut salt_bytes).is_err() {
         return None;
     }
 
-    let salt = hex::encode(salt_bytes);
+    let salt = SaltString::encode_b64(&salt_bytes).ok()?;
 
-    let value_to_hash: Vec<u8> = secret
-        .iter()
-        .copied()
-        .chain(std::iter::once(b':'))
-        .chain(salt.as_bytes().iter().copied())
-        .collect();
-    
-    let hash = sha256::digest(value_to_hash);
+    let hash = Argon2::default().hash_password(secret, &salt).ok()?;
+
+    Some(hash.to_string())
+}
+
+/// Generates a new random client secret, 48 bytes encoded as base64.
+pub fn generate_client_secret() -> String {
+    use rand::{ Rng, SeedableRng };
+    use base64::{
+        engine::{ GeneralPurpose, GeneralPurposeConfig, Engine },
+        alphabet::STANDARD,
+    };
+
+    let secret_bytes_32 = rand::rngs::StdRng::from_entropy().gen::<[u8; 32]>(); // 32-byte secret
+    let secret_bytes_16 = rand::rngs::StdRng::from_entropy().gen::<[u8; 16]>(); // 16-byte secret
+    let secret_bytes: Vec<_> = secret_bytes_32.into_iter().chain(secret_bytes_16).collect();
+
+    let encoder = GeneralPurpose::new(&STANDARD, GeneralPurposeConfig::default());
+    encoder.encode(secret_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEGACY_KEYSTR: &str = "ceaf5f9ded65f6ef15d90dd0b25f338108ee6ff180f8207bc1a1753ab01eb87f:pepper";
+
+    #[test]
+    fn argon2id_keystr_verifies_its_secret() {
+        let keystr = generate_client_keystr(b"topsecret").expect("key should be generated");
 
-    let keystr = format!("{hash}:{salt}");
-    Some(keystr)
+        assert!(keystr.starts_with(ARGON2ID_PREFIX));
+        assert!(verify_argon2id(b"topsecret", &keystr));
+    }
+
+    #[test]
+    fn argon2id_keystr_rejects_other_secrets() {
+        let keystr = generate_client_keystr(b"topsecret").expect("key should be generated");
+
+        assert!(!verify_argon2id(b"topsecreT", &keystr));
+        assert!(!verify_argon2id(b"", &keystr));
+    }
+
+    #[test]
+    fn argon2id_keystrs_are_salted() {
+        let first = generate_client_keystr(b"topsecret").expect("key should be generated");
+        let second = generate_client_keystr(b"topsecret").expect("key should be generated");
+
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn malformed_argon2id_keystr_is_rejected() {
+        assert!(!verify_argon2id(b"topsecret", "$argon2id$not-a-hash"));
+    }
+
+    #[test]
+    fn legacy_keystr_verifies_its_secret() {
+        assert!(verify_legacy(b"topsecret", LEGACY_KEYSTR));
+    }
+
+    #[test]
+    fn legacy_keystr_rejects_other_secrets() {
+        assert!(!verify_legacy(b"topsecreT", LEGACY_KEYSTR));
+        assert!(!verify_legacy(b"topsecret", "ceaf5f9ded65f6ef15d90dd0b25f338108ee6ff180f8207bc1a1753ab01eb87f:salt"));
+        assert!(!verify_legacy(b"topsecret", "no salt"));
+    }
 }
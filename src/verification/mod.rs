@@ -13,12 +13,20 @@ use reqwest::header::{HeaderValue, InvalidHeaderValue, HeaderName};
 use uuid::Uuid;
 
 pub mod id_secret;
+pub mod oidc;
+pub mod rate_limit;
+pub mod scope_cache;
 pub mod scopes;
+pub mod session;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ClientSecretHeader(Vec<u8>);
 
 impl ClientSecretHeader {
+    pub fn new(secret: Vec<u8>) -> Self {
+        Self(secret)
+    }
+
     pub fn as_bytes(&self) -> &[u8] {
         &self.0
     }
@@ -50,6 +58,10 @@ impl TryIntoHeaderValue for ClientSecretHeader {
 pub struct ClientIdHeader(Uuid);
 
 impl ClientIdHeader {
+    pub fn new(id: Uuid) -> Self {
+        Self(id)
+    }
+
     pub fn inner(&self) -> Uuid {
         self.0
     }
@@ -82,3 +94,42 @@ impl TryIntoHeaderValue for ClientIdHeader {
         HeaderValue::from_str(&self.0.hyphenated().to_string())
     }
 }
+
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionTokenHeader(String);
+
+impl SessionTokenHeader {
+    pub fn new(token: String) -> Self {
+        Self(token)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Header for SessionTokenHeader {
+    fn name() -> HeaderName {
+        HeaderName::from_static("session-token")
+    }
+    fn parse<M: actix_web::HttpMessage>(msg: &M) -> Result<Self, ParseError> {
+        let Some(header) = msg.headers().get(Self::name()) else {
+            return Err(ParseError::Header);
+        };
+
+        let Ok(token) = header.to_str() else {
+            return Err(ParseError::Header);
+        };
+
+        Ok(Self(token.to_string()))
+    }
+}
+
+impl TryIntoHeaderValue for SessionTokenHeader {
+    type Error = InvalidHeaderValue;
+    fn try_into_value(self) -> Result<HeaderValue, Self::Error> {
+        HeaderValue::from_str(&self.0)
+    }
+}
@@ -0,0 +1,349 @@
+//! This module contains the token-bucket rate limiter and the actix
+//! middleware ([`RateLimiter`]) that applies it to every request.
+//!
+//! Requests are limited per client, using the client's `rate_limit` column or
+//! the server default, once their `client-id` and `client-secret` pair is in
+//! the [`ScopeCache`][super::scope_cache::ScopeCache]. That only happens after
+//! the pair has been verified, so knowing a client's id isn't enough to use up
+//! (or skip) its limit.
+//!
+//! Everything else (no client, a wrong secret, or a client that hasn't been
+//! verified yet) is limited per IP, and requests without any usable IP share a
+//! single bucket. With the scope cache disabled, every request is limited per
+//! IP.
+
+use std::collections::HashMap;
+use std::future::{ ready, Ready };
+use std::net::{ IpAddr, SocketAddr };
+use std::rc::Rc;
+use std::sync::Mutex;
+use std::time::{ Duration, Instant };
+
+use actix_web::{
+    body::EitherBody,
+    dev::{ forward_ready, Service, ServiceRequest, ServiceResponse, Transform },
+    http::header::Header,
+    Error,
+    HttpResponse,
+};
+use futures_util::future::LocalBoxFuture;
+use uuid::Uuid;
+
+use crate::logging::*;
+use crate::state::AppState;
+
+use super::{ ClientIdHeader, ClientSecretHeader };
+
+
+/// Past this many buckets, idle ones are dropped whenever a new one is added.
+const MAX_BUCKETS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RateLimitKey {
+    Client(Uuid),
+    Ip(IpAddr),
+    /// Requests with no usable client id or IP address.
+    Unidentified,
+}
+
+impl std::fmt::Display for RateLimitKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Client(id) => write!(f, "client {id}"),
+            Self::Ip(ip) => write!(f, "ip {ip}"),
+            Self::Unidentified => write!(f, "unidentified requests"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    per_minute: u32,
+    rejecting: bool,
+}
+
+impl Bucket {
+    fn new(per_minute: u32) -> Self {
+        Self {
+            tokens: per_minute as f64,
+            updated: Instant::now(),
+            per_minute,
+            rejecting: false,
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let refilled = now.duration_since(self.updated).as_secs_f64() * self.per_minute as f64 / 60.0;
+
+        self.tokens = (self.tokens + refilled).min(self.per_minute as f64);
+        self.updated = now;
+    }
+
+    fn is_full(&self) -> bool {
+        self.tokens >= self.per_minute as f64
+    }
+}
+
+/// Every rate limit bucket, shared between workers through
+/// [`AppState`].
+#[derive(Debug)]
+pub struct RateLimits {
+    buckets: Mutex<HashMap<RateLimitKey, Bucket>>,
+    client_default: u32,
+    anonymous: u32,
+    trust_proxy: bool,
+}
+
+impl RateLimits {
+    pub fn new(client_default: u32, anonymous: u32, trust_proxy: bool) -> Self {
+        Self {
+            buckets: Mutex::new(HashMap::new()),
+            client_default,
+            anonymous,
+            trust_proxy,
+        }
+    }
+
+    fn has_client(&self, id: Uuid) -> bool {
+        self.buckets
+            .lock()
+            .map(|buckets| buckets.contains_key(&RateLimitKey::Client(id)))
+            .unwrap_or(false)
+    }
+
+    fn add_client(&self, id: Uuid, per_minute: Option<i32>) {
+        let per_minute = per_minute
+            .map(|limit| limit.max(0) as u32)
+            .unwrap_or(self.client_default);
+
+        if let Ok(mut buckets) = self.buckets.lock() {
+            Self::make_room(&mut buckets);
+            buckets.entry(RateLimitKey::Client(id)).or_insert_with(|| Bucket::new(per_minute));
+        }
+    }
+
+    /// Forget a client's bucket, so its limit is looked up again on its next
+    /// request.
+    pub fn forget_client(&self, id: Uuid) {
+        if let Ok(mut buckets) = self.buckets.lock() {
+            buckets.remove(&RateLimitKey::Client(id));
+        }
+    }
+
+    fn make_room(buckets: &mut HashMap<RateLimitKey, Bucket>) {
+        if buckets.len() < MAX_BUCKETS {
+            return;
+        }
+
+        // A full bucket behaves exactly like a missing one, so they're free to
+        // drop
+        buckets.retain(|_, bucket| {
+            bucket.refill();
+            !bucket.is_full()
+        });
+    }
+
+    /// Take a token from a bucket.
+    ///
+    /// On rejection, returns how long until the next token is available.
+    pub fn check(&self, key: RateLimitKey) -> Result<(), Duration> {
+        let Ok(mut buckets) = self.buckets.lock() else {
+            error!("Rate limit buckets poisoned, not limiting");
+            return Ok(());
+        };
+
+        if !buckets.contains_key(&key) {
+            Self::make_room(&mut buckets);
+        }
+        let anonymous = self.anonymous;
+        let bucket = buckets.entry(key).or_insert_with(|| Bucket::new(anonymous));
+
+        if bucket.per_minute == 0 {
+            return Ok(());
+        }
+
+        bucket.refill();
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            bucket.rejecting = false;
+            return Ok(());
+        }
+
+        if !bucket.rejecting {
+            warn!("Rate limiting {key} ({} requests per minute)", bucket.per_minute);
+            bucket.rejecting = true;
+        } else {
+            trace!("Rejected request from {key}, still rate limited");
+        }
+
+        let missing = 1.0 - bucket.tokens;
+        Err(Duration::from_secs_f64(missing * 60.0 / bucket.per_minute as f64))
+    }
+
+    async fn key_for(&self, req: &ServiceRequest, state: &AppState) -> RateLimitKey {
+        use crate::database::prepared::clients::get_client_rate_limit;
+
+        if let Some(id) = verified_client(req, state) {
+            if self.has_client(id) {
+                return RateLimitKey::Client(id);
+            }
+
+            let limit = match state.db().acquire().await {
+                Ok(mut db_conn) => get_client_rate_limit(&mut db_conn, id).await,
+                Err(e) => Err(e),
+            };
+            match limit {
+                Ok(Some(limit)) => {
+                    self.add_client(id, limit);
+                    return RateLimitKey::Client(id);
+                },
+                Ok(None) => debug!("Client {id} is no longer usable, rate limiting by IP instead"),
+                Err(e) => error!("Failed to get rate limit for client {id}, rate limiting by IP instead: {e}"),
+            }
+        }
+
+        self.ip_key_for(req)
+    }
+
+    /// Limits by the forwarded IP if proxies are trusted, falling back to the
+    /// peer's IP if there isn't a usable one.
+    fn ip_key_for(&self, req: &ServiceRequest) -> RateLimitKey {
+        let forwarded = self.trust_proxy
+            .then(|| req.connection_info().realip_remote_addr().and_then(parse_ip))
+            .flatten();
+
+        forwarded
+            .or_else(|| req.peer_addr().map(|addr| addr.ip()))
+            .map_or(RateLimitKey::Unidentified, RateLimitKey::Ip)
+    }
+}
+
+
+/// The client id of a request, if its id and secret pair was verified
+/// recently enough to still be in the scope cache.
+fn verified_client(req: &ServiceRequest, state: &AppState) -> Option<Uuid> {
+    let id = ClientIdHeader::parse(req).ok()?.inner();
+    let secret = ClientSecretHeader::parse(req).ok()?;
+
+    state.scope_cache().get(id, secret.as_bytes()).map(|_| id)
+}
+
+/// Parses an address from a forwarding header, which may or may not have a
+/// port.
+fn parse_ip(addr: &str) -> Option<IpAddr> {
+    addr.parse()
+        .ok()
+        .or_else(|| addr.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+}
+
+
+/// Middleware that rejects requests over their rate limit with a
+/// `429 Too Many Requests` and a `Retry-After` header.
+#[derive(Clone)]
+pub struct RateLimiter {
+    state: AppState,
+}
+
+impl RateLimiter {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimiter
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RateLimitMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware { service: Rc::new(service), state: self.state.clone() }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: Rc<S>,
+    state: AppState,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let state = self.state.clone();
+
+        Box::pin(async move {
+            let limits = state.rate_limits();
+
+            let key = limits.key_for(&req, &state).await;
+
+            match limits.check(key) {
+                Ok(()) => service.call(req).await.map(ServiceResponse::map_into_left_body),
+                Err(retry_after) => {
+                    let response = HttpResponse::TooManyRequests()
+                        .insert_header(("Retry-After", (retry_after.as_secs_f64().ceil() as u64).max(1).to_string()))
+                        .body("Too many requests");
+
+                    Ok(req.into_response(response).map_into_right_body())
+                },
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forwarded_addresses_parse_with_or_without_port() {
+        let ip: IpAddr = "203.0.113.7".parse().expect("valid ip");
+
+        assert_eq!(parse_ip("203.0.113.7"), Some(ip));
+        assert_eq!(parse_ip("203.0.113.7:4242"), Some(ip));
+        assert_eq!(parse_ip("[::1]:4242"), Some(IpAddr::from([0, 0, 0, 0, 0, 0, 0, 1])));
+        assert_eq!(parse_ip("unknown"), None);
+    }
+
+    #[test]
+    fn requests_over_the_limit_are_rejected() {
+        let limits = RateLimits::new(60, 2, false);
+
+        assert!(limits.check(RateLimitKey::Unidentified).is_ok());
+        assert!(limits.check(RateLimitKey::Unidentified).is_ok());
+        assert!(limits.check(RateLimitKey::Unidentified).is_err());
+    }
+
+    #[test]
+    fn forgotten_clients_lose_their_bucket() {
+        let limits = RateLimits::new(60, 2, false);
+        let id = Uuid::new_v4();
+
+        limits.add_client(id, Some(1));
+        assert!(limits.has_client(id));
+        assert!(limits.check(RateLimitKey::Client(id)).is_ok());
+        assert!(limits.check(RateLimitKey::Client(id)).is_err());
+
+        limits.forget_client(id);
+        assert!(!limits.has_client(id));
+    }
+}
@@ -0,0 +1,108 @@
+//! This module contains [`ScopeCache`], which remembers the scopes of
+//! recently verified clients so that most requests don't need to touch the
+//! database (or run a password hash) to authenticate.
+//!
+//! Entries are keyed on the client id and a SHA-256 digest of the secret that
+//! was provided, so a wrong secret never hits a cached entry. The plaintext
+//! secret is never stored.
+//!
+//! Since cached scopes skip [`client_allowed`][super::id_secret::client_allowed]
+//! entirely, a client's expiry and `last_seen_at` are only as accurate as the
+//! cache TTL. Anything that changes a client should call
+//! [`ScopeCache::invalidate`].
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{ Duration, Instant };
+
+use sha2::{ Digest, Sha256 };
+use uuid::Uuid;
+
+use super::scopes::Scopes;
+
+
+type CacheKey = (Uuid, [u8; 32]);
+
+#[derive(Debug)]
+pub struct ScopeCache {
+    entries: Mutex<HashMap<CacheKey, (Scopes, Instant)>>,
+    ttl: Duration,
+    capacity: usize,
+}
+
+impl ScopeCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            ttl,
+            capacity,
+        }
+    }
+
+    fn key(client_id: Uuid, secret: &[u8]) -> CacheKey {
+        (client_id, Sha256::digest(secret).into())
+    }
+
+    fn enabled(&self) -> bool {
+        !self.ttl.is_zero() && self.capacity > 0
+    }
+
+    /// Get the cached scopes for a client, if that exact id and secret pair
+    /// was verified within the TTL.
+    pub fn get(&self, client_id: Uuid, secret: &[u8]) -> Option<Scopes> {
+        if !self.enabled() {
+            return None;
+        }
+
+        let key = Self::key(client_id, secret);
+        let mut entries = self.entries.lock().ok()?;
+
+        match entries.get(&key) {
+            Some((scopes, inserted)) if inserted.elapsed() < self.ttl => Some(scopes.clone()),
+            Some(_) => {
+                entries.remove(&key);
+                None
+            },
+            None => None,
+        }
+    }
+
+    /// Cache the scopes of a client that was just verified.
+    ///
+    /// If the cache is full, expired entries are dropped first, then the
+    /// oldest entry.
+    pub fn insert(&self, client_id: Uuid, secret: &[u8], scopes: Scopes) {
+        if !self.enabled() {
+            return;
+        }
+
+        let key = Self::key(client_id, secret);
+        let Ok(mut entries) = self.entries.lock() else {
+            return;
+        };
+
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            let ttl = self.ttl;
+            entries.retain(|_, (_, inserted)| inserted.elapsed() < ttl);
+        }
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, (_, inserted))| *inserted)
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+
+        entries.insert(key, (scopes, Instant::now()));
+    }
+
+    /// Drop every cached entry for a client, so its next request is verified
+    /// against the database again.
+    pub fn invalidate(&self, client_id: Uuid) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.retain(|(id, _), _| *id != client_id);
+        }
+    }
+}
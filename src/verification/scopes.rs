@@ -136,6 +136,9 @@ define_scopes!(
     write_teacher_name,
     write_teacher_pronouns,
     write_teacher_absence,
+    // Future absences, their approval, recurring absences and calendar feeds,
+    // on top of the signed-in teacher's own privileges
+    write_teacher_futures,
     
     write_period_name,
     write_period_temp_time,
@@ -0,0 +1,201 @@
+//! This module contains [`SessionSigner`], which issues and verifies the
+//! session tokens handed out by the `login` mutation.
+//!
+//! A token is `payload.signature`, where `payload` is base64url JSON and
+//! `signature` is a base64url HMAC-SHA256 of `payload`. Tokens are signed
+//! with the `SESSION_SECRET` environment variable. If that isn't set, a random
+//! secret is generated at startup, so every session ends when the server
+//! restarts.
+
+use std::time::Duration;
+
+use base64::engine::{ general_purpose::URL_SAFE_NO_PAD, Engine };
+use chrono::{ DateTime, Utc };
+use hmac::{ Hmac, Mac };
+use serde::{ Deserialize, Serialize };
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::types::{ Privileges, Session };
+
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Serialize, Deserialize)]
+struct SessionPayload {
+    #[serde(rename = "tid")]
+    teacher_id: Uuid,
+    #[serde(rename = "sec")]
+    secretary: bool,
+    #[serde(rename = "adm")]
+    admin: bool,
+    exp: i64,
+}
+
+pub struct SessionSigner {
+    secret: Vec<u8>,
+    ttl: Duration,
+}
+
+impl std::fmt::Debug for SessionSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionSigner")
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SessionSigner {
+    pub fn new(secret: Vec<u8>, ttl: Duration) -> Self {
+        Self { secret, ttl }
+    }
+
+    pub fn from_env() -> Self {
+        let ttl = crate::env::session_ttl();
+
+        match std::env::var("SESSION_SECRET") {
+            Ok(secret) if !secret.is_empty() => Self::new(secret.into_bytes(), ttl),
+            _ => {
+                use rand::RngCore;
+
+                crate::logging::warn!("SESSION_SECRET not set, sessions won't survive a restart");
+                let mut secret = vec![0; 32];
+                rand::thread_rng().fill_bytes(&mut secret);
+                Self::new(secret, ttl)
+            },
+        }
+    }
+
+    fn mac(&self) -> Option<HmacSha256> {
+        HmacSha256::new_from_slice(&self.secret).ok()
+    }
+
+    /// Start a new session for a teacher.
+    pub fn issue(&self, teacher_id: Uuid, privileges: Privileges) -> Option<(String, Session)> {
+        let expires_at = Utc::now() + chrono::Duration::from_std(self.ttl).ok()?;
+
+        let payload = SessionPayload {
+            teacher_id,
+            secretary: privileges.secretary,
+            admin: privileges.admin,
+            exp: expires_at.timestamp(),
+        };
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&payload).ok()?);
+
+        let mut mac = self.mac()?;
+        mac.update(payload.as_bytes());
+        let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+
+        let session = Session {
+            teacher_id,
+            privileges,
+            expires_at: DateTime::from_timestamp(expires_at.timestamp(), 0)?,
+        };
+
+        Some((format!("{payload}.{signature}"), session))
+    }
+
+    /// Check a session token's signature and expiry.
+    pub fn verify(&self, token: &str) -> Option<Session> {
+        let (payload, signature) = token.split_once('.')?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+
+        let mut mac = self.mac()?;
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&signature).ok()?;
+
+        let payload: SessionPayload = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).ok()?).ok()?;
+
+        let expires_at = DateTime::from_timestamp(payload.exp, 0)?;
+        if expires_at <= Utc::now() {
+            return None;
+        }
+
+        Some(Session {
+            teacher_id: payload.teacher_id,
+            privileges: Privileges { secretary: payload.secretary, admin: payload.admin },
+            expires_at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRIVILEGES: Privileges = Privileges { secretary: true, admin: false };
+
+    fn signer() -> SessionSigner {
+        SessionSigner::new(b"test secret".to_vec(), Duration::from_secs(60 * 60))
+    }
+
+    #[test]
+    fn issued_token_verifies() {
+        let signer = signer();
+        let teacher_id = Uuid::new_v4();
+
+        let (token, issued) = signer.issue(teacher_id, PRIVILEGES).expect("token should be issued");
+        let verified = signer.verify(&token).expect("token should verify");
+
+        assert_eq!(verified, issued);
+        assert_eq!(verified.teacher_id, teacher_id);
+        assert_eq!(verified.privileges, PRIVILEGES);
+    }
+
+    #[test]
+    fn token_from_another_secret_is_rejected() {
+        let other = SessionSigner::new(b"other secret".to_vec(), Duration::from_secs(60 * 60));
+
+        let (token, _) = other.issue(Uuid::new_v4(), PRIVILEGES).expect("token should be issued");
+
+        assert!(signer().verify(&token).is_none());
+    }
+
+    #[test]
+    fn expired_token_is_rejected() {
+        let signer = SessionSigner::new(b"test secret".to_vec(), Duration::ZERO);
+
+        let (token, _) = signer.issue(Uuid::new_v4(), PRIVILEGES).expect("token should be issued");
+
+        assert!(signer.verify(&token).is_none());
+    }
+
+    #[test]
+    fn tampered_payload_is_rejected() {
+        let signer = signer();
+        let teacher_id = Uuid::new_v4();
+
+        let (token, _) = signer.issue(teacher_id, PRIVILEGES).expect("token should be issued");
+        let (_, signature) = token.split_once('.').expect("token should have a signature");
+
+        let escalated = SessionPayload {
+            teacher_id,
+            secretary: true,
+            admin: true,
+            exp: Utc::now().timestamp() + 60 * 60,
+        };
+        let escalated = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&escalated).expect("payload should serialize"));
+
+        assert!(signer.verify(&format!("{escalated}.{signature}")).is_none());
+    }
+
+    #[test]
+    fn tampered_signature_is_rejected() {
+        let signer = signer();
+
+        let (token, _) = signer.issue(Uuid::new_v4(), PRIVILEGES).expect("token should be issued");
+        let (payload, _) = token.split_once('.').expect("token should have a signature");
+        let forged = URL_SAFE_NO_PAD.encode([0; 32]);
+
+        assert!(signer.verify(&format!("{payload}.{forged}")).is_none());
+    }
+
+    #[test]
+    fn malformed_tokens_are_rejected() {
+        let signer = signer();
+
+        assert!(signer.verify("").is_none());
+        assert!(signer.verify("no-signature").is_none());
+        assert!(signer.verify("not base64!.not base64!").is_none());
+    }
+}
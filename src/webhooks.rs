@@ -0,0 +1,282 @@
+//! This module sends webhook notifications of events, so other services (like
+//! the notif system) don't have to poll for changes.
+//!
+//! Events are added to the `webhook_deliveries` outbox in the same
+//! transaction as the change that caused them (see [`notify`]), once for each
+//! active webhook that wants them, so an event is only sent if its change is
+//! committed. The
+//! [`WebhookDispatcher`] sends them in the background, retrying failed
+//! deliveries with exponential backoff until
+//! [`webhook_max_attempts`][crate::env::webhook_max_attempts] is reached.
+//! Since the outbox is in the database, nothing is lost if the server
+//! restarts, and servers sharing the database never send the same delivery at
+//! once.
+//!
+//! Each delivery is a `POST` of JSON like:
+//! ```json
+//! { "event": "absence_set", "occurredAt": "2024-01-01T12:00:00Z", "data": { ... } }
+//! ```
+//! with these headers:
+//! - `X-Eureka-Event`: the event, like `absence_set`
+//! - `X-Eureka-Delivery`: the delivery's id, which is the same for retries
+//! - `X-Eureka-Timestamp`: when this attempt was sent, in Unix seconds
+//! - `X-Eureka-Signature`: `sha256=` and the hex HMAC-SHA256 of
+//!   `{timestamp}.{body}`, keyed with the webhook's secret
+//!
+//! Receivers should check the signature, and reject old timestamps to stop
+//! replays.
+
+use std::time::Duration;
+
+use chrono::Utc;
+use hmac::{ Hmac, Mac };
+use serde_json::json;
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::database::Ctx;
+use crate::database::prepared::webhooks::DueDelivery;
+use crate::logging::*;
+use crate::state::AppState;
+use crate::types::{ RolloverTrigger, WebhookEvent };
+
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// How long to wait before retrying a failed delivery the first time. Each
+/// retry after that waits twice as long as the last.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(30);
+
+/// The longest to wait between retries.
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(60 * 60);
+
+/// The most deliveries sent each time the outbox is checked.
+const DELIVERY_BATCH_SIZE: i64 = 50;
+
+
+/// Generates a new signing secret for a webhook.
+pub fn new_secret() -> String {
+    use rand::RngCore;
+
+    let mut secret = [0; 32];
+    rand::thread_rng().fill_bytes(&mut secret);
+    hex::encode(secret)
+}
+
+/// Signs a delivery body sent at `timestamp`, returning the hex signature.
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> Option<String> {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).ok()?;
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    Some(hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Adds an event to the outbox of every webhook that wants it.
+///
+/// Call this with the transaction that made the change, so the event is only
+/// queued if the change is committed, and the change is rolled back if the
+/// event can't be queued.
+pub async fn notify(db_conn: &mut Ctx, event: WebhookEvent, data: serde_json::Value) -> Result<(), sqlx::Error> {
+    use crate::database::prepared::webhooks::enqueue_webhook_event;
+
+    let payload = json!({
+        "event": event.str(),
+        "occurredAt": Utc::now(),
+        "data": data,
+    }).to_string();
+
+    match enqueue_webhook_event(db_conn, event, &payload).await? {
+        0 => trace!("No webhooks want {} events", event.str()),
+        queued => debug!("Queued {queued} webhook deliveries of {}", event.str()),
+    }
+
+    Ok(())
+}
+
+/// Adds an `absence_set` event for a teacher's current absences to the outbox.
+/// `source` says what set them, like `api` or `rollover`.
+pub async fn notify_absence_set(db_conn: &mut Ctx, teacher_id: Uuid, source: &str) -> Result<(), sqlx::Error> {
+    use crate::database::prepared::absences::get_all_absences_for_teacher;
+    use crate::database::prepared::teacher::get_teacher;
+
+    let teacher = get_teacher(db_conn, teacher_id).await?;
+    let absences = get_all_absences_for_teacher(db_conn, teacher_id).await?;
+
+    notify(db_conn, WebhookEvent::AbsenceSet, json!({
+        "teacherId": teacher_id,
+        "fullyAbsent": teacher.get_fully_absent(),
+        "periods": absences.iter().map(|absence| absence.period).collect::<Vec<_>>(),
+        "source": source,
+    })).await
+}
+
+/// Adds a successful `flush_completed` event to the outbox, along with an
+/// `absence_set` event for each teacher the rollover updated.
+pub async fn notify_flush_completed(
+    db_conn: &mut Ctx,
+    trigger: RolloverTrigger,
+    updated: &[Uuid],
+) -> Result<(), sqlx::Error> {
+    notify(db_conn, WebhookEvent::FlushCompleted, json!({
+        "trigger": trigger.str(),
+        "success": true,
+        "teachersUpdated": updated,
+        "error": null,
+    })).await?;
+
+    for teacher_id in updated.iter().copied() {
+        notify_absence_set(db_conn, teacher_id, "rollover").await?;
+    }
+
+    Ok(())
+}
+
+/// Adds a failed `flush_completed` event to the outbox. Since the rollover
+/// was rolled back, this is queued on its own.
+pub async fn notify_flush_failed(
+    db_conn: &mut Ctx,
+    trigger: RolloverTrigger,
+    error: &sqlx::Error,
+) -> Result<(), sqlx::Error> {
+    notify(db_conn, WebhookEvent::FlushCompleted, json!({
+        "trigger": trigger.str(),
+        "success": false,
+        "teachersUpdated": [],
+        "error": error.to_string(),
+    })).await
+}
+
+
+/// A background task that sends the deliveries in the webhook outbox.
+pub struct WebhookDispatcher {
+    state: AppState,
+    client: reqwest::Client,
+    poll_interval: Duration,
+    max_attempts: u32,
+    /// How long claimed deliveries are held, long enough to send a batch.
+    claim_lease: Duration,
+}
+
+impl WebhookDispatcher {
+    pub fn new(state: AppState) -> Result<Self, String> {
+        use crate::env::{ webhook_poll_interval, webhook_max_attempts, webhook_timeout };
+
+        let client = reqwest::Client::builder()
+            .timeout(webhook_timeout())
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            state,
+            client,
+            poll_interval: webhook_poll_interval(),
+            max_attempts: webhook_max_attempts(),
+            claim_lease: webhook_timeout()
+                .saturating_mul(DELIVERY_BATCH_SIZE as u32)
+                .saturating_add(RETRY_BASE_DELAY),
+        })
+    }
+
+    pub fn spawn(self) {
+        tokio::spawn(self.start());
+    }
+
+    pub async fn start(self) {
+        info!("Webhook dispatcher started, checking every {} seconds", self.poll_interval.as_secs());
+
+        loop {
+            if let Err(e) = self.deliver_due().await {
+                error!("Failed to send webhook deliveries: {e}");
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    /// Sends every delivery that's due, until none are left.
+    ///
+    /// No database connection is held while a delivery is being sent, so slow
+    /// endpoints don't tie up the pool.
+    async fn deliver_due(&self) -> Result<(), sqlx::Error> {
+        use crate::database::prepared::webhooks::claim_due_deliveries;
+
+        loop {
+            let due = {
+                let mut db_conn = self.state.db().acquire().await?;
+                claim_due_deliveries(&mut db_conn, DELIVERY_BATCH_SIZE, self.claim_lease.as_secs_f64()).await?
+            };
+            let batch_size = due.len();
+
+            for delivery in due {
+                self.deliver(delivery).await?;
+            }
+
+            if (batch_size as i64) < DELIVERY_BATCH_SIZE {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn deliver(&self, delivery: DueDelivery) -> Result<(), sqlx::Error> {
+        use crate::database::prepared::webhooks::{ mark_attempt_failed, mark_delivered };
+
+        let outcome = self.send(&delivery).await;
+
+        let mut db_conn = self.state.db().acquire().await?;
+        match outcome {
+            Ok(status) => {
+                debug!("Delivered {} webhook {} to {}", delivery.event, delivery.id, delivery.url);
+                mark_delivered(&mut db_conn, delivery.id, status).await
+            },
+            Err((status, e)) => {
+                let attempts = delivery.attempts as u32 + 1;
+                let retry_in = (attempts < self.max_attempts).then(|| {
+                    RETRY_BASE_DELAY
+                        .saturating_mul(2u32.saturating_pow(attempts - 1))
+                        .min(RETRY_MAX_DELAY)
+                });
+
+                match retry_in {
+                    Some(retry_in) => warn!(
+                        "Webhook delivery {} to {} failed (attempt {attempts}), retrying in {} seconds: {e}",
+                        delivery.id, delivery.url, retry_in.as_secs(),
+                    ),
+                    None => error!(
+                        "Webhook delivery {} to {} failed (attempt {attempts}), giving up: {e}",
+                        delivery.id, delivery.url,
+                    ),
+                }
+
+                mark_attempt_failed(&mut db_conn, delivery.id, status, &e, retry_in.map(|retry_in| retry_in.as_secs_f64())).await
+            },
+        }
+    }
+
+    /// Sends a delivery, returning the response status if it was accepted, or
+    /// the status (if there was one) and an error if it wasn't.
+    async fn send(&self, delivery: &DueDelivery) -> Result<i32, (Option<i32>, String)> {
+        let timestamp = Utc::now().timestamp();
+        let signature = sign(&delivery.secret, timestamp, &delivery.payload)
+            .ok_or_else(|| (None, "Failed to sign delivery".to_string()))?;
+
+        let response = self.client
+            .post(&delivery.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Eureka-Event", &delivery.event)
+            .header("X-Eureka-Delivery", delivery.id.to_string())
+            .header("X-Eureka-Timestamp", timestamp.to_string())
+            .header("X-Eureka-Signature", format!("sha256={signature}"))
+            .body(delivery.payload.clone())
+            .send()
+            .await
+            .map_err(|e| (None, e.to_string()))?;
+
+        let status = response.status();
+        if status.is_success() {
+            Ok(status.as_u16().into())
+        } else {
+            Err((Some(status.as_u16().into()), format!("Endpoint responded with {status}")))
+        }
+    }
+}